@@ -0,0 +1,8 @@
+CREATE TABLE relay_rooms (
+  matrix_room_id VARCHAR NOT NULL,
+  rocketchat_server_id VARCHAR NOT NULL,
+  relay_matrix_user_id VARCHAR NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  CONSTRAINT relay_rooms_pk PRIMARY KEY (matrix_room_id)
+)
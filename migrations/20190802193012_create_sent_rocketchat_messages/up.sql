@@ -0,0 +1,6 @@
+CREATE TABLE sent_rocketchat_messages (
+  rocketchat_server_id VARCHAR NOT NULL,
+  rocketchat_message_id VARCHAR NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  CONSTRAINT sent_rocketchat_messages_pk PRIMARY KEY (rocketchat_server_id, rocketchat_message_id)
+)
@@ -0,0 +1,9 @@
+CREATE TABLE login_tokens (
+  token_hash VARCHAR NOT NULL,
+  matrix_user_id VARCHAR NOT NULL,
+  rocketchat_server_id VARCHAR NOT NULL,
+  admin_room_id VARCHAR NOT NULL,
+  expires_at BIGINT NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  CONSTRAINT login_tokens_pk PRIMARY KEY (token_hash)
+)
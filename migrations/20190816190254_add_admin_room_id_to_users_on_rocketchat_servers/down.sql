@@ -0,0 +1,16 @@
+CREATE TABLE users_on_rocketchat_servers_backup (
+  last_message_sent BIG INT NOT NULL DEFAULT 0,
+  matrix_user_id VARCHAR NOT NULL,
+  rocketchat_server_id VARCHAR NOT NULL,
+  rocketchat_user_id VARCHAR,
+  rocketchat_auth_token VARCHAR,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  matrix_access_token VARCHAR
+);
+INSERT INTO users_on_rocketchat_servers_backup
+  SELECT last_message_sent, matrix_user_id, rocketchat_server_id, rocketchat_user_id, rocketchat_auth_token, created_at, updated_at,
+    matrix_access_token
+  FROM users_on_rocketchat_servers;
+DROP TABLE users_on_rocketchat_servers;
+ALTER TABLE users_on_rocketchat_servers_backup RENAME TO users_on_rocketchat_servers;
@@ -0,0 +1 @@
+ALTER TABLE users_on_rocketchat_servers ADD COLUMN admin_room_id VARCHAR;
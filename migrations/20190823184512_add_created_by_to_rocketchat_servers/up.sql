@@ -0,0 +1 @@
+ALTER TABLE rocketchat_servers ADD COLUMN created_by VARCHAR;
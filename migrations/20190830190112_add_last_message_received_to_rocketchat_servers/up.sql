@@ -0,0 +1 @@
+ALTER TABLE rocketchat_servers ADD COLUMN last_message_received BIGINT NOT NULL DEFAULT 0;
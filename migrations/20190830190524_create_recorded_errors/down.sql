@@ -0,0 +1 @@
+DROP TABLE recorded_errors;
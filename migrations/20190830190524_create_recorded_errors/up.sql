@@ -0,0 +1,7 @@
+CREATE TABLE recorded_errors (
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  matrix_user_id VARCHAR NOT NULL,
+  rocketchat_server_id VARCHAR NOT NULL,
+  message VARCHAR NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+)
@@ -0,0 +1 @@
+DROP TABLE plumbed_rooms;
@@ -0,0 +1,10 @@
+CREATE TABLE plumbed_rooms (
+  matrix_room_id VARCHAR NOT NULL,
+  rocketchat_server_id VARCHAR NOT NULL,
+  rocketchat_channel_id VARCHAR NOT NULL,
+  created_by VARCHAR NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  CONSTRAINT plumbed_rooms_pk PRIMARY KEY (matrix_room_id),
+  CONSTRAINT plumbed_rooms_channel_unique UNIQUE (rocketchat_server_id, rocketchat_channel_id)
+)
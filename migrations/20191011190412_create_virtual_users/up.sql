@@ -0,0 +1,7 @@
+CREATE TABLE virtual_users (
+  matrix_user_id VARCHAR NOT NULL,
+  rocketchat_server_id VARCHAR NOT NULL,
+  rocketchat_user_id VARCHAR NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  CONSTRAINT virtual_users_pk PRIMARY KEY (matrix_user_id)
+)
@@ -0,0 +1 @@
+ALTER TABLE rocketchat_servers ADD COLUMN space_room_id VARCHAR;
@@ -0,0 +1 @@
+DROP TABLE puppet_events;
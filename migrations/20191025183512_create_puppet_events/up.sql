@@ -0,0 +1,5 @@
+CREATE TABLE puppet_events (
+  event_id VARCHAR NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  CONSTRAINT puppet_events_pk PRIMARY KEY (event_id)
+)
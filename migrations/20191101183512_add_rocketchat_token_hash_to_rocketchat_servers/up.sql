@@ -0,0 +1 @@
+ALTER TABLE rocketchat_servers ADD COLUMN rocketchat_token_hash VARCHAR;
@@ -0,0 +1,8 @@
+CREATE TABLE bridged_rooms (
+  matrix_room_id VARCHAR NOT NULL,
+  rocketchat_server_id VARCHAR NOT NULL,
+  rocketchat_channel_id VARCHAR NOT NULL,
+  room_type VARCHAR NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  CONSTRAINT bridged_rooms_pk PRIMARY KEY (matrix_room_id)
+)
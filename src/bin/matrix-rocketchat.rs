@@ -3,7 +3,6 @@
 #![deny(missing_docs)]
 
 extern crate clap;
-extern crate iron;
 extern crate matrix_rocketchat;
 extern crate num_cpus;
 #[macro_use]
@@ -17,8 +16,7 @@ use std::fs::OpenOptions;
 use std::path::Path;
 use std::process;
 
-use clap::{App, Arg};
-use iron::Listening;
+use clap::{App, Arg, SubCommand};
 use matrix_rocketchat::errors::*;
 use matrix_rocketchat::{Config, Server};
 use slog::{Drain, FnValue, Level, LevelFilter, Record};
@@ -33,19 +31,36 @@ fn main() {
     }
 }
 
-fn run() -> Result<Listening> {
+fn run() -> Result<()> {
     let matches = App::new("matrix-rocketchat")
         .version("0.1")
         .author("Andreas Studer <foss@exul.org>")
         .about("An application service to bridge Matrix and Rocket.Chat.")
         .arg(Arg::with_name("config").short("c").long("config").help("Path to config file").takes_value(true))
+        .subcommand(
+            SubCommand::with_name("rekey")
+                .about("Encrypts the tokens that are stored in the database with the configured database_encryption_key")
+                .arg(
+                    Arg::with_name("old-key")
+                        .long("old-key")
+                        .help("The key that the tokens are currently encrypted with")
+                        .takes_value(true),
+                ),
+        )
         .get_matches();
 
     let config_path = matches.value_of("config").unwrap_or("config.yaml").to_string();
     let config = Config::read_from_file(&config_path).chain_err(|| ErrorKind::ReadFileError(config_path))?;
     let log = build_logger(&config);
+
+    if let Some(rekey_matches) = matches.subcommand_matches("rekey") {
+        Server::new(&config, log).reencrypt_tokens(rekey_matches.value_of("old-key"))?;
+        return Ok(());
+    }
+
     let threads = num_cpus::get() * 8;
-    Server::new(&config, log).run(threads)
+    Server::new(&config, log).run(threads)?;
+    Ok(())
 }
 
 fn build_logger(config: &Config) -> slog::Logger {
@@ -5,9 +5,10 @@ use ruma_client_api::unversioned::get_supported_versions::{
     Endpoint as GetSupportedVersionsEndpoint, Response as GetSupportedVersionsResponse,
 };
 use ruma_client_api::Endpoint;
+use ruma_events::room::join_rules::JoinRule;
 use ruma_events::room::member::MemberEvent;
 use ruma_events::room::message::MessageType;
-use ruma_identifiers::{RoomAliasId, RoomId, UserId};
+use ruma_identifiers::{EventId, RoomAliasId, RoomId, UserId};
 use serde_json;
 use slog::Logger;
 
@@ -22,6 +23,8 @@ pub mod r0;
 pub trait MatrixApi: Send + Sync + MatrixApiClone {
     /// Create a room.
     fn create_room(&self, room_name: Option<String>, room_alias_name: Option<String>, creator_id: &UserId) -> Result<RoomId>;
+    /// Create a space, a room that groups other rooms.
+    fn create_space(&self, name: String, creator_id: &UserId) -> Result<RoomId>;
     /// Delete a room alias.
     fn delete_room_alias(&self, matrix_room_alias_id: RoomAliasId) -> Result<()>;
     /// Forget a room.
@@ -44,20 +47,35 @@ pub trait MatrixApi: Send + Sync + MatrixApiClone {
     fn get_room_members(&self, room_id: RoomId, sender_id: Option<UserId>) -> Result<Vec<MemberEvent>>;
     /// Get the topic for a room.
     fn get_room_topic(&self, room_id: RoomId) -> Result<Option<String>>;
+    /// Get the power level of a user in a room.
+    fn get_user_power_level(&self, room_id: RoomId, user_id: UserId) -> Result<i64>;
+    /// Get the power levels of all users that have an explicit power level in a room.
+    fn get_user_power_levels(&self, room_id: RoomId) -> Result<HashMap<UserId, i64>>;
     /// Invite a user to a room.
     fn invite(&self, room_id: RoomId, receiver_user_id: UserId, sender_user_id: UserId) -> Result<()>;
     /// Determine if the bot user has access to a room.
     fn is_room_accessible_by_bot(&self, room_id: RoomId) -> Result<bool>;
     /// Join a room with a user.
     fn join(&self, room_id: RoomId, user_id: UserId) -> Result<()>;
+    /// Kick a user from a room. The optional reason is shown to the members of the room.
+    fn kick_user(&self, room_id: RoomId, user_id: UserId, sender_id: UserId, reason: Option<String>) -> Result<()>;
     /// Leave a room.
     fn leave_room(&self, room_id: RoomId, user_id: UserId) -> Result<()>;
+    /// Login a user via the shared secret authenticator of the homeserver. Returns the users access token.
+    fn login_with_shared_secret(&self, user_id: UserId, shared_secret: &str) -> Result<String>;
     /// Set the canonical alias for a room.
     fn put_canonical_room_alias(&self, room_id: RoomId, matrix_room_alias_id: Option<RoomAliasId>) -> Result<()>;
+    /// Add an alias to a room.
+    fn put_room_alias(&self, room_id: RoomId, matrix_room_alias_id: RoomAliasId) -> Result<()>;
+    /// Redact an event in a room. The optional reason is shown to the members of the room.
+    fn redact_event(&self, room_id: RoomId, event_id: EventId, user_id: UserId, reason: Option<String>) -> Result<()>;
     /// Register a user.
     fn register(&self, user_id_local_part: String) -> Result<()>;
     /// Send a text message to a room.
     fn send_text_message(&self, room_id: RoomId, user_id: UserId, body: String) -> Result<()>;
+    /// Send a text message to a room with the access token of a user (double puppeting).
+    /// Returns the ID of the event that was sent.
+    fn send_text_message_as_user(&self, room_id: RoomId, access_token: &str, body: String) -> Result<EventId>;
     /// Send an data message (audio, file, image, video) to a room.
     fn send_data_message(&self, room_id: RoomId, user_id: UserId, body: String, url: String, mtype: MessageType) -> Result<()>;
     /// Set the default power levels for a room. Only the bot will be able to control the room.
@@ -65,12 +83,23 @@ pub trait MatrixApi: Send + Sync + MatrixApiClone {
     fn set_default_powerlevels(&self, room_id: RoomId, room_creator_user_id: UserId) -> Result<()>;
     /// Set the display name for a user
     fn set_display_name(&self, user_id: UserId, name: String) -> Result<()>;
+    /// Publish a room to the room directory of the homeserver or remove it, the room is listed in
+    /// the network of the application service.
+    fn set_room_directory_visibility(&self, network_id: &str, room_id: RoomId, published: bool) -> Result<()>;
+    /// Set the join rule for a room, this controls who can join the room without an invite.
+    fn set_room_join_rule(&self, room_id: RoomId, join_rule: JoinRule) -> Result<()>;
     /// Set the name for a room
     fn set_room_name(&self, room_id: RoomId, name: String) -> Result<()>;
     /// Set the topic for a room.
     fn set_room_topic(&self, room_id: RoomId, topic: String) -> Result<()>;
+    /// Add a room to a space, the room is removed from the space if no servers to join it via are given.
+    fn set_space_child(&self, space_room_id: RoomId, child_room_id: RoomId, via: Vec<String>) -> Result<()>;
+    /// Set the power levels of users in a room, the power levels of all other users are kept.
+    fn set_user_power_levels(&self, room_id: RoomId, user_id: UserId, power_levels: HashMap<UserId, i64>) -> Result<()>;
     /// Upload a file to the media storage
     fn upload(&self, data: Vec<u8>, content_type: HeaderValue) -> Result<String>;
+    /// Get the user that an access token belongs to.
+    fn whoami(&self, access_token: &str) -> Result<UserId>;
 }
 
 /// Helper trait because Clone cannot be part of the `MatrixApi` trait since that would cause the
@@ -5,7 +5,9 @@ use std::io::Read;
 use http::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
 use http::{Method, StatusCode};
 use pulldown_cmark::{html, Options, Parser};
+use ring::{digest, hmac};
 use ruma_client_api::r0::account::register::{self, Endpoint as RegisterEndpoint};
+use ruma_client_api::r0::alias::create_alias::Endpoint as CreateAliasEndpoint;
 use ruma_client_api::r0::alias::delete_alias::Endpoint as DeleteAliasEndpoint;
 use ruma_client_api::r0::alias::get_alias::{self, Endpoint as GetAliasEndpoint};
 use ruma_client_api::r0::media::create_content::{self, Endpoint as CreateContentEndpoint};
@@ -25,6 +27,7 @@ use ruma_client_api::r0::sync::get_state_events_for_empty_key::{self, Endpoint a
 use ruma_client_api::r0::sync::sync_events::Endpoint as SyncEventsEndpoint;
 use ruma_client_api::Endpoint;
 use ruma_events::collections::all::Event;
+use ruma_events::room::join_rules::JoinRule;
 use ruma_events::room::member::MemberEvent;
 use ruma_events::room::message::MessageType;
 use ruma_events::EventType;
@@ -37,6 +40,27 @@ use api::{RequestData, RestApi};
 use config::Config;
 use errors::*;
 
+/// Login endpoint path
+const LOGIN_PATH: &str = "/_matrix/client/r0/login";
+/// Whoami endpoint path
+const WHOAMI_PATH: &str = "/_matrix/client/r0/account/whoami";
+/// Device name that is used when logging in users for double puppeting
+const DOUBLE_PUPPET_DEVICE_NAME: &str = "Rocket.Chat Bridge";
+
+/// Response payload from the Matrix login endpoint.
+#[derive(Deserialize)]
+struct LoginResponse {
+    /// The access token of the user that logged in
+    access_token: String,
+}
+
+/// Response payload from the Matrix whoami endpoint.
+#[derive(Deserialize)]
+struct WhoamiResponse {
+    /// The user that the access token belongs to
+    user_id: UserId,
+}
+
 #[derive(Clone)]
 /// Rocket.Chat REST API v0
 pub struct MatrixApi {
@@ -59,6 +83,32 @@ impl MatrixApi {
         params.insert("access_token", &self.access_token);
         params
     }
+
+    // Returns `None` if the room doesn't have a power levels event.
+    fn get_power_levels(&self, room_id: RoomId) -> Result<Option<Value>> {
+        let path_params =
+            get_state_events_for_empty_key::PathParams { room_id, event_type: EventType::RoomPowerLevels.to_string() };
+        let endpoint = self.base_url.clone() + &GetStateEventsForEmptyKeyEndpoint::request_path(path_params);
+        let params = self.params_hash();
+
+        let (body, status_code) = RestApi::call_matrix(&GetStateEventsForEmptyKeyEndpoint::method(), &endpoint, "{}", &params)?;
+        if status_code == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        if !status_code.is_success() {
+            return Err(build_error(&endpoint, &body, &status_code));
+        }
+
+        let power_levels: Value = serde_json::from_str(&body).chain_err(|| {
+            ErrorKind::InvalidJSON(format!(
+                "Could not deserialize response from Matrix get_state_events_for_empty_key API endpoint: `{}`",
+                body
+            ))
+        })?;
+
+        Ok(Some(power_levels))
+    }
 }
 
 impl super::MatrixApi for MatrixApi {
@@ -96,6 +146,33 @@ impl super::MatrixApi for MatrixApi {
         Ok(create_room_response.room_id)
     }
 
+    fn create_space(&self, name: String, creator_id: &UserId) -> Result<RoomId> {
+        let endpoint = self.base_url.clone() + &CreateRoomEndpoint::request_path(());
+        // the ruma client api body params don't support the room type, which marks the room as space
+        let mut creation_content = Map::new();
+        creation_content.insert("type".to_string(), json!("m.space"));
+        let mut body_params = Map::new();
+        body_params.insert("name".to_string(), json!(name));
+        body_params.insert("preset".to_string(), json!("public_chat"));
+        body_params.insert("creation_content".to_string(), json!(creation_content));
+        let payload = serde_json::to_string(&body_params).chain_err(|| body_params_error!("create_space"))?;
+        let user_id = creator_id.to_string();
+        let mut params = self.params_hash();
+        params.insert("user_id", &user_id);
+
+        let (body, status_code) = RestApi::call_matrix(&CreateRoomEndpoint::method(), &endpoint, payload, &params)?;
+        if !status_code.is_success() {
+            return Err(build_error(&endpoint, &body, &status_code));
+        }
+
+        let create_room_response: create_room::Response = serde_json::from_str(&body).chain_err(|| {
+            ErrorKind::InvalidJSON(format!("Could not deserialize response from Matrix create_room API endpoint: `{}`", body))
+        })?;
+
+        debug!(self.logger, "Successfully created space with ID {}", create_room_response.room_id);
+        Ok(create_room_response.room_id)
+    }
+
     fn delete_room_alias(&self, matrix_room_alias_id: RoomAliasId) -> Result<()> {
         // the ruma client api path params cannot be used here, because they are not url encoded
         let encoded_room_alias =
@@ -342,6 +419,38 @@ impl super::MatrixApi for MatrixApi {
         Ok(Some(room_topic_response["topic"].to_string().replace("\"", "")))
     }
 
+    fn get_user_power_level(&self, room_id: RoomId, user_id: UserId) -> Result<i64> {
+        let power_levels = match self.get_power_levels(room_id.clone())? {
+            Some(power_levels) => power_levels,
+            // without a power levels event the creator of the room is the only user with elevated permissions
+            None => {
+                let room_creator_id = self.get_room_creator(room_id)?;
+                return Ok(if room_creator_id == user_id { 100 } else { 0 });
+            }
+        };
+
+        let power_level = power_levels["users"][user_id.to_string()].as_i64();
+        Ok(power_level.or_else(|| power_levels["users_default"].as_i64()).unwrap_or_default())
+    }
+
+    fn get_user_power_levels(&self, room_id: RoomId) -> Result<HashMap<UserId, i64>> {
+        let mut user_power_levels = HashMap::new();
+        let power_levels = match self.get_power_levels(room_id)? {
+            Some(power_levels) => power_levels,
+            None => return Ok(user_power_levels),
+        };
+
+        if let Some(users) = power_levels["users"].as_object() {
+            for (user_id, power_level) in users {
+                if let (Ok(user_id), Some(power_level)) = (UserId::try_from(user_id.as_ref()), power_level.as_i64()) {
+                    user_power_levels.insert(user_id, power_level);
+                }
+            }
+        }
+
+        Ok(user_power_levels)
+    }
+
     fn invite(&self, room_id: RoomId, receiver_user_id: UserId, sender_user_id: UserId) -> Result<()> {
         let path_params = invite_user::PathParams { room_id: room_id.clone() };
         let endpoint = self.base_url.clone() + &InviteUserEndpoint::request_path(path_params);
@@ -386,6 +495,30 @@ impl super::MatrixApi for MatrixApi {
         Ok(())
     }
 
+    fn kick_user(&self, room_id: RoomId, user_id: UserId, sender_id: UserId, reason: Option<String>) -> Result<()> {
+        // the room ID contains reserved characters, so it has to be url encoded
+        let encoded_room_id = url::form_urlencoded::byte_serialize(room_id.to_string().as_bytes()).collect::<String>();
+        let endpoint = self.base_url.clone() + &format!("/_matrix/client/r0/rooms/{}/kick", encoded_room_id);
+        let sender_id = sender_id.to_string();
+        let mut params = self.params_hash();
+        params.insert("user_id", &sender_id);
+
+        let mut body_params = Map::new();
+        body_params.insert("user_id".to_string(), json!(user_id.to_string()));
+        if let Some(reason) = reason {
+            body_params.insert("reason".to_string(), json!(reason));
+        }
+        let payload = serde_json::to_string(&body_params).chain_err(|| body_params_error!("kick"))?;
+
+        let (body, status_code) = RestApi::call(&Method::POST, &endpoint, RequestData::Body(payload), &params, None)?;
+        if !status_code.is_success() {
+            return Err(build_error(&endpoint, &body, &status_code));
+        }
+
+        debug!(self.logger, "User {} successfully kicked user {} from room {}", sender_id, user_id, room_id);
+        Ok(())
+    }
+
     fn leave_room(&self, room_id: RoomId, user_id: UserId) -> Result<()> {
         let path_params = leave_room::PathParams { room_id };
         let endpoint = self.base_url.clone() + &LeaveRoomEndpoint::request_path(path_params);
@@ -421,6 +554,82 @@ impl super::MatrixApi for MatrixApi {
         Ok(())
     }
 
+    fn put_room_alias(&self, room_id: RoomId, matrix_room_alias_id: RoomAliasId) -> Result<()> {
+        // the ruma client api path params cannot be used here, because they are not url encoded
+        let encoded_room_alias =
+            url::form_urlencoded::byte_serialize(matrix_room_alias_id.to_string().as_bytes()).collect::<String>();
+        let endpoint = self.base_url.clone() + &format!("/_matrix/client/r0/directory/room/{}", &encoded_room_alias);
+        let params = self.params_hash();
+
+        let mut body_params = serde_json::Map::new();
+        body_params.insert("room_id".to_string(), json!(room_id.to_string()));
+        let payload = serde_json::to_string(&body_params).chain_err(|| body_params_error!("room alias"))?;
+
+        let (body, status_code) = RestApi::call_matrix(&CreateAliasEndpoint::method(), &endpoint, payload, &params)?;
+        if !status_code.is_success() {
+            return Err(build_error(&endpoint, &body, &status_code));
+        }
+
+        debug!(self.logger, "Successfully added alias {} to room {}", matrix_room_alias_id, room_id);
+        Ok(())
+    }
+
+    fn login_with_shared_secret(&self, user_id: UserId, shared_secret: &str) -> Result<String> {
+        // the password is the HMAC-SHA512 of the user ID, signed with the shared secret, see
+        // https://github.com/devture/matrix-synapse-shared-secret-auth
+        let key = hmac::SigningKey::new(&digest::SHA512, shared_secret.as_bytes());
+        let signature = hmac::sign(&key, user_id.to_string().as_bytes());
+        let password = signature.as_ref().iter().map(|b| format!("{:02x}", b)).collect::<String>();
+
+        let mut body_params = Map::new();
+        body_params.insert("type".to_string(), json!("m.login.password"));
+        body_params.insert("user".to_string(), json!(user_id.to_string()));
+        body_params.insert("password".to_string(), json!(password));
+        body_params.insert("initial_device_display_name".to_string(), json!(DOUBLE_PUPPET_DEVICE_NAME));
+        let payload = serde_json::to_string(&body_params).chain_err(|| body_params_error!("login"))?;
+        let endpoint = self.base_url.clone() + LOGIN_PATH;
+        let params = HashMap::new();
+
+        let (body, status_code) = RestApi::call(&Method::POST, &endpoint, RequestData::Body(payload), &params, None)?;
+        if !status_code.is_success() {
+            return Err(build_error(&endpoint, &body, &status_code));
+        }
+
+        let login_response: LoginResponse = serde_json::from_str(&body).chain_err(|| {
+            ErrorKind::InvalidJSON(format!("Could not deserialize response from Matrix login API endpoint: `{}`", body))
+        })?;
+
+        debug!(self.logger, "Successfully logged in user {} via shared secret", user_id);
+        Ok(login_response.access_token)
+    }
+
+    fn redact_event(&self, room_id: RoomId, event_id: EventId, user_id: UserId, reason: Option<String>) -> Result<()> {
+        // the IDs contain reserved characters, so they have to be url encoded
+        let encoded_room_id = url::form_urlencoded::byte_serialize(room_id.to_string().as_bytes()).collect::<String>();
+        let encoded_event_id = url::form_urlencoded::byte_serialize(event_id.to_string().as_bytes()).collect::<String>();
+        let txn_id = EventId::new(&self.base_url).chain_err(|| ErrorKind::EventIdGenerationFailed)?;
+        let encoded_txn_id = url::form_urlencoded::byte_serialize(txn_id.to_string().as_bytes()).collect::<String>();
+        let endpoint = self.base_url.clone()
+            + &format!("/_matrix/client/r0/rooms/{}/redact/{}/{}", encoded_room_id, encoded_event_id, encoded_txn_id);
+        let user_id = user_id.to_string();
+        let mut params = self.params_hash();
+        params.insert("user_id", &user_id);
+
+        let mut body_params = Map::new();
+        if let Some(reason) = reason {
+            body_params.insert("reason".to_string(), json!(reason));
+        }
+        let payload = serde_json::to_string(&body_params).chain_err(|| body_params_error!("redact"))?;
+
+        let (body, status_code) = RestApi::call(&Method::PUT, &endpoint, RequestData::Body(payload), &params, None)?;
+        if !status_code.is_success() {
+            return Err(build_error(&endpoint, &body, &status_code));
+        }
+
+        debug!(self.logger, "User {} successfully redacted event {} in room {}", user_id, event_id, room_id);
+        Ok(())
+    }
+
     fn register(&self, user_id_local_part: String) -> Result<()> {
         let endpoint = self.base_url.clone() + &RegisterEndpoint::request_path(());
         let params = self.params_hash();
@@ -442,13 +651,7 @@ impl super::MatrixApi for MatrixApi {
     }
 
     fn send_text_message(&self, room_id: RoomId, user_id: UserId, body: String) -> Result<()> {
-        let formatted_body = render_markdown(&body);
-        let mut message = Map::new();
-        message.insert("body".to_string(), json!(body));
-        message.insert("formatted_body".to_string(), json!(formatted_body));
-        message.insert("msgtype".to_string(), json!(MessageType::Text));
-        message.insert("format".to_string(), json!("org.matrix.custom.html"));
-        let payload = serde_json::to_string(&message).chain_err(|| body_params_error!("send text message"))?;
+        let payload = build_text_message_payload(&body)?;
         let txn_id = EventId::new(&self.base_url).chain_err(|| ErrorKind::EventIdGenerationFailed)?;
         let path_params = send_message_event::PathParams {
             room_id: room_id.clone(),
@@ -470,6 +673,34 @@ impl super::MatrixApi for MatrixApi {
         Ok(())
     }
 
+    fn send_text_message_as_user(&self, room_id: RoomId, access_token: &str, body: String) -> Result<EventId> {
+        let payload = build_text_message_payload(&body)?;
+        let txn_id = EventId::new(&self.base_url).chain_err(|| ErrorKind::EventIdGenerationFailed)?;
+        let path_params = send_message_event::PathParams {
+            room_id: room_id.clone(),
+            event_type: EventType::RoomMessage,
+            txn_id: txn_id.to_string(),
+        };
+        let endpoint = self.base_url.clone() + &SendMessageEventEndpoint::request_path(path_params);
+        let mut params = HashMap::new();
+        params.insert("access_token", access_token);
+
+        let (body, status_code) = RestApi::call_matrix(&SendMessageEventEndpoint::method(), &endpoint, payload, &params)?;
+        if !status_code.is_success() {
+            return Err(build_error(&endpoint, &body, &status_code));
+        }
+
+        let send_message_response: send_message_event::Response = serde_json::from_str(&body).chain_err(|| {
+            ErrorKind::InvalidJSON(format!(
+                "Could not deserialize response from Matrix send_message_event API endpoint: `{}`",
+                body
+            ))
+        })?;
+
+        debug!(self.logger, "Puppet successfully sent a text message to room {}", room_id);
+        Ok(send_message_response.event_id)
+    }
+
     fn send_data_message(&self, room_id: RoomId, user_id: UserId, body: String, url: String, mtype: MessageType) -> Result<()> {
         let mut message = Map::new();
         message.insert("body".to_string(), json!(body));
@@ -538,6 +769,44 @@ impl super::MatrixApi for MatrixApi {
         Ok(())
     }
 
+    fn set_room_directory_visibility(&self, network_id: &str, room_id: RoomId, published: bool) -> Result<()> {
+        // the IDs contain reserved characters, so they have to be url encoded
+        let encoded_network_id = url::form_urlencoded::byte_serialize(network_id.as_bytes()).collect::<String>();
+        let encoded_room_id = url::form_urlencoded::byte_serialize(room_id.to_string().as_bytes()).collect::<String>();
+        let endpoint = self.base_url.clone()
+            + &format!("/_matrix/client/r0/directory/list/appservice/{}/{}", encoded_network_id, encoded_room_id);
+        let params = self.params_hash();
+
+        let visibility = if published { "public" } else { "private" };
+        let mut body_params = Map::new();
+        body_params.insert("visibility".to_string(), json!(visibility));
+        let payload = serde_json::to_string(&body_params).chain_err(|| body_params_error!("room directory visibility"))?;
+
+        let (body, status_code) = RestApi::call(&Method::PUT, &endpoint, RequestData::Body(payload), &params, None)?;
+        if !status_code.is_success() {
+            return Err(build_error(&endpoint, &body, &status_code));
+        }
+
+        debug!(self.logger, "Successfully set the visibility of room {} in network {} to {}", room_id, network_id, visibility);
+        Ok(())
+    }
+
+    fn set_room_join_rule(&self, room_id: RoomId, join_rule: JoinRule) -> Result<()> {
+        let path_params = send_state_event_for_empty_key::PathParams { room_id, event_type: EventType::RoomJoinRules };
+        let endpoint = self.base_url.clone() + &SendStateEventForEmptyKeyEndpoint::request_path(path_params);
+        let params = self.params_hash();
+        let mut body_params = serde_json::Map::new();
+        body_params.insert("join_rule".to_string(), json!(join_rule));
+        let payload = serde_json::to_string(&body_params).chain_err(|| body_params_error!("join rule"))?;
+
+        let (body, status_code) =
+            RestApi::call_matrix(&SendStateEventForEmptyKeyEndpoint::method(), &endpoint, payload, &params)?;
+        if !status_code.is_success() {
+            return Err(build_error(&endpoint, &body, &status_code));
+        }
+        Ok(())
+    }
+
     fn set_room_name(&self, room_id: RoomId, name: String) -> Result<()> {
         let path_params = send_state_event_for_empty_key::PathParams { room_id, event_type: EventType::RoomName };
         let endpoint = self.base_url.clone() + &SendStateEventForEmptyKeyEndpoint::request_path(path_params);
@@ -570,6 +839,65 @@ impl super::MatrixApi for MatrixApi {
         Ok(())
     }
 
+    fn set_space_child(&self, space_room_id: RoomId, child_room_id: RoomId, via: Vec<String>) -> Result<()> {
+        // the room IDs contain reserved characters, so they have to be url encoded
+        let encoded_space_id = url::form_urlencoded::byte_serialize(space_room_id.to_string().as_bytes()).collect::<String>();
+        let encoded_child_id = url::form_urlencoded::byte_serialize(child_room_id.to_string().as_bytes()).collect::<String>();
+        let endpoint = self.base_url.clone()
+            + &format!("/_matrix/client/r0/rooms/{}/state/m.space.child/{}", encoded_space_id, encoded_child_id);
+        let params = self.params_hash();
+
+        // a space child event without servers removes the room from the space
+        let mut body_params = Map::new();
+        if !via.is_empty() {
+            body_params.insert("via".to_string(), json!(via));
+        }
+        let payload = serde_json::to_string(&body_params).chain_err(|| body_params_error!("space child"))?;
+
+        let (body, status_code) = RestApi::call(&Method::PUT, &endpoint, RequestData::Body(payload), &params, None)?;
+        if !status_code.is_success() {
+            return Err(build_error(&endpoint, &body, &status_code));
+        }
+
+        debug!(self.logger, "Successfully updated room {} in space {}", child_room_id, space_room_id);
+        Ok(())
+    }
+
+    fn set_user_power_levels(&self, room_id: RoomId, user_id: UserId, power_levels: HashMap<UserId, i64>) -> Result<()> {
+        let mut body_params = match self.get_power_levels(room_id.clone())? {
+            Some(Value::Object(body_params)) => body_params,
+            // a new power levels event replaces the implicit power level of the room creator
+            _ => {
+                let room_creator_id = self.get_room_creator(room_id.clone())?;
+                let mut body_params = Map::new();
+                let mut users = Map::new();
+                users.insert(room_creator_id.to_string(), json!(100));
+                body_params.insert("users".to_string(), json!(users));
+                body_params
+            }
+        };
+
+        let mut users = body_params.get("users").and_then(|users| users.as_object().cloned()).unwrap_or_default();
+        for (id, power_level) in power_levels {
+            users.insert(id.to_string(), json!(power_level));
+        }
+        body_params.insert("users".to_string(), json!(users));
+
+        let path_params = send_state_event_for_empty_key::PathParams { room_id, event_type: EventType::RoomPowerLevels };
+        let endpoint = self.base_url.clone() + &SendStateEventForEmptyKeyEndpoint::request_path(path_params);
+        let user_id = user_id.to_string();
+        let mut params = self.params_hash();
+        params.insert("user_id", &user_id);
+        let payload = serde_json::to_string(&body_params).chain_err(|| body_params_error!("power levels"))?;
+
+        let (body, status_code) =
+            RestApi::call_matrix(&SendStateEventForEmptyKeyEndpoint::method(), &endpoint, payload, &params)?;
+        if !status_code.is_success() {
+            return Err(build_error(&endpoint, &body, &status_code));
+        }
+        Ok(())
+    }
+
     fn upload(&self, data: Vec<u8>, content_type: HeaderValue) -> Result<String> {
         let endpoint = self.base_url.clone() + &CreateContentEndpoint::request_path(());
         let params = self.params_hash();
@@ -591,6 +919,23 @@ impl super::MatrixApi for MatrixApi {
 
         Ok(create_content_response.content_uri.clone())
     }
+
+    fn whoami(&self, access_token: &str) -> Result<UserId> {
+        let endpoint = self.base_url.clone() + WHOAMI_PATH;
+        let mut params = HashMap::new();
+        params.insert("access_token", access_token);
+
+        let (body, status_code) = RestApi::call(&Method::GET, &endpoint, RequestData::Body(""), &params, None)?;
+        if !status_code.is_success() {
+            return Err(build_error(&endpoint, &body, &status_code));
+        }
+
+        let whoami_response: WhoamiResponse = serde_json::from_str(&body).chain_err(|| {
+            ErrorKind::InvalidJSON(format!("Could not deserialize response from Matrix whoami API endpoint: `{}`", body))
+        })?;
+
+        Ok(whoami_response.user_id)
+    }
 }
 
 fn build_error(endpoint: &str, body: &str, status_code: &StatusCode) -> Error {
@@ -613,6 +958,16 @@ fn build_error(endpoint: &str, body: &str, status_code: &StatusCode) -> Error {
     Error::from(ErrorKind::MatrixError(matrix_error_resp.error))
 }
 
+fn build_text_message_payload(body: &str) -> Result<String> {
+    let formatted_body = render_markdown(body);
+    let mut message = Map::new();
+    message.insert("body".to_string(), json!(body));
+    message.insert("formatted_body".to_string(), json!(formatted_body));
+    message.insert("msgtype".to_string(), json!(MessageType::Text));
+    message.insert("format".to_string(), json!("org.matrix.custom.html"));
+    serde_json::to_string(&message).chain_err(|| body_params_error!("send text message")).map_err(Error::from)
+}
+
 fn render_markdown(input: &str) -> String {
     // The html will not have the same length as the msg, but it's a good starting point
     let mut output = String::with_capacity(input.len());
@@ -17,6 +17,9 @@ pub mod v1;
 const MAX_REQUESTS_PER_ENDPOINT_CALL: i32 = 1000;
 const MIN_MAJOR_VERSION: i32 = 0;
 const MIN_MINOR_VERSION: i32 = 70;
+const MODERATOR_POWER_LEVEL: i64 = 50;
+// below the power level of the bot, so that owners cannot take over the room from the bridge
+const OWNER_POWER_LEVEL: i64 = 95;
 
 /// A Rocket.Chat REST API endpoint.
 pub trait Endpoint<T: Into<Body>> {
@@ -52,6 +55,11 @@ pub struct Channel {
     pub id: String,
     /// Name of the Rocket.Chat room
     pub name: Option<String>,
+    /// Topic of the Rocket.Chat room
+    pub topic: Option<String>,
+    /// Number of users in the Rocket.Chat room
+    #[serde(rename = "usersCount")]
+    pub users_count: Option<i32>,
 }
 
 /// A Rocket.Chat message
@@ -87,6 +95,25 @@ pub struct MessageAttachment {
     pub title_link: String,
 }
 
+/// The roles of a member of a Rocket.Chat channel or group
+#[derive(Clone, Deserialize, Debug, Serialize)]
+pub struct MemberRoles {
+    /// The member that has the roles
+    #[serde(rename = "u")]
+    pub user: User,
+    /// Names of the roles (owner, moderator, leader)
+    pub roles: Vec<String>,
+}
+
+/// A role that grants a member elevated permissions in a Rocket.Chat channel or group
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Role {
+    /// Can manage messages and users of the channel
+    Moderator,
+    /// Can manage the channel and its moderators
+    Owner,
+}
+
 /// A Rocket.Chat user
 #[derive(Clone, Deserialize, Debug, Serialize)]
 pub struct User {
@@ -100,7 +127,8 @@ pub struct User {
 /// A Rocket.Chat message
 #[derive(Deserialize, Debug, Serialize)]
 pub struct WebhookMessage {
-    /// ID of the message
+    /// ID of the message, membership events (a user joined or left the channel) don't have one
+    #[serde(default)]
     pub message_id: String,
     /// Rocket.Chat token
     pub token: Option<String>,
@@ -113,6 +141,7 @@ pub struct WebhookMessage {
     /// Name of the user who sent the message
     pub user_name: String,
     /// Message content
+    #[serde(default)]
     pub text: String,
 }
 
@@ -120,30 +149,68 @@ pub struct WebhookMessage {
 pub trait RocketchatApi {
     /// Get the url of an image that is attached to a message.
     fn attachments(&self, message_id: &str) -> Result<Vec<Attachment>>;
+    /// Grant a user a role in a channel
+    fn channels_add_role(&self, room_id: &str, user_id: &str, role: Role) -> Result<()>;
+    /// Remove a user from a channel
+    fn channels_kick(&self, room_id: &str, user_id: &str) -> Result<()>;
     /// Get all members of a channel
     fn channels_members(&self, room_id: &str) -> Result<Vec<User>>;
     /// List of channels on the Rocket.Chat server
     fn channels_list(&self) -> Result<Vec<Channel>>;
     /// Get all the channels that the user of the request has joiend.
     fn channels_list_joined(&self) -> Result<Vec<Channel>>;
+    /// Revoke a role of a user in a channel
+    fn channels_remove_role(&self, room_id: &str, user_id: &str, role: Role) -> Result<()>;
+    /// Get the members of a channel that have a role
+    fn channels_roles(&self, room_id: &str) -> Result<Vec<MemberRoles>>;
     /// Get a chat message
     fn chat_get_message(&self, message_id: &str) -> Result<Message>;
-    /// Post a chat message
-    fn chat_post_message(&self, text: &str, room_id: &str) -> Result<()>;
+    /// Post a chat message, returns the ID of the created message if the server sent it
+    fn chat_post_message(&self, text: &str, room_id: &str) -> Result<Option<String>>;
+    /// Post a chat message that is relayed for another user, the message is displayed with the
+    /// alias and the avatar of that user. Returns the ID of the created message if the server sent it.
+    fn chat_post_relay_message(&self, text: &str, room_id: &str, alias: &str, avatar: Option<&str>) -> Result<Option<String>>;
     /// List of direct messages the user is part of
     fn dm_list(&self) -> Result<Vec<Channel>>;
+    /// Grant a user a role in a group
+    fn groups_add_role(&self, room_id: &str, user_id: &str, role: Role) -> Result<()>;
+    /// Remove a user from a group
+    fn groups_kick(&self, room_id: &str, user_id: &str) -> Result<()>;
     /// List of al private groups the authenticated user has joined on the Rocket.Chat server
     fn groups_list(&self) -> Result<Vec<Channel>>;
     /// Get all members of a group
     fn groups_members(&self, room_id: &str) -> Result<Vec<User>>;
-    /// Login a user on the Rocket.Chat server
-    fn login(&self, username: &str, password: &str) -> Result<(String, String)>;
+    /// Revoke a role of a user in a group
+    fn groups_remove_role(&self, room_id: &str, user_id: &str, role: Role) -> Result<()>;
+    /// Get the members of a group that have a role
+    fn groups_roles(&self, room_id: &str) -> Result<Vec<MemberRoles>>;
+    /// Create a direct message room with a user, returns the existing room if there is one already
+    fn im_create(&self, username: &str) -> Result<Channel>;
+    /// Get all members of a direct message, this includes direct messages with more than two users
+    fn im_members(&self, room_id: &str) -> Result<Vec<User>>;
+    /// Login a user on the Rocket.Chat server, the code is only needed if the user enabled
+    /// two-factor authentication
+    fn login(&self, username: &str, password: &str, code: Option<&str>) -> Result<(String, String)>;
+    /// Logout the user, the auth token is no longer valid afterwards
+    fn logout(&self) -> Result<()>;
     /// Get current user information
     fn me(&self) -> Result<User>;
-    /// Post a message with an attachment
-    fn rooms_upload(&self, file: Vec<u8>, filename: &str, mimetype: HeaderValue, room_id: &str) -> Result<()>;
+    /// Post a message with an attachment and an optional text, returns the ID of the created message
+    /// if the server sent it
+    fn rooms_upload(
+        &self,
+        file: Vec<u8>,
+        filename: &str,
+        mimetype: HeaderValue,
+        message: Option<&str>,
+        room_id: &str,
+    ) -> Result<Option<String>>;
     /// Get information like user_id, status, etc. about a user
     fn users_info(&self, username: &str) -> Result<User>;
+    /// Get information like the username about a user by the users ID
+    fn users_info_by_id(&self, user_id: &str) -> Result<User>;
+    /// The version of the Rocket.Chat server that was detected when creating the API
+    fn version(&self) -> String;
     /// Set credentials that are used for all API calls that need authentication
     fn with_credentials(self: Box<Self>, user_id: String, auth_token: String) -> Box<RocketchatApi>;
 }
@@ -197,7 +264,7 @@ impl RocketchatApi {
         let minor: i32 = versions.next().unwrap_or("0").parse().unwrap_or(0);
 
         if major == MIN_MAJOR_VERSION && minor >= MIN_MINOR_VERSION {
-            let rocketchat_api = v1::RocketchatApi::new(base_url, logger);
+            let rocketchat_api = v1::RocketchatApi::new(base_url, version, logger);
             return Ok(Box::new(rocketchat_api));
         }
 
@@ -212,6 +279,40 @@ impl RocketchatApi {
     }
 }
 
+impl MemberRoles {
+    /// The role with the most permissions, roles that are not mapped by the bridge (like leader) are ignored.
+    pub fn role(&self) -> Option<Role> {
+        if self.roles.iter().any(|role| role == "owner") {
+            Some(Role::Owner)
+        } else if self.roles.iter().any(|role| role == "moderator") {
+            Some(Role::Moderator)
+        } else {
+            None
+        }
+    }
+}
+
+impl Role {
+    /// The role that matches a Matrix power level, users without elevated permissions don't have a role.
+    pub fn from_power_level(power_level: i64) -> Option<Role> {
+        if power_level >= OWNER_POWER_LEVEL {
+            Some(Role::Owner)
+        } else if power_level >= MODERATOR_POWER_LEVEL {
+            Some(Role::Moderator)
+        } else {
+            None
+        }
+    }
+
+    /// The Matrix power level that matches the role.
+    pub fn power_level(self) -> i64 {
+        match self {
+            Role::Moderator => MODERATOR_POWER_LEVEL,
+            Role::Owner => OWNER_POWER_LEVEL,
+        }
+    }
+}
+
 impl Key for WebhookMessage {
     type Value = WebhookMessage;
 }
@@ -8,37 +8,71 @@ use serde_json;
 use slog::Logger;
 
 use api::rocketchat::{
-    Attachment as RocketchatAttachment, Channel, Endpoint, File as RocketchatFile, Message as RocketchatMessage,
-    MessageAttachment, User,
+    Attachment as RocketchatAttachment, Channel, Endpoint, File as RocketchatFile, MemberRoles, Message as RocketchatMessage,
+    MessageAttachment, Role, User,
 };
 use api::{RequestData, RestApi};
 use errors::*;
 use i18n::*;
 
+/// Channel add moderator endpoint path
+pub const CHANNELS_ADD_MODERATOR_PATH: &str = "/api/v1/channels.addModerator";
+/// Channel add owner endpoint path
+pub const CHANNELS_ADD_OWNER_PATH: &str = "/api/v1/channels.addOwner";
+/// Channel kick endpoint path
+pub const CHANNELS_KICK_PATH: &str = "/api/v1/channels.kick";
 /// Room members endpoint path
 pub const CHANNELS_MEMBERS_PATH: &str = "/api/v1/channels.members";
 /// Channels list endpoint path
 pub const CHANNELS_LIST_PATH: &str = "/api/v1/channels.list";
 /// Joined rooms endpoint path
 pub const CHANNELS_LIST_JOINED_PATH: &str = "/api/v1/channels.list.joined";
+/// Channel remove moderator endpoint path
+pub const CHANNELS_REMOVE_MODERATOR_PATH: &str = "/api/v1/channels.removeModerator";
+/// Channel remove owner endpoint path
+pub const CHANNELS_REMOVE_OWNER_PATH: &str = "/api/v1/channels.removeOwner";
+/// Channel roles endpoint path
+pub const CHANNELS_ROLES_PATH: &str = "/api/v1/channels.roles";
 /// Get a chat message endpoint path
 pub const CHAT_GET_MESSAGE_PATH: &str = "/api/v1/chat.getMessage";
 /// Post chat message endpoint path
 pub const CHAT_POST_MESSAGE_PATH: &str = "/api/v1/chat.postMessage";
 /// Direct messages list endpoint path
 pub const DM_LIST_PATH: &str = "/api/v1/dm.list";
+/// Group add moderator endpoint path
+pub const GROUPS_ADD_MODERATOR_PATH: &str = "/api/v1/groups.addModerator";
+/// Group add owner endpoint path
+pub const GROUPS_ADD_OWNER_PATH: &str = "/api/v1/groups.addOwner";
+/// Group kick endpoint path
+pub const GROUPS_KICK_PATH: &str = "/api/v1/groups.kick";
 /// Group list endpoint path
 pub const GROUPS_LIST_PATH: &str = "/api/v1/groups.list";
 /// Group members endpoint path
 pub const GROUPS_MEMBERS_PATH: &str = "/api/v1/groups.members";
+/// Group remove moderator endpoint path
+pub const GROUPS_REMOVE_MODERATOR_PATH: &str = "/api/v1/groups.removeModerator";
+/// Group remove owner endpoint path
+pub const GROUPS_REMOVE_OWNER_PATH: &str = "/api/v1/groups.removeOwner";
+/// Group roles endpoint path
+pub const GROUPS_ROLES_PATH: &str = "/api/v1/groups.roles";
+/// Create direct message endpoint path
+pub const IM_CREATE_PATH: &str = "/api/v1/im.create";
+/// Direct message members endpoint path
+pub const IM_MEMBERS_PATH: &str = "/api/v1/im.members";
 /// Login endpoint path
 pub const LOGIN_PATH: &str = "/api/v1/login";
+/// Logout endpoint path
+pub const LOGOUT_PATH: &str = "/api/v1/logout";
 /// Me endpoint path
 pub const ME_PATH: &str = "/api/v1/me";
 /// Users list endpoint path
 pub const USERS_INFO_PATH: &str = "/api/v1/users.info";
 /// Upload a file endpoint path
 pub const ROOMS_UPLOAD_PATH: &str = "/api/v1/rooms.upload";
+/// Error that is returned by the login endpoint when a two-factor authentication code is needed
+pub const TWO_FACTOR_REQUIRED_ERROR: &str = "totp-required";
+/// Error that is returned by the login endpoint when the two-factor authentication code is wrong
+pub const TWO_FACTOR_INVALID_ERROR: &str = "totp-invalid";
 
 /// A single Message on the Rocket.Chat server.
 #[derive(Deserialize, Debug, Serialize)]
@@ -222,6 +256,86 @@ impl<'a> Endpoint<String> for GroupsMembersEndpoint<'a> {
     }
 }
 
+/// V1 endpoints to remove a user from a channel or a group or to change the roles of a user
+pub struct RoomMemberEndpoint<'a> {
+    base_url: String,
+    user_id: String,
+    auth_token: String,
+    path: &'a str,
+    payload: RoomMemberPayload<'a>,
+}
+
+/// Payload of the endpoints that change the membership or the roles of a user
+#[derive(Serialize)]
+pub struct RoomMemberPayload<'a> {
+    #[serde(rename = "roomId")]
+    room_id: &'a str,
+    #[serde(rename = "userId")]
+    user_id: &'a str,
+}
+
+impl<'a> Endpoint<String> for RoomMemberEndpoint<'a> {
+    fn method(&self) -> Method {
+        Method::POST
+    }
+
+    fn url(&self) -> String {
+        self.base_url.clone() + self.path
+    }
+
+    fn payload(&self) -> Result<RequestData<String>> {
+        let payload = serde_json::to_string(&self.payload)
+            .chain_err(|| ErrorKind::InvalidJSON("Could not serialize room member payload".to_string()))?;
+        Ok(RequestData::Body(payload))
+    }
+
+    fn headers(&self) -> Result<Option<HeaderMap>> {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_str("application/json")?);
+        headers.insert("X-User-Id", HeaderValue::from_str(&self.user_id)?);
+        headers.insert("X-Auth-Token", HeaderValue::from_str(&self.auth_token)?);
+        Ok(Some(headers))
+    }
+}
+
+/// V1 create direct message endpoint
+pub struct ImCreateEndpoint<'a> {
+    base_url: String,
+    user_id: String,
+    auth_token: String,
+    payload: ImCreatePayload<'a>,
+}
+
+/// Payload of the create direct message endpoint
+#[derive(Serialize)]
+pub struct ImCreatePayload<'a> {
+    username: &'a str,
+}
+
+impl<'a> Endpoint<String> for ImCreateEndpoint<'a> {
+    fn method(&self) -> Method {
+        Method::POST
+    }
+
+    fn url(&self) -> String {
+        self.base_url.clone() + IM_CREATE_PATH
+    }
+
+    fn payload(&self) -> Result<RequestData<String>> {
+        let payload = serde_json::to_string(&self.payload)
+            .chain_err(|| ErrorKind::InvalidJSON("Could not serialize create direct message payload".to_string()))?;
+        Ok(RequestData::Body(payload))
+    }
+
+    fn headers(&self) -> Result<Option<HeaderMap>> {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_str("application/json")?);
+        headers.insert("X-User-Id", HeaderValue::from_str(&self.user_id)?);
+        headers.insert("X-Auth-Token", HeaderValue::from_str(&self.auth_token)?);
+        Ok(Some(headers))
+    }
+}
+
 /// V1 login endpoint
 pub struct LoginEndpoint<'a> {
     base_url: String,
@@ -233,6 +347,8 @@ pub struct LoginEndpoint<'a> {
 pub struct LoginPayload<'a> {
     username: &'a str,
     password: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code: Option<&'a str>,
 }
 
 impl<'a> Endpoint<String> for LoginEndpoint<'a> {
@@ -255,6 +371,35 @@ impl<'a> Endpoint<String> for LoginEndpoint<'a> {
     }
 }
 
+/// V1 logout endpoint
+pub struct LogoutEndpoint {
+    base_url: String,
+    user_id: String,
+    auth_token: String,
+}
+
+impl Endpoint<String> for LogoutEndpoint {
+    fn method(&self) -> Method {
+        Method::POST
+    }
+
+    fn url(&self) -> String {
+        self.base_url.clone() + LOGOUT_PATH
+    }
+
+    fn payload(&self) -> Result<RequestData<String>> {
+        Ok(RequestData::Body("".to_string()))
+    }
+
+    fn headers(&self) -> Result<Option<HeaderMap>> {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_str("application/json")?);
+        headers.insert("X-User-Id", HeaderValue::from_str(&self.user_id)?);
+        headers.insert("X-Auth-Token", HeaderValue::from_str(&self.auth_token)?);
+        Ok(Some(headers))
+    }
+}
+
 /// V1 post chat message endpoint
 pub struct ChatPostMessageEndpoint<'a> {
     base_url: String,
@@ -269,6 +414,10 @@ pub struct PostChatMessagePayload<'a> {
     #[serde(rename = "roomId")]
     room_id: &'a str,
     text: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    alias: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    avatar: Option<&'a str>,
 }
 
 impl<'a> Endpoint<String> for ChatPostMessageEndpoint<'a> {
@@ -309,6 +458,7 @@ pub struct PostFileMessagePayload<'a> {
     file: Vec<u8>,
     filename: &'a str,
     mimetype: HeaderValue,
+    message: Option<&'a str>,
 }
 
 impl<'a> Endpoint<String> for RoomsUploadEndpoint<'a> {
@@ -326,7 +476,11 @@ impl<'a> Endpoint<String> for RoomsUploadEndpoint<'a> {
         c.seek(SeekFrom::Start(0))?;
 
         let part = Part::reader(c).file_name(self.payload.filename.to_owned()).mime_str(self.payload.mimetype.to_str()?)?;
-        let form = Form::new().part("file", part);
+        let mut form = Form::new().part("file", part);
+        if let Some(message) = self.payload.message {
+            form = form.text("msg", message.to_owned());
+        }
+
         Ok(RequestData::MultipartForm(form))
     }
 
@@ -432,6 +586,12 @@ impl<'a> Endpoint<String> for ChannelsListJoinedEndpoint<'a> {
 pub struct ChannelsListResponse {
     /// A list of channels on the Rocket.Chat server
     pub channels: Vec<Channel>,
+    /// Number of channels in this response
+    pub count: i32,
+    /// Number of channels that were skipped
+    pub offset: i32,
+    /// Number of channels on the Rocket.Chat server
+    pub total: i32,
 }
 
 /// User credentials.
@@ -452,15 +612,35 @@ pub struct DMListResponse {
     pub ims: Vec<Channel>,
 }
 
+/// Response payload from the Rocket.Chat im.create endpoint.
+#[derive(Deserialize)]
+pub struct ImCreateResponse {
+    /// The direct message room, it's returned as well if the room already existed
+    pub room: Channel,
+}
+
 /// Response payload from the Rocket.Chat groups.list endpoint.
 #[derive(Deserialize)]
 pub struct GroupsListResponse {
     /// A list of groups on the Rocket.Chat server
     pub groups: Vec<Channel>,
+    /// Number of groups in this response
+    pub count: i32,
+    /// Number of groups that were skipped
+    pub offset: i32,
+    /// Number of groups the user is a member of
+    pub total: i32,
 }
 
+/// Response payload from the Rocket.Chat channels.roles and groups.roles endpoints.
 #[derive(Deserialize)]
+pub struct RolesResponse {
+    /// Members of the channel or group that have a role
+    pub roles: Vec<MemberRoles>,
+}
+
 /// Response payload from the Rocket.Chat login endpoint.
+#[derive(Deserialize)]
 pub struct LoginResponse {
     /// Status of the response (success, error)
     pub status: String,
@@ -468,6 +648,22 @@ pub struct LoginResponse {
     pub data: Credentials,
 }
 
+/// Error payload from the Rocket.Chat login endpoint when a two-factor authentication code is needed.
+#[derive(Deserialize, Default)]
+pub struct TwoFactorErrorResponse {
+    /// Information about the requested code
+    #[serde(default)]
+    pub details: Option<TwoFactorErrorDetails>,
+}
+
+/// Information about the two-factor authentication code that the Rocket.Chat server requested.
+#[derive(Deserialize)]
+pub struct TwoFactorErrorDetails {
+    /// The method that is used to generate the code (totp, email)
+    #[serde(default)]
+    pub method: Option<String>,
+}
+
 /// Response payload from the Rocket.Chat me endpoint.
 #[derive(Deserialize)]
 pub struct MeResponse {
@@ -482,6 +678,22 @@ pub struct UsersInfoResponse {
     pub user: User,
 }
 
+/// Response payload from the Rocket.Chat chat.postMessage and rooms.upload endpoints.
+#[derive(Deserialize, Default)]
+pub struct PostMessageResponse {
+    /// The message that was created, older Rocket.Chat versions don't return it for uploads
+    #[serde(default)]
+    pub message: Option<PostedMessage>,
+}
+
+/// A message that was created on the Rocket.Chat server.
+#[derive(Deserialize)]
+pub struct PostedMessage {
+    /// The unique message identifier
+    #[serde(rename = "_id")]
+    pub id: String,
+}
+
 /// Response payload from the Rocket.Chat chat.message endpoint.
 #[derive(Deserialize)]
 pub struct MessageResponse {
@@ -509,12 +721,14 @@ pub struct RocketchatApi {
     user_id: String,
     /// The auth token that is passed to the auth header
     auth_token: String,
+    /// The version of the Rocket.Chat server
+    version: String,
 }
 
 impl RocketchatApi {
     /// Create a new `RocketchatApi`.
-    pub fn new(base_url: String, logger: Logger) -> RocketchatApi {
-        RocketchatApi { base_url, logger, user_id: "".to_string(), auth_token: "".to_string() }
+    pub fn new(base_url: String, version: String, logger: Logger) -> RocketchatApi {
+        RocketchatApi { base_url, logger, user_id: "".to_string(), auth_token: "".to_string(), version }
     }
 }
 
@@ -559,6 +773,20 @@ impl super::RocketchatApi for RocketchatApi {
         Ok(files)
     }
 
+    fn channels_add_role(&self, room_id: &str, user_id: &str, role: Role) -> Result<()> {
+        debug!(self.logger, "Granting user {} the role {:?} in channel {} on Rocket.Chat server", user_id, role, room_id);
+        let path = match role {
+            Role::Moderator => CHANNELS_ADD_MODERATOR_PATH,
+            Role::Owner => CHANNELS_ADD_OWNER_PATH,
+        };
+        post_room_member(self, path, room_id, user_id)
+    }
+
+    fn channels_kick(&self, room_id: &str, user_id: &str) -> Result<()> {
+        debug!(self.logger, "Removing user {} from channel {} on Rocket.Chat server", user_id, room_id);
+        post_room_member(self, CHANNELS_KICK_PATH, room_id, user_id)
+    }
+
     fn channels_members(&self, room_id: &str) -> Result<Vec<User>> {
         debug!(self.logger, "Getting rooms members for room {} from Rocket.Chat server", room_id);
 
@@ -585,28 +813,24 @@ impl super::RocketchatApi for RocketchatApi {
     fn channels_list(&self) -> Result<Vec<Channel>> {
         debug!(self.logger, "Getting channel list from Rocket.Chat server {}", &self.base_url);
 
-        let channels_list_endpoint = GetWithAuthEndpoint {
-            base_url: self.base_url.clone(),
-            user_id: self.user_id.clone(),
-            auth_token: self.auth_token.clone(),
-            path: CHANNELS_LIST_PATH,
-            query_params: HashMap::new(),
-        };
+        let mut channels = Vec::new();
+        let mut offset = 0;
+        for i in 0..super::MAX_REQUESTS_PER_ENDPOINT_CALL {
+            if i == super::MAX_REQUESTS_PER_ENDPOINT_CALL {
+                bail_error!(ErrorKind::TooManyRequests(CHANNELS_LIST_PATH.to_string()))
+            }
 
-        let (body, status_code) = RestApi::call_rocketchat(&channels_list_endpoint)?;
-        if !status_code.is_success() {
-            return Err(build_error(&channels_list_endpoint.url(), &body, &status_code));
-        }
+            let mut channels_response = get_channels_list(&self, offset)?;
+            channels.append(&mut channels_response.channels);
+            let subtotal = channels_response.count + channels_response.offset;
+            if subtotal == channels_response.total {
+                break;
+            }
 
-        let channels_list_response: ChannelsListResponse = serde_json::from_str(&body).chain_err(|| {
-            ErrorKind::InvalidJSON(format!(
-                "Could not deserialize response from Rocket.Chat channels.list API \
-                 endpoint: `{}`",
-                body
-            ))
-        })?;
+            offset = subtotal;
+        }
 
-        Ok(channels_list_response.channels)
+        Ok(channels)
     }
 
     fn channels_list_joined(&self) -> Result<Vec<Channel>> {
@@ -632,6 +856,20 @@ impl super::RocketchatApi for RocketchatApi {
         Ok(channels)
     }
 
+    fn channels_remove_role(&self, room_id: &str, user_id: &str, role: Role) -> Result<()> {
+        debug!(self.logger, "Revoking the role {:?} of user {} in channel {} on Rocket.Chat server", role, user_id, room_id);
+        let path = match role {
+            Role::Moderator => CHANNELS_REMOVE_MODERATOR_PATH,
+            Role::Owner => CHANNELS_REMOVE_OWNER_PATH,
+        };
+        post_room_member(self, path, room_id, user_id)
+    }
+
+    fn channels_roles(&self, room_id: &str) -> Result<Vec<MemberRoles>> {
+        debug!(self.logger, "Getting roles for channel {} from Rocket.Chat server", room_id);
+        get_roles(self, CHANNELS_ROLES_PATH, room_id)
+    }
+
     fn chat_get_message(&self, message_id: &str) -> Result<RocketchatMessage> {
         debug!(self.logger, "Retreiving message {}", message_id);
 
@@ -687,14 +925,14 @@ impl super::RocketchatApi for RocketchatApi {
         Ok(message)
     }
 
-    fn chat_post_message(&self, text: &str, room_id: &str) -> Result<()> {
+    fn chat_post_message(&self, text: &str, room_id: &str) -> Result<Option<String>> {
         debug!(self.logger, "Forwarding message to to Rocket.Chat room {}", room_id);
 
         let chat_post_message_endpoint = ChatPostMessageEndpoint {
             base_url: self.base_url.clone(),
             user_id: self.user_id.clone(),
             auth_token: self.auth_token.clone(),
-            payload: PostChatMessagePayload { text: Some(text), room_id },
+            payload: PostChatMessagePayload { text: Some(text), room_id, alias: None, avatar: None },
         };
 
         let (body, status_code) = RestApi::call_rocketchat(&chat_post_message_endpoint)?;
@@ -702,7 +940,25 @@ impl super::RocketchatApi for RocketchatApi {
             return Err(build_error(&chat_post_message_endpoint.url(), &body, &status_code));
         }
 
-        Ok(())
+        Ok(posted_message_id(&body))
+    }
+
+    fn chat_post_relay_message(&self, text: &str, room_id: &str, alias: &str, avatar: Option<&str>) -> Result<Option<String>> {
+        debug!(self.logger, "Relaying message from {} to Rocket.Chat room {}", alias, room_id);
+
+        let chat_post_message_endpoint = ChatPostMessageEndpoint {
+            base_url: self.base_url.clone(),
+            user_id: self.user_id.clone(),
+            auth_token: self.auth_token.clone(),
+            payload: PostChatMessagePayload { text: Some(text), room_id, alias: Some(alias), avatar },
+        };
+
+        let (body, status_code) = RestApi::call_rocketchat(&chat_post_message_endpoint)?;
+        if !status_code.is_success() {
+            return Err(build_error(&chat_post_message_endpoint.url(), &body, &status_code));
+        }
+
+        Ok(posted_message_id(&body))
     }
 
     fn dm_list(&self) -> Result<Vec<Channel>> {
@@ -732,44 +988,116 @@ impl super::RocketchatApi for RocketchatApi {
         Ok(direct_messages_list_response.ims)
     }
 
+    fn groups_add_role(&self, room_id: &str, user_id: &str, role: Role) -> Result<()> {
+        debug!(self.logger, "Granting user {} the role {:?} in group {} on Rocket.Chat server", user_id, role, room_id);
+        let path = match role {
+            Role::Moderator => GROUPS_ADD_MODERATOR_PATH,
+            Role::Owner => GROUPS_ADD_OWNER_PATH,
+        };
+        post_room_member(self, path, room_id, user_id)
+    }
+
+    fn groups_kick(&self, room_id: &str, user_id: &str) -> Result<()> {
+        debug!(self.logger, "Removing user {} from group {} on Rocket.Chat server", user_id, room_id);
+        post_room_member(self, GROUPS_KICK_PATH, room_id, user_id)
+    }
+
     fn groups_list(&self) -> Result<Vec<Channel>> {
         debug!(self.logger, "Getting group list from Rocket.Chat server {}", &self.base_url);
 
-        let channels_list_endpoint = GetWithAuthEndpoint {
+        let mut groups = Vec::new();
+        let mut offset = 0;
+        for i in 0..super::MAX_REQUESTS_PER_ENDPOINT_CALL {
+            if i == super::MAX_REQUESTS_PER_ENDPOINT_CALL {
+                bail_error!(ErrorKind::TooManyRequests(GROUPS_LIST_PATH.to_string()))
+            }
+
+            let mut groups_response = get_groups_list(&self, offset)?;
+            groups.append(&mut groups_response.groups);
+            let subtotal = groups_response.count + groups_response.offset;
+            if subtotal == groups_response.total {
+                break;
+            }
+
+            offset = subtotal;
+        }
+
+        Ok(groups)
+    }
+
+    fn groups_members(&self, room_id: &str) -> Result<Vec<User>> {
+        debug!(self.logger, "Getting group members for group {} from Rocket.Chat server", room_id);
+
+        let mut users = Vec::new();
+        let mut offset = 0;
+        for i in 0..super::MAX_REQUESTS_PER_ENDPOINT_CALL {
+            if i == super::MAX_REQUESTS_PER_ENDPOINT_CALL {
+                bail_error!(ErrorKind::TooManyRequests(GROUPS_MEMBERS_PATH.to_string()))
+            }
+
+            let mut members_response = get_group_members(&self, room_id, offset)?;
+            users.append(&mut members_response.members);
+            let subtotal = members_response.count + members_response.offset;
+            if subtotal == members_response.total {
+                break;
+            }
+
+            offset = subtotal;
+        }
+
+        Ok(users)
+    }
+
+    fn groups_remove_role(&self, room_id: &str, user_id: &str, role: Role) -> Result<()> {
+        debug!(self.logger, "Revoking the role {:?} of user {} in group {} on Rocket.Chat server", role, user_id, room_id);
+        let path = match role {
+            Role::Moderator => GROUPS_REMOVE_MODERATOR_PATH,
+            Role::Owner => GROUPS_REMOVE_OWNER_PATH,
+        };
+        post_room_member(self, path, room_id, user_id)
+    }
+
+    fn groups_roles(&self, room_id: &str) -> Result<Vec<MemberRoles>> {
+        debug!(self.logger, "Getting roles for group {} from Rocket.Chat server", room_id);
+        get_roles(self, GROUPS_ROLES_PATH, room_id)
+    }
+
+    fn im_create(&self, username: &str) -> Result<Channel> {
+        debug!(self.logger, "Creating direct message with user {} on Rocket.Chat server {}", username, &self.base_url);
+
+        let im_create_endpoint = ImCreateEndpoint {
             base_url: self.base_url.clone(),
             user_id: self.user_id.clone(),
             auth_token: self.auth_token.clone(),
-            path: GROUPS_LIST_PATH,
-            query_params: HashMap::new(),
+            payload: ImCreatePayload { username },
         };
 
-        let (body, status_code) = RestApi::call_rocketchat(&channels_list_endpoint)?;
+        let (body, status_code) = RestApi::call_rocketchat(&im_create_endpoint)?;
         if !status_code.is_success() {
-            return Err(build_error(&channels_list_endpoint.url(), &body, &status_code));
+            return Err(build_error(&im_create_endpoint.url(), &body, &status_code));
         }
 
-        let channels_list_response: GroupsListResponse = serde_json::from_str(&body).chain_err(|| {
+        let im_create_response: ImCreateResponse = serde_json::from_str(&body).chain_err(|| {
             ErrorKind::InvalidJSON(format!(
-                "Could not deserialize response from Rocket.Chat groups.list API \
-                 endpoint: `{}`",
+                "Could not deserialize response from Rocket.Chat im.create API endpoint: `{}`",
                 body
             ))
         })?;
 
-        Ok(channels_list_response.groups)
+        Ok(im_create_response.room)
     }
 
-    fn groups_members(&self, room_id: &str) -> Result<Vec<User>> {
-        debug!(self.logger, "Getting group members for group {} from Rocket.Chat server", room_id);
+    fn im_members(&self, room_id: &str) -> Result<Vec<User>> {
+        debug!(self.logger, "Getting direct message members for room {} from Rocket.Chat server", room_id);
 
         let mut users = Vec::new();
         let mut offset = 0;
         for i in 0..super::MAX_REQUESTS_PER_ENDPOINT_CALL {
             if i == super::MAX_REQUESTS_PER_ENDPOINT_CALL {
-                bail_error!(ErrorKind::TooManyRequests(GROUPS_MEMBERS_PATH.to_string()))
+                bail_error!(ErrorKind::TooManyRequests(IM_MEMBERS_PATH.to_string()))
             }
 
-            let mut members_response = get_group_members(&self, room_id, offset)?;
+            let mut members_response = get_im_members(&self, room_id, offset)?;
             users.append(&mut members_response.members);
             let subtotal = members_response.count + members_response.offset;
             if subtotal == members_response.total {
@@ -782,10 +1110,11 @@ impl super::RocketchatApi for RocketchatApi {
         Ok(users)
     }
 
-    fn login(&self, username: &str, password: &str) -> Result<(String, String)> {
+    fn login(&self, username: &str, password: &str, code: Option<&str>) -> Result<(String, String)> {
         debug!(self.logger, "Logging in user with username {} on Rocket.Chat server {}", username, &self.base_url);
 
-        let login_endpoint = LoginEndpoint { base_url: self.base_url.clone(), payload: LoginPayload { username, password } };
+        let login_endpoint =
+            LoginEndpoint { base_url: self.base_url.clone(), payload: LoginPayload { username, password, code } };
 
         let (body, status_code) = RestApi::call_rocketchat(&login_endpoint)?;
         if !status_code.is_success() {
@@ -798,6 +1127,23 @@ impl super::RocketchatApi for RocketchatApi {
         Ok((login_response.data.user_id, login_response.data.auth_token))
     }
 
+    fn logout(&self) -> Result<()> {
+        debug!(self.logger, "Logging out user with user_id {} on Rocket.Chat server {}", self.user_id, &self.base_url);
+
+        let logout_endpoint = LogoutEndpoint {
+            base_url: self.base_url.clone(),
+            user_id: self.user_id.clone(),
+            auth_token: self.auth_token.clone(),
+        };
+
+        let (body, status_code) = RestApi::call_rocketchat(&logout_endpoint)?;
+        if !status_code.is_success() {
+            return Err(build_error(&logout_endpoint.url(), &body, &status_code));
+        }
+
+        Ok(())
+    }
+
     fn me(&self) -> Result<User> {
         debug!(self.logger, "Querying username for user_id {} on Rocket.Chat server {}", self.user_id, &self.base_url);
 
@@ -821,14 +1167,21 @@ impl super::RocketchatApi for RocketchatApi {
         Ok(user)
     }
 
-    fn rooms_upload(&self, file: Vec<u8>, filename: &str, mimetype: HeaderValue, room_id: &str) -> Result<()> {
+    fn rooms_upload(
+        &self,
+        file: Vec<u8>,
+        filename: &str,
+        mimetype: HeaderValue,
+        message: Option<&str>,
+        room_id: &str,
+    ) -> Result<Option<String>> {
         debug!(self.logger, "Uploading file to room {}", room_id);
 
         let post_file_message_endpoint = RoomsUploadEndpoint {
             base_url: self.base_url.clone(),
             user_id: self.user_id.clone(),
             auth_token: self.auth_token.clone(),
-            payload: PostFileMessagePayload { file, filename, mimetype },
+            payload: PostFileMessagePayload { file, filename, mimetype, message },
             room_id,
         };
 
@@ -837,35 +1190,21 @@ impl super::RocketchatApi for RocketchatApi {
             return Err(build_error(&post_file_message_endpoint.url(), &body, &status_code));
         }
 
-        Ok(())
+        Ok(posted_message_id(&body))
     }
 
     fn users_info(&self, username: &str) -> Result<User> {
         debug!(self.logger, "Querying user info for user {} on Rocket.Chat server {}", &username, &self.base_url);
+        get_users_info(self, "username", username)
+    }
 
-        let mut query_params = HashMap::new();
-        query_params.insert("username", username);
-        let users_info_endpoint = GetWithAuthEndpoint {
-            base_url: self.base_url.clone(),
-            user_id: self.user_id.clone(),
-            auth_token: self.auth_token.clone(),
-            path: USERS_INFO_PATH,
-            query_params,
-        };
-
-        let (body, status_code) = RestApi::call_rocketchat(&users_info_endpoint)?;
-        if !status_code.is_success() {
-            return Err(build_error(&users_info_endpoint.url(), &body, &status_code));
-        }
-
-        let users_info_response: UsersInfoResponse = serde_json::from_str(&body).chain_err(|| {
-            ErrorKind::InvalidJSON(format!(
-                "Could not deserialize response from Rocket.Chat users.info API endpoint: `{}`",
-                body
-            ))
-        })?;
+    fn users_info_by_id(&self, user_id: &str) -> Result<User> {
+        debug!(self.logger, "Querying user info for user ID {} on Rocket.Chat server {}", &user_id, &self.base_url);
+        get_users_info(self, "userId", user_id)
+    }
 
-        Ok(users_info_response.user)
+    fn version(&self) -> String {
+        self.version.clone()
     }
 
     fn with_credentials(mut self: Box<Self>, user_id: String, auth_token: String) -> Box<super::RocketchatApi> {
@@ -875,6 +1214,12 @@ impl super::RocketchatApi for RocketchatApi {
     }
 }
 
+// The message ID is only used to detect echoes, so a response without a message is not an error.
+fn posted_message_id(body: &str) -> Option<String> {
+    let post_message_response: PostMessageResponse = serde_json::from_str(body).unwrap_or_default();
+    post_message_response.message.map(|message| message.id)
+}
+
 fn get_channel_members(rocketchat_api: &RocketchatApi, room_id: &str, offset: i32) -> Result<MembersResponse> {
     let offset_param = offset.to_string();
     let mut query_params = HashMap::new();
@@ -927,6 +1272,82 @@ fn get_group_members(rocketchat_api: &RocketchatApi, room_id: &str, offset: i32)
     Ok(group_members_response)
 }
 
+fn get_im_members(rocketchat_api: &RocketchatApi, room_id: &str, offset: i32) -> Result<MembersResponse> {
+    let offset_param = offset.to_string();
+    let mut query_params = HashMap::new();
+    query_params.insert("roomId", room_id);
+    query_params.insert("offset", &offset_param);
+    let im_members_endpoint = GetWithAuthEndpoint {
+        base_url: rocketchat_api.base_url.clone(),
+        user_id: rocketchat_api.user_id.clone(),
+        auth_token: rocketchat_api.auth_token.clone(),
+        path: IM_MEMBERS_PATH,
+        query_params,
+    };
+
+    let (body, status_code) = RestApi::call_rocketchat(&im_members_endpoint)?;
+    if !status_code.is_success() {
+        return Err(build_error(&im_members_endpoint.url(), &body, &status_code));
+    }
+
+    let im_members_response: MembersResponse = serde_json::from_str(&body).chain_err(|| {
+        ErrorKind::InvalidJSON(format!(
+            "Could not deserialize response from Rocket.Chat direct message members API endpoint: `{}`",
+            body
+        ))
+    })?;
+    Ok(im_members_response)
+}
+
+fn get_channels_list(rocketchat_api: &RocketchatApi, offset: i32) -> Result<ChannelsListResponse> {
+    let offset_param = offset.to_string();
+    let mut query_params = HashMap::new();
+    query_params.insert("offset", offset_param.as_ref());
+    let channels_list_endpoint = GetWithAuthEndpoint {
+        base_url: rocketchat_api.base_url.clone(),
+        user_id: rocketchat_api.user_id.clone(),
+        auth_token: rocketchat_api.auth_token.clone(),
+        path: CHANNELS_LIST_PATH,
+        query_params,
+    };
+
+    let (body, status_code) = RestApi::call_rocketchat(&channels_list_endpoint)?;
+    if !status_code.is_success() {
+        return Err(build_error(&channels_list_endpoint.url(), &body, &status_code));
+    }
+
+    let channels_list_response: ChannelsListResponse = serde_json::from_str(&body).chain_err(|| {
+        ErrorKind::InvalidJSON(format!(
+            "Could not deserialize response from Rocket.Chat channels.list API endpoint: `{}`",
+            body
+        ))
+    })?;
+    Ok(channels_list_response)
+}
+
+fn get_groups_list(rocketchat_api: &RocketchatApi, offset: i32) -> Result<GroupsListResponse> {
+    let offset_param = offset.to_string();
+    let mut query_params = HashMap::new();
+    query_params.insert("offset", offset_param.as_ref());
+    let groups_list_endpoint = GetWithAuthEndpoint {
+        base_url: rocketchat_api.base_url.clone(),
+        user_id: rocketchat_api.user_id.clone(),
+        auth_token: rocketchat_api.auth_token.clone(),
+        path: GROUPS_LIST_PATH,
+        query_params,
+    };
+
+    let (body, status_code) = RestApi::call_rocketchat(&groups_list_endpoint)?;
+    if !status_code.is_success() {
+        return Err(build_error(&groups_list_endpoint.url(), &body, &status_code));
+    }
+
+    let groups_list_response: GroupsListResponse = serde_json::from_str(&body).chain_err(|| {
+        ErrorKind::InvalidJSON(format!("Could not deserialize response from Rocket.Chat groups.list API endpoint: `{}`", body))
+    })?;
+    Ok(groups_list_response)
+}
+
 fn channels_list_joined(rocketchat_api: &RocketchatApi, offset: i32) -> Result<GetJoinedChannelsResponse> {
     let offset_param = offset.to_string();
     let mut query_params = HashMap::new();
@@ -952,6 +1373,74 @@ fn channels_list_joined(rocketchat_api: &RocketchatApi, offset: i32) -> Result<G
     Ok(channels_list_joined_response)
 }
 
+fn get_roles(rocketchat_api: &RocketchatApi, path: &str, room_id: &str) -> Result<Vec<MemberRoles>> {
+    let mut query_params = HashMap::new();
+    query_params.insert("roomId", room_id);
+    let roles_endpoint = GetWithAuthEndpoint {
+        base_url: rocketchat_api.base_url.clone(),
+        user_id: rocketchat_api.user_id.clone(),
+        auth_token: rocketchat_api.auth_token.clone(),
+        path,
+        query_params,
+    };
+
+    let (body, status_code) = RestApi::call_rocketchat(&roles_endpoint)?;
+    if !status_code.is_success() {
+        return Err(build_error(&roles_endpoint.url(), &body, &status_code));
+    }
+
+    let roles_response: RolesResponse = serde_json::from_str(&body).chain_err(|| {
+        ErrorKind::InvalidJSON(format!("Could not deserialize response from Rocket.Chat {} API endpoint: `{}`", path, body))
+    })?;
+    Ok(roles_response.roles)
+}
+
+fn post_room_member(rocketchat_api: &RocketchatApi, path: &str, room_id: &str, user_id: &str) -> Result<()> {
+    let room_member_endpoint = RoomMemberEndpoint {
+        base_url: rocketchat_api.base_url.clone(),
+        user_id: rocketchat_api.user_id.clone(),
+        auth_token: rocketchat_api.auth_token.clone(),
+        path,
+        payload: RoomMemberPayload { room_id, user_id },
+    };
+
+    let (body, status_code) = RestApi::call_rocketchat(&room_member_endpoint)?;
+    if !status_code.is_success() {
+        return Err(build_error(&room_member_endpoint.url(), &body, &status_code));
+    }
+
+    Ok(())
+}
+
+fn get_users_info(rocketchat_api: &RocketchatApi, key: &'static str, value: &str) -> Result<User> {
+    let mut query_params = HashMap::new();
+    query_params.insert(key, value);
+    let users_info_endpoint = GetWithAuthEndpoint {
+        base_url: rocketchat_api.base_url.clone(),
+        user_id: rocketchat_api.user_id.clone(),
+        auth_token: rocketchat_api.auth_token.clone(),
+        path: USERS_INFO_PATH,
+        query_params,
+    };
+
+    let (body, status_code) = RestApi::call_rocketchat(&users_info_endpoint)?;
+    if !status_code.is_success() {
+        return Err(build_error(&users_info_endpoint.url(), &body, &status_code));
+    }
+
+    let users_info_response: UsersInfoResponse = serde_json::from_str(&body).chain_err(|| {
+        ErrorKind::InvalidJSON(format!("Could not deserialize response from Rocket.Chat users.info API endpoint: `{}`", body))
+    })?;
+
+    Ok(users_info_response.user)
+}
+
+// The method (totp, email) is only sent by newer Rocket.Chat versions, older versions only support TOTP.
+fn two_factor_method(body: &str) -> String {
+    let two_factor_error_response: TwoFactorErrorResponse = serde_json::from_str(body).unwrap_or_default();
+    two_factor_error_response.details.and_then(|details| details.method).unwrap_or_else(|| "totp".to_string())
+}
+
 fn build_error(endpoint: &str, body: &str, status_code: &StatusCode) -> Error {
     let json_error_msg = format!(
         "Could not deserialize error from Rocket.Chat API endpoint {} with status code {}: `{}`",
@@ -961,11 +1450,35 @@ fn build_error(endpoint: &str, body: &str, status_code: &StatusCode) -> Error {
     let rocketchat_error_resp: RocketchatErrorResponse =
         match serde_json::from_str(body).chain_err(|| json_error).map_err(Error::from) {
             Ok(rocketchat_error_resp) => rocketchat_error_resp,
+            // the credentials were rejected, some Rocket.Chat versions don't send a JSON body in that case
+            Err(_) if *status_code == StatusCode::UNAUTHORIZED => {
+                return Error {
+                    error_chain: ErrorKind::RocketchatAuthenticationFailed(body.to_string()).into(),
+                    user_message: Some(t!(["errors", "authentication_failed"])),
+                };
+            }
             Err(err) => {
                 return err;
             }
         };
 
+    match rocketchat_error_resp.error.as_ref().map(String::as_str) {
+        Some(TWO_FACTOR_REQUIRED_ERROR) => {
+            let method = two_factor_method(body);
+            return Error {
+                error_chain: ErrorKind::RocketchatTwoFactorRequired(method.clone()).into(),
+                user_message: Some(t!(["errors", "two_factor_required"]).with_vars(vec![("method", method)])),
+            };
+        }
+        Some(TWO_FACTOR_INVALID_ERROR) => {
+            return Error {
+                error_chain: ErrorKind::RocketchatTwoFactorCodeInvalid.into(),
+                user_message: Some(t!(["errors", "two_factor_code_invalid"])),
+            };
+        }
+        _ => {}
+    }
+
     if *status_code == StatusCode::UNAUTHORIZED {
         return Error {
             error_chain: ErrorKind::RocketchatAuthenticationFailed(rocketchat_error_resp.message.unwrap_or_default()).into(),
@@ -1,7 +1,9 @@
 use std::convert::TryFrom;
+use std::env;
 use std::fs::File;
 use std::io::Read;
 use std::net::SocketAddr;
+use std::time::Duration;
 
 use ruma_identifiers::UserId;
 use serde_yaml;
@@ -21,6 +23,9 @@ pub struct Config {
     pub as_url: String,
     /// The URL under wich the homeserver is reachable.
     pub hs_url: String,
+    /// The URL under which users reach the homeserver, it's used for links to media (like avatars)
+    /// that are shown on the Rocket.Chat server. Defaults to `hs_url`.
+    pub hs_public_url: Option<String>,
     /// Domain of the homeserver
     pub hs_domain: String,
     /// Local part of the bot name which is also the namespace of the application service
@@ -44,8 +49,37 @@ pub struct Config {
     pub pkcs12_path: Option<String>,
     /// Password to decrypt the PKCS 12 file
     pub pkcs12_password: Option<String>,
+    /// Template that is used when relaying messages from Matrix users that are not logged in on
+    /// the Rocket.Chat server. `{displayname}` and `{message}` are replaced with the display name
+    /// of the sender and the message body. Defaults to `**{displayname}**: {message}`.
+    pub relay_message_template: Option<String>,
+    /// Shared secret of the homeservers shared secret authenticator. If it is set, the application
+    /// service logs in local Matrix users to send messages they write in a Rocket.Chat client as
+    /// their real Matrix user (double puppeting).
+    pub double_puppeting_shared_secret: Option<String>,
+    /// Key that is used to encrypt the Rocket.Chat tokens that are stored in the database, 32 bytes
+    /// encoded as hex. The environment variable `MATRIX_ROCKETCHAT_DATABASE_ENCRYPTION_KEY` takes precedence.
+    pub database_encryption_key: Option<String>,
+    /// Matrix users that are allowed to manage all connected Rocket.Chat servers (change the URL,
+    /// rotate the webhook token, delete the server).
+    pub bridge_admins: Option<Vec<UserId>>,
+    /// If this flag is set to true, the bridged rooms of public Rocket.Chat channels are published to
+    /// the room directory of the homeserver, listed in the network of their Rocket.Chat server.
+    pub publish_rooms_to_directory: Option<bool>,
+    /// If this flag is set to true, a Matrix space is created for each connected Rocket.Chat server,
+    /// which contains the bridged rooms of the public channels of the server.
+    pub create_spaces: Option<bool>,
+    /// Interval in seconds in which the roles of the members of bridged channels and groups are
+    /// synchronized with the power levels of the rooms. Defaults to 600 seconds, 0 disables it.
+    pub role_sync_interval_in_seconds: Option<u64>,
 }
 
+/// Interval in which the roles are synchronized if no interval is configured
+pub const DEFAULT_ROLE_SYNC_INTERVAL_IN_SECONDS: u64 = 600;
+
+/// Environment variable that can be used instead of the `database_encryption_key` setting
+pub const DATABASE_ENCRYPTION_KEY_ENV: &str = "MATRIX_ROCKETCHAT_DATABASE_ENCRYPTION_KEY";
+
 impl Config {
     /// Loads the configuration from a YAML File.
     pub fn read_from_file(path: &str) -> Result<Config> {
@@ -57,6 +91,35 @@ impl Config {
         Ok(config)
     }
 
+    /// Secret to encrypt the tokens that are stored in the database, tokens are stored in
+    /// plaintext if no secret is configured.
+    pub fn database_encryption_key(&self) -> Option<String> {
+        match env::var(DATABASE_ENCRYPTION_KEY_ENV) {
+            Ok(ref key) if !key.is_empty() => Some(key.clone()),
+            _ => self.database_encryption_key.clone(),
+        }
+    }
+
+    /// The URL under which users reach the homeserver, falls back to `hs_url` if no public URL is
+    /// configured.
+    pub fn hs_public_url(&self) -> String {
+        self.hs_public_url.clone().unwrap_or_else(|| self.hs_url.clone())
+    }
+
+    /// The interval in which the roles are synchronized, returns `None` if the periodic
+    /// synchronization is disabled.
+    pub fn role_sync_interval(&self) -> Option<Duration> {
+        match self.role_sync_interval_in_seconds.unwrap_or(DEFAULT_ROLE_SYNC_INTERVAL_IN_SECONDS) {
+            0 => None,
+            seconds => Some(Duration::from_secs(seconds)),
+        }
+    }
+
+    /// Check if the user is a bridge admin.
+    pub fn is_bridge_admin(&self, user_id: &UserId) -> bool {
+        self.bridge_admins.as_ref().map(|bridge_admins| bridge_admins.contains(user_id)).unwrap_or(false)
+    }
+
     /// Matrix id of the bot user.
     pub fn matrix_bot_user_id(&self) -> Result<UserId> {
         let user_id = format!("@{}:{}", &self.sender_localpart, &self.hs_domain);
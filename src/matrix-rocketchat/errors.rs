@@ -170,6 +170,56 @@ error_chain!{
             display("User login on Rocket.Chat server failed: {}", error_msg)
         }
 
+        RocketchatTwoFactorRequired(method: String) {
+            description("Rocket.Chat login requires a two-factor authentication code")
+            display("User login on Rocket.Chat server requires a two-factor authentication code (method: {})", method)
+        }
+
+        RocketchatTwoFactorCodeInvalid {
+            description("Rocket.Chat rejected the two-factor authentication code")
+            display("The two-factor authentication code was rejected by the Rocket.Chat server")
+        }
+
+        NoPendingTwoFactorLogin(user_id: String) {
+            description("No login is waiting for a two-factor authentication code")
+            display("No login of user {} is waiting for a two-factor authentication code", user_id)
+        }
+
+        InvalidMatrixAccessToken(user_id: String, error_msg: String) {
+            description("The Matrix access token is not valid for the user")
+            display("The Matrix access token of user {} is not valid: {}", user_id, error_msg)
+        }
+
+        NotLoggedIn(user_id: String, rocketchat_url: String) {
+            description("The user is not logged in on the Rocket.Chat server")
+            display("User {} is not logged in on the Rocket.Chat server {}", user_id, rocketchat_url)
+        }
+
+        TokenEncryptionError {
+            description("Error when encrypting a token")
+            display("Could not encrypt the token")
+        }
+
+        TokenDecryptionError(msg: String) {
+            description("Error when decrypting a token")
+            display("Could not decrypt the token: {}", msg)
+        }
+
+        InvalidDatabaseEncryptionKey {
+            description("The database encryption key is invalid")
+            display("The database encryption key has to be 32 bytes encoded as hex, for example the output of `openssl rand -hex 32`")
+        }
+
+        InvalidLoginToken {
+            description("The login token is invalid or expired")
+            display("Attempt to login with an invalid or expired login token")
+        }
+
+        LoginTokenMismatch(user_id: String, rocketchat_url: String) {
+            description("The login token was issued for another user or Rocket.Chat server")
+            display("Login token used for user {} on {} was issued for another user or server", user_id, rocketchat_url)
+        }
+
         ApiCallFailed(url: String) {
             description("Call to REST API failed")
             display("Could not call REST API endpoint {}", url)
@@ -228,6 +278,21 @@ error_chain!{
             display("No admin room found that is connected to the Rocket.Chat server {}", rocketchat_url)
         }
 
+        UnknownCommand(command: String) {
+            description("The command is not known")
+            display("Received the unknown command {}", command)
+        }
+
+        InvalidCommandSyntax(message: String) {
+            description("The command could not be parsed")
+            display("Could not parse the command {}", message)
+        }
+
+        InvalidCommandArguments(command: String) {
+            description("The arguments don't match the command")
+            display("Received the command {} with invalid arguments", command)
+        }
+
         RocketchatTokenMissing{
             description("A token is needed to connect new Rocket.Chat servers")
             display("Attempt to connect a Rocket.Chat server without a token")
@@ -254,6 +319,16 @@ error_chain!{
             display("Attempt to connect with the Rocket.Chat server ID {}, which is already in use.", rocketchat_server_id)
         }
 
+        RocketchatServerManagementNotAllowed(user_id: String, rocketchat_url: String) {
+            description("The user is not allowed to manage the Rocket.Chat server")
+            display("User {} is not allowed to manage the Rocket.Chat server {}", user_id, rocketchat_url)
+        }
+
+        RocketchatUrlAlreadyInUse(rocketchat_url: String) {
+            description("The URL is already used by another Rocket.Chat server")
+            display("The URL {} is already used by another Rocket.Chat server", rocketchat_url)
+        }
+
         RocketchatTokenAlreadyInUse(token: String) {
             description("The token is already used by another server")
             display("The token {} is already in use by another server", token)
@@ -264,6 +339,11 @@ error_chain!{
             display("The channel or group {} does not exist on the Rocket.Chat server", rocketchat_room_name)
         }
 
+        RocketchatUserNotFound(rocketchat_username: String) {
+            description("No user with the given username found on the Rocket.Chat server")
+            display("The user {} does not exist on the Rocket.Chat server", rocketchat_username)
+        }
+
         RocketchatChannelAlreadyBridged(channel_name: String) {
             description("The channel with the given name is already bridged")
             display("The channel {} is already bridged", channel_name)
@@ -294,6 +374,46 @@ error_chain!{
             display("The room {} has aliases ({}) associated with it, cannot unbridge", display_name, aliases)
         }
 
+        MatrixRoomNotFound(matrix_room: String) {
+            description("No Matrix room with the given ID or alias found")
+            display("The Matrix room {} could not be found", matrix_room)
+        }
+
+        UnknownRoomAlias(room_alias_id: String) {
+            description("The room alias does not belong to a Rocket.Chat channel that can be bridged")
+            display("The room alias {} does not belong to a Rocket.Chat channel that can be bridged", room_alias_id)
+        }
+
+        UnknownUser(user_id: String) {
+            description("The user ID does not belong to a Rocket.Chat user")
+            display("The user ID {} does not belong to a Rocket.Chat user", user_id)
+        }
+
+        UnknownThirdPartyProtocol(protocol: String) {
+            description("The third party protocol is not provided by the application service")
+            display("The third party protocol {} is not provided by the application service", protocol)
+        }
+
+        MatrixRoomAlreadyBridged(matrix_room: String) {
+            description("The Matrix room is already bridged")
+            display("The Matrix room {} is already bridged", matrix_room)
+        }
+
+        NotRoomAdmin(user_id: String, matrix_room: String) {
+            description("The user is not an admin of the Matrix room")
+            display("User {} is not an admin of the Matrix room {}", user_id, matrix_room)
+        }
+
+        RelayOfNotBridgedRoom(display_name: String) {
+            description("Relaying can only be enabled for bridged rooms")
+            display("Cannot enable or disable relaying for room {}, because it's not bridged", display_name)
+        }
+
+        RelayNotEnabled(display_name: String) {
+            description("Relaying is not enabled for the room")
+            display("Relaying is not enabled for room {}", display_name)
+        }
+
         ReadConfigError {
             description("Error when reading the config content to a string")
             display("Could not read config content to string")
@@ -324,11 +444,6 @@ error_chain!{
             display("Could not get logger from iron")
         }
 
-        GettingMatrixUserForDirectMessageRoomError {
-            description("Error when getting matrix user for a direct message room")
-            display("Could not get matrix user for direct message room")
-        }
-
         TooManyUsersInAdminRoom(room_id: RoomId) {
             description("Too many users in admin room")
             display("Room {} has more then two members and cannot be used as admin room", room_id)
@@ -392,14 +507,39 @@ error_chain!{
 }
 
 impl Error {
+    /// Append a note to the message that is shown to the user. Errors without a user message get
+    /// the internal error message, so that the note is shown in any case.
+    pub fn append_user_message(mut self, note: &I18n) -> Error {
+        let user_message = self.user_message.unwrap_or_else(|| t!(["defaults", "internal_error"]));
+        self.user_message = Some(user_message.append(note));
+        self
+    }
+
+    /// Returns true if the Rocket.Chat server rejected the credentials that were used for the request
+    pub fn is_rocketchat_authentication_failure(&self) -> bool {
+        match *self.error_chain {
+            ErrorKind::RocketchatAuthenticationFailed(_) => true,
+            _ => false,
+        }
+    }
+
     pub fn status_code(&self) -> Status {
         match *self.error_chain {
-            ErrorKind::InvalidAccessToken(_) | ErrorKind::InvalidRocketchatToken(_) => Status::Forbidden,
+            ErrorKind::InvalidAccessToken(_)
+            | ErrorKind::InvalidRocketchatToken(_)
+            | ErrorKind::InvalidLoginToken
+            | ErrorKind::LoginTokenMismatch(_, _)
+            | ErrorKind::RocketchatServerManagementNotAllowed(_, _) => Status::Forbidden,
             ErrorKind::MissingAccessToken
             | ErrorKind::MissingRocketchatToken
-            | ErrorKind::RocketchatAuthenticationFailed(_) => Status::Unauthorized,
+            | ErrorKind::RocketchatAuthenticationFailed(_)
+            | ErrorKind::RocketchatTwoFactorRequired(_)
+            | ErrorKind::RocketchatTwoFactorCodeInvalid => Status::Unauthorized,
             ErrorKind::InvalidJSON(_) => Status::UnprocessableEntity,
-            ErrorKind::AdminRoomForRocketchatServerNotFound(_) => Status::NotFound,
+            ErrorKind::AdminRoomForRocketchatServerNotFound(_)
+            | ErrorKind::UnknownRoomAlias(_)
+            | ErrorKind::UnknownUser(_)
+            | ErrorKind::UnknownThirdPartyProtocol(_) => Status::NotFound,
             _ => Status::InternalServerError,
         }
     }
@@ -4,12 +4,24 @@
 mod rocketchat;
 /// Process login request for Rocket.Chat
 mod rocketchat_login;
+/// Login page for Rocket.Chat
+mod rocketchat_login_page;
+/// Processes room alias queries from the Matrix homeserver
+mod rooms;
+/// Processes third party protocol lookups from the Matrix homeserver
+mod thirdparty;
 /// Processes requests from the Matrix homeserver
 mod transactions;
+/// Processes user queries from the Matrix homeserver
+mod users;
 /// Sends a welcome message to the caller
 mod welcome;
 
 pub use self::rocketchat::Rocketchat;
 pub use self::rocketchat_login::RocketchatLogin;
+pub use self::rocketchat_login_page::RocketchatLoginPage;
+pub use self::rooms::Rooms;
+pub use self::thirdparty::{ThirdPartyLocation, ThirdPartyProtocol, ThirdPartyUser};
 pub use self::transactions::Transactions;
+pub use self::users::Users;
 pub use self::welcome::Welcome;
@@ -37,7 +37,7 @@ impl Handler for Rocketchat {
         let message = request.extensions.get::<WebhookMessage>().expect("Middleware ensures the presence of a message");
         let server = request.extensions.get::<RocketchatServer>().expect("Middleware ensures the presence of a server");
 
-        let virtual_user = VirtualUser::new(&self.config, &logger, self.matrix_api.as_ref());
+        let virtual_user = VirtualUser::new(&self.config, &connection, &logger, self.matrix_api.as_ref());
         let forwarder = Forwarder::new(&self.config, &connection, &logger, self.matrix_api.as_ref(), &virtual_user);
         if let Err(err) = forwarder.send(server, message) {
             log::log_error(&logger, &err);
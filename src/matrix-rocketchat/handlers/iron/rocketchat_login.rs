@@ -10,9 +10,11 @@ use config::Config;
 use errors::*;
 use i18n::*;
 use log::IronLogger;
-use models::{ConnectionPool, Credentials, RocketchatServer};
+use models::{ConnectionPool, LoginRequest, LoginToken, RocketchatServer};
 
 /// `RocketchatLogin` is an endpoint that allows a user to login to Rocket.Chat via REST API.
+/// The request has to contain a one-time login token that was issued to the user in the admin room,
+/// the token can't be used again, even if the login fails.
 pub struct RocketchatLogin {
     /// Application service configuration
     pub config: Config,
@@ -26,7 +28,8 @@ impl Handler for RocketchatLogin {
         info!(logger, "Received login command via REST API");
 
         let connection = ConnectionPool::from_request(request)?;
-        let credentials = deserialize_credentials(&mut request.body)?;
+        let login_request = deserialize_login_request(&mut request.body)?;
+        let credentials = &login_request.credentials;
         let server = match RocketchatServer::find_by_url(&connection, &credentials.rocketchat_url)? {
             Some(server) => server,
             None => {
@@ -38,15 +41,34 @@ impl Handler for RocketchatLogin {
             }
         };
 
-        if let Err(err) = server.login(&self.config, &connection, &logger, self.matrix_api.as_ref(), &credentials, None) {
-            return Err(err)?;
+        let login_token = match LoginToken::find(&connection, &login_request.token)? {
+            Some(login_token) => login_token,
+            None => return Err(user_error!(ErrorKind::InvalidLoginToken, t!(["errors", "invalid_login_token"])))?,
+        };
+
+        // every attempt uses up the token, otherwise it could be used to guess the password
+        login_token.delete(&connection)?;
+
+        if login_token.matrix_user_id != credentials.user_id || login_token.rocketchat_server_id != server.id {
+            return Err(user_error!(
+                ErrorKind::LoginTokenMismatch(credentials.user_id.to_string(), credentials.rocketchat_url.clone()),
+                t!(["errors", "login_token_mismatch"]).with_vars(vec![
+                    ("matrix_user_id", credentials.user_id.to_string()),
+                    ("rocketchat_url", credentials.rocketchat_url.clone()),
+                ])
+            ))?;
         }
 
+        let admin_room_id = Some(login_token.admin_room_id.clone());
+        server
+            .login(&self.config, &connection, &logger, self.matrix_api.as_ref(), credentials, admin_room_id)
+            .map_err(|err| err.append_user_message(&t!(["errors", "login_token_used"])))?;
+
         Ok(Response::with((status::Ok, t!(["handlers", "rocketchat_login_successful"]).l(DEFAULT_LANGUAGE))))
     }
 }
 
-fn deserialize_credentials(body: &mut Body) -> Result<Credentials> {
+fn deserialize_login_request(body: &mut Body) -> Result<LoginRequest> {
     let mut payload = String::new();
     body.read_to_string(&mut payload).chain_err(|| ErrorKind::InternalServerError)?;
     serde_json::from_str(&payload)
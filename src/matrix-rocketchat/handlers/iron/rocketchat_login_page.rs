@@ -0,0 +1,184 @@
+use std::collections::HashMap;
+use std::io::Read;
+
+use diesel::sqlite::SqliteConnection;
+use iron::headers::ContentType;
+use iron::method::Method;
+use iron::modifiers::Header;
+use iron::prelude::*;
+use iron::request::Body;
+use iron::{status, Handler};
+use router::Router;
+use slog::Logger;
+use url::form_urlencoded;
+
+use api::MatrixApi;
+use config::Config;
+use errors::*;
+use i18n::*;
+use log::{self, IronLogger};
+use models::{ConnectionPool, Credentials, LoginToken, RocketchatServer, TwoFactorLogin};
+
+/// Template of the login page
+const LOGIN_PAGE_TEMPLATE: &str = include_str!("../../../../assets/login.html");
+
+/// `RocketchatLoginPage` serves an HTML form that allows a user to login to Rocket.Chat via the
+/// browser. The page is only accessible via the one-time link that the bot sends to the admin room.
+pub struct RocketchatLoginPage {
+    /// Application service configuration
+    pub config: Config,
+    /// Matrix REST API
+    pub matrix_api: Box<MatrixApi>,
+}
+
+/// The form that is shown on the login page
+enum LoginForm {
+    /// Username and password or personal access token
+    Credentials,
+    /// Two-factor authentication code
+    Code,
+}
+
+impl Handler for RocketchatLoginPage {
+    fn handle(&self, request: &mut Request) -> IronResult<Response> {
+        let logger = IronLogger::from_request(request)?;
+        let connection = ConnectionPool::from_request(request)?;
+
+        let token = request.extensions.get::<Router>().and_then(|params| params.find("token")).unwrap_or_default().to_string();
+        let login_token = match LoginToken::find(&connection, &token)? {
+            Some(login_token) => login_token,
+            None => {
+                info!(logger, "Login page requested with an invalid or expired token");
+                let body = t!(["handlers", "rocketchat_login_link_invalid"]).l(DEFAULT_LANGUAGE);
+                return Ok(Response::with((status::NotFound, body)));
+            }
+        };
+
+        let server = match RocketchatServer::find_by_id(&connection, &login_token.rocketchat_server_id)? {
+            Some(server) => server,
+            None => {
+                login_token.delete(&connection)?;
+                let body = t!(["handlers", "rocketchat_login_link_invalid"]).l(DEFAULT_LANGUAGE);
+                return Ok(Response::with((status::NotFound, body)));
+            }
+        };
+
+        if request.method != Method::Post {
+            return Ok(render(&server, &login_token, LoginForm::Credentials, None));
+        }
+
+        let fields = deserialize_form(&mut request.body)?;
+        self.login(&connection, &logger, &server, &login_token, &fields)
+    }
+}
+
+impl RocketchatLoginPage {
+    fn login(
+        &self,
+        connection: &SqliteConnection,
+        logger: &Logger,
+        server: &RocketchatServer,
+        login_token: &LoginToken,
+        fields: &HashMap<String, String>,
+    ) -> IronResult<Response> {
+        let mut two_factor_login = match fields.get("code") {
+            Some(code) => match TwoFactorLogin::take(&login_token.matrix_user_id, &server.id) {
+                Some(mut two_factor_login) => {
+                    two_factor_login.credentials.code = Some(code.clone());
+                    two_factor_login
+                }
+                None => {
+                    let message = t!(["errors", "no_pending_two_factor_login"]).l(DEFAULT_LANGUAGE);
+                    return Ok(render(server, login_token, LoginForm::Credentials, Some(message)));
+                }
+            },
+            None => TwoFactorLogin::new(server.id.clone(), build_credentials(server, login_token, fields)),
+        };
+
+        let admin_room_id = Some(login_token.admin_room_id.clone());
+        let err = match server.login(
+            &self.config,
+            connection,
+            logger,
+            self.matrix_api.as_ref(),
+            &two_factor_login.credentials,
+            admin_room_id,
+        ) {
+            Ok(()) => {
+                login_token.delete(connection)?;
+                let body = t!(["handlers", "rocketchat_login_successful"]).l(DEFAULT_LANGUAGE);
+                return Ok(Response::with((status::Ok, body)));
+            }
+            Err(err) => err,
+        };
+
+        log::log_info(logger, &err);
+        let message = match err.user_message {
+            Some(ref user_message) => user_message.l(DEFAULT_LANGUAGE),
+            None => t!(["defaults", "internal_error"]).l(DEFAULT_LANGUAGE),
+        };
+        match *err.error_chain {
+            ErrorKind::RocketchatTwoFactorRequired(_) | ErrorKind::RocketchatTwoFactorCodeInvalid => {
+                two_factor_login.credentials.code = None;
+                two_factor_login.save();
+                Ok(render(server, login_token, LoginForm::Code, Some(message)))
+            }
+            _ => Ok(render(server, login_token, LoginForm::Credentials, Some(message))),
+        }
+    }
+}
+
+fn build_credentials(server: &RocketchatServer, login_token: &LoginToken, fields: &HashMap<String, String>) -> Credentials {
+    let field = |name: &str| fields.get(name).cloned().unwrap_or_default();
+    let personal_access_token = field("personal_access_token");
+
+    if personal_access_token.is_empty() {
+        Credentials {
+            user_id: login_token.matrix_user_id.clone(),
+            rocketchat_username: field("username"),
+            password: field("password"),
+            rocketchat_user_id: None,
+            personal_access_token: None,
+            code: None,
+            rocketchat_url: server.rocketchat_url.clone(),
+        }
+    } else {
+        Credentials {
+            user_id: login_token.matrix_user_id.clone(),
+            rocketchat_username: String::new(),
+            password: String::new(),
+            rocketchat_user_id: Some(field("rocketchat_user_id")),
+            personal_access_token: Some(personal_access_token),
+            code: None,
+            rocketchat_url: server.rocketchat_url.clone(),
+        }
+    }
+}
+
+fn render(server: &RocketchatServer, login_token: &LoginToken, form: LoginForm, message: Option<String>) -> Response {
+    let (credentials_hidden, code_hidden) = match form {
+        LoginForm::Credentials => ("", "hidden"),
+        LoginForm::Code => ("hidden", ""),
+    };
+    let message_hidden = if message.is_some() { "" } else { "hidden" };
+
+    let body = LOGIN_PAGE_TEMPLATE
+        .replace("${rocketchat_url}", &escape_html(&server.rocketchat_url))
+        .replace("${matrix_user_id}", &escape_html(&login_token.matrix_user_id.to_string()))
+        .replace("${message_hidden}", message_hidden)
+        .replace("${message}", &escape_html(&message.unwrap_or_default()))
+        .replace("${credentials_hidden}", credentials_hidden)
+        .replace("${code_hidden}", code_hidden);
+
+    Response::with((status::Ok, Header(ContentType::html()), body))
+}
+
+fn deserialize_form(body: &mut Body) -> Result<HashMap<String, String>> {
+    let mut payload = String::new();
+    body.read_to_string(&mut payload).chain_err(|| ErrorKind::InternalServerError)?;
+    Ok(form_urlencoded::parse(payload.as_bytes()).into_owned().collect())
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;").replace('\'', "&#39;")
+}
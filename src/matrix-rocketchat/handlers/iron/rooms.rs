@@ -0,0 +1,178 @@
+use std::convert::TryFrom;
+
+use diesel::sqlite::SqliteConnection;
+use iron::prelude::*;
+use iron::{status, Handler};
+use router::Router;
+use ruma_events::room::join_rules::JoinRule;
+use ruma_identifiers::RoomAliasId;
+use slog::Logger;
+use url::percent_encoding::percent_decode;
+
+use api::rocketchat::Channel;
+use api::{MatrixApi, RocketchatApi};
+use config::Config;
+use errors::*;
+use log::{self, IronLogger};
+use middleware::AccessToken;
+use models::{ConnectionPool, RocketchatRoom, RocketchatServer, Room, RoomType};
+
+/// Rooms is an endpoint of the application service API which is called by the homeserver when a
+/// user queries a room alias in the namespace of the application service. Public Rocket.Chat
+/// channels are bridged on demand, so users can join them without using the admin room.
+pub struct Rooms {
+    config: Config,
+    matrix_api: Box<MatrixApi>,
+}
+
+/// The part of a room alias that identifies the Rocket.Chat channel.
+enum ChannelIdentifier {
+    /// `#rocketchat#<server_id>#<channel_id>`, the alias that the bridge uses for bridged rooms
+    Id(String),
+    /// `#rocketchat_<server_id>_<channel_name>`, the alias that users can type in their clients
+    Name(String),
+}
+
+impl Rooms {
+    /// Rooms endpoint with middleware
+    pub fn chain(config: Config, matrix_api: Box<MatrixApi>) -> Chain {
+        let rooms = Rooms { config: config.clone(), matrix_api };
+        let mut chain = Chain::new(rooms);
+        chain.link_before(AccessToken { config });
+
+        chain
+    }
+
+    fn bridge_room(&self, connection: &SqliteConnection, logger: &Logger, room_alias_id: &RoomAliasId) -> Result<()> {
+        let unknown_room_alias = || Error::from(ErrorKind::UnknownRoomAlias(room_alias_id.to_string()));
+
+        let (server_id, channel_identifier) = self.parse_room_alias(room_alias_id).ok_or_else(unknown_room_alias)?;
+        let server = RocketchatServer::find_by_id(connection, &server_id)?.ok_or_else(unknown_room_alias)?;
+
+        // the homeserver doesn't tell the application service who queried the alias, so the
+        // credentials of any user that is logged in on the Rocket.Chat server are used
+        for user_on_rocketchat_server in server.logged_in_users_on_rocketchat_server(connection)? {
+            let rocketchat_api = RocketchatApi::new(server.rocketchat_url.clone(), logger.clone())?.with_credentials(
+                user_on_rocketchat_server.rocketchat_user_id.clone().unwrap_or_default(),
+                user_on_rocketchat_server.rocketchat_auth_token.clone().unwrap_or_default(),
+            );
+
+            let channels = match rocketchat_api.channels_list() {
+                Err(ref err) if err.is_rocketchat_authentication_failure() => {
+                    let matrix_user_id = &user_on_rocketchat_server.matrix_user_id;
+                    server.expire_session(&self.config, connection, logger, self.matrix_api.as_ref(), matrix_user_id)?;
+                    continue;
+                }
+                result => result?,
+            };
+
+            // only public channels are bridged on demand, groups are only accessible for their members
+            let channel = channels
+                .into_iter()
+                .find(|channel| match channel_identifier {
+                    ChannelIdentifier::Id(ref id) => &channel.id == id,
+                    ChannelIdentifier::Name(ref name) => channel.name.as_ref() == Some(name),
+                })
+                .ok_or_else(unknown_room_alias)?;
+
+            return self.bridge_channel(connection, logger, &server, rocketchat_api.as_ref(), channel, room_alias_id);
+        }
+
+        info!(logger, "No user is logged in on {}, cannot bridge room alias {}", server.id, room_alias_id);
+        Err(unknown_room_alias())
+    }
+
+    fn bridge_channel(
+        &self,
+        connection: &SqliteConnection,
+        logger: &Logger,
+        server: &RocketchatServer,
+        rocketchat_api: &RocketchatApi,
+        channel: Channel,
+        room_alias_id: &RoomAliasId,
+    ) -> Result<()> {
+        let matrix_api = self.matrix_api.as_ref();
+        let rocketchat_room = RocketchatRoom::new(&self.config, logger, matrix_api, channel.id.clone(), &server.id);
+        let room_id = match rocketchat_room.bridged_matrix_id(connection)? {
+            Some(room_id) => room_id,
+            None => {
+                let bot_user_id = self.config.matrix_bot_user_id()?;
+                let members = rocketchat_api.channels_members(&channel.id)?;
+                let usernames: Vec<String> = members.into_iter().map(|member| member.username).collect();
+                let room_id = rocketchat_room.bridge(
+                    connection,
+                    rocketchat_api,
+                    RoomType::Channel,
+                    &channel.name,
+                    &usernames,
+                    &bot_user_id,
+                    None,
+                )?;
+
+                let roles = rocketchat_room.roles(rocketchat_api, RoomType::Channel)?;
+                let room = Room::new(&self.config, logger, matrix_api, room_id.clone());
+                room.sync_rocketchat_roles(connection, &roles, &server.id)?;
+
+                // the channel is public, so everybody can join the new room via the alias. Rooms
+                // that are already bridged keep their join rule, it was chosen by their admins.
+                matrix_api.set_room_join_rule(room_id.clone(), JoinRule::Public)?;
+                room_id
+            }
+        };
+
+        if matrix_api.get_room_alias(room_alias_id.clone())?.is_none() {
+            matrix_api.put_room_alias(room_id.clone(), room_alias_id.clone())?;
+        }
+
+        info!(logger, "Successfully bridged Rocket.Chat channel {} to {} for alias {}", channel.id, room_id, room_alias_id);
+        Ok(())
+    }
+
+    fn parse_room_alias(&self, room_alias_id: &RoomAliasId) -> Option<(String, ChannelIdentifier)> {
+        if room_alias_id.hostname().to_string() != self.config.hs_domain {
+            return None;
+        }
+
+        let alias = room_alias_id.alias();
+        let id_prefix = format!("{}#", self.config.sender_localpart);
+        let name_prefix = format!("{}_", self.config.sender_localpart);
+
+        // Rocket.Chat server IDs only contain lowercase letters and digits, so the first separator
+        // after the prefix always ends the server ID
+        if alias.starts_with(&id_prefix) {
+            let mut parts = alias[id_prefix.len()..].splitn(2, '#');
+            let server_id = parts.next()?.to_string();
+            let channel_id = parts.next().filter(|id| !id.is_empty())?.to_string();
+            Some((server_id, ChannelIdentifier::Id(channel_id)))
+        } else if alias.starts_with(&name_prefix) {
+            let mut parts = alias[name_prefix.len()..].splitn(2, '_');
+            let server_id = parts.next()?.to_string();
+            let channel_name = parts.next().filter(|name| !name.is_empty())?.to_string();
+            Some((server_id, ChannelIdentifier::Name(channel_name)))
+        } else {
+            None
+        }
+    }
+}
+
+impl Handler for Rooms {
+    fn handle(&self, request: &mut Request) -> IronResult<Response> {
+        let logger = IronLogger::from_request(request)?;
+
+        let url_room_alias =
+            request.extensions.get::<Router>().and_then(|params| params.find("room_alias")).unwrap_or_default().to_string();
+        let room_alias = percent_decode(url_room_alias.as_bytes()).decode_utf8_lossy().to_string();
+        let room_alias_id = RoomAliasId::try_from(room_alias.as_ref())
+            .chain_err(|| ErrorKind::InvalidRoomAliasId(room_alias.clone()))
+            .map_err(Error::from)?;
+        debug!(logger, "Homeserver queried room alias {}", room_alias_id);
+
+        let connection = ConnectionPool::from_request(request)?;
+        if let Err(err) = self.bridge_room(&connection, &logger, &room_alias_id) {
+            log::log_info(&logger, &err);
+            return Err(err)?;
+        }
+
+        Ok(Response::with((status::Ok, "{}".to_string())))
+    }
+}
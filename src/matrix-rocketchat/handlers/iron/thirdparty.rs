@@ -0,0 +1,346 @@
+use std::collections::HashMap;
+
+use diesel::sqlite::SqliteConnection;
+use iron::prelude::*;
+use iron::{status, Handler};
+use router::Router;
+use serde_json;
+use slog::Logger;
+use url::form_urlencoded;
+
+use api::{MatrixApi, RocketchatApi};
+use config::Config;
+use errors::*;
+use log::{self, IronLogger};
+use middleware::AccessToken;
+use models::{ConnectionPool, RocketchatRoom, RocketchatServer, VirtualUser};
+
+/// The ID of the third party protocol that the application service provides. It has to match the
+/// `protocols` in the registration of the application service.
+const PROTOCOL: &str = "rocketchat";
+/// Field that identifies the Rocket.Chat server
+const SERVER_FIELD: &str = "server";
+/// Field that identifies a Rocket.Chat channel
+const CHANNEL_FIELD: &str = "channel";
+/// Field that identifies a Rocket.Chat user
+const USERNAME_FIELD: &str = "username";
+
+/// `ThirdPartyProtocol` is an endpoint of the application service API which is called by the
+/// homeserver to get the metadata of the protocol. Each connected Rocket.Chat server is an instance
+/// of the protocol, which is shown as a network in the room directory of Matrix clients.
+pub struct ThirdPartyProtocol {}
+
+/// `ThirdPartyLocation` is an endpoint of the application service API which is called by the
+/// homeserver to find the portal alias of a public Rocket.Chat channel. The channels are listed with
+/// the credentials of the first logged in user of each server, so the result contains the channels
+/// that this user can see, not necessarily the ones that the user who sent the query can see.
+pub struct ThirdPartyLocation {
+    config: Config,
+    matrix_api: Box<MatrixApi>,
+}
+
+/// `ThirdPartyUser` is an endpoint of the application service API which is called by the
+/// homeserver to find the virtual user of a Rocket.Chat user.
+pub struct ThirdPartyUser {
+    config: Config,
+    matrix_api: Box<MatrixApi>,
+}
+
+/// Metadata of the third party protocol
+#[derive(Serialize)]
+struct Protocol {
+    /// Fields that are used to identify a user
+    user_fields: Vec<&'static str>,
+    /// Fields that are used to identify a location
+    location_fields: Vec<&'static str>,
+    /// Icon of the protocol
+    icon: String,
+    /// Description of the fields
+    field_types: HashMap<&'static str, FieldType>,
+    /// Connected Rocket.Chat servers
+    instances: Vec<ProtocolInstance>,
+}
+
+/// Description of a field that is used to look up locations or users
+#[derive(Serialize)]
+struct FieldType {
+    /// Regular expression that the value of the field has to match
+    regexp: &'static str,
+    /// Placeholder that is shown to the user
+    placeholder: &'static str,
+}
+
+/// A connected Rocket.Chat server
+#[derive(Serialize)]
+struct ProtocolInstance {
+    /// Description of the instance
+    desc: String,
+    /// Fields that are preset when looking up locations or users on this instance
+    fields: HashMap<&'static str, String>,
+    /// ID that identifies the instance in the room directory
+    network_id: String,
+}
+
+/// A public Rocket.Chat channel that can be joined via its portal alias
+#[derive(Serialize)]
+struct Location {
+    /// Room alias that is used to join the bridged room
+    alias: String,
+    /// The ID of the protocol
+    protocol: &'static str,
+    /// Fields that identify the channel
+    fields: HashMap<&'static str, String>,
+}
+
+/// A Rocket.Chat user and its virtual user on the homeserver
+#[derive(Serialize)]
+struct User {
+    /// Matrix ID of the virtual user
+    userid: String,
+    /// The ID of the protocol
+    protocol: &'static str,
+    /// Fields that identify the user
+    fields: HashMap<&'static str, String>,
+}
+
+impl ThirdPartyProtocol {
+    /// ThirdPartyProtocol endpoint with middleware
+    pub fn chain(config: Config) -> Chain {
+        let mut chain = Chain::new(ThirdPartyProtocol {});
+        chain.link_before(AccessToken { config });
+
+        chain
+    }
+}
+
+impl Handler for ThirdPartyProtocol {
+    fn handle(&self, request: &mut Request) -> IronResult<Response> {
+        let logger = IronLogger::from_request(request)?;
+        let protocol = protocol_param(request)?;
+        debug!(logger, "Homeserver queried third party protocol {}", protocol);
+
+        let connection = ConnectionPool::from_request(request)?;
+        let instances = RocketchatServer::find_connected_servers(&connection)?
+            .into_iter()
+            .map(|server| {
+                let mut fields = HashMap::new();
+                fields.insert(SERVER_FIELD, server.id.clone());
+                ProtocolInstance { desc: server.rocketchat_url, fields, network_id: server.id }
+            })
+            .collect();
+
+        let mut field_types = HashMap::new();
+        field_types.insert(SERVER_FIELD, FieldType { regexp: "[a-z0-9]+", placeholder: "Rocket.Chat server ID" });
+        field_types.insert(CHANNEL_FIELD, FieldType { regexp: ".+", placeholder: "Channel name" });
+        field_types.insert(USERNAME_FIELD, FieldType { regexp: ".+", placeholder: "Username" });
+
+        let protocol = Protocol {
+            user_fields: vec![SERVER_FIELD, USERNAME_FIELD],
+            location_fields: vec![SERVER_FIELD, CHANNEL_FIELD],
+            icon: String::new(),
+            field_types,
+            instances,
+        };
+
+        let payload = serde_json::to_string(&protocol).expect("Protocol is always serializable");
+        Ok(Response::with((status::Ok, payload)))
+    }
+}
+
+impl ThirdPartyLocation {
+    /// ThirdPartyLocation endpoint with middleware
+    pub fn chain(config: Config, matrix_api: Box<MatrixApi>) -> Chain {
+        let location = ThirdPartyLocation { config: config.clone(), matrix_api };
+        let mut chain = Chain::new(location);
+        chain.link_before(AccessToken { config });
+
+        chain
+    }
+
+    fn find_locations(
+        &self,
+        connection: &SqliteConnection,
+        logger: &Logger,
+        fields: &HashMap<String, String>,
+    ) -> Result<Vec<Location>> {
+        let channel_name = fields.get(CHANNEL_FIELD);
+        let matrix_api = self.matrix_api.as_ref();
+        let mut locations = Vec::new();
+
+        for server in servers(connection, fields)? {
+            let query = |rocketchat_api: &RocketchatApi| rocketchat_api.channels_list();
+            let channels = match query_rocketchat_server(&self.config, connection, logger, matrix_api, &server, query) {
+                Ok(Some(channels)) => channels,
+                Ok(None) => continue,
+                Err(err) => {
+                    // a server that is not reachable doesn't prevent listing the channels of the other servers
+                    log::log_info(logger, &err);
+                    continue;
+                }
+            };
+
+            // only public channels are listed, groups are only accessible for their members
+            for channel in channels {
+                let name = match channel.name {
+                    Some(name) => name,
+                    None => continue,
+                };
+
+                if channel_name.map_or(false, |channel_name| channel_name != &name) {
+                    continue;
+                }
+
+                let alias = RocketchatRoom::build_portal_alias_id(&self.config, &server.id, &name)?;
+                let mut fields = HashMap::new();
+                fields.insert(SERVER_FIELD, server.id.clone());
+                fields.insert(CHANNEL_FIELD, name);
+                locations.push(Location { alias: alias.to_string(), protocol: PROTOCOL, fields });
+            }
+        }
+
+        Ok(locations)
+    }
+}
+
+impl Handler for ThirdPartyLocation {
+    fn handle(&self, request: &mut Request) -> IronResult<Response> {
+        let logger = IronLogger::from_request(request)?;
+        let protocol = protocol_param(request)?;
+        let fields = query_fields(request);
+        debug!(logger, "Homeserver queried third party locations of protocol {} with fields {:?}", protocol, fields);
+
+        let connection = ConnectionPool::from_request(request)?;
+        let locations = match self.find_locations(&connection, &logger, &fields) {
+            Ok(locations) => locations,
+            Err(err) => {
+                log::log_info(&logger, &err);
+                return Err(err)?;
+            }
+        };
+
+        let payload = serde_json::to_string(&locations).expect("Location is always serializable");
+        Ok(Response::with((status::Ok, payload)))
+    }
+}
+
+impl ThirdPartyUser {
+    /// ThirdPartyUser endpoint with middleware
+    pub fn chain(config: Config, matrix_api: Box<MatrixApi>) -> Chain {
+        let user = ThirdPartyUser { config: config.clone(), matrix_api };
+        let mut chain = Chain::new(user);
+        chain.link_before(AccessToken { config });
+
+        chain
+    }
+
+    fn find_users(
+        &self,
+        connection: &SqliteConnection,
+        logger: &Logger,
+        fields: &HashMap<String, String>,
+    ) -> Result<Vec<User>> {
+        let username = match fields.get(USERNAME_FIELD) {
+            Some(username) => username,
+            None => return Ok(Vec::new()),
+        };
+
+        let matrix_api = self.matrix_api.as_ref();
+        let virtual_user = VirtualUser::new(&self.config, connection, logger, matrix_api);
+        let mut users = Vec::new();
+
+        for server in servers(connection, fields)? {
+            let query = |rocketchat_api: &RocketchatApi| rocketchat_api.users_info(username);
+            let user = match query_rocketchat_server(&self.config, connection, logger, matrix_api, &server, query) {
+                Ok(Some(user)) => user,
+                Ok(None) => continue,
+                Err(err) => {
+                    // Rocket.Chat responds with an error if the user doesn't exist
+                    log::log_debug(logger, &err);
+                    continue;
+                }
+            };
+
+            let user_id = virtual_user.build_user_id(&user.id, &server.id)?;
+            let mut fields = HashMap::new();
+            fields.insert(SERVER_FIELD, server.id.clone());
+            fields.insert(USERNAME_FIELD, user.username);
+            users.push(User { userid: user_id.to_string(), protocol: PROTOCOL, fields });
+        }
+
+        Ok(users)
+    }
+}
+
+impl Handler for ThirdPartyUser {
+    fn handle(&self, request: &mut Request) -> IronResult<Response> {
+        let logger = IronLogger::from_request(request)?;
+        let protocol = protocol_param(request)?;
+        let fields = query_fields(request);
+        debug!(logger, "Homeserver queried third party users of protocol {} with fields {:?}", protocol, fields);
+
+        let connection = ConnectionPool::from_request(request)?;
+        let users = match self.find_users(&connection, &logger, &fields) {
+            Ok(users) => users,
+            Err(err) => {
+                log::log_info(&logger, &err);
+                return Err(err)?;
+            }
+        };
+
+        let payload = serde_json::to_string(&users).expect("User is always serializable");
+        Ok(Response::with((status::Ok, payload)))
+    }
+}
+
+fn protocol_param(request: &Request) -> Result<String> {
+    let protocol =
+        request.extensions.get::<Router>().and_then(|params| params.find("protocol")).unwrap_or_default().to_string();
+    if protocol != PROTOCOL {
+        bail_error!(ErrorKind::UnknownThirdPartyProtocol(protocol));
+    }
+
+    Ok(protocol)
+}
+
+fn query_fields(request: &Request) -> HashMap<String, String> {
+    request.url.query().map(|query| form_urlencoded::parse(query.as_bytes()).into_owned().collect()).unwrap_or_default()
+}
+
+/// The connected Rocket.Chat servers that match the server field, all of them if the field is missing.
+fn servers(connection: &SqliteConnection, fields: &HashMap<String, String>) -> Result<Vec<RocketchatServer>> {
+    let servers = RocketchatServer::find_connected_servers(connection)?;
+    Ok(servers.into_iter().filter(|server| fields.get(SERVER_FIELD).map_or(true, |id| id == &server.id)).collect())
+}
+
+/// The homeserver doesn't tell the application service who sent the query, so the credentials of
+/// any user that is logged in on the Rocket.Chat server are used. Returns `None` if nobody is
+/// logged in.
+fn query_rocketchat_server<T, F>(
+    config: &Config,
+    connection: &SqliteConnection,
+    logger: &Logger,
+    matrix_api: &MatrixApi,
+    server: &RocketchatServer,
+    query: F,
+) -> Result<Option<T>>
+where
+    F: Fn(&RocketchatApi) -> Result<T>,
+{
+    for user_on_rocketchat_server in server.logged_in_users_on_rocketchat_server(connection)? {
+        let rocketchat_api = RocketchatApi::new(server.rocketchat_url.clone(), logger.clone())?.with_credentials(
+            user_on_rocketchat_server.rocketchat_user_id.clone().unwrap_or_default(),
+            user_on_rocketchat_server.rocketchat_auth_token.clone().unwrap_or_default(),
+        );
+
+        match query(rocketchat_api.as_ref()) {
+            Err(ref err) if err.is_rocketchat_authentication_failure() => {
+                let matrix_user_id = &user_on_rocketchat_server.matrix_user_id;
+                server.expire_session(config, connection, logger, matrix_api, matrix_user_id)?;
+            }
+            result => return result.map(Some),
+        }
+    }
+
+    info!(logger, "No user is logged in on {}, cannot query the Rocket.Chat server", server.id);
+    Ok(None)
+}
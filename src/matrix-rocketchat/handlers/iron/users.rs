@@ -0,0 +1,103 @@
+use std::convert::TryFrom;
+
+use diesel::sqlite::SqliteConnection;
+use iron::prelude::*;
+use iron::{status, Handler};
+use router::Router;
+use ruma_identifiers::UserId;
+use slog::Logger;
+use url::percent_encoding::percent_decode;
+
+use api::{MatrixApi, RocketchatApi};
+use config::Config;
+use errors::*;
+use log::{self, IronLogger};
+use middleware::AccessToken;
+use models::{ConnectionPool, RocketchatServer, VirtualUser};
+
+/// Users is an endpoint of the application service API which is called by the homeserver when
+/// a user in the namespace of the application service is queried. Virtual users of existing
+/// Rocket.Chat users are registered on demand.
+pub struct Users {
+    config: Config,
+    matrix_api: Box<MatrixApi>,
+}
+
+impl Users {
+    /// Users endpoint with middleware
+    pub fn chain(config: Config, matrix_api: Box<MatrixApi>) -> Chain {
+        let users = Users { config: config.clone(), matrix_api };
+        let mut chain = Chain::new(users);
+        chain.link_before(AccessToken { config });
+
+        chain
+    }
+
+    fn provision_user(&self, connection: &SqliteConnection, logger: &Logger, user_id: &UserId) -> Result<()> {
+        let unknown_user = || Error::from(ErrorKind::UnknownUser(user_id.to_string()));
+
+        let is_local_user = user_id.hostname().to_string() == self.config.hs_domain;
+        if !is_local_user || !self.config.is_application_service_virtual_user(user_id) {
+            return Err(unknown_user());
+        }
+
+        let virtual_user = VirtualUser::new(&self.config, connection, logger, self.matrix_api.as_ref());
+        if virtual_user.is_registered(user_id)? {
+            debug!(logger, "Virtual user {} is already registered", user_id);
+            return Ok(());
+        }
+
+        let (server_id, rocketchat_user_id) = VirtualUser::rocketchat_server_and_user_id_from_matrix_id(user_id);
+        let server = RocketchatServer::find_by_id(connection, &server_id)?.ok_or_else(unknown_user)?;
+
+        // the homeserver doesn't tell the application service who queried the user, so the
+        // credentials of any user that is logged in on the Rocket.Chat server are used
+        for user_on_rocketchat_server in server.logged_in_users_on_rocketchat_server(connection)? {
+            let rocketchat_api = RocketchatApi::new(server.rocketchat_url.clone(), logger.clone())?.with_credentials(
+                user_on_rocketchat_server.rocketchat_user_id.clone().unwrap_or_default(),
+                user_on_rocketchat_server.rocketchat_auth_token.clone().unwrap_or_default(),
+            );
+
+            let user = match rocketchat_api.users_info_by_id(&rocketchat_user_id) {
+                Ok(user) => user,
+                Err(ref err) if err.is_rocketchat_authentication_failure() => {
+                    let matrix_user_id = &user_on_rocketchat_server.matrix_user_id;
+                    server.expire_session(&self.config, connection, logger, self.matrix_api.as_ref(), matrix_user_id)?;
+                    continue;
+                }
+                Err(err) => {
+                    log::log_debug(logger, &err);
+                    return Err(unknown_user());
+                }
+            };
+
+            virtual_user.find_or_register(&server.id, &user.id, &user.username)?;
+            info!(logger, "Successfully registered virtual user {} on demand", user_id);
+            return Ok(());
+        }
+
+        info!(logger, "No user is logged in on {}, cannot register virtual user {}", server.id, user_id);
+        Err(unknown_user())
+    }
+}
+
+impl Handler for Users {
+    fn handle(&self, request: &mut Request) -> IronResult<Response> {
+        let logger = IronLogger::from_request(request)?;
+
+        let url_user_id =
+            request.extensions.get::<Router>().and_then(|params| params.find("user_id")).unwrap_or_default().to_string();
+        let user_id = percent_decode(url_user_id.as_bytes()).decode_utf8_lossy().to_string();
+        let user_id =
+            UserId::try_from(user_id.as_ref()).chain_err(|| ErrorKind::InvalidUserId(user_id.clone())).map_err(Error::from)?;
+        debug!(logger, "Homeserver queried user {}", user_id);
+
+        let connection = ConnectionPool::from_request(request)?;
+        if let Err(err) = self.provision_user(&connection, &logger, &user_id) {
+            log::log_info(&logger, &err);
+            return Err(err)?;
+        }
+
+        Ok(Response::with((status::Ok, "{}".to_string())))
+    }
+}
@@ -0,0 +1,193 @@
+use errors::*;
+use i18n::*;
+
+/// The commands that can be sent to the admin room
+pub const COMMANDS: &[CommandSpec] = &[
+    CommandSpec { name: "connect", min_args: 1, max_args: 3, contains_credentials: false },
+    CommandSpec { name: "help", min_args: 0, max_args: 1, contains_credentials: false },
+    CommandSpec { name: "login-link", min_args: 0, max_args: 0, contains_credentials: false },
+    CommandSpec { name: "login-token", min_args: 2, max_args: 2, contains_credentials: true },
+    CommandSpec { name: "2fa", min_args: 1, max_args: 1, contains_credentials: false },
+    CommandSpec { name: "login", min_args: 2, max_args: 2, contains_credentials: true },
+    CommandSpec { name: "logout", min_args: 0, max_args: 1, contains_credentials: false },
+    CommandSpec { name: "status", min_args: 0, max_args: 0, contains_credentials: false },
+    CommandSpec { name: "whoami", min_args: 0, max_args: 0, contains_credentials: false },
+    CommandSpec { name: "list", min_args: 0, max_args: 5, contains_credentials: false },
+    CommandSpec { name: "bridge", min_args: 1, max_args: 1, contains_credentials: false },
+    CommandSpec { name: "unbridge", min_args: 1, max_args: 1, contains_credentials: false },
+    CommandSpec { name: "plumb", min_args: 2, max_args: 2, contains_credentials: false },
+    CommandSpec { name: "relay", min_args: 1, max_args: 1, contains_credentials: false },
+    CommandSpec { name: "unrelay", min_args: 1, max_args: 1, contains_credentials: false },
+    CommandSpec { name: "dm", min_args: 1, max_args: 1, contains_credentials: false },
+    CommandSpec { name: "double-puppet", min_args: 1, max_args: 1, contains_credentials: true },
+    CommandSpec { name: "disconnect", min_args: 0, max_args: 0, contains_credentials: false },
+    CommandSpec { name: "rotate-token", min_args: 1, max_args: 1, contains_credentials: false },
+    CommandSpec { name: "change-url", min_args: 1, max_args: 1, contains_credentials: false },
+    CommandSpec { name: "delete-server", min_args: 0, max_args: 0, contains_credentials: false },
+];
+
+/// Describes a command and the arguments it accepts.
+pub struct CommandSpec {
+    /// The name of the command, which is the first word of the message
+    pub name: &'static str,
+    /// The minimal number of arguments
+    pub min_args: usize,
+    /// The maximal number of arguments
+    pub max_args: usize,
+    /// The command contains credentials and is redacted from the admin room
+    pub contains_credentials: bool,
+}
+
+/// A command that was sent to the admin room.
+#[derive(Debug, PartialEq)]
+pub struct Command {
+    /// The name of the command
+    pub name: String,
+    /// The arguments that were passed to the command
+    pub args: Vec<String>,
+}
+
+impl CommandSpec {
+    /// Find the specification of a command by its name.
+    pub fn find(name: &str) -> Option<&'static CommandSpec> {
+        COMMANDS.iter().find(|spec| spec.name == name)
+    }
+
+    /// The usage instructions of the command.
+    pub fn usage(&self) -> I18n {
+        t!(["commands", self.name])
+    }
+}
+
+impl Command {
+    /// Parse a message into a command. Arguments are separated by whitespace, arguments that
+    /// contain whitespace can be quoted with double or single quotes. A quote only starts a quoted
+    /// argument at the beginning of an argument, quotes inside of an argument are kept as they are
+    /// (`login user pa"ss` works). Inside of quotes a backslash escapes the quote character and the
+    /// backslash itself, everywhere else backslashes are kept as they are, so that passwords don't
+    /// have to be escaped. Arguments that start with a quote have to be quoted with the other quote
+    /// character or escaped, for example `login user '"password'`.
+    pub fn parse(message: &str) -> Result<Command> {
+        let mut words = Vec::new();
+        let mut word = String::new();
+        let mut in_word = false;
+        let mut quote = None;
+        let mut chars = message.trim().chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match (c, quote) {
+                ('\\', Some(q)) if chars.peek() == Some(&q) || chars.peek() == Some(&'\\') => {
+                    word.push(chars.next().unwrap_or('\\'));
+                }
+                (c, Some(q)) if c == q => quote = None,
+                (c, Some(_)) => word.push(c),
+                ('"', None) | ('\'', None) if !in_word => {
+                    quote = Some(c);
+                    in_word = true;
+                }
+                (c, None) if c.is_whitespace() => {
+                    if in_word {
+                        words.push(word.clone());
+                        word.clear();
+                        in_word = false;
+                    }
+                }
+                (c, None) => {
+                    word.push(c);
+                    in_word = true;
+                }
+            }
+        }
+
+        if quote.is_some() {
+            bail_error!(ErrorKind::InvalidCommandSyntax(message.to_string()), t!(["errors", "command_unterminated_quote"]));
+        }
+
+        if in_word {
+            words.push(word);
+        }
+
+        let mut words = words.into_iter();
+        let name = words.next().unwrap_or_default();
+        Ok(Command { name, args: words.collect() })
+    }
+
+    /// Returns true if the message contains a command with credentials, which has to be redacted.
+    /// The name of the command is compared case-insensitively. If the message can't be parsed, the
+    /// first word without quotes is used. Names that are no command but look like a mistyped
+    /// command with credentials (`loginn`, `logn`) are redacted as well, the user most likely sent
+    /// credentials anyway.
+    pub fn contains_credentials(message: &str) -> bool {
+        let name = match Command::parse(message) {
+            Ok(command) => command.name,
+            Err(_) => message.split_whitespace().next().unwrap_or_default().trim_matches(|c| c == '"' || c == '\'').to_string(),
+        };
+        let name = name.to_lowercase();
+
+        if let Some(spec) = CommandSpec::find(&name) {
+            return spec.contains_credentials;
+        }
+
+        COMMANDS
+            .iter()
+            .filter(|spec| spec.contains_credentials)
+            .any(|spec| name.starts_with(spec.name) || edit_distance(&name, spec.name) <= 1)
+    }
+
+    /// Look up the specification of the command and make sure that the number of arguments matches.
+    pub fn validate(&self) -> Result<&'static CommandSpec> {
+        let spec = match CommandSpec::find(&self.name) {
+            Some(spec) => spec,
+            None => return Err(self.unknown_command_error()),
+        };
+
+        if self.args.len() < spec.min_args || self.args.len() > spec.max_args {
+            return Err(self.usage_error());
+        }
+
+        Ok(spec)
+    }
+
+    /// An error that tells the user how the command is used.
+    pub fn usage_error(&self) -> Error {
+        let spec = match CommandSpec::find(&self.name) {
+            Some(spec) => spec,
+            None => return self.unknown_command_error(),
+        };
+
+        user_error!(
+            ErrorKind::InvalidCommandArguments(self.name.clone()),
+            t!(["errors", "command_usage"]).with_vars(vec![("command", self.name.clone())]).append(&spec.usage())
+        )
+    }
+
+    /// An error that tells the user that the command does not exist.
+    pub fn unknown_command_error(&self) -> Error {
+        user_error!(
+            ErrorKind::UnknownCommand(self.name.clone()),
+            t!(["errors", "unknown_command"]).with_vars(vec![("command", self.name.clone())])
+        )
+    }
+
+    /// The argument at the position or an empty string if there is no such argument.
+    pub fn arg(&self, index: usize) -> &str {
+        self.args.get(index).map(String::as_str).unwrap_or_default()
+    }
+}
+
+// Number of characters that have to be inserted, removed or replaced to turn one word into the other.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous_row: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a_char) in a.chars().enumerate() {
+        let mut row = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous_row[j] + if a_char == *b_char { 0 } else { 1 };
+            row.push(substitution.min(previous_row[j + 1] + 1).min(row[j] + 1));
+        }
+        previous_row = row;
+    }
+
+    previous_row[b.len()]
+}
@@ -1,20 +1,30 @@
+use std::convert::TryFrom;
+use std::time::{SystemTime, UNIX_EPOCH};
+
 use diesel::sqlite::SqliteConnection;
 use diesel::Connection;
 use ruma_events::room::message::MessageEvent;
 use ruma_events::room::message::MessageEventContent;
-use ruma_identifiers::{RoomAliasId, UserId};
+use ruma_identifiers::{RoomAliasId, RoomId, UserId};
 use slog::Logger;
 
 use api::rocketchat::Channel;
 use api::{MatrixApi, RocketchatApi};
 use config::Config;
 use errors::*;
+use handlers::matrix::{Command, CommandSpec};
 use i18n::*;
+use log;
 use models::{
-    Credentials, NewRocketchatServer, NewUserOnRocketchatServer, RocketchatRoom, RocketchatServer, Room, UserOnRocketchatServer,
+    BridgedRoom, Credentials, LoginToken, NewPlumbedRoom, NewRelayRoom, NewRocketchatServer, NewUserOnRocketchatServer,
+    PlumbedRoom, RecordedError, RelayRoom, RocketchatRoom, RocketchatServer, Room, RoomType, TwoFactorLogin,
+    UserOnRocketchatServer, VirtualUser, LOGIN_TOKEN_TIMEOUT_IN_SECONDS, TWO_FACTOR_LOGIN_TIMEOUT_IN_SECONDS,
 };
 use MAX_ROCKETCHAT_SERVER_ID_LENGTH;
 
+/// Number of rooms that are shown on one page of the `list` command
+const ROOMS_PER_PAGE: usize = 50;
+
 /// Handles command messages from the admin room
 pub struct CommandHandler<'a> {
     config: &'a Config,
@@ -46,42 +56,109 @@ impl<'a> CommandHandler<'a> {
             }
         };
 
-        if message.starts_with("connect") {
-            debug!(self.logger, "Received connect command: {}", message);
-
-            self.connect(event, &message)?;
-        } else if message == "help" {
-            debug!(self.logger, "Received help command");
-
-            self.help(event)?;
-        } else if message.starts_with("login") {
-            debug!(self.logger, "Received login command");
-
-            let server = self.get_rocketchat_server()?;
-            self.login(event, &server, &message)?;
-        } else if message == "list" {
-            debug!(self.logger, "Received list command");
+        if message.trim().is_empty() {
+            debug!(self.logger, "Skipping empty message");
+            return Ok(());
+        }
 
-            let server = self.get_rocketchat_server()?;
-            self.list_rocketchat_rooms(event, &server)?;
-        } else if message.starts_with("bridge") {
-            debug!(self.logger, "Received bridge command");
+        // the message contains credentials, it's redacted no matter if the command succeeds
+        let redaction_note = if Command::contains_credentials(&message) { Some(self.redact_command(event)?) } else { None };
+        let result = Command::parse(&message).and_then(|command| self.execute(event, &command, redaction_note.as_ref()));
+        match redaction_note {
+            Some(ref redaction_note) => result.map_err(|err| err.append_user_message(redaction_note)),
+            None => result,
+        }
+    }
 
-            let server = self.get_rocketchat_server()?;
-            self.bridge(event, &server, &message)?;
-        } else if message.starts_with("unbridge") {
-            debug!(self.logger, "Received unbridge command");
+    fn execute(&self, event: &MessageEvent, command: &Command, redaction_note: Option<&I18n>) -> Result<()> {
+        let spec = command.validate()?;
+        debug!(self.logger, "Received {} command", spec.name);
 
-            let server = self.get_rocketchat_server()?;
-            self.unbridge(event, &server, &message)?;
-        } else {
-            debug!(self.logger, "Skipping event, don't know how to handle command `{}`", message);
+        match spec.name {
+            "connect" => self.connect(event, command),
+            "help" => self.help(event, command),
+            "login-link" => {
+                let server = self.get_rocketchat_server()?;
+                self.login_link(event, &server)
+            }
+            "login-token" => {
+                let server = self.get_rocketchat_server()?;
+                self.login_with_personal_access_token(event, &server, command, redaction_note)
+            }
+            "2fa" => {
+                let server = self.get_rocketchat_server()?;
+                self.two_factor_login(event, &server, command)
+            }
+            "login" => {
+                let server = self.get_rocketchat_server()?;
+                self.login(event, &server, command, redaction_note)
+            }
+            "logout" => {
+                let server = self.get_rocketchat_server()?;
+                self.logout(event, &server, command)
+            }
+            "status" | "whoami" => {
+                let server = self.get_rocketchat_server()?;
+                self.status(event, &server)
+            }
+            "list" => {
+                let server = self.get_rocketchat_server()?;
+                let result = self.list_rocketchat_rooms(event, &server, command);
+                self.expire_session_on_authentication_failure(event, &server, result)
+            }
+            "bridge" => {
+                let server = self.get_rocketchat_server()?;
+                let result = self.bridge(event, &server, command);
+                self.expire_session_on_authentication_failure(event, &server, result)
+            }
+            "unbridge" => {
+                let server = self.get_rocketchat_server()?;
+                let result = self.unbridge(event, &server, command);
+                self.expire_session_on_authentication_failure(event, &server, result)
+            }
+            "plumb" => {
+                let server = self.get_rocketchat_server()?;
+                let result = self.plumb(event, &server, command);
+                self.expire_session_on_authentication_failure(event, &server, result)
+            }
+            "relay" => {
+                let server = self.get_rocketchat_server()?;
+                self.relay(event, &server, command)
+            }
+            "unrelay" => {
+                let server = self.get_rocketchat_server()?;
+                self.unrelay(event, &server, command)
+            }
+            "dm" => {
+                let server = self.get_rocketchat_server()?;
+                let result = self.direct_message(event, &server, command);
+                self.expire_session_on_authentication_failure(event, &server, result)
+            }
+            "double-puppet" => {
+                let server = self.get_rocketchat_server()?;
+                self.double_puppet(event, &server, command, redaction_note)
+            }
+            "disconnect" => {
+                let server = self.get_rocketchat_server()?;
+                self.disconnect(event, &server)
+            }
+            "rotate-token" => {
+                let server = self.get_rocketchat_server()?;
+                self.rotate_token(event, server, command)
+            }
+            "change-url" => {
+                let server = self.get_rocketchat_server()?;
+                self.change_url(event, server, command)
+            }
+            "delete-server" => {
+                let server = self.get_rocketchat_server()?;
+                self.delete_server(event, &server)
+            }
+            _ => Err(command.unknown_command_error()),
         }
-
-        Ok(())
     }
 
-    fn connect(&self, event: &MessageEvent, message: &str) -> Result<()> {
+    fn connect(&self, event: &MessageEvent, command: &Command) -> Result<()> {
         self.connection
             .transaction(|| {
                 if self.admin_room.is_connected(self.connection)? {
@@ -91,14 +168,13 @@ impl<'a> CommandHandler<'a> {
                     );
                 }
 
-                let mut command = message.split_whitespace().collect::<Vec<&str>>().into_iter();
-                let rocketchat_url = command.by_ref().nth(1).unwrap_or_default();
+                let rocketchat_url = command.arg(0);
 
                 debug!(self.logger, "Connecting to Rocket.Chat server {}", rocketchat_url);
 
-                let server = match command.by_ref().next() {
+                let server = match command.args.get(1) {
                     Some(token) => {
-                        let rocketchat_id = command.by_ref().next().unwrap_or_default();
+                        let rocketchat_id = command.arg(2);
                         self.connect_new_rocketchat_server(rocketchat_id, rocketchat_url, token, &event.sender)?
                     }
                     None => self.get_existing_rocketchat_server(rocketchat_url)?,
@@ -175,15 +251,29 @@ impl<'a> CommandHandler<'a> {
         // see if we can reach the server and if the server has a supported API version
         RocketchatApi::new(rocketchat_url.to_owned(), self.logger.clone())?;
 
-        let new_rocketchat_server =
-            NewRocketchatServer { id: rocketchat_server_id, rocketchat_url, rocketchat_token: Some(token) };
+        let new_rocketchat_server = NewRocketchatServer {
+            id: rocketchat_server_id,
+            rocketchat_url,
+            rocketchat_token: Some(token),
+            created_by: Some(user_id.clone()),
+        };
 
         RocketchatServer::insert(self.connection, &new_rocketchat_server)
     }
 
-    fn help(&self, event: &MessageEvent) -> Result<()> {
-        let help_message =
-            CommandHandler::build_help_message(self.connection, self.admin_room, self.config.as_url.clone(), &event.sender)?;
+    fn help(&self, event: &MessageEvent, command: &Command) -> Result<()> {
+        let help_message = match command.args.first() {
+            Some(name) => match CommandSpec::find(name) {
+                Some(spec) => spec.usage().l(DEFAULT_LANGUAGE),
+                None => bail_error!(
+                    ErrorKind::UnknownCommand(name.clone()),
+                    t!(["errors", "unknown_command"]).with_vars(vec![("command", name.clone())])
+                ),
+            },
+            None => {
+                CommandHandler::build_help_message(self.connection, self.admin_room, self.config.as_url.clone(), &event.sender)?
+            }
+        };
         let bot_user_id = self.config.matrix_bot_user_id()?;
         self.matrix_api.send_text_message(self.admin_room.id.clone(), bot_user_id, help_message)?;
 
@@ -191,30 +281,400 @@ impl<'a> CommandHandler<'a> {
         Ok(())
     }
 
-    fn login(&self, event: &MessageEvent, server: &RocketchatServer, message: &str) -> Result<()> {
-        let mut command = message.split_whitespace().collect::<Vec<&str>>().into_iter();
-        let username = command.by_ref().nth(1).unwrap_or_default();
-        let password = command.by_ref().fold("".to_string(), |acc, x| acc + x);
+    fn login(
+        &self,
+        event: &MessageEvent,
+        server: &RocketchatServer,
+        command: &Command,
+        redaction_note: Option<&I18n>,
+    ) -> Result<()> {
+        let username = command.arg(0);
+        let password = command.arg(1);
 
         let credentials = Credentials {
             user_id: event.sender.clone(),
             rocketchat_username: username.to_string(),
             password: password.to_string(),
+            rocketchat_user_id: None,
+            personal_access_token: None,
+            code: None,
             rocketchat_url: server.rocketchat_url.clone(),
         };
 
+        let err = match server.login(self.config, self.connection, self.logger, self.matrix_api, &credentials, None) {
+            Ok(()) => return self.send_login_help(event, server, redaction_note),
+            Err(err) => err,
+        };
+
+        let method = match *err.error_chain {
+            ErrorKind::RocketchatTwoFactorRequired(ref method) => method.clone(),
+            _ => return Err(err),
+        };
+
+        TwoFactorLogin::new(server.id.clone(), credentials).save();
+
+        let bot_user_id = self.config.matrix_bot_user_id()?;
+        let timeout = (TWO_FACTOR_LOGIN_TIMEOUT_IN_SECONDS / 60).to_string();
+        let mut message =
+            t!(["admin_room", "two_factor_code_required"]).with_vars(vec![("method", method), ("timeout", timeout)]);
+        if let Some(redaction_note) = redaction_note {
+            message = message.append(redaction_note);
+        }
+        self.matrix_api.send_text_message(self.admin_room.id.clone(), bot_user_id, message.l(DEFAULT_LANGUAGE))?;
+
+        info!(self.logger, "Login of user {} is waiting for a two-factor authentication code", event.sender);
+        Ok(())
+    }
+
+    fn two_factor_login(&self, event: &MessageEvent, server: &RocketchatServer, command: &Command) -> Result<()> {
+        let code = command.arg(0);
+
+        let mut two_factor_login = match TwoFactorLogin::take(&event.sender, &server.id) {
+            Some(two_factor_login) => two_factor_login,
+            None => bail_error!(
+                ErrorKind::NoPendingTwoFactorLogin(event.sender.to_string()),
+                t!(["errors", "no_pending_two_factor_login"])
+            ),
+        };
+
+        two_factor_login.credentials.code = Some(code.to_string());
         let admin_room_id = Some(self.admin_room.id.clone());
-        server.login(self.config, self.connection, self.logger, self.matrix_api, &credentials, admin_room_id)
+        let result = server.login(
+            self.config,
+            self.connection,
+            self.logger,
+            self.matrix_api,
+            &two_factor_login.credentials,
+            admin_room_id,
+        );
+
+        if let Err(ref err) = result {
+            if let ErrorKind::RocketchatTwoFactorCodeInvalid = *err.error_chain {
+                // the user can try another code until the login times out
+                two_factor_login.credentials.code = None;
+                two_factor_login.save();
+            }
+        }
+
+        result
+    }
+
+    // Commands that contain credentials are redacted, so that they don't stay in the room history.
+    // Returns a note for the reply to the command that tells the user if the redaction succeeded.
+    fn redact_command(&self, event: &MessageEvent) -> Result<I18n> {
+        let bot_user_id = self.config.matrix_bot_user_id()?;
+        let reason = t!(["admin_room", "command_redaction_reason"]).l(DEFAULT_LANGUAGE);
+        match self.matrix_api.redact_event(self.admin_room.id.clone(), event.event_id.clone(), bot_user_id, Some(reason)) {
+            Ok(()) => {
+                debug!(self.logger, "Successfully redacted command {} in room {}", event.event_id, self.admin_room.id);
+                Ok(t!(["admin_room", "command_redacted"]))
+            }
+            Err(err) => {
+                warn!(self.logger, "Could not redact command {} in room {}: {}", event.event_id, self.admin_room.id, err);
+                Ok(t!(["admin_room", "command_redaction_failed"]))
+            }
+        }
+    }
+
+    fn send_login_help(&self, event: &MessageEvent, server: &RocketchatServer, redaction_note: Option<&I18n>) -> Result<()> {
+        let mut user_on_rocketchat_server = UserOnRocketchatServer::find(self.connection, &event.sender, server.id.clone())?;
+        user_on_rocketchat_server.set_admin_room_id(self.connection, Some(self.admin_room.id.clone()))?;
+
+        let help_message =
+            CommandHandler::build_help_message(self.connection, self.admin_room, self.config.as_url.clone(), &event.sender)?;
+        let message = match redaction_note {
+            Some(redaction_note) => format!("{}\n\n{}", help_message, redaction_note.l(DEFAULT_LANGUAGE)),
+            None => help_message,
+        };
+        let bot_user_id = self.config.matrix_bot_user_id()?;
+        self.matrix_api.send_text_message(self.admin_room.id.clone(), bot_user_id, message)
+    }
+
+    // The stored credentials are rejected when the session on the Rocket.Chat server expired or
+    // was revoked, in that case the user has to login again.
+    fn expire_session_on_authentication_failure(
+        &self,
+        event: &MessageEvent,
+        server: &RocketchatServer,
+        result: Result<()>,
+    ) -> Result<()> {
+        let err = match result {
+            Ok(()) => return Ok(()),
+            Err(err) => err,
+        };
+
+        if !err.is_rocketchat_authentication_failure() {
+            return Err(err);
+        }
+
+        let mut user_on_rocketchat_server = UserOnRocketchatServer::find(self.connection, &event.sender, server.id.clone())?;
+        if !user_on_rocketchat_server.is_logged_in() {
+            return Err(err);
+        }
+
+        user_on_rocketchat_server.set_admin_room_id(self.connection, Some(self.admin_room.id.clone()))?;
+        server.expire_session(self.config, self.connection, self.logger, self.matrix_api, &event.sender)
+    }
+
+    fn login_link(&self, event: &MessageEvent, server: &RocketchatServer) -> Result<()> {
+        let token = LoginToken::create(self.connection, event.sender.clone(), server.id.clone(), self.admin_room.id.clone())?;
+        let login_url = format!("{}/rocketchat/login/{}", self.config.as_url, token);
+
+        let bot_user_id = self.config.matrix_bot_user_id()?;
+        let timeout = (LOGIN_TOKEN_TIMEOUT_IN_SECONDS / 60).to_string();
+        let message =
+            t!(["admin_room", "login_link"]).with_vars(vec![("login_url", login_url), ("token", token), ("timeout", timeout)]);
+        self.matrix_api.send_text_message(self.admin_room.id.clone(), bot_user_id, message.l(DEFAULT_LANGUAGE))?;
+
+        info!(self.logger, "Successfully sent login link to user {}", event.sender);
+        Ok(())
+    }
+
+    fn login_with_personal_access_token(
+        &self,
+        event: &MessageEvent,
+        server: &RocketchatServer,
+        command: &Command,
+        redaction_note: Option<&I18n>,
+    ) -> Result<()> {
+        let rocketchat_user_id = command.arg(0);
+        let personal_access_token = command.arg(1);
+
+        let credentials = Credentials {
+            user_id: event.sender.clone(),
+            rocketchat_username: String::new(),
+            password: String::new(),
+            rocketchat_user_id: Some(rocketchat_user_id.to_string()),
+            personal_access_token: Some(personal_access_token.to_string()),
+            code: None,
+            rocketchat_url: server.rocketchat_url.clone(),
+        };
+
+        server.login(self.config, self.connection, self.logger, self.matrix_api, &credentials, None)?;
+        self.send_login_help(event, server, redaction_note)
+    }
+
+    fn logout(&self, event: &MessageEvent, server: &RocketchatServer, command: &Command) -> Result<()> {
+        let leave_rooms = match command.arg(0) {
+            "" => false,
+            "leave-rooms" => true,
+            _ => return Err(command.usage_error()),
+        };
+
+        let user_on_rocketchat_server =
+            UserOnRocketchatServer::find_by_matrix_user_id(self.connection, &event.sender, server.id.clone())?;
+        let mut user_on_rocketchat_server = match user_on_rocketchat_server.filter(|user| user.is_logged_in()) {
+            Some(user_on_rocketchat_server) => user_on_rocketchat_server,
+            None => bail_error!(
+                ErrorKind::NotLoggedIn(event.sender.to_string(), server.rocketchat_url.clone()),
+                t!(["errors", "not_logged_in"]).with_vars(vec![("rocketchat_url", server.rocketchat_url.clone())])
+            ),
+        };
+
+        let rocketchat_api = RocketchatApi::new(server.rocketchat_url.clone(), self.logger.clone())?.with_credentials(
+            user_on_rocketchat_server.rocketchat_user_id.clone().unwrap_or_default(),
+            user_on_rocketchat_server.rocketchat_auth_token.clone().unwrap_or_default(),
+        );
+
+        // the credentials are removed first, so that the user is logged out on the bridge even if
+        // one of the following steps fails
+        user_on_rocketchat_server.set_credentials(self.connection, None, None)?;
+        user_on_rocketchat_server.set_matrix_access_token(self.connection, None)?;
+        user_on_rocketchat_server.set_admin_room_id(self.connection, None)?;
+
+        let left_rooms =
+            if leave_rooms { self.leave_bridged_rooms(&event.sender, server, rocketchat_api.as_ref())? } else { Vec::new() };
+
+        // the session is gone anyway when it already expired
+        match rocketchat_api.logout() {
+            Err(ref err) if err.is_rocketchat_authentication_failure() => {
+                info!(self.logger, "Session of user {} on Rocket.Chat server {} was already expired", event.sender, server.id)
+            }
+            result => result?,
+        }
+
+        let mut message =
+            t!(["admin_room", "logout_successful"]).with_vars(vec![("rocketchat_url", server.rocketchat_url.clone())]);
+        if !left_rooms.is_empty() {
+            message =
+                message.append(&t!(["admin_room", "left_bridged_rooms"]).with_vars(vec![("rooms", left_rooms.join(", "))]));
+        }
+        let bot_user_id = self.config.matrix_bot_user_id()?;
+        self.matrix_api.send_text_message(self.admin_room.id.clone(), bot_user_id, message.l(DEFAULT_LANGUAGE))?;
+
+        info!(self.logger, "Successfully logged out user {} from Rocket.Chat server {}", event.sender, server.rocketchat_url);
+        Ok(())
     }
 
-    fn list_rocketchat_rooms(&self, event: &MessageEvent, server: &RocketchatServer) -> Result<()> {
+    // Removes the user from the rooms that the bridge created for the channels and groups the user is
+    // a member of on Rocket.Chat. Rooms of channels the user is not a member of on Rocket.Chat and
+    // plumbed rooms (existing Matrix rooms) are kept. Returns the names of the rooms that were left.
+    fn leave_bridged_rooms(
+        &self,
+        user_id: &UserId,
+        server: &RocketchatServer,
+        rocketchat_api: &RocketchatApi,
+    ) -> Result<Vec<String>> {
+        let bot_user_id = self.config.matrix_bot_user_id()?;
+        let reason = t!(["admin_room", "logout_reason"]).l(DEFAULT_LANGUAGE);
+        let mut rocketchat_rooms = match rocketchat_api.channels_list_joined() {
+            Err(ref err) if err.is_rocketchat_authentication_failure() => {
+                info!(self.logger, "Session of user {} expired, cannot find the bridged rooms to leave", user_id);
+                return Ok(Vec::new());
+            }
+            result => result?,
+        };
+        rocketchat_rooms.extend(rocketchat_api.groups_list()?);
+
+        let mut left_rooms = Vec::new();
+        for rocketchat_room in rocketchat_rooms {
+            let name = rocketchat_room.name.clone().unwrap_or_default();
+            if PlumbedRoom::find_by_rocketchat_channel_id(self.connection, &server.id, &rocketchat_room.id)?.is_some() {
+                debug!(self.logger, "Not removing user {} from the plumbed room of {}", user_id, name);
+                continue;
+            }
+
+            let rocketchat_room =
+                RocketchatRoom::new(self.config, self.logger, self.matrix_api, rocketchat_room.id, &server.id);
+            let room_id = match rocketchat_room.matrix_id()? {
+                Some(room_id) => room_id,
+                None => continue,
+            };
+
+            let room = Room::new(self.config, self.logger, self.matrix_api, room_id.clone());
+            if !room.user_ids(None)?.iter().any(|id| id == user_id) {
+                continue;
+            }
+
+            let kicked = self.matrix_api.kick_user(room_id.clone(), user_id.clone(), bot_user_id.clone(), Some(reason.clone()));
+            if let Err(err) = kicked {
+                log::log_error(self.logger, &err);
+                continue;
+            }
+
+            debug!(self.logger, "Removed user {} from bridged room {}", user_id, room_id);
+            left_rooms.push(name);
+        }
+
+        Ok(left_rooms)
+    }
+
+    fn status(&self, event: &MessageEvent, server: &RocketchatServer) -> Result<()> {
+        let unknown = t!(["admin_room", "status_unknown"]).l(DEFAULT_LANGUAGE);
+        let mut rocketchat_version = t!(["admin_room", "status_rocketchat_server_unreachable"]).l(DEFAULT_LANGUAGE);
+        let mut login = t!(["admin_room", "status_not_logged_in"]).l(DEFAULT_LANGUAGE);
+        let mut rooms_count = unknown.clone();
+        let mut direct_messages_count = unknown;
+
+        let user_on_rocketchat_server =
+            UserOnRocketchatServer::find_by_matrix_user_id(self.connection, &event.sender, server.id.clone())?;
+        let last_message_sent = user_on_rocketchat_server.as_ref().map(|user| user.last_message_sent).unwrap_or_default();
+
+        match RocketchatApi::new(server.rocketchat_url.clone(), self.logger.clone()) {
+            Ok(rocketchat_api) => {
+                rocketchat_version = rocketchat_api.version();
+                if let Some(user) = user_on_rocketchat_server.filter(|user| user.is_logged_in()) {
+                    let rocketchat_api = rocketchat_api.with_credentials(
+                        user.rocketchat_user_id.clone().unwrap_or_default(),
+                        user.rocketchat_auth_token.clone().unwrap_or_default(),
+                    );
+
+                    match rocketchat_api.me() {
+                        Ok(rocketchat_user) => {
+                            login = t!(["admin_room", "status_logged_in"])
+                                .with_vars(vec![("rocketchat_username", rocketchat_user.username)])
+                                .l(DEFAULT_LANGUAGE);
+                            rooms_count = self.count_bridged_rooms(&user, rocketchat_api.as_ref())?.to_string();
+                            direct_messages_count = self.count_direct_messages(&user, rocketchat_api.as_ref())?.to_string();
+                        }
+                        Err(ref err) if err.is_rocketchat_authentication_failure() => {
+                            login = t!(["admin_room", "status_login_invalid"]).l(DEFAULT_LANGUAGE);
+                        }
+                        Err(err) => return Err(err),
+                    }
+                }
+            }
+            Err(err) => debug!(self.logger, "Could not reach Rocket.Chat server {}: {}", server.rocketchat_url, err),
+        }
+
+        let mut errors = String::new();
+        for recorded_error in RecordedError::find_recent(self.connection, &event.sender, &server.id)? {
+            errors = errors + "*   " + &recorded_error.created_at + " UTC: " + &recorded_error.message + "\n\n";
+        }
+        if errors.is_empty() {
+            errors = t!(["admin_room", "status_no_errors"]).l(DEFAULT_LANGUAGE);
+        }
+
+        let bot_user_id = self.config.matrix_bot_user_id()?;
+        let message = t!(["admin_room", "status"]).with_vars(vec![
+            ("rocketchat_url", server.rocketchat_url.clone()),
+            ("rocketchat_version", rocketchat_version),
+            ("login", login),
+            ("rooms_count", rooms_count),
+            ("direct_messages_count", direct_messages_count),
+            ("last_message_sent", format_last_message_time(last_message_sent)?),
+            ("last_message_received", format_last_message_time(server.last_message_received)?),
+            ("errors", errors),
+        ]);
+        self.matrix_api.send_text_message(self.admin_room.id.clone(), bot_user_id, message.l(DEFAULT_LANGUAGE))?;
+
+        info!(self.logger, "Successfully sent the status for user {}", event.sender);
+        Ok(())
+    }
+
+    // Only the channels and groups that the user joined on Rocket.Chat are checked, servers can have
+    // thousands of public channels.
+    fn count_bridged_rooms(&self, user: &UserOnRocketchatServer, rocketchat_api: &RocketchatApi) -> Result<usize> {
+        let mut rocketchat_rooms = rocketchat_api.channels_list_joined()?;
+        rocketchat_rooms.extend(rocketchat_api.groups_list()?);
+
+        let mut rooms_count = 0;
+        for rocketchat_room in rocketchat_rooms {
+            let rocketchat_room =
+                RocketchatRoom::new(self.config, self.logger, self.matrix_api, rocketchat_room.id, &user.rocketchat_server_id);
+            if rocketchat_room.is_bridged_for_user(self.connection, &user.matrix_user_id)? {
+                rooms_count += 1;
+            }
+        }
+
+        Ok(rooms_count)
+    }
+
+    fn count_direct_messages(&self, user: &UserOnRocketchatServer, rocketchat_api: &RocketchatApi) -> Result<usize> {
+        let virtual_user = VirtualUser::new(self.config, self.connection, self.logger, self.matrix_api);
+        let rocketchat_user_id = user.rocketchat_user_id.clone().unwrap_or_default();
+
+        let mut direct_messages_count = 0;
+        for direct_message in rocketchat_api.dm_list()? {
+            // the ID of a direct message channel consists of the IDs of both participants
+            let other_rocketchat_user_id = direct_message.id.replace(&rocketchat_user_id, "");
+            let sender_id = virtual_user.build_user_id(&other_rocketchat_user_id, &user.rocketchat_server_id)?;
+            let room = Room::get_dm(
+                self.config,
+                self.connection,
+                self.logger,
+                self.matrix_api,
+                direct_message.id,
+                &sender_id,
+                &user.matrix_user_id,
+            )?;
+            if room.is_some() {
+                direct_messages_count += 1;
+            }
+        }
+
+        Ok(direct_messages_count)
+    }
+
+    fn list_rocketchat_rooms(&self, event: &MessageEvent, server: &RocketchatServer, command: &Command) -> Result<()> {
+        let options = ListOptions::from_command(command)?;
         let user_on_rocketchat_server = UserOnRocketchatServer::find(self.connection, &event.sender, server.id.clone())?;
         let rocketchat_api = RocketchatApi::new(server.rocketchat_url.clone(), self.logger.clone())?.with_credentials(
             user_on_rocketchat_server.rocketchat_user_id.unwrap_or_default(),
             user_on_rocketchat_server.rocketchat_auth_token.unwrap_or_default(),
         );
         let bot_user_id = self.config.matrix_bot_user_id()?;
-        let list = self.build_rocketchat_rooms_list(rocketchat_api.as_ref(), &server.id, &event.sender)?;
+        let list = self.build_rocketchat_rooms_list(rocketchat_api.as_ref(), &server.id, &event.sender, &options)?;
         let message = t!(["admin_room", "list_rocketchat_rooms"]).with_vars(vec![("list", list)]);
         self.matrix_api.send_text_message(self.admin_room.id.clone(), bot_user_id, message.l(DEFAULT_LANGUAGE))?;
 
@@ -222,7 +682,7 @@ impl<'a> CommandHandler<'a> {
         Ok(())
     }
 
-    fn bridge(&self, event: &MessageEvent, server: &RocketchatServer, message: &str) -> Result<()> {
+    fn bridge(&self, event: &MessageEvent, server: &RocketchatServer, command: &Command) -> Result<()> {
         let bot_user_id = self.config.matrix_bot_user_id()?;
         let user_on_rocketchat_server = UserOnRocketchatServer::find(self.connection, &event.sender, server.id.clone())?;
         let rocketchat_api = RocketchatApi::new(server.rocketchat_url.clone(), self.logger.clone())?.with_credentials(
@@ -233,19 +693,18 @@ impl<'a> CommandHandler<'a> {
         let channels = rocketchat_api.channels_list()?;
         let groups = rocketchat_api.groups_list()?;
 
-        let mut command = message.split_whitespace().collect::<Vec<&str>>().into_iter();
-        let rocketchat_room_name = command.by_ref().nth(1).unwrap_or_default();
+        let rocketchat_room_name = command.arg(0);
 
-        let (rocketchat_room_id, users) =
+        let (rocketchat_room_id, room_type, users) =
             match channels.iter().find(|channel| channel.name.clone().unwrap_or_default() == rocketchat_room_name) {
                 Some(channel) => {
                     let users = rocketchat_api.channels_members(&channel.id)?;
-                    (channel.id.clone(), users)
+                    (channel.id.clone(), RoomType::Channel, users)
                 }
                 None => match groups.iter().find(|group| group.name.clone().unwrap_or_default() == rocketchat_room_name) {
                     Some(group) => {
                         let users = rocketchat_api.groups_members(&group.id)?;
-                        (group.id.clone(), users)
+                        (group.id.clone(), RoomType::Group, users)
                     }
                     None => {
                         bail_error!(
@@ -267,7 +726,7 @@ impl<'a> CommandHandler<'a> {
         }
 
         let rocketchat_room = RocketchatRoom::new(self.config, self.logger, self.matrix_api, rocketchat_room_id, &server.id);
-        let room_id = match rocketchat_room.matrix_id()? {
+        let room_id = match rocketchat_room.bridged_matrix_id(self.connection)? {
             Some(room_id) => {
                 let room = Room::new(self.config, self.logger, self.matrix_api, room_id.clone());
                 room.bridge_for_user(event.sender.clone(), rocketchat_room_name.to_string())?;
@@ -276,15 +735,21 @@ impl<'a> CommandHandler<'a> {
             None => {
                 let usernames: Vec<String> = users.into_iter().map(|u| u.username).collect();
                 rocketchat_room.bridge(
+                    self.connection,
                     rocketchat_api.as_ref(),
+                    room_type,
                     &Some(rocketchat_room_name.to_string()),
                     &usernames,
                     &bot_user_id,
-                    &event.sender,
+                    Some(&event.sender),
                 )?
             }
         };
 
+        let roles = rocketchat_room.roles(rocketchat_api.as_ref(), room_type)?;
+        let room = Room::new(self.config, self.logger, self.matrix_api, room_id.clone());
+        room.sync_rocketchat_roles(self.connection, &roles, &server.id)?;
+
         let message = t!(["admin_room", "room_successfully_bridged"])
             .with_vars(vec![("rocketchat_room_name", rocketchat_room_name.to_string())]);
         self.matrix_api.send_text_message(self.admin_room.id.clone(), bot_user_id.clone(), message.l(DEFAULT_LANGUAGE))?;
@@ -293,9 +758,8 @@ impl<'a> CommandHandler<'a> {
         Ok(())
     }
 
-    fn unbridge(&self, event: &MessageEvent, server: &RocketchatServer, message: &str) -> Result<()> {
-        let mut command = message.split_whitespace().collect::<Vec<&str>>().into_iter();
-        let name = command.nth(1).unwrap_or_default().to_string();
+    fn unbridge(&self, event: &MessageEvent, server: &RocketchatServer, command: &Command) -> Result<()> {
+        let name = command.arg(0).to_string();
 
         let user_on_rocketchat_server = UserOnRocketchatServer::find(self.connection, &event.sender, server.id.clone())?;
         let rocketchat_api = RocketchatApi::new(server.rocketchat_url.clone(), self.logger.clone())?.with_credentials(
@@ -305,6 +769,12 @@ impl<'a> CommandHandler<'a> {
 
         let rocketchat_room =
             RocketchatRoom::from_name(self.config, self.logger, self.matrix_api, &name, &server.id, rocketchat_api.as_ref())?;
+        if let Some(plumbed_room) =
+            PlumbedRoom::find_by_rocketchat_channel_id(self.connection, &server.id, &rocketchat_room.id)?
+        {
+            return self.unbridge_plumbed_room(event, &plumbed_room, &name);
+        }
+
         let rocketchat_room_id = match rocketchat_room.matrix_id()? {
             Some(rocketchat_room_id) => rocketchat_room_id,
             None => {
@@ -344,12 +814,21 @@ impl<'a> CommandHandler<'a> {
         }
 
         self.matrix_api.delete_room_alias(canonical_alias_id)?;
+        rocketchat_room.unpublish(self.connection, &room.id)?;
 
         for user_id in user_ids {
             debug!(self.logger, "Leaving and forgetting room {} for user {}", room.id, user_id);
             room.forget(user_id)?;
         }
 
+        if let Some(relay_room) = RelayRoom::find_by_matrix_room_id(self.connection, &room.id)? {
+            relay_room.delete(self.connection)?;
+        }
+
+        if let Some(bridged_room) = BridgedRoom::find_by_matrix_room_id(self.connection, &room.id)? {
+            bridged_room.delete(self.connection)?;
+        }
+
         let bot_user_id = self.config.matrix_bot_user_id()?;
         let message = t!(["admin_room", "room_successfully_unbridged"]).with_vars(vec![("rocketchat_room_name", name.clone())]);
         self.matrix_api.send_text_message(self.admin_room.id.clone(), bot_user_id, message.l(DEFAULT_LANGUAGE))?;
@@ -358,6 +837,480 @@ impl<'a> CommandHandler<'a> {
         Ok(())
     }
 
+    fn plumb(&self, event: &MessageEvent, server: &RocketchatServer, command: &Command) -> Result<()> {
+        let matrix_room = command.arg(0);
+        let rocketchat_room_name = command.arg(1);
+
+        let user_on_rocketchat_server = UserOnRocketchatServer::find(self.connection, &event.sender, server.id.clone())?;
+        let rocketchat_api = RocketchatApi::new(server.rocketchat_url.clone(), self.logger.clone())?.with_credentials(
+            user_on_rocketchat_server.rocketchat_user_id.clone().unwrap_or_default(),
+            user_on_rocketchat_server.rocketchat_auth_token.clone().unwrap_or_default(),
+        );
+
+        let rocketchat_room = RocketchatRoom::from_name(
+            self.config,
+            self.logger,
+            self.matrix_api,
+            rocketchat_room_name,
+            &server.id,
+            rocketchat_api.as_ref(),
+        )?;
+        if rocketchat_room.id.is_empty() {
+            bail_error!(
+                ErrorKind::RocketchatChannelOrGroupNotFound(rocketchat_room_name.to_string()),
+                t!(["errors", "rocketchat_channel_or_group_not_found"])
+                    .with_vars(vec![("rocketchat_room_name", rocketchat_room_name.to_string())])
+            );
+        }
+
+        if rocketchat_room.bridged_matrix_id(self.connection)?.is_some() {
+            bail_error!(
+                ErrorKind::RocketchatChannelAlreadyBridged(rocketchat_room_name.to_string()),
+                t!(["errors", "rocketchat_channel_already_bridged"])
+                    .with_vars(vec![("rocketchat_room_name", rocketchat_room_name.to_string())])
+            );
+        }
+
+        // the room was found in the channels or groups of the user, so the user can see it
+        let room_type = rocketchat_room.detect_room_type(rocketchat_api.as_ref())?.unwrap_or(RoomType::Channel);
+        let users = rocketchat_room.members(rocketchat_api.as_ref(), room_type)?;
+        let username = rocketchat_api.me()?.username;
+        if !users.iter().any(|u| u.username == username) {
+            bail_error!(
+                ErrorKind::RocketchatJoinFirst(rocketchat_room_name.to_string()),
+                t!(["errors", "rocketchat_join_first"])
+                    .with_vars(vec![("rocketchat_room_name", rocketchat_room_name.to_string())])
+            );
+        }
+
+        let matrix_room_id = self.get_matrix_room_id(matrix_room)?;
+        if matrix_room_id == self.admin_room.id
+            || PlumbedRoom::find_by_matrix_room_id(self.connection, &matrix_room_id)?.is_some()
+        {
+            bail_error!(
+                ErrorKind::MatrixRoomAlreadyBridged(matrix_room.to_string()),
+                t!(["errors", "matrix_room_already_bridged"]).with_vars(vec![("matrix_room", matrix_room.to_string())])
+            );
+        }
+
+        let new_plumbed_room = NewPlumbedRoom {
+            matrix_room_id: matrix_room_id.clone(),
+            rocketchat_server_id: server.id.clone(),
+            rocketchat_channel_id: rocketchat_room.id.clone(),
+            created_by: event.sender.clone(),
+        };
+        PlumbedRoom::add_pending(new_plumbed_room, self.admin_room.id.clone());
+
+        // the room is plumbed as soon as the bot user joined it, which only works if the room is
+        // public or the bot user was invited already
+        let bot_user_id = self.config.matrix_bot_user_id()?;
+        if let Err(err) = self.matrix_api.join(matrix_room_id.clone(), bot_user_id.clone()) {
+            debug!(self.logger, "Bot user could not join room {}, waiting for an invite: {}", matrix_room_id, err);
+            let message = t!(["admin_room", "plumb_invite_bot"]).with_vars(vec![
+                ("bot_user_id", bot_user_id.to_string()),
+                ("matrix_room", matrix_room.to_string()),
+                ("rocketchat_room_name", rocketchat_room_name.to_string()),
+            ]);
+            self.matrix_api.send_text_message(self.admin_room.id.clone(), bot_user_id, message.l(DEFAULT_LANGUAGE))?;
+        }
+
+        info!(self.logger, "Plumbing of room {} with {} requested by {}", matrix_room_id, rocketchat_room.id, event.sender);
+        Ok(())
+    }
+
+    fn unbridge_plumbed_room(&self, event: &MessageEvent, plumbed_room: &PlumbedRoom, name: &str) -> Result<()> {
+        let room = Room::new(self.config, self.logger, self.matrix_api, plumbed_room.matrix_room_id.clone());
+        if !room.is_room_admin(&event.sender)? {
+            bail_error!(
+                ErrorKind::NotRoomAdmin(event.sender.to_string(), room.id.to_string()),
+                t!(["errors", "not_room_admin"]).with_vars(vec![("matrix_room", room.id.to_string())])
+            );
+        }
+
+        self.unplumb(plumbed_room)?;
+
+        let bot_user_id = self.config.matrix_bot_user_id()?;
+        let message =
+            t!(["admin_room", "room_successfully_unbridged"]).with_vars(vec![("rocketchat_room_name", name.to_string())]);
+        self.matrix_api.send_text_message(self.admin_room.id.clone(), bot_user_id, message.l(DEFAULT_LANGUAGE))?;
+
+        info!(self.logger, "Successfully unbridged plumbed room {}", room.id);
+        Ok(())
+    }
+
+    // The Matrix users stay in a plumbed room, only the virtual users and the bot user leave it.
+    // Returns the virtual users that left the room.
+    fn unplumb(&self, plumbed_room: &PlumbedRoom) -> Result<Vec<UserId>> {
+        let room = Room::new(self.config, self.logger, self.matrix_api, plumbed_room.matrix_room_id.clone());
+        plumbed_room.delete(self.connection)?;
+
+        let mut virtual_user_ids = Vec::new();
+        for user_id in room.user_ids(None)? {
+            if self.config.is_application_service_virtual_user(&user_id) {
+                debug!(self.logger, "Leaving and forgetting room {} for user {}", room.id, user_id);
+                room.forget(user_id.clone())?;
+                virtual_user_ids.push(user_id);
+            }
+        }
+
+        if let Some(relay_room) = RelayRoom::find_by_matrix_room_id(self.connection, &room.id)? {
+            relay_room.delete(self.connection)?;
+        }
+
+        if let Some(bridged_room) = BridgedRoom::find_by_matrix_room_id(self.connection, &room.id)? {
+            bridged_room.delete(self.connection)?;
+        }
+
+        room.forget(self.config.matrix_bot_user_id()?)?;
+        Ok(virtual_user_ids)
+    }
+
+    fn relay(&self, event: &MessageEvent, server: &RocketchatServer, command: &Command) -> Result<()> {
+        let name = command.arg(0).to_string();
+
+        let room_id = self.get_bridged_room_id(&event.sender, server, &name)?;
+        let new_relay_room = NewRelayRoom {
+            matrix_room_id: room_id.clone(),
+            rocketchat_server_id: server.id.clone(),
+            relay_matrix_user_id: event.sender.clone(),
+        };
+        RelayRoom::upsert(self.connection, &new_relay_room)?;
+
+        let bot_user_id = self.config.matrix_bot_user_id()?;
+        let message = t!(["admin_room", "relay_enabled"]).with_vars(vec![("rocketchat_room_name", name.clone())]);
+        self.matrix_api.send_text_message(self.admin_room.id.clone(), bot_user_id, message.l(DEFAULT_LANGUAGE))?;
+
+        info!(self.logger, "Successfully enabled relaying for room {} via user {}", room_id, event.sender);
+        Ok(())
+    }
+
+    fn unrelay(&self, event: &MessageEvent, server: &RocketchatServer, command: &Command) -> Result<()> {
+        let name = command.arg(0).to_string();
+
+        let room_id = self.get_bridged_room_id(&event.sender, server, &name)?;
+        match RelayRoom::find_by_matrix_room_id(self.connection, &room_id)? {
+            Some(relay_room) => relay_room.delete(self.connection)?,
+            None => {
+                bail_error!(
+                    ErrorKind::RelayNotEnabled(name.clone()),
+                    t!(["errors", "relay_not_enabled"]).with_vars(vec![("rocketchat_room_name", name)])
+                );
+            }
+        }
+
+        let bot_user_id = self.config.matrix_bot_user_id()?;
+        let message = t!(["admin_room", "relay_disabled"]).with_vars(vec![("rocketchat_room_name", name.clone())]);
+        self.matrix_api.send_text_message(self.admin_room.id.clone(), bot_user_id, message.l(DEFAULT_LANGUAGE))?;
+
+        info!(self.logger, "Successfully disabled relaying for room {}", room_id);
+        Ok(())
+    }
+
+    fn direct_message(&self, event: &MessageEvent, server: &RocketchatServer, command: &Command) -> Result<()> {
+        let rocketchat_username = command.arg(0);
+
+        let user_on_rocketchat_server = UserOnRocketchatServer::find(self.connection, &event.sender, server.id.clone())?;
+        let rocketchat_api = RocketchatApi::new(server.rocketchat_url.clone(), self.logger.clone())?.with_credentials(
+            user_on_rocketchat_server.rocketchat_user_id.clone().unwrap_or_default(),
+            user_on_rocketchat_server.rocketchat_auth_token.clone().unwrap_or_default(),
+        );
+
+        let room = Room::start_dm(
+            self.config,
+            self.connection,
+            self.logger,
+            self.matrix_api,
+            rocketchat_api.as_ref(),
+            &server.id,
+            rocketchat_username,
+            &event.sender,
+        )?;
+
+        let bot_user_id = self.config.matrix_bot_user_id()?;
+        let message = t!(["admin_room", "direct_message_started"])
+            .with_vars(vec![("rocketchat_username", rocketchat_username.to_string())]);
+        self.matrix_api.send_text_message(self.admin_room.id.clone(), bot_user_id, message.l(DEFAULT_LANGUAGE))?;
+
+        info!(self.logger, "Direct message room {} with {} started by {}", room.id, rocketchat_username, event.sender);
+        Ok(())
+    }
+
+    fn double_puppet(
+        &self,
+        event: &MessageEvent,
+        server: &RocketchatServer,
+        command: &Command,
+        redaction_note: Option<&I18n>,
+    ) -> Result<()> {
+        let access_token = command.arg(0);
+
+        let mut user_on_rocketchat_server = UserOnRocketchatServer::find(self.connection, &event.sender, server.id.clone())?;
+        let mut message = if access_token.is_empty() || access_token == "disable" {
+            user_on_rocketchat_server.set_matrix_access_token(self.connection, None)?;
+            t!(["admin_room", "double_puppeting_disabled"])
+        } else {
+            self.validate_matrix_access_token(&event.sender, access_token)?;
+            user_on_rocketchat_server.set_matrix_access_token(self.connection, Some(access_token.to_string()))?;
+            t!(["admin_room", "double_puppeting_enabled"])
+        };
+        if let Some(redaction_note) = redaction_note {
+            message = message.append(redaction_note);
+        }
+
+        let bot_user_id = self.config.matrix_bot_user_id()?;
+        self.matrix_api.send_text_message(self.admin_room.id.clone(), bot_user_id, message.l(DEFAULT_LANGUAGE))?;
+
+        info!(self.logger, "Successfully updated double puppeting access token for user {}", event.sender);
+        Ok(())
+    }
+
+    // messages would be sent as whichever user the token belongs to, so only tokens of the sender
+    // are accepted.
+    fn validate_matrix_access_token(&self, user_id: &UserId, access_token: &str) -> Result<()> {
+        let error_msg = match self.matrix_api.whoami(access_token) {
+            Ok(ref token_user_id) if token_user_id == user_id => return Ok(()),
+            Ok(token_user_id) => format!("Token belongs to user {}", token_user_id),
+            Err(err) => err.to_string(),
+        };
+
+        bail_error!(
+            ErrorKind::InvalidMatrixAccessToken(user_id.to_string(), error_msg),
+            t!(["errors", "invalid_matrix_access_token"]).with_vars(vec![("matrix_user_id", user_id.to_string())])
+        );
+    }
+
+    fn disconnect(&self, event: &MessageEvent, server: &RocketchatServer) -> Result<()> {
+        // admin rooms are connected to a server via their topic
+        self.matrix_api.set_room_topic(self.admin_room.id.clone(), String::new())?;
+
+        if let Some(mut user_on_rocketchat_server) =
+            UserOnRocketchatServer::find_by_matrix_user_id(self.connection, &event.sender, server.id.clone())?
+        {
+            if user_on_rocketchat_server.admin_room_id.as_ref() == Some(&self.admin_room.id) {
+                user_on_rocketchat_server.set_admin_room_id(self.connection, None)?;
+            }
+        }
+
+        let bot_user_id = self.config.matrix_bot_user_id()?;
+        let message = t!(["admin_room", "room_successfully_disconnected"])
+            .with_vars(vec![("rocketchat_url", server.rocketchat_url.clone())]);
+        self.matrix_api.send_text_message(self.admin_room.id.clone(), bot_user_id, message.l(DEFAULT_LANGUAGE))?;
+
+        info!(
+            self.logger,
+            "Successfully disconnected room {} from Rocket.Chat server {}", self.admin_room.id, server.rocketchat_url
+        );
+        Ok(())
+    }
+
+    fn rotate_token(&self, event: &MessageEvent, mut server: RocketchatServer, command: &Command) -> Result<()> {
+        self.check_server_management_permission(event, &server)?;
+
+        let token = command.arg(0);
+
+        if RocketchatServer::find_by_token(self.connection, token)?.is_some() {
+            bail_error!(
+                ErrorKind::RocketchatTokenAlreadyInUse(token.to_owned()),
+                t!(["errors", "token_already_in_use"]).with_vars(vec![("token", token.to_owned())])
+            );
+        }
+
+        server.set_rocketchat_token(self.connection, Some(token.to_string()))?;
+
+        let bot_user_id = self.config.matrix_bot_user_id()?;
+        let message =
+            t!(["admin_room", "rocketchat_token_rotated"]).with_vars(vec![("rocketchat_url", server.rocketchat_url.clone())]);
+        self.matrix_api.send_text_message(self.admin_room.id.clone(), bot_user_id, message.l(DEFAULT_LANGUAGE))?;
+
+        info!(self.logger, "Successfully rotated the token of Rocket.Chat server {}", server.rocketchat_url);
+        Ok(())
+    }
+
+    fn change_url(&self, event: &MessageEvent, mut server: RocketchatServer, command: &Command) -> Result<()> {
+        self.check_server_management_permission(event, &server)?;
+
+        let rocketchat_url = command.arg(0);
+        if RocketchatServer::find_by_url(self.connection, rocketchat_url)?.is_some() {
+            bail_error!(
+                ErrorKind::RocketchatUrlAlreadyInUse(rocketchat_url.to_owned()),
+                t!(["errors", "rocketchat_url_already_in_use"]).with_vars(vec![("rocketchat_url", rocketchat_url.to_owned())])
+            );
+        }
+
+        // see if we can reach the server and if the server has a supported API version
+        RocketchatApi::new(rocketchat_url.to_owned(), self.logger.clone())?;
+
+        let admin_room_ids = self.connected_admin_room_ids(&server)?;
+        let old_rocketchat_url = server.rocketchat_url.clone();
+        server.set_rocketchat_url(self.connection, rocketchat_url.to_string())?;
+        for admin_room_id in admin_room_ids {
+            self.matrix_api.set_room_topic(admin_room_id, rocketchat_url.to_string())?;
+        }
+
+        let bot_user_id = self.config.matrix_bot_user_id()?;
+        let message = t!(["admin_room", "rocketchat_url_changed"]).with_vars(vec![
+            ("old_rocketchat_url", old_rocketchat_url.clone()),
+            ("rocketchat_url", rocketchat_url.to_string()),
+        ]);
+        self.matrix_api.send_text_message(self.admin_room.id.clone(), bot_user_id, message.l(DEFAULT_LANGUAGE))?;
+
+        info!(self.logger, "Successfully changed the URL of Rocket.Chat server {} to {}", old_rocketchat_url, rocketchat_url);
+        Ok(())
+    }
+
+    fn delete_server(&self, event: &MessageEvent, server: &RocketchatServer) -> Result<()> {
+        self.check_server_management_permission(event, server)?;
+
+        let bot_user_id = self.config.matrix_bot_user_id()?;
+        let reason = t!(["admin_room", "rocketchat_server_deleted_reason"]).l(DEFAULT_LANGUAGE);
+        let mut virtual_user_ids: Vec<UserId> = Vec::new();
+        let mut rooms_count = 0;
+
+        for room_id in self.matrix_api.get_joined_rooms(bot_user_id.clone())? {
+            let room = Room::new(self.config, self.logger, self.matrix_api, room_id.clone());
+            if let Some(plumbed_room) = PlumbedRoom::find_by_matrix_room_id(self.connection, &room_id)? {
+                if plumbed_room.rocketchat_server_id == server.id {
+                    for user_id in self.unplumb(&plumbed_room)? {
+                        if !virtual_user_ids.contains(&user_id) {
+                            virtual_user_ids.push(user_id);
+                        }
+                    }
+                    rooms_count += 1;
+                }
+                continue;
+            }
+
+            if room.is_admin_room(self.connection)? {
+                if self.matrix_api.get_room_topic(room_id.clone())? == Some(server.rocketchat_url.clone()) {
+                    self.matrix_api.set_room_topic(room_id, String::new())?;
+                }
+                continue;
+            }
+
+            match room.rocketchat_server(self.connection)? {
+                Some(ref room_server) if room_server.id == server.id => {}
+                _ => continue,
+            }
+
+            if let Some(alias_id) = self.matrix_api.get_room_canonical_alias(room_id.clone())? {
+                self.matrix_api.delete_room_alias(alias_id)?;
+            }
+
+            for user_id in room.user_ids(None)? {
+                if user_id == bot_user_id {
+                    continue;
+                } else if self.config.is_application_service_virtual_user(&user_id) {
+                    room.forget(user_id.clone())?;
+                    if !virtual_user_ids.contains(&user_id) {
+                        virtual_user_ids.push(user_id);
+                    }
+                } else {
+                    self.matrix_api.kick_user(room_id.clone(), user_id, bot_user_id.clone(), Some(reason.clone()))?;
+                }
+            }
+
+            room.forget(bot_user_id.clone())?;
+            rooms_count += 1;
+        }
+
+        if let Some(ref space_room_id) = server.space_room_id {
+            let space = Room::new(self.config, self.logger, self.matrix_api, space_room_id.clone());
+            for user_id in space.user_ids(None)? {
+                if user_id != bot_user_id {
+                    self.matrix_api.kick_user(space_room_id.clone(), user_id, bot_user_id.clone(), Some(reason.clone()))?;
+                }
+            }
+            space.forget(bot_user_id.clone())?;
+        }
+
+        // the virtual users are also part of the direct message rooms
+        for virtual_user_id in &virtual_user_ids {
+            for room_id in self.matrix_api.get_joined_rooms(virtual_user_id.clone())? {
+                Room::new(self.config, self.logger, self.matrix_api, room_id).forget(virtual_user_id.clone())?;
+            }
+        }
+
+        server.delete(self.connection)?;
+
+        let message = t!(["admin_room", "rocketchat_server_deleted"]).with_vars(vec![
+            ("rocketchat_url", server.rocketchat_url.clone()),
+            ("rooms_count", rooms_count.to_string()),
+            ("virtual_users_count", virtual_user_ids.len().to_string()),
+        ]);
+        self.matrix_api.send_text_message(self.admin_room.id.clone(), bot_user_id, message.l(DEFAULT_LANGUAGE))?;
+
+        info!(self.logger, "Successfully deleted Rocket.Chat server {}", server.rocketchat_url);
+        Ok(())
+    }
+
+    fn check_server_management_permission(&self, event: &MessageEvent, server: &RocketchatServer) -> Result<()> {
+        if !server.is_manageable_by(self.config, &event.sender) {
+            bail_error!(
+                ErrorKind::RocketchatServerManagementNotAllowed(event.sender.to_string(), server.rocketchat_url.clone()),
+                t!(["errors", "rocketchat_server_management_not_allowed"])
+                    .with_vars(vec![("rocketchat_url", server.rocketchat_url.clone())])
+            );
+        }
+
+        Ok(())
+    }
+
+    // Admin rooms are connected to a server via their topic, which contains the URL of the server.
+    fn connected_admin_room_ids(&self, server: &RocketchatServer) -> Result<Vec<RoomId>> {
+        let bot_user_id = self.config.matrix_bot_user_id()?;
+        let mut admin_room_ids = Vec::new();
+        for room_id in self.matrix_api.get_joined_rooms(bot_user_id)? {
+            if self.matrix_api.get_room_topic(room_id.clone())? != Some(server.rocketchat_url.clone()) {
+                continue;
+            }
+
+            if Room::new(self.config, self.logger, self.matrix_api, room_id.clone()).is_admin_room(self.connection)? {
+                admin_room_ids.push(room_id);
+            }
+        }
+
+        Ok(admin_room_ids)
+    }
+
+    /// Get the Matrix room ID of a Rocket.Chat channel or group that is bridged for the user.
+    fn get_bridged_room_id(&self, user_id: &UserId, server: &RocketchatServer, name: &str) -> Result<RoomId> {
+        let user_on_rocketchat_server = UserOnRocketchatServer::find(self.connection, user_id, server.id.clone())?;
+        let rocketchat_api = RocketchatApi::new(server.rocketchat_url.clone(), self.logger.clone())?.with_credentials(
+            user_on_rocketchat_server.rocketchat_user_id.clone().unwrap_or_default(),
+            user_on_rocketchat_server.rocketchat_auth_token.clone().unwrap_or_default(),
+        );
+
+        let rocketchat_room =
+            RocketchatRoom::from_name(self.config, self.logger, self.matrix_api, name, &server.id, rocketchat_api.as_ref())?;
+        match rocketchat_room.bridged_matrix_id(self.connection)? {
+            Some(ref room_id) if rocketchat_room.is_bridged_for_user(self.connection, user_id)? => Ok(room_id.clone()),
+            _ => Err(user_error!(
+                ErrorKind::RelayOfNotBridgedRoom(name.to_string()),
+                t!(["errors", "relay_of_not_bridged_room"]).with_vars(vec![("rocketchat_room_name", name.to_string())])
+            )),
+        }
+    }
+
+    // Matrix rooms can be referenced by their ID or by one of their aliases.
+    fn get_matrix_room_id(&self, matrix_room: &str) -> Result<RoomId> {
+        let room_id = if matrix_room.starts_with('#') {
+            match RoomAliasId::try_from(matrix_room) {
+                Ok(room_alias_id) => self.matrix_api.get_room_alias(room_alias_id)?,
+                Err(_) => None,
+            }
+        } else {
+            RoomId::try_from(matrix_room).ok()
+        };
+
+        match room_id {
+            Some(room_id) => Ok(room_id),
+            None => Err(user_error!(
+                ErrorKind::MatrixRoomNotFound(matrix_room.to_string()),
+                t!(["errors", "matrix_room_not_found"]).with_vars(vec![("matrix_room", matrix_room.to_string())])
+            )),
+        }
+    }
+
     fn get_existing_rocketchat_server(&self, rocketchat_url: &str) -> Result<RocketchatServer> {
         let server: RocketchatServer = match RocketchatServer::find_by_url(self.connection, rocketchat_url)? {
             Some(server) => server,
@@ -374,48 +1327,85 @@ impl<'a> CommandHandler<'a> {
         rocketchat_api: &RocketchatApi,
         rocketchat_server_id: &str,
         user_id: &UserId,
+        options: &ListOptions,
     ) -> Result<String> {
-        let channels = rocketchat_api.channels_list()?;
-        let groups = rocketchat_api.groups_list()?;
-        let mut joined_rocketchat_rooms = rocketchat_api.channels_list_joined()?;
-        joined_rocketchat_rooms.extend(groups.iter().cloned());
+        let mut channels = rocketchat_api.channels_list()?;
+        let mut groups = rocketchat_api.groups_list()?;
+        let joined_channels = rocketchat_api.channels_list_joined()?;
+        channels.sort_by_key(room_sort_key);
+        groups.sort_by_key(room_sort_key);
 
-        let channel_list =
-            self.format_rocketchat_rooms_list(rocketchat_server_id, user_id, &channels, &joined_rocketchat_rooms)?;
-        let groups_list =
-            self.format_rocketchat_rooms_list(rocketchat_server_id, user_id, &groups, &joined_rocketchat_rooms)?;
-        let channels_title = t!(["admin_room", "channels"]).l(DEFAULT_LANGUAGE);
-        let groups_title = t!(["admin_room", "groups"]).l(DEFAULT_LANGUAGE);
-        let list = format!("{}:\n{}\n{}:\n{}", channels_title, channel_list, groups_title, groups_list);
+        // the groups list only contains groups the user is a member of
+        let mut entries: Vec<RoomListEntry> = channels
+            .into_iter()
+            .map(|channel| {
+                let joined = joined_channels.iter().any(|jc| jc.id == channel.id);
+                RoomListEntry { room: channel, is_group: false, joined, bridged: None }
+            })
+            .chain(groups.into_iter().map(|group| RoomListEntry { room: group, is_group: true, joined: true, bridged: None }))
+            .filter(|entry| options.matches(entry))
+            .collect();
 
-        Ok(list)
-    }
+        // looking up the bridge status needs a request to the homeserver, so it's only done for all
+        // rooms when the list is filtered by it
+        if options.bridged {
+            for entry in &mut entries {
+                entry.bridged = Some(self.is_bridged_for_user(rocketchat_server_id, user_id, &entry.room)?);
+            }
+            entries.retain(|entry| entry.bridged == Some(true));
+        }
 
-    fn format_rocketchat_rooms_list(
-        &self,
-        rocketchat_server_id: &str,
-        user_id: &UserId,
-        rocketchat_rooms: &[Channel],
-        joined_rocketchat_rooms: &[Channel],
-    ) -> Result<String> {
-        let mut list = "".to_string();
-        for r in rocketchat_rooms {
-            let rocketchat_room =
-                RocketchatRoom::new(self.config, self.logger, self.matrix_api, r.id.clone(), rocketchat_server_id);
-            let formatter = if rocketchat_room.is_bridged_for_user(user_id)? {
-                "**"
-            } else if joined_rocketchat_rooms.iter().any(|jc| jc.id == r.id) {
-                "*"
-            } else {
-                ""
+        if entries.is_empty() {
+            return Ok(t!(["admin_room", "list_no_rooms_found"]).l(DEFAULT_LANGUAGE));
+        }
+
+        let pages = (entries.len() + ROOMS_PER_PAGE - 1) / ROOMS_PER_PAGE;
+        if options.page > pages {
+            bail_error!(
+                ErrorKind::InvalidCommandArguments("list".to_string()),
+                t!(["errors", "list_page_not_found"])
+                    .with_vars(vec![("page", options.page.to_string()), ("pages", pages.to_string())])
+            );
+        }
+
+        let page_entries = entries.into_iter().skip((options.page - 1) * ROOMS_PER_PAGE).take(ROOMS_PER_PAGE);
+        let mut channel_list = "".to_string();
+        let mut group_list = "".to_string();
+        for entry in page_entries {
+            let bridged = match entry.bridged {
+                Some(bridged) => bridged,
+                None => self.is_bridged_for_user(rocketchat_server_id, user_id, &entry.room)?,
             };
+            let line = format_room_list_entry(&entry, bridged);
+            if entry.is_group {
+                group_list = group_list + &line;
+            } else {
+                channel_list = channel_list + &line;
+            }
+        }
 
-            list = list + "*   " + formatter + &r.name.clone().unwrap_or(rocketchat_room.id) + formatter + "\n\n";
+        let mut list = "".to_string();
+        if !channel_list.is_empty() {
+            list = list + &format!("{}:\n{}\n", t!(["admin_room", "channels"]).l(DEFAULT_LANGUAGE), channel_list);
+        }
+        if !group_list.is_empty() {
+            list = list + &format!("{}:\n{}\n", t!(["admin_room", "groups"]).l(DEFAULT_LANGUAGE), group_list);
+        }
+        if pages > 1 {
+            let page_info = t!(["admin_room", "list_page"])
+                .with_vars(vec![("page", options.page.to_string()), ("pages", pages.to_string())]);
+            list = list + &page_info.l(DEFAULT_LANGUAGE);
         }
 
         Ok(list)
     }
 
+    fn is_bridged_for_user(&self, rocketchat_server_id: &str, user_id: &UserId, room: &Channel) -> Result<bool> {
+        let rocketchat_room =
+            RocketchatRoom::new(self.config, self.logger, self.matrix_api, room.id.clone(), rocketchat_server_id);
+        rocketchat_room.is_bridged_for_user(self.connection, user_id)
+    }
+
     fn get_rocketchat_server(&self) -> Result<RocketchatServer> {
         match self.admin_room.rocketchat_server_for_admin_room(self.connection)? {
             Some(server) => Ok(server),
@@ -455,3 +1445,95 @@ impl<'a> CommandHandler<'a> {
         Ok(message.l(DEFAULT_LANGUAGE))
     }
 }
+
+fn format_last_message_time(timestamp: i64) -> Result<String> {
+    if timestamp == 0 {
+        return Ok(t!(["admin_room", "status_never"]).l(DEFAULT_LANGUAGE));
+    }
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).chain_err(|| ErrorKind::InternalServerError)?.as_secs() as i64;
+    let seconds = (now - timestamp).max(0);
+    let (key, count) = if seconds < 60 {
+        ("seconds_ago", seconds)
+    } else if seconds < 3600 {
+        ("minutes_ago", seconds / 60)
+    } else if seconds < 86400 {
+        ("hours_ago", seconds / 3600)
+    } else {
+        ("days_ago", seconds / 86400)
+    };
+
+    Ok(t!(["admin_room", key]).with_vars(vec![("count", count.to_string())]).l(DEFAULT_LANGUAGE))
+}
+
+/// Options of the `list` command
+struct ListOptions {
+    filter: String,
+    joined: bool,
+    bridged: bool,
+    page: usize,
+}
+
+/// A Rocket.Chat room that is shown in the room list
+struct RoomListEntry {
+    room: Channel,
+    is_group: bool,
+    joined: bool,
+    bridged: Option<bool>,
+}
+
+impl ListOptions {
+    fn from_command(command: &Command) -> Result<ListOptions> {
+        let mut options = ListOptions { filter: "".to_string(), joined: false, bridged: false, page: 1 };
+        let mut args = command.args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--joined" => options.joined = true,
+                "--bridged" => options.bridged = true,
+                "--page" => {
+                    options.page = match args.next().and_then(|page| page.parse::<usize>().ok()) {
+                        Some(page) if page > 0 => page,
+                        _ => return Err(command.usage_error()),
+                    };
+                }
+                filter if !filter.starts_with("--") && options.filter.is_empty() => options.filter = filter.to_lowercase(),
+                _ => return Err(command.usage_error()),
+            }
+        }
+
+        Ok(options)
+    }
+
+    fn matches(&self, entry: &RoomListEntry) -> bool {
+        let name_matches = entry.room.name.clone().unwrap_or_default().to_lowercase().contains(&self.filter);
+        name_matches && (!self.joined || entry.joined)
+    }
+}
+
+fn room_sort_key(room: &Channel) -> String {
+    room.name.clone().unwrap_or_else(|| room.id.clone()).to_lowercase()
+}
+
+fn format_room_list_entry(entry: &RoomListEntry, bridged: bool) -> String {
+    let formatter = if bridged {
+        "**"
+    } else if entry.joined {
+        "*"
+    } else {
+        ""
+    };
+
+    let name = entry.room.name.clone().unwrap_or_else(|| entry.room.id.clone());
+    let mut line = format!("*   {}{}{}", formatter, name, formatter);
+    if let Some(users_count) = entry.room.users_count {
+        let members = t!(["admin_room", "list_members"]).with_vars(vec![("count", users_count.to_string())]);
+        line = line + &format!(" ({})", members.l(DEFAULT_LANGUAGE));
+    }
+    if let Some(ref topic) = entry.room.topic {
+        if !topic.is_empty() {
+            line = line + " - " + topic;
+        }
+    }
+
+    line + "\n\n"
+}
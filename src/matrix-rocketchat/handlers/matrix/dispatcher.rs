@@ -6,7 +6,7 @@ use slog::Logger;
 use api::MatrixApi;
 use config::Config;
 use errors::*;
-use handlers::matrix::{MembershipHandler, MessageHandler};
+use handlers::matrix::{MembershipHandler, MessageHandler, PowerLevelsHandler};
 use handlers::ErrorNotifier;
 use log;
 use models::Room;
@@ -63,6 +63,21 @@ impl<'a> Dispatcher<'a> {
                         return self.handle_error(&err, room_id);
                     }
                 }
+                Event::RoomPowerLevels(power_levels_event) => {
+                    let room_id = match &power_levels_event.room_id {
+                        Some(room_id) => room_id,
+                        None => {
+                            debug!(self.logger, "Skipping event, no room is specified");
+                            return Ok(());
+                        }
+                    };
+                    let room = Room::new(self.config, self.logger, self.matrix_api.as_ref(), room_id.clone());
+                    let handler =
+                        PowerLevelsHandler::new(self.config, self.connection, self.logger, self.matrix_api.as_ref(), &room);
+                    if let Err(err) = handler.process(&power_levels_event) {
+                        return self.handle_error(&err, room_id);
+                    }
+                }
                 _ => debug!(self.logger, "Skipping event, because the event type is not known"),
             }
         }
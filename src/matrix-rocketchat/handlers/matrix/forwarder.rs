@@ -1,16 +1,40 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
 use diesel::sqlite::SqliteConnection;
 use ruma_events::room::message::{MessageEvent, MessageEventContent};
+use ruma_identifiers::{RoomId, UserId};
 use slog::Logger;
 use url::Url;
 
 use api::{MatrixApi, RocketchatApi};
+use config::Config;
 use errors::*;
 use http::header::HeaderValue;
 use i18n::*;
-use models::{RocketchatServer, UserOnRocketchatServer};
+use log;
+use models::{PuppetEvent, RecordedError, RelayRoom, RocketchatServer, SentRocketchatMessage, UserOnRocketchatServer};
+
+lazy_static! {
+    /// Display names and avatar URLs of the senders of relayed messages by room and user, together
+    /// with the time when they were looked up
+    static ref SENDER_PROFILES: Mutex<HashMap<(String, String), (Instant, SenderProfile)>> = { Mutex::new(HashMap::new()) };
+}
+
+/// Display name and avatar URL of the sender of a relayed message
+type SenderProfile = (String, Option<String>);
+
+/// Time after which a cached sender profile is looked up again, in case a membership event that
+/// changed it was missed (for example while the application service was down)
+const SENDER_PROFILE_TTL_IN_SECONDS: u64 = 300;
+
+/// Template that is used to relay messages if no template is configured
+pub const DEFAULT_RELAY_MESSAGE_TEMPLATE: &str = "**{displayname}**: {message}";
 
 /// Forwards messages
 pub struct Forwarder<'a> {
+    config: &'a Config,
     connection: &'a SqliteConnection,
     logger: &'a Logger,
     matrix_api: &'a MatrixApi,
@@ -18,52 +42,274 @@ pub struct Forwarder<'a> {
 
 impl<'a> Forwarder<'a> {
     /// Create a new `Forwarder`.
-    pub fn new(connection: &'a SqliteConnection, logger: &'a Logger, matrix_api: &'a MatrixApi) -> Forwarder<'a> {
-        Forwarder { connection, logger, matrix_api }
+    pub fn new(
+        config: &'a Config,
+        connection: &'a SqliteConnection,
+        logger: &'a Logger,
+        matrix_api: &'a MatrixApi,
+    ) -> Forwarder<'a> {
+        Forwarder { config, connection, logger, matrix_api }
     }
 
     /// Forwards messages to Rocket.Chat
     pub fn process(&self, event: &MessageEvent, server: RocketchatServer, channel_id: &str) -> Result<()> {
+        if self.config.is_application_service_user(&event.sender) {
+            debug!(self.logger, "Skipping event, because it was sent by a virtual user");
+            return Ok(());
+        }
+
+        if PuppetEvent::remove(self.connection, &event.event_id)? {
+            debug!(self.logger, "Skipping event, because it was sent by the application service on behalf of the user");
+            return Ok(());
+        }
+
         let mut user_on_rocketchat_server =
-            match UserOnRocketchatServer::find_by_matrix_user_id(self.connection, &event.sender, server.id)? {
-                Some(user_on_rocketchat_server) => user_on_rocketchat_server,
-                None => {
-                    debug!(self.logger, "Skipping event, because it was sent by a virtual user");
-                    return Ok(());
+            match UserOnRocketchatServer::find_by_matrix_user_id(self.connection, &event.sender, server.id.clone())? {
+                Some(ref user_on_rocketchat_server) if !user_on_rocketchat_server.is_logged_in() => {
+                    return self.relay(event, &server, channel_id);
                 }
+                Some(user_on_rocketchat_server) => user_on_rocketchat_server,
+                None => return self.relay(event, &server, channel_id),
             };
 
-        let rocketchat_api = RocketchatApi::new(server.rocketchat_url, self.logger.clone())?.with_credentials(
+        match self.forward(event, &server, channel_id, &user_on_rocketchat_server) {
+            Err(ref err) if err.is_rocketchat_authentication_failure() => {
+                // the session on the Rocket.Chat server expired or was revoked
+                server.expire_session(self.config, self.connection, self.logger, self.matrix_api, &event.sender)
+            }
+            Err(err) => self.record_error(event, server, err),
+            Ok(()) => user_on_rocketchat_server.set_last_message_sent(self.connection),
+        }
+    }
+
+    fn forward(
+        &self,
+        event: &MessageEvent,
+        server: &RocketchatServer,
+        channel_id: &str,
+        user_on_rocketchat_server: &UserOnRocketchatServer,
+    ) -> Result<()> {
+        let rocketchat_api = RocketchatApi::new(server.rocketchat_url.clone(), self.logger.clone())?.with_credentials(
             user_on_rocketchat_server.rocketchat_user_id.clone().unwrap_or_default(),
             user_on_rocketchat_server.rocketchat_auth_token.clone().unwrap_or_default(),
         );
+        let _sending = SentRocketchatMessage::sending();
 
-        match event.content {
+        let message_id = match event.content {
+            MessageEventContent::Text(ref content) => rocketchat_api.chat_post_message(&content.body, channel_id)?,
+            MessageEventContent::Image(ref content) => {
+                let mt = content.clone().info.chain_err(|| ErrorKind::MissingMimeType)?.mimetype;
+                self.forward_file_to_rocketchat(
+                    rocketchat_api.as_ref(),
+                    &content.url,
+                    Some(mt),
+                    &content.body,
+                    None,
+                    channel_id,
+                )?
+            }
+            MessageEventContent::File(ref content) => {
+                let mt = content.clone().info.chain_err(|| ErrorKind::MissingMimeType)?.mimetype;
+                self.forward_file_to_rocketchat(
+                    rocketchat_api.as_ref(),
+                    &content.url,
+                    Some(mt),
+                    &content.body,
+                    None,
+                    channel_id,
+                )?
+            }
+            MessageEventContent::Audio(ref content) => {
+                let mt = content.clone().info.chain_err(|| ErrorKind::MissingMimeType)?.mimetype;
+                self.forward_file_to_rocketchat(rocketchat_api.as_ref(), &content.url, mt, &content.body, None, channel_id)?
+            }
+            MessageEventContent::Video(ref content) => {
+                let mt = content.clone().info.chain_err(|| ErrorKind::MissingMimeType)?.mimetype;
+                self.forward_file_to_rocketchat(rocketchat_api.as_ref(), &content.url, mt, &content.body, None, channel_id)?
+            }
+            MessageEventContent::Emote(_) | MessageEventContent::Location(_) | MessageEventContent::Notice(_) => {
+                info!(self.logger, "Not forwarding message, forwarding emote, location or notice messages is not implemented.");
+                return Ok(());
+            }
+        };
+
+        self.add_to_sent_messages(server, message_id)
+    }
+
+    /// Relays a message from a user that is not logged in on the Rocket.Chat server via the
+    /// Rocket.Chat account of the relay user, if relaying is enabled for the room.
+    fn relay(&self, event: &MessageEvent, server: &RocketchatServer, channel_id: &str) -> Result<()> {
+        let room_id = match event.room_id {
+            Some(ref room_id) => room_id.clone(),
+            None => {
+                debug!(self.logger, "Skipping event, no room is specified");
+                return Ok(());
+            }
+        };
+
+        let relay_room = match RelayRoom::find_by_matrix_room_id(self.connection, &room_id)? {
+            Some(relay_room) => relay_room,
+            None => {
+                debug!(
+                    self.logger,
+                    "Skipping event, because the sender {} is not logged in and relaying is not enabled for room {}",
+                    event.sender,
+                    room_id
+                );
+                return Ok(());
+            }
+        };
+
+        let mut relay_user = match UserOnRocketchatServer::find_by_matrix_user_id(
+            self.connection,
+            &relay_room.relay_matrix_user_id,
+            server.id.clone(),
+        )? {
+            Some(ref relay_user) if !relay_user.is_logged_in() => {
+                info!(self.logger, "Not relaying message, relay user {} is not logged in", relay_room.relay_matrix_user_id);
+                return Ok(());
+            }
+            Some(relay_user) => relay_user,
+            None => {
+                info!(self.logger, "Not relaying message, relay user {} not found", relay_room.relay_matrix_user_id);
+                return Ok(());
+            }
+        };
+
+        match self.relay_as(event, server, channel_id, room_id, &relay_user) {
+            Err(ref err) if err.is_rocketchat_authentication_failure() => {
+                // the session of the relay user on the Rocket.Chat server expired or was revoked
+                let relay_user_id = &relay_room.relay_matrix_user_id;
+                server.expire_session(self.config, self.connection, self.logger, self.matrix_api, relay_user_id)
+            }
+            Err(err) => self.record_error(event, server, err),
+            Ok(()) => relay_user.set_last_message_sent(self.connection),
+        }
+    }
+
+    fn relay_as(
+        &self,
+        event: &MessageEvent,
+        server: &RocketchatServer,
+        channel_id: &str,
+        room_id: RoomId,
+        relay_user: &UserOnRocketchatServer,
+    ) -> Result<()> {
+        let rocketchat_api = RocketchatApi::new(server.rocketchat_url.clone(), self.logger.clone())?.with_credentials(
+            relay_user.rocketchat_user_id.clone().unwrap_or_default(),
+            relay_user.rocketchat_auth_token.clone().unwrap_or_default(),
+        );
+
+        let (display_name, avatar_url) = self.sender_profile(room_id, &event.sender)?;
+        let avatar_url = avatar_url.as_ref().map(String::as_str);
+        let _sending = SentRocketchatMessage::sending();
+
+        // files are uploaded with the relay message as text, so that the file is posted only once.
+        // Uploads don't support the alias and avatar overrides, the template still names the sender.
+        let message_id = match event.content {
             MessageEventContent::Text(ref content) => {
-                rocketchat_api.chat_post_message(&content.body, channel_id)?;
+                let text = self.build_relay_message(&display_name, &content.body);
+                rocketchat_api.chat_post_relay_message(&text, channel_id, &display_name, avatar_url)?
             }
             MessageEventContent::Image(ref content) => {
+                let text = self.build_relay_message(&display_name, &content.body);
                 let mt = content.clone().info.chain_err(|| ErrorKind::MissingMimeType)?.mimetype;
-                self.forward_file_to_rocketchat(rocketchat_api.as_ref(), &content.url, Some(mt), &content.body, channel_id)?;
+                let api = rocketchat_api.as_ref();
+                self.forward_file_to_rocketchat(api, &content.url, Some(mt), &content.body, Some(&text), channel_id)?
             }
             MessageEventContent::File(ref content) => {
+                let text = self.build_relay_message(&display_name, &content.body);
                 let mt = content.clone().info.chain_err(|| ErrorKind::MissingMimeType)?.mimetype;
-                self.forward_file_to_rocketchat(rocketchat_api.as_ref(), &content.url, Some(mt), &content.body, channel_id)?;
+                let api = rocketchat_api.as_ref();
+                self.forward_file_to_rocketchat(api, &content.url, Some(mt), &content.body, Some(&text), channel_id)?
             }
             MessageEventContent::Audio(ref content) => {
+                let text = self.build_relay_message(&display_name, &content.body);
                 let mt = content.clone().info.chain_err(|| ErrorKind::MissingMimeType)?.mimetype;
-                self.forward_file_to_rocketchat(rocketchat_api.as_ref(), &content.url, mt, &content.body, channel_id)?;
+                let api = rocketchat_api.as_ref();
+                self.forward_file_to_rocketchat(api, &content.url, mt, &content.body, Some(&text), channel_id)?
             }
             MessageEventContent::Video(ref content) => {
+                let text = self.build_relay_message(&display_name, &content.body);
                 let mt = content.clone().info.chain_err(|| ErrorKind::MissingMimeType)?.mimetype;
-                self.forward_file_to_rocketchat(rocketchat_api.as_ref(), &content.url, mt, &content.body, channel_id)?;
+                let api = rocketchat_api.as_ref();
+                self.forward_file_to_rocketchat(api, &content.url, mt, &content.body, Some(&text), channel_id)?
             }
             MessageEventContent::Emote(_) | MessageEventContent::Location(_) | MessageEventContent::Notice(_) => {
-                info!(self.logger, "Not forwarding message, forwarding emote, location or notice messages is not implemented.")
+                info!(self.logger, "Not relaying message, relaying emote, location or notice messages is not implemented.");
+                return Ok(());
+            }
+        };
+
+        self.add_to_sent_messages(server, message_id)
+    }
+
+    /// Remember the error for the sender, so that it shows up in the status of the user.
+    fn record_error(&self, event: &MessageEvent, server: &RocketchatServer, err: Error) -> Result<()> {
+        if let Err(record_err) = RecordedError::record(self.connection, &event.sender, &server.id, &err) {
+            log::log_error(self.logger, &record_err);
+        }
+
+        Err(err)
+    }
+
+    /// Forget the cached profile of a room member, so that it's looked up again with the next
+    /// relayed message. Called when the membership of the user changes, which includes changes of
+    /// the display name and the avatar.
+    pub fn forget_sender_profile(room_id: &RoomId, user_id: &UserId) {
+        if let Ok(mut sender_profiles) = SENDER_PROFILES.lock() {
+            sender_profiles.remove(&(room_id.to_string(), user_id.to_string()));
+        }
+    }
+
+    /// Get the display name and the avatar URL of the sender. The display name falls back to the
+    /// user ID if the user didn't set one. The profile is cached until the membership of the user
+    /// changes or `SENDER_PROFILE_TTL_IN_SECONDS` passed.
+    fn sender_profile(&self, room_id: RoomId, sender_id: &UserId) -> Result<SenderProfile> {
+        let key = (room_id.to_string(), sender_id.to_string());
+        let ttl = Duration::from_secs(SENDER_PROFILE_TTL_IN_SECONDS);
+        let cached_profile = SENDER_PROFILES.lock().ok().and_then(|profiles| profiles.get(&key).cloned());
+        if let Some((looked_up_at, sender_profile)) = cached_profile {
+            if looked_up_at.elapsed() < ttl {
+                return Ok(sender_profile);
             }
         }
 
-        user_on_rocketchat_server.set_last_message_sent(self.connection)
+        let sender_profile = self.lookup_sender_profile(room_id, sender_id)?;
+        if let Ok(mut sender_profiles) = SENDER_PROFILES.lock() {
+            sender_profiles.retain(|_, &mut (looked_up_at, _)| looked_up_at.elapsed() < ttl);
+            sender_profiles.insert(key, (Instant::now(), sender_profile.clone()));
+        }
+
+        Ok(sender_profile)
+    }
+
+    fn lookup_sender_profile(&self, room_id: RoomId, sender_id: &UserId) -> Result<SenderProfile> {
+        let member_events = self.matrix_api.get_room_members(room_id, None)?;
+        let member_event = member_events.into_iter().find(|member_event| member_event.state_key == sender_id.to_string());
+
+        let (display_name, avatar_url) = match member_event {
+            Some(member_event) => (member_event.content.displayname, member_event.content.avatar_url),
+            None => (None, None),
+        };
+
+        let display_name = display_name.unwrap_or_else(|| sender_id.to_string());
+        let avatar_url = match avatar_url {
+            Some(mxc_url) => {
+                let url = Url::parse(&mxc_url).chain_err(|| ErrorKind::InternalServerError)?;
+                let host = url.host_str().unwrap_or_default();
+                let media_id = url.path().trim_start_matches('/');
+                Some(format!("{}/_matrix/media/r0/download/{}/{}", self.config.hs_public_url(), host, media_id))
+            }
+            None => None,
+        };
+
+        Ok((display_name, avatar_url))
+    }
+
+    fn build_relay_message(&self, display_name: &str, message: &str) -> String {
+        let template = self.config.relay_message_template.clone().unwrap_or_else(|| DEFAULT_RELAY_MESSAGE_TEMPLATE.to_string());
+        template.replace("{displayname}", display_name).replace("{message}", message)
     }
 
     fn forward_file_to_rocketchat(
@@ -72,8 +318,9 @@ impl<'a> Forwarder<'a> {
         url: &str,
         mimetype: Option<String>,
         body: &str,
+        message: Option<&str>,
         channel_id: &str,
-    ) -> Result<()> {
+    ) -> Result<Option<String>> {
         let url = Url::parse(url).chain_err(|| ErrorKind::InternalServerError)?;
         let host = url.host_str().unwrap_or_default();
         let file_id = url.path().trim_start_matches('/');
@@ -81,14 +328,33 @@ impl<'a> Forwarder<'a> {
 
         let mime: HeaderValue = HeaderValue::from_str(&mimetype.unwrap_or_default())?;
 
-        if let Err(err) = rocketchat_api.rooms_upload(file, body, mime, channel_id) {
-            bail_error!(
-                ErrorKind::RocketchatUploadFailed(url.to_string(), err.to_string()),
-                t!(["errors", "rocketchat_server_upload_failed"])
-                    .with_vars(vec![("url", url.to_string()), ("err", err.to_string())])
-            );
+        match rocketchat_api.rooms_upload(file, body, mime, message, channel_id) {
+            Ok(message_id) => Ok(message_id),
+            Err(err) => {
+                // the session of the user is expired when the authentication failed, which requires
+                // the original error
+                if err.is_rocketchat_authentication_failure() {
+                    return Err(err);
+                }
+
+                bail_error!(
+                    ErrorKind::RocketchatUploadFailed(url.to_string(), err.to_string()),
+                    t!(["errors", "rocketchat_server_upload_failed"])
+                        .with_vars(vec![("url", url.to_string()), ("err", err.to_string())])
+                );
+            }
         }
+    }
 
-        Ok(())
+    // The Rocket.Chat webhook sends every message back to the application service, the ID is stored
+    // to be able to ignore the echo.
+    fn add_to_sent_messages(&self, server: &RocketchatServer, message_id: Option<String>) -> Result<()> {
+        match message_id {
+            Some(message_id) => SentRocketchatMessage::insert(self.connection, &server.id, &message_id),
+            None => {
+                debug!(self.logger, "Rocket.Chat server did not return a message ID, echo of the message cannot be detected");
+                Ok(())
+            }
+        }
     }
 }
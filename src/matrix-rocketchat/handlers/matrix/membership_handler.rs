@@ -3,17 +3,31 @@ use std::convert::TryFrom;
 use diesel::sqlite::SqliteConnection;
 use iron::url::Host;
 use ruma_events::room::member::{MemberEvent, MembershipState};
-use ruma_identifiers::UserId;
+use ruma_identifiers::{RoomId, UserId};
 use slog::Logger;
 
-use api::MatrixApi;
+use api::rocketchat::User;
+use api::{MatrixApi, RocketchatApi};
 use config::Config;
 use errors::*;
-use handlers::matrix::CommandHandler;
+use handlers::matrix::{CommandHandler, Forwarder};
 use handlers::ErrorNotifier;
 use i18n::*;
 use log;
-use models::Room;
+use models::{
+    BridgedRoom, NewPlumbedRoom, PlumbedRoom, RocketchatRoom, RocketchatServer, Room, RoomType, UserOnRocketchatServer,
+    VirtualUser,
+};
+
+/// Who can be in a room that is bridged to a Rocket.Chat channel, group or direct message
+enum RoomAccess {
+    /// Everybody can join a public channel
+    Public,
+    /// Only the members of a group or a direct message can join
+    Members(Vec<User>),
+    /// The room is not visible with the credentials that were used
+    Unknown,
+}
 
 /// Handles membership events for a specific room
 pub struct MembershipHandler<'a> {
@@ -42,6 +56,11 @@ impl<'a> MembershipHandler<'a> {
         let state_key =
             UserId::try_from(event.state_key.as_ref()).chain_err(|| ErrorKind::InvalidUserId(event.state_key.clone()))?;
         let addressed_to_matrix_bot = state_key == matrix_bot_user_id;
+        let addressed_to_virtual_user = self.config.is_application_service_virtual_user(&state_key)
+            && !self.config.is_application_service_user(&event.sender);
+
+        // the event can contain a new display name or avatar of the user
+        Forwarder::forget_sender_profile(&self.room.id, &state_key);
 
         match event.content.membership {
             MembershipState::Invite if addressed_to_matrix_bot => {
@@ -49,6 +68,14 @@ impl<'a> MembershipHandler<'a> {
 
                 self.handle_bot_invite(matrix_bot_user_id)?;
             }
+            MembershipState::Invite if addressed_to_virtual_user => {
+                debug!(
+                    self.logger,
+                    "Virtual user `{}` got invite for room `{}` from {}", state_key, self.room.id, event.sender
+                );
+
+                self.handle_virtual_user_invite(&event.sender, state_key)?;
+            }
             MembershipState::Join if addressed_to_matrix_bot => {
                 debug!(self.logger, "Received join event for bot user {} and room {}", matrix_bot_user_id, self.room.id);
 
@@ -57,7 +84,15 @@ impl<'a> MembershipHandler<'a> {
             MembershipState::Join => {
                 debug!(self.logger, "Received join event for user {} and room {}", &state_key, self.room.id);
 
-                self.handle_user_join()?;
+                self.handle_user_join(state_key)?;
+            }
+            MembershipState::Leave | MembershipState::Ban if addressed_to_virtual_user => {
+                debug!(
+                    self.logger,
+                    "Virtual user `{}` was removed from room `{}` by {}", state_key, self.room.id, event.sender
+                );
+
+                self.handle_virtual_user_kick(&event.sender, &state_key)?;
             }
             MembershipState::Leave if !addressed_to_matrix_bot => {
                 debug!(self.logger, "User {} left room {}", event.sender, self.room.id);
@@ -92,8 +127,214 @@ impl<'a> MembershipHandler<'a> {
         Ok(())
     }
 
+    // Inviting a virtual user into a fresh room starts a direct message with the Rocket.Chat user
+    // and the room is bridged to it. Invites into any other room are declined. The virtual user only
+    // joins the room if the inviter is logged in, and leaves it again if the direct message could
+    // not be started.
+    fn handle_virtual_user_invite(&self, inviter_id: &UserId, virtual_user_id: UserId) -> Result<()> {
+        let (server, user_on_rocketchat_server) = match self.logged_in_inviter(inviter_id, &virtual_user_id) {
+            Ok(Some(logged_in_inviter)) => logged_in_inviter,
+            result => {
+                self.leave_room(virtual_user_id);
+                return result.map(|_| ());
+            }
+        };
+
+        // the virtual user has to join the room to see who else is in it
+        self.matrix_api.join(self.room.id.clone(), virtual_user_id.clone())?;
+
+        let result = self.start_direct_message(&server, &user_on_rocketchat_server, &virtual_user_id);
+        if let Ok(true) = result {
+            return Ok(());
+        }
+
+        self.leave_room(virtual_user_id);
+        result.map(|_| ())
+    }
+
+    // Declines the invite of the virtual user or leaves the room if the user joined already.
+    fn leave_room(&self, virtual_user_id: UserId) {
+        if let Err(err) = self.matrix_api.leave_room(self.room.id.clone(), virtual_user_id) {
+            log::log_error(self.logger, &err);
+        }
+    }
+
+    // Returns the server of the virtual user and the inviter if the inviter is logged in on it.
+    fn logged_in_inviter(
+        &self,
+        inviter_id: &UserId,
+        virtual_user_id: &UserId,
+    ) -> Result<Option<(RocketchatServer, UserOnRocketchatServer)>> {
+        let (server_id, _) = VirtualUser::rocketchat_server_and_user_id_from_matrix_id(virtual_user_id);
+        let server = match RocketchatServer::find_by_id(self.conn, &server_id)? {
+            Some(server) => server,
+            None => {
+                info!(self.logger, "No Rocket.Chat server with ID {} found for virtual user {}", server_id, virtual_user_id);
+                return Ok(None);
+            }
+        };
+
+        let user_on_rocketchat_server =
+            match UserOnRocketchatServer::find_by_matrix_user_id(self.conn, inviter_id, server.id.clone())? {
+                Some(user_on_rocketchat_server) => user_on_rocketchat_server,
+                None => {
+                    info!(self.logger, "User {} is not registered on {}, not starting a direct message", inviter_id, server.id);
+                    return Ok(None);
+                }
+            };
+
+        if !user_on_rocketchat_server.is_logged_in() {
+            info!(self.logger, "User {} is not logged in on {}, not starting a direct message", inviter_id, server.id);
+            return Ok(None);
+        }
+
+        Ok(Some((server, user_on_rocketchat_server)))
+    }
+
+    // Returns `true` if the room was bridged to a direct message channel.
+    fn start_direct_message(
+        &self,
+        server: &RocketchatServer,
+        user_on_rocketchat_server: &UserOnRocketchatServer,
+        virtual_user_id: &UserId,
+    ) -> Result<bool> {
+        let inviter_id = &user_on_rocketchat_server.matrix_user_id;
+        if !self.is_fresh_room(inviter_id, virtual_user_id)? {
+            info!(
+                self.logger,
+                "Room {} is not a new room, not starting a direct message with {}", self.room.id, virtual_user_id
+            );
+            return Ok(false);
+        }
+
+        let rocketchat_api = RocketchatApi::new(server.rocketchat_url.clone(), self.logger.clone())?.with_credentials(
+            user_on_rocketchat_server.rocketchat_user_id.clone().unwrap_or_default(),
+            user_on_rocketchat_server.rocketchat_auth_token.clone().unwrap_or_default(),
+        );
+
+        match self.bridge_direct_message(rocketchat_api.as_ref(), server, inviter_id, virtual_user_id) {
+            Err(ref err) if err.is_rocketchat_authentication_failure() => {
+                server.expire_session(self.config, self.conn, self.logger, self.matrix_api, inviter_id)?;
+                Ok(false)
+            }
+            result => result,
+        }
+    }
+
+    // A room is fresh if nobody except the inviter and the virtual user joined or was invited and
+    // it isn't bridged yet. Rooms that are created by the application service always contain the
+    // bot or another virtual user.
+    fn is_fresh_room(&self, inviter_id: &UserId, virtual_user_id: &UserId) -> Result<bool> {
+        if PlumbedRoom::find_by_matrix_room_id(self.conn, &self.room.id)?.is_some()
+            || BridgedRoom::find_by_matrix_room_id(self.conn, &self.room.id)?.is_some()
+        {
+            return Ok(false);
+        }
+
+        let member_events = self.matrix_api.get_room_members(self.room.id.clone(), Some(virtual_user_id.clone()))?;
+        let is_fresh_room = member_events
+            .iter()
+            .filter(|member_event| match member_event.content.membership {
+                MembershipState::Join | MembershipState::Invite => true,
+                _ => false,
+            })
+            .all(|member_event| {
+                member_event.state_key == inviter_id.to_string() || member_event.state_key == virtual_user_id.to_string()
+            });
+        Ok(is_fresh_room)
+    }
+
+    fn bridge_direct_message(
+        &self,
+        rocketchat_api: &RocketchatApi,
+        server: &RocketchatServer,
+        inviter_id: &UserId,
+        virtual_user_id: &UserId,
+    ) -> Result<bool> {
+        let virtual_user = VirtualUser::new(self.config, self.conn, self.logger, self.matrix_api);
+        let rocketchat_user_id = virtual_user.rocketchat_user_id(virtual_user_id)?;
+        let user = rocketchat_api.users_info_by_id(&rocketchat_user_id)?;
+        let direct_message = rocketchat_api.im_create(&user.username)?;
+
+        let channel = RocketchatRoom::new(self.config, self.logger, self.matrix_api, direct_message.id, &server.id);
+        let bridged_rooms = BridgedRoom::find_by_rocketchat_channel_id(self.conn, &server.id, &channel.id)?;
+        if let Some(bridged_room) = bridged_rooms.first() {
+            info!(
+                self.logger,
+                "Direct message with {} is already bridged to room {}", user.username, bridged_room.matrix_room_id
+            );
+            return Ok(false);
+        }
+
+        // the room is not created by the application service and has no alias, so the direct message
+        // channel is recorded for the room
+        channel.record_room_type(self.conn, &self.room.id, RoomType::DirectMessage)?;
+
+        info!(self.logger, "Direct message room {} with {} started by {}", self.room.id, user.username, inviter_id);
+        Ok(true)
+    }
+
+    // Kicks and bans of virtual users are mirrored on the Rocket.Chat server, the Rocket.Chat server
+    // checks if the Matrix user has the permission to remove the user from the channel.
+    fn handle_virtual_user_kick(&self, kicker_id: &UserId, virtual_user_id: &UserId) -> Result<()> {
+        let (server, channel_id) = match self.room.rocketchat_channel(self.conn)? {
+            Some(rocketchat_channel) => rocketchat_channel,
+            None => {
+                debug!(self.logger, "Room {} is not bridged, not removing the user on Rocket.Chat", self.room.id);
+                return Ok(());
+            }
+        };
+
+        let (server_id, rocketchat_user_id) = VirtualUser::rocketchat_server_and_user_id_from_matrix_id(virtual_user_id);
+        if server_id != server.id {
+            debug!(self.logger, "Virtual user {} doesn't belong to the Rocket.Chat server {}", virtual_user_id, server.id);
+            return Ok(());
+        }
+
+        let user_on_rocketchat_server =
+            UserOnRocketchatServer::find_by_matrix_user_id(self.conn, kicker_id, server.id.clone())?;
+        let user_on_rocketchat_server = match user_on_rocketchat_server.filter(|user| user.is_logged_in()) {
+            Some(user_on_rocketchat_server) => user_on_rocketchat_server,
+            None => {
+                info!(
+                    self.logger,
+                    "User {} is not logged in on {}, not removing the user on Rocket.Chat", kicker_id, server.id
+                );
+                return Ok(());
+            }
+        };
+
+        let rocketchat_api = RocketchatApi::new(server.rocketchat_url.clone(), self.logger.clone())?.with_credentials(
+            user_on_rocketchat_server.rocketchat_user_id.clone().unwrap_or_default(),
+            user_on_rocketchat_server.rocketchat_auth_token.clone().unwrap_or_default(),
+        );
+
+        let channel = RocketchatRoom::new(self.config, self.logger, self.matrix_api, channel_id, &server.id);
+        let result = channel.room_type(self.conn, rocketchat_api.as_ref()).and_then(|room_type| match room_type {
+            Some(room_type) => channel.kick(rocketchat_api.as_ref(), room_type, &rocketchat_user_id),
+            None => Err(simple_error!(ErrorKind::RocketchatChannelOrGroupNotFound(channel.id.clone()))),
+        });
+        match result {
+            Ok(()) => {
+                info!(self.logger, "User {} removed {} from Rocket.Chat channel {}", kicker_id, rocketchat_user_id, channel.id);
+                Ok(())
+            }
+            Err(ref err) if err.is_rocketchat_authentication_failure() => {
+                server.expire_session(self.config, self.conn, self.logger, self.matrix_api, kicker_id)
+            }
+            Err(err) => {
+                info!(self.logger, "Could not remove {} from Rocket.Chat channel {}: {}", rocketchat_user_id, channel.id, err);
+                Ok(())
+            }
+        }
+    }
+
     fn handle_bot_join(&self, matrix_bot_user_id: UserId) -> Result<()> {
-        let is_admin_room = match self.room.is_admin_room() {
+        if let Some((new_plumbed_room, admin_room_id)) = PlumbedRoom::take_pending(&self.room.id) {
+            return self.setup_plumbed_room(&new_plumbed_room, admin_room_id, matrix_bot_user_id);
+        }
+
+        let is_admin_room = match self.room.is_admin_room(self.conn) {
             Ok(is_admin_room) => is_admin_room,
             Err(err) => {
                 warn!(
@@ -145,19 +386,132 @@ impl<'a> MembershipHandler<'a> {
         Ok(())
     }
 
-    fn handle_user_join(&self) -> Result<()> {
-        if self.room.is_admin_room()? && !self.is_private_room()? {
-            info!(self.logger, "Another user join the admin room {}, bot user is leaving", self.room.id);
-            let bot_user_id = self.config.matrix_bot_user_id()?;
-            let body = t!(["errors", "other_user_joined"]).l(DEFAULT_LANGUAGE);
-            self.matrix_api.send_text_message(self.room.id.clone(), bot_user_id.clone(), body)?;
-            self.room.forget(bot_user_id)?;
+    fn setup_plumbed_room(
+        &self,
+        new_plumbed_room: &NewPlumbedRoom,
+        admin_room_id: RoomId,
+        matrix_bot_user_id: UserId,
+    ) -> Result<()> {
+        debug!(self.logger, "Plumbing room {} with {}", self.room.id, new_plumbed_room.rocketchat_channel_id);
+
+        // the admin permissions are checked again, they could have changed while the bridge waited for the invite
+        if !self.room.is_room_admin(&new_plumbed_room.created_by)? {
+            info!(self.logger, "User {} is not an admin of room {}, bot is leaving", new_plumbed_room.created_by, self.room.id);
+            let err = user_error!(
+                ErrorKind::NotRoomAdmin(new_plumbed_room.created_by.to_string(), self.room.id.to_string()),
+                t!(["errors", "not_room_admin"]).with_vars(vec![("matrix_room", self.room.id.to_string())])
+            );
+            let error_notifier = ErrorNotifier { config: self.config, logger: self.logger, matrix_api: self.matrix_api };
+            error_notifier.send_message_to_user(&err, admin_room_id)?;
+            return self.room.forget(matrix_bot_user_id);
         }
+
+        PlumbedRoom::insert(self.conn, new_plumbed_room)?;
+
+        let message =
+            t!(["admin_room", "room_successfully_plumbed"]).with_vars(vec![("matrix_room", self.room.id.to_string())]);
+        self.matrix_api.send_text_message(admin_room_id, matrix_bot_user_id, message.l(DEFAULT_LANGUAGE))?;
+
+        info!(self.logger, "Successfully plumbed room {} with {}", self.room.id, new_plumbed_room.rocketchat_channel_id);
         Ok(())
     }
 
+    fn handle_user_join(&self, user_id: UserId) -> Result<()> {
+        if self.room.is_admin_room(self.conn)? {
+            if !self.is_private_room()? {
+                info!(self.logger, "Another user join the admin room {}, bot user is leaving", self.room.id);
+                let bot_user_id = self.config.matrix_bot_user_id()?;
+                let body = t!(["errors", "other_user_joined"]).l(DEFAULT_LANGUAGE);
+                self.matrix_api.send_text_message(self.room.id.clone(), bot_user_id.clone(), body)?;
+                self.room.forget(bot_user_id)?;
+            }
+            return Ok(());
+        }
+
+        if self.config.is_application_service_user(&user_id) {
+            return Ok(());
+        }
+
+        self.check_group_membership(user_id)
+    }
+
+    // The content of a private group must only be visible to its members, so Matrix users that join
+    // a room that is bridged to a group have to be members of the group on Rocket.Chat. Users are
+    // only kicked if the room is known to be private, the type is recorded when the room is bridged.
+    fn check_group_membership(&self, user_id: UserId) -> Result<()> {
+        // plumbed rooms are owned by their Matrix admins, they decide who can join the room
+        if PlumbedRoom::find_by_matrix_room_id(self.conn, &self.room.id)?.is_some() {
+            return Ok(());
+        }
+
+        let (server, channel_id) = match self.room.rocketchat_channel(self.conn)? {
+            Some(rocketchat_channel) => rocketchat_channel,
+            None => return Ok(()),
+        };
+
+        let channel = RocketchatRoom::new(self.config, self.logger, self.matrix_api, channel_id, &server.id);
+        let mut room_type = BridgedRoom::find_by_matrix_room_id(self.conn, &self.room.id)?.and_then(|room| room.room_type());
+        if room_type == Some(RoomType::Channel) {
+            return Ok(());
+        }
+
+        let users_on_rocketchat_server = self.room.logged_in_users(self.conn, server.id.clone())?;
+
+        // groups are only visible to their members, so the credentials of a user that can see the
+        // group are needed to get its members
+        for user_on_rocketchat_server in users_on_rocketchat_server.into_iter().filter(|user| user.is_logged_in()) {
+            let rocketchat_api = RocketchatApi::new(server.rocketchat_url.clone(), self.logger.clone())?.with_credentials(
+                user_on_rocketchat_server.rocketchat_user_id.clone().unwrap_or_default(),
+                user_on_rocketchat_server.rocketchat_auth_token.clone().unwrap_or_default(),
+            );
+
+            let members = match self.private_room_members(&channel, rocketchat_api.as_ref(), &mut room_type) {
+                Ok(RoomAccess::Public) => return Ok(()),
+                Ok(RoomAccess::Members(members)) => members,
+                Ok(RoomAccess::Unknown) => continue,
+                Err(ref err) if err.is_rocketchat_authentication_failure() => {
+                    let matrix_user_id = user_on_rocketchat_server.matrix_user_id.clone();
+                    server.expire_session(self.config, self.conn, self.logger, self.matrix_api, &matrix_user_id)?;
+                    continue;
+                }
+                Err(err) => {
+                    log::log_error(self.logger, &err);
+                    continue;
+                }
+            };
+
+            return self.room.kick_users_without_group_membership(self.conn, vec![user_id], &members, &server);
+        }
+
+        if room_type.is_none() {
+            info!(self.logger, "Type of room {} is unknown, user {} is allowed to join", self.room.id, user_id);
+            return Ok(());
+        }
+
+        info!(self.logger, "Membership of user {} in private room {} cannot be verified", user_id, self.room.id);
+        self.room.kick_users_without_group_membership(self.conn, vec![user_id], &[], &server)
+    }
+
+    fn private_room_members(
+        &self,
+        channel: &RocketchatRoom,
+        rocketchat_api: &RocketchatApi,
+        room_type: &mut Option<RoomType>,
+    ) -> Result<RoomAccess> {
+        if room_type.is_none() {
+            *room_type = channel.room_type(self.conn, rocketchat_api)?;
+        }
+
+        match *room_type {
+            Some(RoomType::Channel) => Ok(RoomAccess::Public),
+            // direct messages with more than two users are bridged like groups
+            Some(room_type) => Ok(RoomAccess::Members(channel.members(rocketchat_api, room_type)?)),
+            None => Ok(RoomAccess::Unknown),
+        }
+    }
+
     fn handle_user_leave(&self) -> Result<()> {
-        if self.room.is_admin_room()? {
+        if self.room.is_admin_room(self.conn)? {
             let bot_user_id = self.config.matrix_bot_user_id()?;
             return self.room.forget(bot_user_id);
         }
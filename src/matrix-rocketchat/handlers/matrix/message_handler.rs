@@ -44,10 +44,10 @@ impl<'a> MessageHandler<'a> {
 
         let matrix_api = self.matrix_api.as_ref();
         let room = Room::new(self.config, self.logger, self.matrix_api.as_ref(), room_id.clone());
-        if room.is_admin_room()? {
+        if room.is_admin_room(self.connection)? {
             CommandHandler::new(self.config, self.connection, self.logger, matrix_api, &room).process(event)?;
         } else if let Some((server, channel_id)) = self.get_rocketchat_server_with_room(&room)? {
-            Forwarder::new(self.connection, self.logger, matrix_api).process(event, server, &channel_id)?;
+            Forwarder::new(self.config, self.connection, self.logger, matrix_api).process(event, server, &channel_id)?;
         } else {
             debug!(self.logger, "Skipping event, because the room {} is not bridged", room_id);
         }
@@ -57,10 +57,8 @@ impl<'a> MessageHandler<'a> {
 
     fn get_rocketchat_server_with_room(&self, room: &Room) -> Result<Option<(RocketchatServer, String)>> {
         // if it's a normal room, this will match
-        if let Some(channel_id) = room.rocketchat_channel_id()? {
-            if let Some(server) = room.rocketchat_server(self.connection)? {
-                return Ok(Some((server, channel_id)));
-            }
+        if let Some(rocketchat_channel) = room.rocketchat_channel(self.connection)? {
+            return Ok(Some(rocketchat_channel));
         }
 
         room.rocketchat_for_direct_room(self.connection)
@@ -1,5 +1,7 @@
 //! Event handlers
 
+/// Parses commands that are sent to the admin room
+mod command;
 /// Handles commands from the admin room
 mod command_handler;
 /// Dispatches incomming events to the correct component
@@ -10,9 +12,13 @@ mod forwarder;
 mod membership_handler;
 /// Handles message events
 mod message_handler;
+/// Handles power level changes in bridged rooms
+mod power_levels_handler;
 
+pub use self::command::{Command, CommandSpec, COMMANDS};
 pub use self::command_handler::CommandHandler;
 pub use self::dispatcher::Dispatcher;
 pub use self::forwarder::Forwarder;
 pub use self::membership_handler::MembershipHandler;
 pub use self::message_handler::MessageHandler;
+pub use self::power_levels_handler::PowerLevelsHandler;
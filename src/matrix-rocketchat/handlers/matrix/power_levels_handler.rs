@@ -0,0 +1,168 @@
+use std::collections::HashSet;
+
+use diesel::sqlite::SqliteConnection;
+use ruma_events::room::power_levels::{PowerLevelsEvent, PowerLevelsEventContent};
+use ruma_identifiers::UserId;
+use slog::Logger;
+
+use api::rocketchat::Role;
+use api::{MatrixApi, RocketchatApi};
+use config::Config;
+use errors::*;
+use models::{RocketchatRoom, RocketchatServer, Room, RoomType, UserOnRocketchatServer, VirtualUser};
+
+/// Handles power level changes in bridged rooms
+pub struct PowerLevelsHandler<'a> {
+    config: &'a Config,
+    conn: &'a SqliteConnection,
+    logger: &'a Logger,
+    matrix_api: &'a MatrixApi,
+    room: &'a Room<'a>,
+}
+
+impl<'a> PowerLevelsHandler<'a> {
+    /// Create a new `PowerLevelsHandler`.
+    pub fn new(
+        config: &'a Config,
+        conn: &'a SqliteConnection,
+        logger: &'a Logger,
+        matrix_api: &'a MatrixApi,
+        room: &'a Room<'a>,
+    ) -> PowerLevelsHandler<'a> {
+        PowerLevelsHandler { config, conn, logger, matrix_api, room }
+    }
+
+    /// Forwards power level changes of bridged users to Rocket.Chat as role changes. The roles are
+    /// changed with the credentials of the user that changed the power levels, so Rocket.Chat only
+    /// applies the changes that the user is allowed to make.
+    pub fn process(&self, event: &PowerLevelsEvent) -> Result<()> {
+        if self.config.is_application_service_user(&event.sender) {
+            debug!(self.logger, "Skipping power levels event, because it was sent by the application service");
+            return Ok(());
+        }
+
+        let (server, channel_id) = match self.room.rocketchat_channel(self.conn)? {
+            Some(rocketchat_channel) => rocketchat_channel,
+            None => {
+                debug!(self.logger, "Room {} is not bridged, not changing any roles on Rocket.Chat", self.room.id);
+                return Ok(());
+            }
+        };
+
+        let user_on_rocketchat_server =
+            UserOnRocketchatServer::find_by_matrix_user_id(self.conn, &event.sender, server.id.clone())?;
+        let user_on_rocketchat_server = match user_on_rocketchat_server.filter(|user| user.is_logged_in()) {
+            Some(user_on_rocketchat_server) => user_on_rocketchat_server,
+            None => {
+                info!(
+                    self.logger,
+                    "User {} is not logged in on {}, not changing any roles on Rocket.Chat", event.sender, server.id
+                );
+                return Ok(());
+            }
+        };
+
+        let rocketchat_api = RocketchatApi::new(server.rocketchat_url.clone(), self.logger.clone())?.with_credentials(
+            user_on_rocketchat_server.rocketchat_user_id.clone().unwrap_or_default(),
+            user_on_rocketchat_server.rocketchat_auth_token.clone().unwrap_or_default(),
+        );
+        let channel = RocketchatRoom::new(self.config, self.logger, self.matrix_api, channel_id, &server.id);
+        let room_type = match channel.room_type(self.conn, rocketchat_api.as_ref()) {
+            Ok(Some(RoomType::DirectMessage)) => {
+                debug!(self.logger, "Room {} is bridged to a direct message, which has no roles", self.room.id);
+                return Ok(());
+            }
+            Ok(Some(room_type)) => room_type,
+            Ok(None) => {
+                info!(
+                    self.logger,
+                    "User {} cannot see Rocket.Chat channel {}, not changing any roles", event.sender, channel.id
+                );
+                return Ok(());
+            }
+            Err(ref err) if err.is_rocketchat_authentication_failure() => {
+                return server.expire_session(self.config, self.conn, self.logger, self.matrix_api, &event.sender);
+            }
+            Err(err) => return Err(err),
+        };
+
+        let previous_content = event.prev_content.as_ref();
+        let mut user_ids: HashSet<&UserId> = event.content.users.keys().collect();
+        if let Some(previous_content) = previous_content {
+            user_ids.extend(previous_content.users.keys());
+        }
+
+        for user_id in user_ids {
+            let previous_role = previous_content.and_then(|content| role(content, user_id));
+            let new_role = role(&event.content, user_id);
+            if previous_role == new_role {
+                continue;
+            }
+
+            let rocketchat_user_id = match self.rocketchat_user_id(&server, user_id)? {
+                Some(rocketchat_user_id) => rocketchat_user_id,
+                None => {
+                    debug!(self.logger, "User {} is not a Rocket.Chat user of {}, not changing the role", user_id, server.id);
+                    continue;
+                }
+            };
+
+            match self.change_role(&channel, rocketchat_api.as_ref(), room_type, &rocketchat_user_id, previous_role, new_role) {
+                Ok(()) => info!(
+                    self.logger,
+                    "User {} changed the role of {} in Rocket.Chat channel {} to {:?}",
+                    event.sender,
+                    rocketchat_user_id,
+                    channel.id,
+                    new_role
+                ),
+                Err(ref err) if err.is_rocketchat_authentication_failure() => {
+                    return server.expire_session(self.config, self.conn, self.logger, self.matrix_api, &event.sender);
+                }
+                Err(err) => {
+                    info!(
+                        self.logger,
+                        "Could not change the role of {} in Rocket.Chat channel {}: {}", rocketchat_user_id, channel.id, err
+                    )
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn change_role(
+        &self,
+        channel: &RocketchatRoom,
+        rocketchat_api: &RocketchatApi,
+        room_type: RoomType,
+        rocketchat_user_id: &str,
+        previous_role: Option<Role>,
+        new_role: Option<Role>,
+    ) -> Result<()> {
+        if let Some(role) = previous_role {
+            channel.remove_role(rocketchat_api, room_type, rocketchat_user_id, role)?;
+        }
+
+        if let Some(role) = new_role {
+            channel.add_role(rocketchat_api, room_type, rocketchat_user_id, role)?;
+        }
+
+        Ok(())
+    }
+
+    fn rocketchat_user_id(&self, server: &RocketchatServer, user_id: &UserId) -> Result<Option<String>> {
+        if self.config.is_application_service_virtual_user(user_id) {
+            let (server_id, rocketchat_user_id) = VirtualUser::rocketchat_server_and_user_id_from_matrix_id(user_id);
+            return Ok(if server_id == server.id { Some(rocketchat_user_id) } else { None });
+        }
+
+        let user_on_rocketchat_server = UserOnRocketchatServer::find_by_matrix_user_id(self.conn, user_id, server.id.clone())?;
+        Ok(user_on_rocketchat_server.filter(|user| user.is_logged_in()).and_then(|user| user.rocketchat_user_id))
+    }
+}
+
+fn role(content: &PowerLevelsEventContent, user_id: &UserId) -> Option<Role> {
+    let power_level = content.users.get(user_id).cloned().unwrap_or(content.users_default);
+    Role::from_power_level(power_level as i64)
+}
@@ -1,22 +1,22 @@
-use std::time::{SystemTime, UNIX_EPOCH};
-
 use diesel::sqlite::SqliteConnection;
 use ruma_events::room::message::MessageType;
 use ruma_identifiers::UserId;
 use slog::Logger;
 
-use api::rocketchat::WebhookMessage;
+use api::rocketchat::{MemberRoles, User, WebhookMessage};
 use api::{MatrixApi, RocketchatApi};
 use config::Config;
 use errors::*;
 use http::header::HeaderValue;
 use i18n::*;
 use log;
-use models::{RocketchatRoom, RocketchatServer, Room, UserOnRocketchatServer, VirtualUser};
+use models::{
+    BridgedRoom, DoublePuppet, PlumbedRoom, RocketchatRoom, RocketchatServer, Room, RoomType, SentRocketchatMessage,
+    UserOnRocketchatServer, VirtualUser,
+};
 
 const IMAGE_MESSAGE_TEXT: &str = "Uploaded an image";
 const FILE_MESSAGE_TEXT: &str = "Uploaded a file";
-const RESEND_THRESHOLD_IN_SECONDS: i64 = 3;
 
 /// Forwards messages from Rocket.Chat to Matrix
 pub struct Forwarder<'a> {
@@ -46,11 +46,19 @@ impl<'a> Forwarder<'a> {
 
     /// Send a message to the Matrix channel.
     pub fn send(&self, server: &RocketchatServer, message: &WebhookMessage) -> Result<()> {
+        // the webhooks for users that joined or left a channel don't contain a message
+        if message.message_id.is_empty() {
+            return self.sync_members(server, message);
+        }
+
+        // direct messages are delivered to the room of the receiver, so they have to be forwarded
+        // even if they were sent by the application service
         let is_direct_message = message.channel_id.contains(&message.user_id);
-        if !is_direct_message && !self.is_sendable_message(message.user_id.clone(), server.id.clone())? {
+        let is_echo = SentRocketchatMessage::remove(self.connection, &server.id, &message.message_id)?;
+        if is_echo && !is_direct_message {
             debug!(
                 self.logger,
-                "Skipping message, because the message was just posted by the user Matrix and echoed back from Rocket.Chat"
+                "Skipping message, because the message was posted by the application service and echoed back from Rocket.Chat"
             );
             return Ok(());
         }
@@ -76,31 +84,130 @@ impl<'a> Forwarder<'a> {
         }
 
         if message.text == IMAGE_MESSAGE_TEXT || message.text == FILE_MESSAGE_TEXT {
-            self.forward_file(server, message, &room, &sender_id)
-        } else {
-            self.matrix_api.send_text_message(room.id.clone(), sender_id, message.text.clone())
+            self.forward_file(server, message, &room, &sender_id)?;
+        } else if is_direct_message || !self.send_as_double_puppet(server, message, &room)? {
+            self.matrix_api.send_text_message(room.id.clone(), sender_id, message.text.clone())?;
         }
+
+        server.set_last_message_received(self.connection)
     }
 
-    fn is_sendable_message(&self, rocketchat_user_id: String, server_id: String) -> Result<bool> {
-        match UserOnRocketchatServer::find_by_rocketchat_user_id(self.connection, server_id, rocketchat_user_id)? {
-            Some(user_on_rocketchatserver) => {
-                let now =
-                    SystemTime::now().duration_since(UNIX_EPOCH).chain_err(|| ErrorKind::InternalServerError)?.as_secs() as i64;
-                let last_sent = now - user_on_rocketchatserver.last_message_sent;
-                debug!(self.logger, "Found {}, last message sent {}s ago", user_on_rocketchatserver.matrix_user_id, last_sent);
-                Ok(last_sent > RESEND_THRESHOLD_IN_SECONDS)
+    // Rocket.Chat doesn't tell if the user joined or left the channel, so the virtual users and the
+    // power levels in the room are synchronized with all members of the channel and their roles.
+    fn sync_members(&self, server: &RocketchatServer, message: &WebhookMessage) -> Result<()> {
+        let channel = RocketchatRoom::new(self.config, self.logger, self.matrix_api, message.channel_id.clone(), &server.id);
+        let room_id = match channel.bridged_matrix_id(self.connection)? {
+            Some(room_id) => room_id,
+            None => {
+                debug!(self.logger, "Ignoring membership event for channel `{}`, it's not bridged", message.channel_id);
+                return Ok(());
+            }
+        };
+
+        let room = Room::new(self.config, self.logger, self.matrix_api, room_id);
+        let bot_user_id = self.config.matrix_bot_user_id()?;
+        let matrix_user_ids: Vec<UserId> =
+            room.user_ids(Some(bot_user_id))?.into_iter().filter(|id| !self.config.is_application_service_user(id)).collect();
+        let users_on_rocketchat_server =
+            UserOnRocketchatServer::find_by_matrix_user_ids(self.connection, matrix_user_ids.clone(), server.id.clone())?;
+
+        // the user whose membership changed may have just been removed from the channel, so the next
+        // logged in user is tried if the members cannot be fetched with the credentials of a user
+        for user_on_rocketchat_server in users_on_rocketchat_server.into_iter().filter(|user| user.is_logged_in()) {
+            let rocketchat_api = RocketchatApi::new(server.rocketchat_url.clone(), self.logger.clone())?.with_credentials(
+                user_on_rocketchat_server.rocketchat_user_id.clone().unwrap_or_default(),
+                user_on_rocketchat_server.rocketchat_auth_token.clone().unwrap_or_default(),
+            );
+
+            let (room_type, members, roles) = match self.members_and_roles(&channel, rocketchat_api.as_ref()) {
+                Ok(Some(members_and_roles)) => members_and_roles,
+                Ok(None) => continue,
+                Err(ref err) if err.is_rocketchat_authentication_failure() => {
+                    let matrix_user_id = &user_on_rocketchat_server.matrix_user_id;
+                    server.expire_session(self.config, self.connection, self.logger, self.matrix_api, matrix_user_id)?;
+                    continue;
+                }
+                Err(err) => {
+                    log::log_error(self.logger, &err);
+                    continue;
+                }
+            };
+
+            room.sync_rocketchat_users(self.connection, &members, &server.id)?;
+
+            // Matrix users that were removed from a private group must not be able to read it anymore
+            let is_plumbed_room = PlumbedRoom::find_by_matrix_room_id(self.connection, &room.id)?.is_some();
+            if !is_plumbed_room && room_type.is_private() {
+                room.kick_users_without_group_membership(self.connection, matrix_user_ids, &members, server)?;
+            }
+
+            // direct messages have no roles
+            if room_type == RoomType::DirectMessage {
+                return Ok(());
             }
-            None => Ok(true),
+
+            return room.sync_rocketchat_roles(self.connection, &roles, &server.id);
+        }
+
+        info!(self.logger, "No logged in user in room {} can see the channel, cannot synchronize the members", room.id);
+        Ok(())
+    }
+
+    // Groups and direct messages are only visible to their members, returns `None` if the user of the
+    // API cannot see the channel.
+    fn members_and_roles(
+        &self,
+        channel: &RocketchatRoom,
+        rocketchat_api: &RocketchatApi,
+    ) -> Result<Option<(RoomType, Vec<User>, Vec<MemberRoles>)>> {
+        let room_type = match channel.room_type(self.connection, rocketchat_api)? {
+            Some(room_type) => room_type,
+            None => return Ok(None),
+        };
+
+        let members = channel.members(rocketchat_api, room_type)?;
+        let roles = channel.roles(rocketchat_api, room_type)?;
+        Ok(Some((room_type, members, roles)))
+    }
+
+    // Messages that a Matrix user wrote in a Rocket.Chat client are sent as the real Matrix user
+    // if an access token for the user is available.
+    fn send_as_double_puppet(&self, server: &RocketchatServer, message: &WebhookMessage, room: &Room) -> Result<bool> {
+        let mut user_on_rocketchat_server = match UserOnRocketchatServer::find_by_rocketchat_user_id(
+            self.connection,
+            server.id.clone(),
+            message.user_id.clone(),
+        )? {
+            Some(user_on_rocketchat_server) => user_on_rocketchat_server,
+            None => return Ok(false),
+        };
+
+        if !room.user_ids(None)?.iter().any(|id| id == &user_on_rocketchat_server.matrix_user_id) {
+            debug!(
+                self.logger,
+                "Not sending message as {}, because the user is not in the room {}",
+                user_on_rocketchat_server.matrix_user_id,
+                room.id
+            );
+            return Ok(false);
         }
+
+        let double_puppet = DoublePuppet::new(self.config, self.connection, self.logger, self.matrix_api);
+        double_puppet.send_text_message(&mut user_on_rocketchat_server, room.id.clone(), message.text.clone())
     }
 
     fn prepare_room(&self, server: &RocketchatServer, message: &WebhookMessage) -> Result<Option<Room>> {
         let is_direct_message_room = message.channel_id.contains(&message.user_id);
         if is_direct_message_room {
-            self.prepare_dm_room(server, message)
-        } else {
-            self.prepare_room_for_channel(server, message)
+            return self.prepare_dm_room(server, message);
+        }
+
+        match self.prepare_room_for_channel(server, message)? {
+            Some(room) => Ok(Some(room)),
+            // direct messages with more than two users don't have a name and their channel ID is not
+            // built from the IDs of the participants
+            None if message.channel_name.is_none() => self.prepare_group_dm_room(server, message),
+            None => Ok(None),
         }
     }
 
@@ -126,16 +233,102 @@ impl<'a> Forwarder<'a> {
         Ok(Some(room))
     }
 
+    fn prepare_group_dm_room(&self, server: &RocketchatServer, message: &WebhookMessage) -> Result<Option<Room>> {
+        // the room is recorded when the direct message is bridged, so the members of the channel only
+        // have to be looked up on Rocket.Chat for the first message
+        let bridged_rooms = BridgedRoom::find_by_rocketchat_channel_id(self.connection, &server.id, &message.channel_id)?;
+        if let Some(bridged_room) = bridged_rooms.into_iter().next() {
+            let bot_user_id = self.config.matrix_bot_user_id()?;
+            let sender_id = self.virtual_user.find_or_register(&server.id, &message.user_id, &message.user_name)?;
+            let room = Room::new(self.config, self.logger, self.matrix_api, bridged_room.matrix_room_id);
+            room.join_user(sender_id, bot_user_id)?;
+            return Ok(Some(room));
+        }
+
+        let receiver = match self.find_member_of_direct_message_channel(server, message)? {
+            Some(receiver) => receiver,
+            None => {
+                debug!(
+                    self.logger,
+                    "Ignoring message, because no logged in user is a member of the channel {}", message.channel_id
+                );
+                return Ok(None);
+            }
+        };
+
+        let rocketchat_api = RocketchatApi::new(server.rocketchat_url.clone(), self.logger.clone())?.with_credentials(
+            receiver.rocketchat_user_id.clone().unwrap_or_default(),
+            receiver.rocketchat_auth_token.clone().unwrap_or_default(),
+        );
+
+        let members = rocketchat_api.im_members(&message.channel_id)?;
+        if members.len() <= 2 {
+            debug!(
+                self.logger,
+                "Not bridging channel {}, because it's not a direct message with multiple users", message.channel_id
+            );
+            return Ok(None);
+        }
+
+        // participants that use the application service are invited with their Matrix user, all other
+        // participants are represented by virtual users
+        let mut matrix_user_ids = Vec::new();
+        let mut rocketchat_usernames = Vec::new();
+        for member in &members {
+            match UserOnRocketchatServer::find_by_rocketchat_user_id(self.connection, server.id.clone(), member.id.clone())? {
+                Some(ref user_on_rocketchat_server) if user_on_rocketchat_server.is_logged_in() => {
+                    matrix_user_ids.push(user_on_rocketchat_server.matrix_user_id.clone())
+                }
+                _ => rocketchat_usernames.push(member.username.clone()),
+            }
+        }
+
+        let mut usernames: Vec<String> = members.iter().map(|member| member.username.clone()).collect();
+        usernames.sort();
+        let room_display_name_suffix = t!(["defaults", "direct_message_room_display_name_suffix"]).l(DEFAULT_LANGUAGE);
+        let display_name = Some(format!("{} {}", usernames.join(", "), room_display_name_suffix));
+
+        let bot_user_id = self.config.matrix_bot_user_id()?;
+        let channel = RocketchatRoom::new(self.config, self.logger, self.matrix_api, message.channel_id.clone(), &server.id);
+        let room_id = channel.bridge(
+            self.connection,
+            rocketchat_api.as_ref(),
+            RoomType::DirectMessage,
+            &display_name,
+            &rocketchat_usernames,
+            &bot_user_id,
+            Some(&receiver.matrix_user_id),
+        )?;
+
+        let room = Room::new(self.config, self.logger, self.matrix_api, room_id);
+        for matrix_user_id in matrix_user_ids.into_iter().filter(|id| id != &receiver.matrix_user_id) {
+            room.join_user(matrix_user_id, bot_user_id.clone())?;
+        }
+
+        let sender_id = self.virtual_user.find_or_register(&server.id, &message.user_id, &message.user_name)?;
+        room.join_user(sender_id, bot_user_id)?;
+        info!(self.logger, "Room {} for direct message with multiple users successfully created", room.id);
+
+        Ok(Some(room))
+    }
+
     fn try_to_find_or_create_direct_message_room(
         &self,
         server: &RocketchatServer,
         receiver: &UserOnRocketchatServer,
         message: &WebhookMessage,
     ) -> Result<Option<Room>> {
+        // direct messages that were started by inviting a virtual user are bridged to the room of the inviter
+        let bridged_rooms = BridgedRoom::find_by_rocketchat_channel_id(self.connection, &server.id, &message.channel_id)?;
+        if let Some(bridged_room) = bridged_rooms.into_iter().next() {
+            return Ok(Some(Room::new(self.config, self.logger, self.matrix_api, bridged_room.matrix_room_id)));
+        }
+
         let sender_id = self.virtual_user.build_user_id(&message.user_id, &server.id)?;
 
         if let Some(room) = Room::get_dm(
             self.config,
+            self.connection,
             self.logger,
             self.matrix_api,
             message.channel_id.clone(),
@@ -161,7 +354,7 @@ impl<'a> Forwarder<'a> {
 
     fn prepare_room_for_channel(&self, server: &RocketchatServer, message: &WebhookMessage) -> Result<Option<Room>> {
         let channel = RocketchatRoom::new(self.config, self.logger, self.matrix_api, message.channel_id.clone(), &server.id);
-        let room_id = match channel.matrix_id()? {
+        let room_id = match channel.bridged_matrix_id(self.connection)? {
             Some(room_id) => room_id,
             None => return Ok(None),
         };
@@ -193,22 +386,26 @@ impl<'a> Forwarder<'a> {
             receiver.rocketchat_auth_token.clone().unwrap_or_default(),
         );
 
-        if rocketchat_api.dm_list()?.iter().any(|dm| dm.id == message.channel_id) {
-            let sender_id = self.virtual_user.find_or_register(&server.id, &message.user_id, &message.user_name)?;
-
-            let room_display_name_suffix = t!(["defaults", "direct_message_room_display_name_suffix"]).l(DEFAULT_LANGUAGE);
-            let room_display_name = format!("{} {}", message.user_name, room_display_name_suffix);
-
-            let display_name = Some(room_display_name);
-            let room_id = Room::create(self.matrix_api, None, &display_name, &sender_id, &receiver.matrix_user_id)?;
-
-            // invite the bot user into the direct message room to be able to read the room state
-            // the bot will leave as soon as the AS gets the join event
-            let invitee_id = self.config.matrix_bot_user_id()?;
-            self.matrix_api.invite(room_id.clone(), invitee_id.clone(), sender_id.clone())?;
-            info!(self.logger, "Direct message room {} successfully created", &room_id);
+        let direct_message_channels = match rocketchat_api.dm_list() {
+            Err(ref err) if err.is_rocketchat_authentication_failure() => {
+                let matrix_user_id = &receiver.matrix_user_id;
+                server.expire_session(self.config, self.connection, self.logger, self.matrix_api, matrix_user_id)?;
+                return Ok(None);
+            }
+            result => result?,
+        };
 
-            let room = Room::new(self.config, self.logger, self.matrix_api, room_id);
+        if direct_message_channels.iter().any(|dm| dm.id == message.channel_id) {
+            let sender_id = self.virtual_user.find_or_register(&server.id, &message.user_id, &message.user_name)?;
+            let room = Room::create_dm(
+                self.config,
+                self.logger,
+                self.matrix_api,
+                message.channel_id.clone(),
+                &sender_id,
+                &message.user_name,
+                &receiver.matrix_user_id,
+            )?;
             Ok(Some(room))
         } else {
             debug!(
@@ -222,11 +419,48 @@ impl<'a> Forwarder<'a> {
         }
     }
 
+    // Direct messages with more than two users have a random channel ID, so the direct messages
+    // of the users that are logged in on the sending Rocket.Chat server are checked to find a user
+    // that has access to the channel. This is only needed until the channel is bridged, afterwards
+    // the recorded room is used.
+    fn find_member_of_direct_message_channel(
+        &self,
+        server: &RocketchatServer,
+        message: &WebhookMessage,
+    ) -> Result<Option<UserOnRocketchatServer>> {
+        for user_on_rocketchat_server in server.logged_in_users_on_rocketchat_server(self.connection)? {
+            let rocketchat_api = RocketchatApi::new(server.rocketchat_url.clone(), self.logger.clone())?.with_credentials(
+                user_on_rocketchat_server.rocketchat_user_id.clone().unwrap_or_default(),
+                user_on_rocketchat_server.rocketchat_auth_token.clone().unwrap_or_default(),
+            );
+
+            let direct_message_channels = match rocketchat_api.dm_list() {
+                Err(ref err) if err.is_rocketchat_authentication_failure() => {
+                    let matrix_user_id = &user_on_rocketchat_server.matrix_user_id;
+                    server.expire_session(self.config, self.connection, self.logger, self.matrix_api, matrix_user_id)?;
+                    continue;
+                }
+                Err(err) => {
+                    log::log_error(self.logger, &err);
+                    continue;
+                }
+                Ok(direct_message_channels) => direct_message_channels,
+            };
+
+            if direct_message_channels.iter().any(|dm| dm.id == message.channel_id) {
+                return Ok(Some(user_on_rocketchat_server));
+            }
+        }
+
+        Ok(None)
+    }
+
     // this is a pretty hacky way to find a Matrix user that could be the recipient for this
     // message. The message itself doesn't contain any information about the recipient so the
     // channel ID has to be checked against all users that use the application service and are
     // logged in on the sending Rocket.Chat server, because direct message channel IDs consist of
-    // the `user_id`s of the two participants.
+    // the `user_id`s of the two participants. Direct messages with more than two users are
+    // handled by `find_member_of_direct_message_channel`.
     fn find_matching_user_for_direct_message(
         &self,
         server: &RocketchatServer,
@@ -272,7 +506,13 @@ impl<'a> Forwarder<'a> {
             user.rocketchat_auth_token.clone().unwrap_or_default(),
         );
 
-        let files = rocketchat_api.attachments(&message.message_id)?;
+        let files = match rocketchat_api.attachments(&message.message_id) {
+            Err(ref err) if err.is_rocketchat_authentication_failure() => {
+                let matrix_user_id = &user.matrix_user_id;
+                return server.expire_session(self.config, self.connection, self.logger, self.matrix_api, matrix_user_id);
+            }
+            result => result?,
+        };
 
         for file in files {
             let file_url = self.matrix_api.upload(file.data.to_vec(), file.content_type.clone())?;
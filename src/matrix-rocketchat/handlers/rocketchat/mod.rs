@@ -2,5 +2,8 @@
 
 /// Forwards message from Rocket.Chat to Matrix
 mod forwarder;
+/// Synchronizes the roles of Rocket.Chat users with the power levels in the bridged rooms
+mod role_synchronizer;
 
 pub use self::forwarder::Forwarder;
+pub use self::role_synchronizer::RoleSynchronizer;
@@ -0,0 +1,92 @@
+use diesel::sqlite::SqliteConnection;
+use slog::Logger;
+
+use api::{MatrixApi, RocketchatApi};
+use config::Config;
+use errors::*;
+use log;
+use models::{BridgedRoom, RocketchatRoom, RocketchatServer, Room, RoomType};
+
+/// Synchronizes the roles of the members of bridged channels and groups with the power levels in
+/// the bridged rooms. Rocket.Chat doesn't notify the application service when a role changes, so
+/// the roles are synchronized periodically.
+pub struct RoleSynchronizer<'a> {
+    /// Application service configuration
+    config: &'a Config,
+    /// SQL database connection
+    connection: &'a SqliteConnection,
+    /// Logger context
+    logger: &'a Logger,
+    /// Matrix REST API
+    matrix_api: &'a MatrixApi,
+}
+
+impl<'a> RoleSynchronizer<'a> {
+    /// Create a new `RoleSynchronizer`.
+    pub fn new(
+        config: &'a Config,
+        connection: &'a SqliteConnection,
+        logger: &'a Logger,
+        matrix_api: &'a MatrixApi,
+    ) -> RoleSynchronizer<'a> {
+        RoleSynchronizer { config, connection, logger, matrix_api }
+    }
+
+    /// Synchronize the roles of all bridged channels and groups on all connected Rocket.Chat
+    /// servers. A room that cannot be synchronized doesn't stop the synchronization of the others.
+    pub fn sync_all(&self) -> Result<()> {
+        for server in RocketchatServer::find_connected_servers(self.connection)? {
+            for bridged_room in BridgedRoom::find_by_rocketchat_server_id(self.connection, &server.id)? {
+                if let Err(err) = self.sync(&server, &bridged_room) {
+                    log::log_error(self.logger, &err);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // The roles are fetched with the credentials of the first logged in user in the room that can
+    // see the channel, direct messages have no roles.
+    fn sync(&self, server: &RocketchatServer, bridged_room: &BridgedRoom) -> Result<()> {
+        let room_type = match bridged_room.room_type() {
+            Some(RoomType::DirectMessage) | None => return Ok(()),
+            Some(room_type) => room_type,
+        };
+
+        let room = Room::new(self.config, self.logger, self.matrix_api, bridged_room.matrix_room_id.clone());
+        let channel = RocketchatRoom::new(
+            self.config,
+            self.logger,
+            self.matrix_api,
+            bridged_room.rocketchat_channel_id.clone(),
+            &server.id,
+        );
+
+        let users_on_rocketchat_server = room.logged_in_users(self.connection, server.id.clone())?;
+        for user_on_rocketchat_server in users_on_rocketchat_server.into_iter().filter(|user| user.is_logged_in()) {
+            let rocketchat_api = RocketchatApi::new(server.rocketchat_url.clone(), self.logger.clone())?.with_credentials(
+                user_on_rocketchat_server.rocketchat_user_id.clone().unwrap_or_default(),
+                user_on_rocketchat_server.rocketchat_auth_token.clone().unwrap_or_default(),
+            );
+
+            let roles = match channel.roles(rocketchat_api.as_ref(), room_type) {
+                Ok(roles) => roles,
+                Err(ref err) if err.is_rocketchat_authentication_failure() => {
+                    let matrix_user_id = &user_on_rocketchat_server.matrix_user_id;
+                    server.expire_session(self.config, self.connection, self.logger, self.matrix_api, matrix_user_id)?;
+                    continue;
+                }
+                Err(err) => {
+                    log::log_error(self.logger, &err);
+                    continue;
+                }
+            };
+
+            return room.sync_rocketchat_roles(self.connection, &roles, &server.id);
+        }
+
+        debug!(self.logger, "No logged in user in room {} can see the channel, cannot synchronize the roles", room.id);
+        Ok(())
+    }
+}
@@ -29,6 +29,12 @@ macro_rules! build_i18n_struct {
                 self
             }
 
+            /// Append another translation to the language string, separated by an empty line
+            pub fn append(mut self, other: &I18n) -> I18n {
+                $(self.$f = format!("{}\n\n{}", self.$f, other.l(stringify!($f)));)*
+                self
+            }
+
             /// Return the translation for a language
             pub fn l(&self, language: &str) -> String {
                 let mut translation = match language{
@@ -21,6 +21,7 @@ extern crate r2d2;
 extern crate r2d2_diesel;
 extern crate regex;
 extern crate reqwest;
+extern crate ring;
 extern crate router;
 extern crate ruma_client_api;
 extern crate ruma_events;
@@ -0,0 +1,140 @@
+use diesel;
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+use ruma_identifiers::RoomId;
+
+use errors::*;
+use models::schema::bridged_rooms;
+
+/// The type of a Rocket.Chat room that is bridged to a Matrix room.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RoomType {
+    /// A public channel that every user on the Rocket.Chat server can join
+    Channel,
+    /// A private group that only its members can see
+    Group,
+    /// A direct message between two or more users
+    DirectMessage,
+}
+
+impl RoomType {
+    /// The value that is stored in the database.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RoomType::Channel => "channel",
+            RoomType::Group => "group",
+            RoomType::DirectMessage => "direct_message",
+        }
+    }
+
+    /// Parse a stored value, returns `None` if the value is unknown.
+    pub fn from_str(value: &str) -> Option<RoomType> {
+        match value {
+            "channel" => Some(RoomType::Channel),
+            "group" => Some(RoomType::Group),
+            "direct_message" => Some(RoomType::DirectMessage),
+            _ => None,
+        }
+    }
+
+    /// Only members of the room are allowed to see it.
+    pub fn is_private(self) -> bool {
+        self != RoomType::Channel
+    }
+}
+
+/// A Matrix room together with the type of the Rocket.Chat room it is bridged to. The type is
+/// recorded when the room is bridged so that it doesn't have to be looked up on every event.
+#[derive(Associations, Debug, Identifiable, Queryable)]
+#[primary_key(matrix_room_id)]
+#[table_name = "bridged_rooms"]
+pub struct BridgedRoom {
+    /// The rooms Matrix ID
+    pub matrix_room_id: RoomId,
+    /// The unique id for the Rocket.Chat server
+    pub rocketchat_server_id: String,
+    /// The ID of the Rocket.Chat channel, group or direct message
+    pub rocketchat_channel_id: String,
+    /// The type of the Rocket.Chat room, see `RoomType`
+    pub room_type: String,
+    /// created timestamp
+    pub created_at: String,
+}
+
+/// A new `BridgedRoom`, not yet saved.
+#[derive(Debug, Insertable)]
+#[table_name = "bridged_rooms"]
+pub struct NewBridgedRoom {
+    /// The rooms Matrix ID
+    pub matrix_room_id: RoomId,
+    /// The unique id for the Rocket.Chat server
+    pub rocketchat_server_id: String,
+    /// The ID of the Rocket.Chat channel, group or direct message
+    pub rocketchat_channel_id: String,
+    /// The type of the Rocket.Chat room, see `RoomType`
+    pub room_type: String,
+}
+
+impl BridgedRoom {
+    /// Insert a `BridgedRoom`. An existing entry for the same Matrix room is replaced, rooms are
+    /// reused when a channel is bridged again.
+    pub fn upsert(connection: &SqliteConnection, new_bridged_room: &NewBridgedRoom) -> Result<BridgedRoom> {
+        diesel::replace_into(bridged_rooms::table)
+            .values(new_bridged_room)
+            .execute(connection)
+            .chain_err(|| ErrorKind::DBInsertError)?;
+
+        let bridged_room = bridged_rooms::table
+            .find(&new_bridged_room.matrix_room_id)
+            .first(connection)
+            .chain_err(|| ErrorKind::DBSelectError)?;
+        Ok(bridged_room)
+    }
+
+    /// Find a `BridgedRoom` by its Matrix room ID. Returns `None` if the type of the room was
+    /// never recorded.
+    pub fn find_by_matrix_room_id(connection: &SqliteConnection, matrix_room_id: &RoomId) -> Result<Option<BridgedRoom>> {
+        let bridged_rooms = bridged_rooms::table
+            .filter(bridged_rooms::matrix_room_id.eq(matrix_room_id))
+            .load(connection)
+            .chain_err(|| ErrorKind::DBSelectError)?;
+        Ok(bridged_rooms.into_iter().next())
+    }
+
+    /// Find all `BridgedRoom`s of a Rocket.Chat channel, group or direct message. A direct message
+    /// can be bridged to more than one Matrix room, one per receiver.
+    pub fn find_by_rocketchat_channel_id(
+        connection: &SqliteConnection,
+        rocketchat_server_id: &str,
+        rocketchat_channel_id: &str,
+    ) -> Result<Vec<BridgedRoom>> {
+        let bridged_rooms = bridged_rooms::table
+            .filter(bridged_rooms::rocketchat_server_id.eq(rocketchat_server_id))
+            .filter(bridged_rooms::rocketchat_channel_id.eq(rocketchat_channel_id))
+            .load(connection)
+            .chain_err(|| ErrorKind::DBSelectError)?;
+        Ok(bridged_rooms)
+    }
+
+    /// Find all `BridgedRoom`s on a Rocket.Chat server.
+    pub fn find_by_rocketchat_server_id(connection: &SqliteConnection, rocketchat_server_id: &str) -> Result<Vec<BridgedRoom>> {
+        let bridged_rooms = bridged_rooms::table
+            .filter(bridged_rooms::rocketchat_server_id.eq(rocketchat_server_id))
+            .load(connection)
+            .chain_err(|| ErrorKind::DBSelectError)?;
+        Ok(bridged_rooms)
+    }
+
+    /// The type of the Rocket.Chat room. Returns `None` if the stored value is unknown.
+    pub fn room_type(&self) -> Option<RoomType> {
+        RoomType::from_str(&self.room_type)
+    }
+
+    /// Delete a `BridgedRoom`, the Matrix room is no longer bridged.
+    pub fn delete(&self, connection: &SqliteConnection) -> Result<()> {
+        diesel::delete(bridged_rooms::table.find(&self.matrix_room_id))
+            .execute(connection)
+            .chain_err(|| ErrorKind::DBDeleteError)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,79 @@
+use diesel::sqlite::SqliteConnection;
+use ruma_identifiers::RoomId;
+use slog::Logger;
+
+use api::MatrixApi;
+use config::Config;
+use errors::*;
+use models::{PuppetEvent, UserOnRocketchatServer};
+
+/// Sends messages on behalf of Matrix users that are logged in on the Rocket.Chat server, so that
+/// messages they write in a Rocket.Chat client show up as their real Matrix user (double puppeting).
+pub struct DoublePuppet<'a> {
+    /// Application service configuration
+    config: &'a Config,
+    /// SQL database connection
+    connection: &'a SqliteConnection,
+    /// Logger context
+    logger: &'a Logger,
+    /// API to call the Matrix homeserver
+    matrix_api: &'a MatrixApi,
+}
+
+impl<'a> DoublePuppet<'a> {
+    /// Create a new `DoublePuppet`.
+    pub fn new(
+        config: &'a Config,
+        connection: &'a SqliteConnection,
+        logger: &'a Logger,
+        matrix_api: &'a MatrixApi,
+    ) -> DoublePuppet<'a> {
+        DoublePuppet { config, connection, logger, matrix_api }
+    }
+
+    /// Send a text message as the Matrix user. Returns `false` if the message could not be sent,
+    /// because there is no access token available for the user.
+    pub fn send_text_message(&self, user: &mut UserOnRocketchatServer, room_id: RoomId, body: String) -> Result<bool> {
+        let access_token = match self.access_token(user)? {
+            Some(access_token) => access_token,
+            None => return Ok(false),
+        };
+
+        let _sending = PuppetEvent::sending();
+        match self.matrix_api.send_text_message_as_user(room_id, &access_token, body) {
+            Ok(event_id) => {
+                PuppetEvent::insert(self.connection, &event_id)?;
+                Ok(true)
+            }
+            Err(err) => {
+                warn!(self.logger, "Sending message as user {} failed: {}", user.matrix_user_id, err);
+                // the access token might have expired, a new one is requested for the next message
+                if self.config.double_puppeting_shared_secret.is_some() {
+                    user.set_matrix_access_token(self.connection, None)?;
+                }
+                Ok(false)
+            }
+        }
+    }
+
+    fn access_token(&self, user: &mut UserOnRocketchatServer) -> Result<Option<String>> {
+        if let Some(ref access_token) = user.matrix_access_token {
+            return Ok(Some(access_token.clone()));
+        }
+
+        let shared_secret = match self.config.double_puppeting_shared_secret {
+            Some(ref shared_secret) => shared_secret,
+            None => return Ok(None),
+        };
+
+        // the shared secret can only be used to login users that are on the bridges homeserver
+        if user.matrix_user_id.hostname().to_string() != self.config.hs_domain {
+            return Ok(None);
+        }
+
+        debug!(self.logger, "Logging in user {} via shared secret for double puppeting", user.matrix_user_id);
+        let access_token = self.matrix_api.login_with_shared_secret(user.matrix_user_id.clone(), shared_secret)?;
+        user.set_matrix_access_token(self.connection, Some(access_token.clone()))?;
+        Ok(Some(access_token))
+    }
+}
@@ -0,0 +1,116 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use diesel;
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+use ring::digest;
+use ring::rand::{SecureRandom, SystemRandom};
+use ruma_identifiers::{RoomId, UserId};
+
+use errors::*;
+use models::schema::login_tokens;
+
+/// Time in which the login token can be used
+pub const LOGIN_TOKEN_TIMEOUT_IN_SECONDS: u64 = 600;
+/// Number of random bytes a login token consists of
+const LOGIN_TOKEN_LENGTH: usize = 32;
+
+/// A one-time token that allows a Matrix user to login on a Rocket.Chat server via the login
+/// page or the login endpoint of the application service. Only a hash of the token is stored.
+#[derive(Associations, Debug, Identifiable, Queryable)]
+#[primary_key(token_hash)]
+#[table_name = "login_tokens"]
+pub struct LoginToken {
+    /// SHA-256 hash of the token
+    pub token_hash: String,
+    /// The Matrix user that requested the token
+    pub matrix_user_id: UserId,
+    /// The unique id for the Rocket.Chat server
+    pub rocketchat_server_id: String,
+    /// The admin room in which the token was requested
+    pub admin_room_id: RoomId,
+    /// Unix timestamp after which the token can no longer be used
+    pub expires_at: i64,
+    /// created timestamp
+    pub created_at: String,
+}
+
+/// A new `LoginToken`, not yet saved.
+#[derive(Insertable)]
+#[table_name = "login_tokens"]
+pub struct NewLoginToken {
+    /// SHA-256 hash of the token
+    pub token_hash: String,
+    /// The Matrix user that requested the token
+    pub matrix_user_id: UserId,
+    /// The unique id for the Rocket.Chat server
+    pub rocketchat_server_id: String,
+    /// The admin room in which the token was requested
+    pub admin_room_id: RoomId,
+    /// Unix timestamp after which the token can no longer be used
+    pub expires_at: i64,
+}
+
+impl LoginToken {
+    /// Create a new login token for the user, returns the plain token which is sent to the user.
+    /// Expired tokens are removed.
+    pub fn create(
+        connection: &SqliteConnection,
+        matrix_user_id: UserId,
+        rocketchat_server_id: String,
+        admin_room_id: RoomId,
+    ) -> Result<String> {
+        let mut bytes = [0u8; LOGIN_TOKEN_LENGTH];
+        SystemRandom::new().fill(&mut bytes).map_err(|_| simple_error!(ErrorKind::InternalServerError))?;
+        let token = to_hex(&bytes);
+
+        let now = now()?;
+        diesel::delete(login_tokens::table.filter(login_tokens::expires_at.lt(now)))
+            .execute(connection)
+            .chain_err(|| ErrorKind::DBDeleteError)?;
+
+        let new_login_token = NewLoginToken {
+            token_hash: hash(&token),
+            matrix_user_id,
+            rocketchat_server_id,
+            admin_room_id,
+            expires_at: now + LOGIN_TOKEN_TIMEOUT_IN_SECONDS as i64,
+        };
+        diesel::insert_into(login_tokens::table)
+            .values(&new_login_token)
+            .execute(connection)
+            .chain_err(|| ErrorKind::DBInsertError)?;
+
+        Ok(token)
+    }
+
+    /// Find a login token. Returns `None` if the token doesn't exist or if it expired.
+    pub fn find(connection: &SqliteConnection, token: &str) -> Result<Option<LoginToken>> {
+        let login_tokens = login_tokens::table
+            .filter(login_tokens::token_hash.eq(hash(token)))
+            .filter(login_tokens::expires_at.ge(now()?))
+            .load(connection)
+            .chain_err(|| ErrorKind::DBSelectError)?;
+        Ok(login_tokens.into_iter().next())
+    }
+
+    /// Delete the token after it was used, so that it cannot be used again.
+    pub fn delete(&self, connection: &SqliteConnection) -> Result<()> {
+        diesel::delete(login_tokens::table.find(&self.token_hash))
+            .execute(connection)
+            .chain_err(|| ErrorKind::DBDeleteError)?;
+        Ok(())
+    }
+}
+
+fn hash(token: &str) -> String {
+    to_hex(digest::digest(&digest::SHA256, token.as_bytes()).as_ref())
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn now() -> Result<i64> {
+    Ok(SystemTime::now().duration_since(UNIX_EPOCH).chain_err(|| ErrorKind::InternalServerError)?.as_secs() as i64)
+}
@@ -1,9 +1,23 @@
 //! Models that manage data and logic used by the application service.
 
+/// A Matrix room together with the type of the Rocket.Chat room it is bridged to
+mod bridged_room;
 /// The database connection pool
 mod connection_pool;
+/// Sends messages on behalf of Matrix users
+mod double_puppet;
 /// A list of Events that are received from the Matirx homeserver.
 mod events;
+/// A one-time token to login via the login page
+mod login_token;
+/// An existing Matrix room that is bridged to a Rocket.Chat channel or group
+mod plumbed_room;
+/// An event that was sent on behalf of a Matrix user
+mod puppet_event;
+/// An error that happened while forwarding messages of a user
+mod recorded_error;
+/// A room in which messages from Matrix users are relayed
+mod relay_room;
 /// A Rocket.Chat channel or group
 mod rocketchat_room;
 /// `RocketchatServer` entry
@@ -12,15 +26,31 @@ mod rocketchat_server;
 mod room;
 /// The database schema
 mod schema;
+/// A message that was sent to the Rocket.Chat server by the application service
+mod sent_rocketchat_message;
+/// The key to encrypt tokens that are stored in the database
+mod token_key;
+/// A login that waits for a two-factor authentication code
+mod two_factor_login;
 /// `UserOnRocketchatServer` entry
 mod user_on_rocketchat_server;
 /// A virtual user on the Matrix homeserver that represents a Rocket.Chat user.
 mod virtual_user;
 
+pub use self::bridged_room::{BridgedRoom, NewBridgedRoom, RoomType};
 pub use self::connection_pool::ConnectionPool;
+pub use self::double_puppet::DoublePuppet;
 pub use self::events::Events;
+pub use self::login_token::{LoginToken, LOGIN_TOKEN_TIMEOUT_IN_SECONDS};
+pub use self::plumbed_room::{NewPlumbedRoom, PlumbedRoom};
+pub use self::puppet_event::{NewPuppetEvent, PuppetEvent};
+pub use self::recorded_error::{NewRecordedError, RecordedError};
+pub use self::relay_room::{NewRelayRoom, RelayRoom};
 pub use self::rocketchat_room::RocketchatRoom;
-pub use self::rocketchat_server::{Credentials, NewRocketchatServer, RocketchatServer};
+pub use self::rocketchat_server::{Credentials, LoginRequest, NewRocketchatServer, RocketchatServer};
 pub use self::room::Room;
+pub use self::sent_rocketchat_message::{NewSentRocketchatMessage, SentRocketchatMessage};
+pub use self::token_key::TokenKey;
+pub use self::two_factor_login::{TwoFactorLogin, TWO_FACTOR_LOGIN_TIMEOUT_IN_SECONDS};
 pub use self::user_on_rocketchat_server::{NewUserOnRocketchatServer, UserOnRocketchatServer};
 pub use self::virtual_user::VirtualUser;
@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use diesel;
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+use ruma_identifiers::{RoomId, UserId};
+
+use errors::*;
+use models::schema::plumbed_rooms;
+
+lazy_static! {
+    /// Plumbed rooms that wait for the bot user to join together with the admin room in which the
+    /// plumbing was requested
+    static ref PENDING_PLUMBED_ROOMS: Mutex<HashMap<RoomId, (NewPlumbedRoom, RoomId)>> = { Mutex::new(HashMap::new()) };
+}
+
+/// An existing Matrix room that is bridged to a Rocket.Chat channel or group. Unlike the rooms
+/// created by the `bridge` command, the room is not managed by the application service.
+#[derive(Associations, Debug, Identifiable, Queryable)]
+#[primary_key(matrix_room_id)]
+#[table_name = "plumbed_rooms"]
+pub struct PlumbedRoom {
+    /// The rooms Matrix ID
+    pub matrix_room_id: RoomId,
+    /// The unique id for the Rocket.Chat server
+    pub rocketchat_server_id: String,
+    /// The ID of the Rocket.Chat channel or group
+    pub rocketchat_channel_id: String,
+    /// The Matrix user who plumbed the room
+    pub created_by: UserId,
+    /// created timestamp
+    pub created_at: String,
+    /// updated timestamp
+    pub updated_at: String,
+}
+
+/// A new `PlumbedRoom`, not yet saved.
+#[derive(Debug, Insertable)]
+#[table_name = "plumbed_rooms"]
+pub struct NewPlumbedRoom {
+    /// The rooms Matrix ID
+    pub matrix_room_id: RoomId,
+    /// The unique id for the Rocket.Chat server
+    pub rocketchat_server_id: String,
+    /// The ID of the Rocket.Chat channel or group
+    pub rocketchat_channel_id: String,
+    /// The Matrix user who plumbed the room
+    pub created_by: UserId,
+}
+
+impl PlumbedRoom {
+    /// Insert a `PlumbedRoom`.
+    pub fn insert(connection: &SqliteConnection, new_plumbed_room: &NewPlumbedRoom) -> Result<PlumbedRoom> {
+        diesel::insert_into(plumbed_rooms::table)
+            .values(new_plumbed_room)
+            .execute(connection)
+            .chain_err(|| ErrorKind::DBInsertError)?;
+
+        let plumbed_room = plumbed_rooms::table
+            .find(&new_plumbed_room.matrix_room_id)
+            .first(connection)
+            .chain_err(|| ErrorKind::DBSelectError)?;
+        Ok(plumbed_room)
+    }
+
+    /// Find a `PlumbedRoom` by its Matrix room ID. Returns `None` if the room is not plumbed.
+    pub fn find_by_matrix_room_id(connection: &SqliteConnection, matrix_room_id: &RoomId) -> Result<Option<PlumbedRoom>> {
+        let plumbed_rooms = plumbed_rooms::table
+            .filter(plumbed_rooms::matrix_room_id.eq(matrix_room_id))
+            .load(connection)
+            .chain_err(|| ErrorKind::DBSelectError)?;
+        Ok(plumbed_rooms.into_iter().next())
+    }
+
+    /// Find the `PlumbedRoom` of a Rocket.Chat channel or group. Returns `None` if the channel
+    /// or group is not plumbed.
+    pub fn find_by_rocketchat_channel_id(
+        connection: &SqliteConnection,
+        rocketchat_server_id: &str,
+        rocketchat_channel_id: &str,
+    ) -> Result<Option<PlumbedRoom>> {
+        let plumbed_rooms = plumbed_rooms::table
+            .filter(plumbed_rooms::rocketchat_server_id.eq(rocketchat_server_id))
+            .filter(plumbed_rooms::rocketchat_channel_id.eq(rocketchat_channel_id))
+            .load(connection)
+            .chain_err(|| ErrorKind::DBSelectError)?;
+        Ok(plumbed_rooms.into_iter().next())
+    }
+
+    /// Delete a `PlumbedRoom`, the Matrix room is no longer bridged.
+    pub fn delete(&self, connection: &SqliteConnection) -> Result<()> {
+        diesel::delete(plumbed_rooms::table.find(&self.matrix_room_id))
+            .execute(connection)
+            .chain_err(|| ErrorKind::DBDeleteError)?;
+        Ok(())
+    }
+
+    /// Remember a room that will be plumbed as soon as the bot user joined it. The request is only
+    /// kept in memory, it has to be repeated if the application service restarts in the meantime.
+    pub fn add_pending(new_plumbed_room: NewPlumbedRoom, admin_room_id: RoomId) {
+        if let Ok(mut pending_plumbed_rooms) = PENDING_PLUMBED_ROOMS.lock() {
+            pending_plumbed_rooms.insert(new_plumbed_room.matrix_room_id.clone(), (new_plumbed_room, admin_room_id));
+        }
+    }
+
+    /// Remove the pending plumbing of a room, returns it together with the admin room in which it
+    /// was requested. Returns `None` if no plumbing was requested for the room.
+    pub fn take_pending(matrix_room_id: &RoomId) -> Option<(NewPlumbedRoom, RoomId)> {
+        let mut pending_plumbed_rooms = PENDING_PLUMBED_ROOMS.lock().ok()?;
+        pending_plumbed_rooms.remove(matrix_room_id)
+    }
+}
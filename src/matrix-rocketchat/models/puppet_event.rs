@@ -0,0 +1,76 @@
+use std::sync::{PoisonError, RwLock, RwLockReadGuard};
+
+use diesel;
+use diesel::dsl::sql;
+use diesel::prelude::*;
+use diesel::sql_types::Bool;
+use diesel::sqlite::SqliteConnection;
+use ruma_identifiers::EventId;
+
+use errors::*;
+use models::schema::puppet_events;
+
+lazy_static! {
+    /// Held for reading while a message is sent on behalf of a Matrix user until its event ID is
+    /// stored, the check for puppet events waits for the write lock. This way the event cannot be
+    /// processed before its ID is known.
+    static ref SENDING: RwLock<()> = { RwLock::new(()) };
+}
+
+/// An event that was sent by the application service on behalf of a Matrix user (double
+/// puppeting). The homeserver sends it to the application service, but it must not be forwarded
+/// to Rocket.Chat again.
+#[derive(Associations, Debug, Identifiable, Queryable)]
+#[primary_key(event_id)]
+#[table_name = "puppet_events"]
+pub struct PuppetEvent {
+    /// The ID of the event that was sent on behalf of the user
+    pub event_id: EventId,
+    /// created timestamp
+    pub created_at: String,
+}
+
+/// A new `PuppetEvent`, not yet saved.
+#[derive(Insertable)]
+#[table_name = "puppet_events"]
+pub struct NewPuppetEvent<'a> {
+    /// The ID of the event that was sent on behalf of the user
+    pub event_id: &'a EventId,
+}
+
+impl PuppetEvent {
+    /// Has to be held from sending a message on behalf of a user until its event ID is inserted,
+    /// the homeserver can push the event before it responds to the request.
+    pub fn sending() -> RwLockReadGuard<'static, ()> {
+        SENDING.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Remember an event that was sent on behalf of a user.
+    pub fn insert(connection: &SqliteConnection, event_id: &EventId) -> Result<()> {
+        let new_puppet_event = NewPuppetEvent { event_id };
+        diesel::replace_into(puppet_events::table)
+            .values(&new_puppet_event)
+            .execute(connection)
+            .chain_err(|| ErrorKind::DBInsertError)?;
+        Ok(())
+    }
+
+    /// Remove an event from the list of puppet events. Returns `true` if the event was sent by the
+    /// application service on behalf of a user. Waits until the events that are currently sent are
+    /// inserted.
+    pub fn remove(connection: &SqliteConnection, event_id: &EventId) -> Result<bool> {
+        let _sending = SENDING.write().unwrap_or_else(PoisonError::into_inner);
+        let deleted_rows =
+            diesel::delete(puppet_events::table.find(event_id)).execute(connection).chain_err(|| ErrorKind::DBDeleteError)?;
+        Ok(deleted_rows > 0)
+    }
+
+    /// Remove the entries of events that were never pushed by the homeserver. Returns the number
+    /// of removed entries.
+    pub fn delete_expired(connection: &SqliteConnection) -> Result<usize> {
+        let expired = sql::<Bool>("created_at < datetime('now', '-1 day')");
+        let deleted_rows =
+            diesel::delete(puppet_events::table.filter(expired)).execute(connection).chain_err(|| ErrorKind::DBDeleteError)?;
+        Ok(deleted_rows)
+    }
+}
@@ -0,0 +1,94 @@
+use diesel;
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+use ruma_identifiers::UserId;
+
+use errors::*;
+use i18n::*;
+use models::schema::recorded_errors;
+
+/// Number of errors that are kept for each user
+const MAX_RECORDED_ERRORS_PER_USER: i64 = 5;
+
+/// An error that happened while forwarding a message of a user from Matrix to Rocket.Chat. The errors
+/// are shown to the user with the `status` command.
+#[derive(Associations, Debug, Identifiable, Queryable)]
+#[table_name = "recorded_errors"]
+pub struct RecordedError {
+    /// The unique identifier of the error
+    pub id: i32,
+    /// The Matrix user for which the error happened
+    pub matrix_user_id: UserId,
+    /// The unique id for the Rocket.Chat server
+    pub rocketchat_server_id: String,
+    /// A message that describes the error
+    pub message: String,
+    /// created timestamp
+    pub created_at: String,
+}
+
+/// A new `RecordedError`, not yet saved.
+#[derive(Insertable)]
+#[table_name = "recorded_errors"]
+pub struct NewRecordedError<'a> {
+    /// The Matrix user for which the error happened
+    pub matrix_user_id: UserId,
+    /// The unique id for the Rocket.Chat server
+    pub rocketchat_server_id: &'a str,
+    /// A message that describes the error
+    pub message: &'a str,
+}
+
+impl RecordedError {
+    /// Record an error for a user. The user message is stored if the error contains one. Only the
+    /// most recent errors of the user are kept.
+    pub fn record(
+        connection: &SqliteConnection,
+        matrix_user_id: &UserId,
+        rocketchat_server_id: &str,
+        err: &Error,
+    ) -> Result<()> {
+        let message = match err.user_message {
+            Some(ref user_message) => user_message.l(DEFAULT_LANGUAGE),
+            None => err.to_string(),
+        };
+
+        let new_recorded_error =
+            NewRecordedError { matrix_user_id: matrix_user_id.clone(), rocketchat_server_id, message: &message };
+        diesel::insert_into(recorded_errors::table)
+            .values(&new_recorded_error)
+            .execute(connection)
+            .chain_err(|| ErrorKind::DBInsertError)?;
+
+        let outdated_error_ids: Vec<i32> = recorded_errors::table
+            .select(recorded_errors::id)
+            .filter(recorded_errors::matrix_user_id.eq(matrix_user_id))
+            .filter(recorded_errors::rocketchat_server_id.eq(rocketchat_server_id))
+            .order(recorded_errors::id.desc())
+            .limit(-1)
+            .offset(MAX_RECORDED_ERRORS_PER_USER)
+            .load(connection)
+            .chain_err(|| ErrorKind::DBSelectError)?;
+        diesel::delete(recorded_errors::table.filter(recorded_errors::id.eq_any(outdated_error_ids)))
+            .execute(connection)
+            .chain_err(|| ErrorKind::DBDeleteError)?;
+
+        Ok(())
+    }
+
+    /// Find the most recent errors of a user on a Rocket.Chat server, the latest error comes first.
+    pub fn find_recent(
+        connection: &SqliteConnection,
+        matrix_user_id: &UserId,
+        rocketchat_server_id: &str,
+    ) -> Result<Vec<RecordedError>> {
+        let recorded_errors = recorded_errors::table
+            .filter(recorded_errors::matrix_user_id.eq(matrix_user_id))
+            .filter(recorded_errors::rocketchat_server_id.eq(rocketchat_server_id))
+            .order(recorded_errors::id.desc())
+            .limit(MAX_RECORDED_ERRORS_PER_USER)
+            .load(connection)
+            .chain_err(|| ErrorKind::DBSelectError)?;
+        Ok(recorded_errors)
+    }
+}
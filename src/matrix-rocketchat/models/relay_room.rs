@@ -0,0 +1,69 @@
+use diesel;
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+use ruma_identifiers::{RoomId, UserId};
+
+use errors::*;
+use models::schema::relay_rooms;
+
+/// A bridged room in which messages from Matrix users that are not logged in on the Rocket.Chat
+/// server are relayed via the Rocket.Chat account of another user.
+#[derive(Associations, Debug, Identifiable, Queryable)]
+#[primary_key(matrix_room_id)]
+#[table_name = "relay_rooms"]
+pub struct RelayRoom {
+    /// The rooms Matrix ID
+    pub matrix_room_id: RoomId,
+    /// The unique id for the Rocket.Chat server
+    pub rocketchat_server_id: String,
+    /// The Matrix user whose Rocket.Chat account is used to relay the messages
+    pub relay_matrix_user_id: UserId,
+    /// created timestamp
+    pub created_at: String,
+    /// updated timestamp
+    pub updated_at: String,
+}
+
+/// A new `RelayRoom`, not yet saved.
+#[derive(Insertable)]
+#[table_name = "relay_rooms"]
+pub struct NewRelayRoom {
+    /// The rooms Matrix ID
+    pub matrix_room_id: RoomId,
+    /// The unique id for the Rocket.Chat server
+    pub rocketchat_server_id: String,
+    /// The Matrix user whose Rocket.Chat account is used to relay the messages
+    pub relay_matrix_user_id: UserId,
+}
+
+impl RelayRoom {
+    /// Insert or update a `RelayRoom`.
+    pub fn upsert(connection: &SqliteConnection, new_relay_room: &NewRelayRoom) -> Result<RelayRoom> {
+        diesel::replace_into(relay_rooms::table)
+            .values(new_relay_room)
+            .execute(connection)
+            .chain_err(|| ErrorKind::DBInsertError)?;
+
+        let relay_room =
+            relay_rooms::table.find(&new_relay_room.matrix_room_id).first(connection).chain_err(|| ErrorKind::DBSelectError)?;
+        Ok(relay_room)
+    }
+
+    /// Find a `RelayRoom` by its Matrix room ID. Returns `None` if relaying is not enabled for
+    /// the room.
+    pub fn find_by_matrix_room_id(connection: &SqliteConnection, matrix_room_id: &RoomId) -> Result<Option<RelayRoom>> {
+        let relay_rooms = relay_rooms::table
+            .filter(relay_rooms::matrix_room_id.eq(matrix_room_id))
+            .load(connection)
+            .chain_err(|| ErrorKind::DBSelectError)?;
+        Ok(relay_rooms.into_iter().next())
+    }
+
+    /// Delete a `RelayRoom`, which disables relaying for the room.
+    pub fn delete(&self, connection: &SqliteConnection) -> Result<()> {
+        diesel::delete(relay_rooms::table.find(&self.matrix_room_id))
+            .execute(connection)
+            .chain_err(|| ErrorKind::DBDeleteError)?;
+        Ok(())
+    }
+}
@@ -1,12 +1,14 @@
 use std::convert::TryFrom;
 
+use diesel::sqlite::SqliteConnection;
 use ruma_identifiers::{RoomAliasId, RoomId, UserId};
 use slog::Logger;
 
+use api::rocketchat::{MemberRoles, Role, User};
 use api::{MatrixApi, RocketchatApi};
 use config::Config;
 use errors::*;
-use models::Room;
+use models::{BridgedRoom, NewBridgedRoom, PlumbedRoom, RocketchatServer, Room, RoomType};
 
 /// A channel or group on a Rocket.Chat server.
 pub struct RocketchatRoom<'a> {
@@ -64,14 +66,17 @@ impl<'a> RocketchatRoom<'a> {
     }
 
     /// Bridges a new room between Rocket.Chat and Matrix. It creates the room on the Matrix
-    /// homeserver and manages the rooms virtual users.
+    /// homeserver, records the type of the Rocket.Chat room and manages the rooms virtual users.
+    /// The invited user is optional, rooms that are bridged on demand have nobody to invite.
     pub fn bridge(
         &self,
+        connection: &SqliteConnection,
         rocketchat_api: &RocketchatApi,
+        room_type: RoomType,
         name: &Option<String>,
         userlist: &[String],
         creator_id: &UserId,
-        invited_user_id: &UserId,
+        invited_user_id: Option<&UserId>,
     ) -> Result<RoomId> {
         debug!(self.logger, "Briding new room, Rocket.Chat channel/group: {}", name.clone().unwrap_or_default());
 
@@ -81,18 +86,186 @@ impl<'a> RocketchatRoom<'a> {
         let matrix_room_alias_id = self.build_room_alias_id()?;
         let alias_id = Some(matrix_room_alias_id);
         self.matrix_api.put_canonical_room_alias(room_id.clone(), alias_id)?;
+        self.record_room_type(connection, &room_id, room_type)?;
 
         let room = Room::new(self.config, self.logger, self.matrix_api, room_id.clone());
-        room.join_all_rocketchat_users(rocketchat_api, userlist, self.server_id)?;
+        room.join_all_rocketchat_users(connection, rocketchat_api, userlist, self.server_id)?;
+        self.publish(connection, &room_id, room_type)?;
 
         Ok(room_id)
     }
 
-    /// Indicates if the channel or group is bridged for a given user.
-    pub fn is_bridged_for_user(&self, user_id: &UserId) -> Result<bool> {
-        let room_alias_id = self.build_room_alias_id()?;
+    /// Publishes the bridged room of a public channel to the room directory and adds it to the
+    /// space of the Rocket.Chat server, if the application service is configured to do so. Rooms
+    /// of groups and direct messages are never published, they are only accessible for their members.
+    pub fn publish(&self, connection: &SqliteConnection, room_id: &RoomId, room_type: RoomType) -> Result<()> {
+        let publish_rooms_to_directory = self.config.publish_rooms_to_directory.unwrap_or(false);
+        let create_spaces = self.config.create_spaces.unwrap_or(false);
+        if (!publish_rooms_to_directory && !create_spaces) || room_type.is_private() {
+            return Ok(());
+        }
+
+        let mut server = match RocketchatServer::find_by_id(connection, self.server_id)? {
+            Some(server) => server,
+            None => return Ok(()),
+        };
+
+        if publish_rooms_to_directory {
+            self.matrix_api.set_room_directory_visibility(self.server_id, room_id.clone(), true)?;
+        }
+
+        if create_spaces {
+            let space_room_id = server.find_or_create_space(self.config, connection, self.logger, self.matrix_api)?;
+            self.matrix_api.set_space_child(space_room_id, room_id.clone(), vec![self.config.hs_domain.clone()])?;
+        }
+
+        debug!(self.logger, "Published room {} of Rocket.Chat channel {}", room_id, self.id);
+        Ok(())
+    }
+
+    /// Removes the bridged room from the room directory and from the space of the Rocket.Chat server.
+    pub fn unpublish(&self, connection: &SqliteConnection, room_id: &RoomId) -> Result<()> {
+        if self.config.publish_rooms_to_directory.unwrap_or(false) {
+            self.matrix_api.set_room_directory_visibility(self.server_id, room_id.clone(), false)?;
+        }
+
+        let space_room_id = RocketchatServer::find_by_id(connection, self.server_id)?.and_then(|server| server.space_room_id);
+        if let Some(space_room_id) = space_room_id {
+            self.matrix_api.set_space_child(space_room_id, room_id.clone(), Vec::new())?;
+        }
+
+        Ok(())
+    }
+
+    /// Indicates if the room is a private group. Groups are only visible to their members, so this
+    /// only works if the user of the API is a member of the group.
+    pub fn is_group(&self, rocketchat_api: &RocketchatApi) -> Result<bool> {
+        Ok(rocketchat_api.groups_list()?.iter().any(|group| group.id == self.id))
+    }
+
+    /// Indicates if the room is a public channel. Groups and direct messages are not part of the
+    /// channel list, so rooms that cannot be found are treated as private.
+    pub fn is_public_channel(&self, rocketchat_api: &RocketchatApi) -> Result<bool> {
+        Ok(rocketchat_api.channels_list()?.iter().any(|channel| channel.id == self.id))
+    }
+
+    /// Looks up the type of the room on the Rocket.Chat server. Groups and direct messages are only
+    /// visible to their members, so `None` is returned if the user of the API cannot see the room.
+    pub fn detect_room_type(&self, rocketchat_api: &RocketchatApi) -> Result<Option<RoomType>> {
+        if self.is_public_channel(rocketchat_api)? {
+            return Ok(Some(RoomType::Channel));
+        }
+
+        if self.is_group(rocketchat_api)? {
+            return Ok(Some(RoomType::Group));
+        }
+
+        if rocketchat_api.dm_list()?.iter().any(|direct_message| direct_message.id == self.id) {
+            return Ok(Some(RoomType::DirectMessage));
+        }
+
+        Ok(None)
+    }
+
+    /// The type of the room that was recorded when it was bridged. Returns `None` for rooms that
+    /// were bridged before the type was recorded.
+    pub fn stored_room_type(&self, connection: &SqliteConnection) -> Result<Option<RoomType>> {
+        let bridged_rooms = BridgedRoom::find_by_rocketchat_channel_id(connection, self.server_id, &self.id)?;
+        Ok(bridged_rooms.iter().filter_map(|bridged_room| bridged_room.room_type()).next())
+    }
+
+    /// Record the type of the room for the Matrix room it is bridged to.
+    pub fn record_room_type(&self, connection: &SqliteConnection, matrix_room_id: &RoomId, room_type: RoomType) -> Result<()> {
+        let new_bridged_room = NewBridgedRoom {
+            matrix_room_id: matrix_room_id.clone(),
+            rocketchat_server_id: self.server_id.to_string(),
+            rocketchat_channel_id: self.id.clone(),
+            room_type: room_type.as_str().to_string(),
+        };
+        BridgedRoom::upsert(connection, &new_bridged_room)?;
+        Ok(())
+    }
+
+    /// The type of the room. Rooms that were bridged before their type was recorded are looked up on
+    /// the Rocket.Chat server once, returns `None` if the user of the API cannot see the room.
+    pub fn room_type(&self, connection: &SqliteConnection, rocketchat_api: &RocketchatApi) -> Result<Option<RoomType>> {
+        if let Some(room_type) = self.stored_room_type(connection)? {
+            return Ok(Some(room_type));
+        }
 
-        match self.matrix_api.get_room_alias(room_alias_id)? {
+        let room_type = match self.detect_room_type(rocketchat_api)? {
+            Some(room_type) => room_type,
+            None => return Ok(None),
+        };
+
+        if let Some(matrix_room_id) = self.bridged_matrix_id(connection)? {
+            self.record_room_type(connection, &matrix_room_id, room_type)?;
+        }
+
+        Ok(Some(room_type))
+    }
+
+    /// Get all members of the channel, group or direct message.
+    pub fn members(&self, rocketchat_api: &RocketchatApi, room_type: RoomType) -> Result<Vec<User>> {
+        match room_type {
+            RoomType::Channel => rocketchat_api.channels_members(&self.id),
+            RoomType::Group => rocketchat_api.groups_members(&self.id),
+            RoomType::DirectMessage => rocketchat_api.im_members(&self.id),
+        }
+    }
+
+    /// Remove a user from the channel or group. Nobody can be removed from a direct message, so
+    /// nothing is done for direct messages.
+    pub fn kick(&self, rocketchat_api: &RocketchatApi, room_type: RoomType, rocketchat_user_id: &str) -> Result<()> {
+        match room_type {
+            RoomType::Channel => rocketchat_api.channels_kick(&self.id, rocketchat_user_id),
+            RoomType::Group => rocketchat_api.groups_kick(&self.id, rocketchat_user_id),
+            RoomType::DirectMessage => Ok(()),
+        }
+    }
+
+    /// Get the members of the channel or group that have a role. Direct messages have no roles.
+    pub fn roles(&self, rocketchat_api: &RocketchatApi, room_type: RoomType) -> Result<Vec<MemberRoles>> {
+        match room_type {
+            RoomType::Channel => rocketchat_api.channels_roles(&self.id),
+            RoomType::Group => rocketchat_api.groups_roles(&self.id),
+            RoomType::DirectMessage => Ok(Vec::new()),
+        }
+    }
+
+    /// Grant a user a role in the channel or group. Direct messages have no roles.
+    pub fn add_role(
+        &self,
+        rocketchat_api: &RocketchatApi,
+        room_type: RoomType,
+        rocketchat_user_id: &str,
+        role: Role,
+    ) -> Result<()> {
+        match room_type {
+            RoomType::Channel => rocketchat_api.channels_add_role(&self.id, rocketchat_user_id, role),
+            RoomType::Group => rocketchat_api.groups_add_role(&self.id, rocketchat_user_id, role),
+            RoomType::DirectMessage => Ok(()),
+        }
+    }
+
+    /// Revoke a role of a user in the channel or group. Direct messages have no roles.
+    pub fn remove_role(
+        &self,
+        rocketchat_api: &RocketchatApi,
+        room_type: RoomType,
+        rocketchat_user_id: &str,
+        role: Role,
+    ) -> Result<()> {
+        match room_type {
+            RoomType::Channel => rocketchat_api.channels_remove_role(&self.id, rocketchat_user_id, role),
+            RoomType::Group => rocketchat_api.groups_remove_role(&self.id, rocketchat_user_id, role),
+            RoomType::DirectMessage => Ok(()),
+        }
+    }
+
+    /// Indicates if the channel or group is bridged for a given user.
+    pub fn is_bridged_for_user(&self, connection: &SqliteConnection, user_id: &UserId) -> Result<bool> {
+        match self.bridged_matrix_id(connection)? {
             Some(room_id) => {
                 let room = Room::new(self.config, self.logger, self.matrix_api, room_id);
                 let is_user_in_room = room.user_ids(None)?.iter().any(|id| id == user_id);
@@ -116,9 +289,27 @@ impl<'a> RocketchatRoom<'a> {
         format!("{}#{}#{}", self.config.sender_localpart, self.server_id, self.id)
     }
 
+    /// Build the Matrix room alias id that users can query to join a public Rocket.Chat channel by
+    /// its name. The homeserver asks the application service for unknown aliases, see `Rooms`.
+    pub fn build_portal_alias_id(config: &Config, server_id: &str, channel_name: &str) -> Result<RoomAliasId> {
+        let alias_id = format!("#{}_{}_{}:{}", config.sender_localpart, server_id, channel_name, config.hs_domain);
+        let room_alias_id =
+            RoomAliasId::try_from(alias_id.as_ref()).chain_err(|| ErrorKind::InvalidRoomAliasId(alias_id.clone()))?;
+        Ok(room_alias_id)
+    }
+
     /// Gets the Matrix room ID for a Rocket.Chat channel or group ID and a Rocket.Chat server.
     pub fn matrix_id(&self) -> Result<Option<RoomId>> {
         let room_alias_id = self.build_room_alias_id()?;
         self.matrix_api.get_room_alias(room_alias_id)
     }
+
+    /// Gets the Matrix room ID for a Rocket.Chat channel or group, this includes existing Matrix rooms
+    /// that were plumbed to the channel or group.
+    pub fn bridged_matrix_id(&self, connection: &SqliteConnection) -> Result<Option<RoomId>> {
+        match PlumbedRoom::find_by_rocketchat_channel_id(connection, self.server_id, &self.id)? {
+            Some(plumbed_room) => Ok(Some(plumbed_room.matrix_room_id)),
+            None => self.matrix_id(),
+        }
+    }
 }
@@ -1,3 +1,5 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
 use diesel;
 use diesel::prelude::*;
 use diesel::sqlite::SqliteConnection;
@@ -9,8 +11,12 @@ use api::{MatrixApi, RocketchatApi};
 use config::Config;
 use errors::*;
 use handlers::matrix::CommandHandler;
-use models::schema::{rocketchat_servers, users_on_rocketchat_servers};
-use models::{Room, UserOnRocketchatServer};
+use i18n::*;
+use models::schema::{
+    bridged_rooms, login_tokens, plumbed_rooms, recorded_errors, relay_rooms, rocketchat_servers, sent_rocketchat_messages,
+    users_on_rocketchat_servers,
+};
+use models::{Room, TokenKey, UserOnRocketchatServer};
 
 /// A Rocket.Chat server.
 #[derive(Associations, Debug, Identifiable, Queryable)]
@@ -20,12 +26,21 @@ pub struct RocketchatServer {
     pub id: String,
     /// The URL to connect to the Rocket.Chat server
     pub rocketchat_url: String,
-    /// The token to identify requests from the Rocket.Chat server
+    /// The token to identify requests from the Rocket.Chat server, it's encrypted in the database
     pub rocketchat_token: Option<String>,
     /// created timestamp
     pub created_at: String,
     /// updated timestamp
     pub updated_at: String,
+    /// The Matrix user that connected the server, it's allowed to manage the server
+    pub created_by: Option<UserId>,
+    /// Time when the last message from the Rocket.Chat server was forwarded to Matrix in seconds since UNIX_EPOCH,
+    /// it's tracked for the whole server and not for each user
+    pub last_message_received: i64,
+    /// The Matrix space that contains the bridged rooms of the server
+    pub space_room_id: Option<RoomId>,
+    /// Keyed hash of the token, the encrypted token cannot be used to look up the server
+    pub rocketchat_token_hash: Option<String>,
 }
 
 /// A new `Room`, not yet saved.
@@ -38,29 +53,69 @@ pub struct NewRocketchatServer<'a> {
     pub rocketchat_url: &'a str,
     /// The token to identify reuqests from the Rocket.Chat server
     pub rocketchat_token: Option<&'a str>,
+    /// The Matrix user that connected the server
+    pub created_by: Option<UserId>,
 }
 
 /// Credentials to perform a login on the Rocket.Chat server. The `user_id` is used to find
-/// the corresponding matrix user.
+/// the corresponding matrix user. The login is either done with the username and the password or
+/// with the Rocket.Chat user ID and a personal access token.
 #[derive(Serialize, Deserialize)]
 pub struct Credentials {
     /// The users unique id on the Matrix homeserver
     pub user_id: UserId,
     /// The username on the Rocket.Chat server
+    #[serde(default)]
     pub rocketchat_username: String,
     /// The password on the Rocket.Chat server
+    #[serde(default)]
     pub password: String,
+    /// The users unique id on the Rocket.Chat server, only used for personal access tokens
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rocketchat_user_id: Option<String>,
+    /// A personal access token that was created on the Rocket.Chat server
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub personal_access_token: Option<String>,
+    /// The two-factor authentication code, only needed if the user enabled two-factor authentication
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
     /// The URL of the Rocket.Chat server on which the user wants to login
     pub rocketchat_url: String,
 }
 
+/// A login request that is sent to the login endpoint of the application service. The token is
+/// issued to the user in the admin room and can only be used once.
+#[derive(Serialize, Deserialize)]
+pub struct LoginRequest {
+    /// The one-time login token
+    pub token: String,
+    /// Credentials to perform the login on the Rocket.Chat server
+    #[serde(flatten)]
+    pub credentials: Credentials,
+}
+
 impl RocketchatServer {
     /// Insert a `RocketchatServer`.
     pub fn insert(connection: &SqliteConnection, new_rocketchat_server: &NewRocketchatServer) -> Result<RocketchatServer> {
-        diesel::insert_into(rocketchat_servers::table)
-            .values(new_rocketchat_server)
-            .execute(connection)
-            .chain_err(|| ErrorKind::DBInsertError)?;
+        let rocketchat_token = TokenKey::encrypt_with_current(new_rocketchat_server.rocketchat_token.map(String::from))?;
+        let encrypted_rocketchat_server = NewRocketchatServer {
+            id: new_rocketchat_server.id,
+            rocketchat_url: new_rocketchat_server.rocketchat_url,
+            rocketchat_token: rocketchat_token.as_ref().map(String::as_str),
+            created_by: new_rocketchat_server.created_by.clone(),
+        };
+        let rocketchat_token_hash = new_rocketchat_server.rocketchat_token.map(TokenKey::hash_with_current).transpose()?;
+        connection.transaction(|| {
+            diesel::insert_into(rocketchat_servers::table)
+                .values(&encrypted_rocketchat_server)
+                .execute(connection)
+                .chain_err(|| ErrorKind::DBInsertError)?;
+            diesel::update(rocketchat_servers::table.find(new_rocketchat_server.id))
+                .set(rocketchat_servers::rocketchat_token_hash.eq(rocketchat_token_hash))
+                .execute(connection)
+                .chain_err(|| ErrorKind::DBUpdateError)?;
+            Ok(())
+        })?;
 
         let server = RocketchatServer::find(connection, new_rocketchat_server.rocketchat_url)?;
         Ok(server)
@@ -69,38 +124,40 @@ impl RocketchatServer {
     /// Find a `RocketchatServer` by its URL, return an error if the `RocketchatServer` is not
     /// found.
     pub fn find(connection: &SqliteConnection, url: &str) -> Result<RocketchatServer> {
-        let server = rocketchat_servers::table
+        let server: RocketchatServer = rocketchat_servers::table
             .filter(rocketchat_servers::rocketchat_url.eq(url))
             .first(connection)
             .chain_err(|| ErrorKind::DBSelectError)?;
-        Ok(server)
+        server.decrypt()
     }
 
     /// Find a `RocketchatServer` by its ID.
     pub fn find_by_id(connection: &SqliteConnection, id: &str) -> Result<Option<RocketchatServer>> {
-        let rocketchat_servers = rocketchat_servers::table
+        let rocketchat_servers: Vec<RocketchatServer> = rocketchat_servers::table
             .filter(rocketchat_servers::id.eq(id))
             .load(connection)
             .chain_err(|| ErrorKind::DBSelectError)?;
-        Ok(rocketchat_servers.into_iter().next())
+        rocketchat_servers.into_iter().next().map(RocketchatServer::decrypt).transpose()
     }
 
     /// Find a `RocketchatServer` by its URL.
     pub fn find_by_url(connection: &SqliteConnection, url: &str) -> Result<Option<RocketchatServer>> {
-        let rocketchat_servers = rocketchat_servers::table
+        let rocketchat_servers: Vec<RocketchatServer> = rocketchat_servers::table
             .filter(rocketchat_servers::rocketchat_url.eq(url))
             .load(connection)
             .chain_err(|| ErrorKind::DBSelectError)?;
-        Ok(rocketchat_servers.into_iter().next())
+        rocketchat_servers.into_iter().next().map(RocketchatServer::decrypt).transpose()
     }
 
     /// Find a `RocketchatServer` bit its token.
     pub fn find_by_token(connection: &SqliteConnection, token: &str) -> Result<Option<RocketchatServer>> {
-        let rocketchat_servers = rocketchat_servers::table
-            .filter(rocketchat_servers::rocketchat_token.eq(Some(token)))
+        // the encrypted tokens cannot be compared in the database, because each of them has its own nonce
+        let rocketchat_token_hash = TokenKey::hash_with_current(token)?;
+        let rocketchat_servers: Vec<RocketchatServer> = rocketchat_servers::table
+            .filter(rocketchat_servers::rocketchat_token_hash.eq(rocketchat_token_hash))
             .load(connection)
             .chain_err(|| ErrorKind::DBSelectError)?;
-        Ok(rocketchat_servers.into_iter().next())
+        rocketchat_servers.into_iter().next().map(RocketchatServer::decrypt).transpose()
     }
 
     /// Get all connected servers.
@@ -109,7 +166,165 @@ impl RocketchatServer {
             .filter(rocketchat_servers::rocketchat_token.is_not_null())
             .load::<RocketchatServer>(connection)
             .chain_err(|| ErrorKind::DBSelectError)?;
-        Ok(rocketchat_servers)
+        rocketchat_servers.into_iter().map(RocketchatServer::decrypt).collect()
+    }
+
+    /// Encrypt the tokens of all servers with a new key and update their hashes, see
+    /// `TokenKey::reencrypt_tokens`. Returns the number of updated tokens.
+    pub fn reencrypt_tokens(
+        connection: &SqliteConnection,
+        decrypt: &Fn(&str) -> Result<String>,
+        encrypt: &Fn(&str) -> Result<String>,
+        hash: &Fn(&str) -> String,
+    ) -> Result<usize> {
+        let rocketchat_servers = rocketchat_servers::table
+            .filter(rocketchat_servers::rocketchat_token.is_not_null())
+            .load::<RocketchatServer>(connection)
+            .chain_err(|| ErrorKind::DBSelectError)?;
+
+        for server in &rocketchat_servers {
+            let rocketchat_token = decrypt(server.rocketchat_token.as_ref().map(String::as_str).unwrap_or_default())?;
+            diesel::update(rocketchat_servers::table.find(&server.id))
+                .set((
+                    rocketchat_servers::rocketchat_token.eq(encrypt(&rocketchat_token)?),
+                    rocketchat_servers::rocketchat_token_hash.eq(hash(&rocketchat_token)),
+                ))
+                .execute(connection)
+                .chain_err(|| ErrorKind::DBUpdateError)?;
+        }
+
+        Ok(rocketchat_servers.len())
+    }
+
+    /// Store the hashes of tokens that were saved before the hashes were introduced, the servers
+    /// cannot be found by their token otherwise. Returns the number of hashed tokens.
+    pub fn hash_missing_tokens(connection: &SqliteConnection) -> Result<usize> {
+        let rocketchat_servers = rocketchat_servers::table
+            .filter(rocketchat_servers::rocketchat_token.is_not_null())
+            .filter(rocketchat_servers::rocketchat_token_hash.is_null())
+            .load::<RocketchatServer>(connection)
+            .chain_err(|| ErrorKind::DBSelectError)?;
+
+        for server in &rocketchat_servers {
+            let rocketchat_token = TokenKey::decrypt_with_current(server.rocketchat_token.clone())?.unwrap_or_default();
+            diesel::update(rocketchat_servers::table.find(&server.id))
+                .set(rocketchat_servers::rocketchat_token_hash.eq(TokenKey::hash_with_current(&rocketchat_token)?))
+                .execute(connection)
+                .chain_err(|| ErrorKind::DBUpdateError)?;
+        }
+
+        Ok(rocketchat_servers.len())
+    }
+
+    /// Update the token that identifies requests from the Rocket.Chat server.
+    pub fn set_rocketchat_token(&mut self, connection: &SqliteConnection, rocketchat_token: Option<String>) -> Result<()> {
+        let encrypted_rocketchat_token = TokenKey::encrypt_with_current(rocketchat_token.clone())?;
+        let rocketchat_token_hash = rocketchat_token.as_ref().map(|token| TokenKey::hash_with_current(token)).transpose()?;
+        diesel::update(rocketchat_servers::table.find(&self.id))
+            .set((
+                rocketchat_servers::rocketchat_token.eq(encrypted_rocketchat_token),
+                rocketchat_servers::rocketchat_token_hash.eq(&rocketchat_token_hash),
+            ))
+            .execute(connection)
+            .chain_err(|| ErrorKind::DBUpdateError)?;
+        self.rocketchat_token = rocketchat_token;
+        self.rocketchat_token_hash = rocketchat_token_hash;
+        Ok(())
+    }
+
+    /// Update the URL of the Rocket.Chat server.
+    pub fn set_rocketchat_url(&mut self, connection: &SqliteConnection, rocketchat_url: String) -> Result<()> {
+        diesel::update(rocketchat_servers::table.find(&self.id))
+            .set(rocketchat_servers::rocketchat_url.eq(&rocketchat_url))
+            .execute(connection)
+            .chain_err(|| ErrorKind::DBUpdateError)?;
+        self.rocketchat_url = rocketchat_url;
+        Ok(())
+    }
+
+    /// Update the time when the last message from the Rocket.Chat server was forwarded to Matrix.
+    pub fn set_last_message_received(&self, connection: &SqliteConnection) -> Result<()> {
+        let last_message_received =
+            SystemTime::now().duration_since(UNIX_EPOCH).chain_err(|| ErrorKind::InternalServerError)?.as_secs() as i64;
+        diesel::update(rocketchat_servers::table.find(&self.id))
+            .set(rocketchat_servers::last_message_received.eq(last_message_received))
+            .execute(connection)
+            .chain_err(|| ErrorKind::DBUpdateError)?;
+        Ok(())
+    }
+
+    /// Get the Matrix space that contains the bridged rooms of the server. The space is created by
+    /// the bot user if it doesn't exist yet and the user that connected the server is invited.
+    pub fn find_or_create_space(
+        &mut self,
+        config: &Config,
+        connection: &SqliteConnection,
+        logger: &Logger,
+        matrix_api: &MatrixApi,
+    ) -> Result<RoomId> {
+        if let Some(ref space_room_id) = self.space_room_id {
+            return Ok(space_room_id.clone());
+        }
+
+        let bot_user_id = config.matrix_bot_user_id()?;
+        let name = t!(["defaults", "space_display_name"]).with_vars(vec![("rocketchat_url", self.rocketchat_url.clone())]);
+        let space_room_id = matrix_api.create_space(name.l(DEFAULT_LANGUAGE), &bot_user_id)?;
+        if config.publish_rooms_to_directory.unwrap_or(false) {
+            matrix_api.set_room_directory_visibility(&self.id, space_room_id.clone(), true)?;
+        }
+
+        if let Some(ref created_by) = self.created_by {
+            matrix_api.invite(space_room_id.clone(), created_by.clone(), bot_user_id)?;
+        }
+
+        diesel::update(rocketchat_servers::table.find(&self.id))
+            .set(rocketchat_servers::space_room_id.eq(Some(space_room_id.clone())))
+            .execute(connection)
+            .chain_err(|| ErrorKind::DBUpdateError)?;
+        self.space_room_id = Some(space_room_id.clone());
+
+        info!(logger, "Created space {} for Rocket.Chat server {}", space_room_id, self.id);
+        Ok(space_room_id)
+    }
+
+    /// Delete the server together with the users, relay rooms, login tokens and recorded errors
+    /// that belong to it.
+    pub fn delete(&self, connection: &SqliteConnection) -> Result<()> {
+        connection.transaction(|| {
+            diesel::delete(
+                users_on_rocketchat_servers::table.filter(users_on_rocketchat_servers::rocketchat_server_id.eq(&self.id)),
+            )
+            .execute(connection)
+            .chain_err(|| ErrorKind::DBDeleteError)?;
+            diesel::delete(relay_rooms::table.filter(relay_rooms::rocketchat_server_id.eq(&self.id)))
+                .execute(connection)
+                .chain_err(|| ErrorKind::DBDeleteError)?;
+            diesel::delete(plumbed_rooms::table.filter(plumbed_rooms::rocketchat_server_id.eq(&self.id)))
+                .execute(connection)
+                .chain_err(|| ErrorKind::DBDeleteError)?;
+            diesel::delete(bridged_rooms::table.filter(bridged_rooms::rocketchat_server_id.eq(&self.id)))
+                .execute(connection)
+                .chain_err(|| ErrorKind::DBDeleteError)?;
+            diesel::delete(login_tokens::table.filter(login_tokens::rocketchat_server_id.eq(&self.id)))
+                .execute(connection)
+                .chain_err(|| ErrorKind::DBDeleteError)?;
+            diesel::delete(recorded_errors::table.filter(recorded_errors::rocketchat_server_id.eq(&self.id)))
+                .execute(connection)
+                .chain_err(|| ErrorKind::DBDeleteError)?;
+            diesel::delete(sent_rocketchat_messages::table.filter(sent_rocketchat_messages::rocketchat_server_id.eq(&self.id)))
+                .execute(connection)
+                .chain_err(|| ErrorKind::DBDeleteError)?;
+            diesel::delete(rocketchat_servers::table.find(&self.id))
+                .execute(connection)
+                .chain_err(|| ErrorKind::DBDeleteError)?;
+            Ok(())
+        })
+    }
+
+    /// Returns true if the user is allowed to manage the server, which is the case for the user
+    /// that connected the server and for the bridge admins.
+    pub fn is_manageable_by(&self, config: &Config, user_id: &UserId) -> bool {
+        self.created_by.as_ref() == Some(user_id) || config.is_bridge_admin(user_id)
     }
 
     /// Perform a login request on the Rocket.Chat server.
@@ -126,10 +341,21 @@ impl RocketchatServer {
         let mut user_on_rocketchat_server = UserOnRocketchatServer::find(connection, &credentials.user_id, self.id.clone())?;
         let rocketchat_api = RocketchatApi::new(self.rocketchat_url.clone(), logger.clone())?;
 
-        let (user_id, auth_token) = rocketchat_api.login(&credentials.rocketchat_username, &credentials.password)?;
+        let (user_id, auth_token) = match (&credentials.rocketchat_user_id, &credentials.personal_access_token) {
+            (Some(user_id), Some(personal_access_token)) => {
+                let rocketchat_api = rocketchat_api.with_credentials(user_id.clone(), personal_access_token.clone());
+                self.validate_personal_access_token(rocketchat_api.as_ref(), user_id)?;
+                (user_id.clone(), personal_access_token.clone())
+            }
+            _ => {
+                let code = credentials.code.as_ref().map(String::as_str);
+                rocketchat_api.login(&credentials.rocketchat_username, &credentials.password, code)?
+            }
+        };
         user_on_rocketchat_server.set_credentials(connection, Some(user_id.clone()), Some(auth_token.clone()))?;
 
         if let Some(room_id) = admin_room_id {
+            user_on_rocketchat_server.set_admin_room_id(connection, Some(room_id.clone()))?;
             let room = Room::new(config, logger, matrix_api, room_id.clone());
             let bot_user_id = config.matrix_bot_user_id()?;
             let as_url = config.as_url.clone();
@@ -142,14 +368,105 @@ impl RocketchatServer {
         Ok(())
     }
 
+    /// Clear the credentials of a user after the Rocket.Chat server rejected the stored auth token,
+    /// because the session expired or was revoked. The user is asked to login again in the admin
+    /// room, messages are not forwarded until the user is logged in again.
+    pub fn expire_session(
+        &self,
+        config: &Config,
+        connection: &SqliteConnection,
+        logger: &Logger,
+        matrix_api: &MatrixApi,
+        matrix_user_id: &UserId,
+    ) -> Result<()> {
+        let mut user_on_rocketchat_server = UserOnRocketchatServer::find(connection, matrix_user_id, self.id.clone())?;
+        let rocketchat_user_id = user_on_rocketchat_server.rocketchat_user_id.clone();
+        user_on_rocketchat_server.set_credentials(connection, rocketchat_user_id, None)?;
+        info!(logger, "Session of user {} on Rocket.Chat server {} expired", matrix_user_id, self.rocketchat_url);
+
+        let room_id = match user_on_rocketchat_server.admin_room_id.clone() {
+            Some(room_id) => room_id,
+            None => match self.find_admin_room_id(config, connection, logger, matrix_api, matrix_user_id)? {
+                Some(room_id) => {
+                    user_on_rocketchat_server.set_admin_room_id(connection, Some(room_id.clone()))?;
+                    room_id
+                }
+                None => {
+                    warn!(logger, "No admin room for user {} found, cannot ask the user to login again", matrix_user_id);
+                    return Ok(());
+                }
+            },
+        };
+
+        let bot_user_id = config.matrix_bot_user_id()?;
+        let message = t!(["admin_room", "session_expired"]).with_vars(vec![("rocketchat_url", self.rocketchat_url.clone())]);
+        matrix_api.send_text_message(room_id, bot_user_id, message.l(DEFAULT_LANGUAGE))
+    }
+
+    // Users that logged in before the admin room was recorded don't have an admin room ID, their admin
+    // room is found via its topic, which contains the URL of the Rocket.Chat server. Rooms that cannot
+    // be checked are skipped, so that one broken room doesn't prevent finding the admin room.
+    fn find_admin_room_id(
+        &self,
+        config: &Config,
+        connection: &SqliteConnection,
+        logger: &Logger,
+        matrix_api: &MatrixApi,
+        matrix_user_id: &UserId,
+    ) -> Result<Option<RoomId>> {
+        let bot_user_id = config.matrix_bot_user_id()?;
+        for room_id in matrix_api.get_joined_rooms(bot_user_id)? {
+            let room = Room::new(config, logger, matrix_api, room_id.clone());
+            match self.is_admin_room_of_user(connection, matrix_api, &room, matrix_user_id) {
+                Ok(true) => return Ok(Some(room_id)),
+                Ok(false) => continue,
+                Err(err) => {
+                    warn!(logger, "Could not check if room {} is the admin room of {}: {}", room_id, matrix_user_id, err);
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn is_admin_room_of_user(
+        &self,
+        connection: &SqliteConnection,
+        matrix_api: &MatrixApi,
+        room: &Room,
+        matrix_user_id: &UserId,
+    ) -> Result<bool> {
+        if matrix_api.get_room_topic(room.id.clone())? != Some(self.rocketchat_url.clone()) {
+            return Ok(false);
+        }
+
+        Ok(room.is_admin_room(connection)? && room.user_ids(None)?.contains(matrix_user_id))
+    }
+
+    // Personal access tokens are used as auth token, the token is valid if the Rocket.Chat server
+    // accepts it for the given user ID.
+    fn validate_personal_access_token(&self, rocketchat_api: &RocketchatApi, rocketchat_user_id: &str) -> Result<()> {
+        let error_msg = match rocketchat_api.me() {
+            Ok(ref user) if user.id == rocketchat_user_id => return Ok(()),
+            Ok(user) => format!("Token belongs to user {}, expected {}", user.id, rocketchat_user_id),
+            Err(err) => err.to_string(),
+        };
+
+        bail_error!(
+            ErrorKind::RocketchatAuthenticationFailed(error_msg),
+            t!(["errors", "invalid_personal_access_token"]).with_vars(vec![("rocketchat_url", self.rocketchat_url.clone())])
+        );
+    }
+
     /// Get all users that are connected to this Rocket.Chat server.
     pub fn logged_in_users_on_rocketchat_server(&self, connection: &SqliteConnection) -> Result<Vec<UserOnRocketchatServer>> {
-        let users_on_rocketchat_server: Vec<UserOnRocketchatServer> = users_on_rocketchat_servers::table
-            .filter(users_on_rocketchat_servers::rocketchat_server_id.eq(&self.id))
-            .filter(users_on_rocketchat_servers::rocketchat_auth_token.is_not_null())
-            .load(connection)
-            .chain_err(|| ErrorKind::DBSelectError)?;
-        Ok(users_on_rocketchat_server)
+        UserOnRocketchatServer::find_logged_in(connection, &self.id)
+    }
+
+    // The token is stored encrypted if a key is configured, see `TokenKey`.
+    fn decrypt(mut self) -> Result<RocketchatServer> {
+        self.rocketchat_token = TokenKey::decrypt_with_current(self.rocketchat_token.take())?;
+        Ok(self)
     }
 }
 
@@ -9,14 +9,17 @@ use ruma_events::room::member::MembershipState;
 use ruma_identifiers::{RoomAliasId, RoomId, UserId};
 use slog::Logger;
 
+use api::rocketchat::{MemberRoles, Role, User};
 use api::{MatrixApi, RocketchatApi};
 use config::Config;
 use errors::*;
 use i18n::*;
-use models::{RocketchatServer, UserOnRocketchatServer, VirtualUser};
+use models::{BridgedRoom, PlumbedRoom, RocketchatServer, RoomType, UserOnRocketchatServer, VirtualUser};
 
 /// The delay in milliseconds between two API requests (to not DOS the server)
 pub const API_QUERY_DELAY: u64 = 500;
+/// The power level a user needs to administrate a room
+pub const ROOM_ADMIN_POWER_LEVEL: i64 = 100;
 
 lazy_static! {
     /// Direct room cache
@@ -48,11 +51,13 @@ impl<'a> Room<'a> {
         alias: Option<String>,
         display_name: &Option<String>,
         creator_id: &UserId,
-        invited_user_id: &UserId,
+        invited_user_id: Option<&UserId>,
     ) -> Result<RoomId> {
         let room_id = matrix_api.create_room(display_name.clone(), alias, creator_id)?;
         matrix_api.set_default_powerlevels(room_id.clone(), creator_id.clone())?;
-        matrix_api.invite(room_id.clone(), invited_user_id.clone(), creator_id.clone())?;
+        if let Some(invited_user_id) = invited_user_id {
+            matrix_api.invite(room_id.clone(), invited_user_id.clone(), creator_id.clone())?;
+        }
 
         Ok(room_id)
     }
@@ -60,6 +65,7 @@ impl<'a> Room<'a> {
     /// Get an existing direct message room.
     pub fn get_dm(
         config: &'a Config,
+        connection: &'a SqliteConnection,
         logger: &'a Logger,
         matrix_api: &'a MatrixApi,
         channel_id: String,
@@ -67,7 +73,7 @@ impl<'a> Room<'a> {
         receiver_id: &UserId,
     ) -> Result<Option<Room<'a>>> {
         // If the user does not exist yet, there is no existing direct message room
-        if matrix_api.get_display_name(sender_id.clone())?.is_none() {
+        if !VirtualUser::new(config, connection, logger, matrix_api).is_registered(sender_id)? {
             return Ok(None);
         }
 
@@ -99,6 +105,74 @@ impl<'a> Room<'a> {
         Ok(None)
     }
 
+    /// Create a direct message room for a Rocket.Chat direct message channel. The room is created
+    /// by the virtual user of the Rocket.Chat user and the Matrix user is invited into it.
+    pub fn create_dm(
+        config: &'a Config,
+        logger: &'a Logger,
+        matrix_api: &'a MatrixApi,
+        channel_id: String,
+        sender_id: &UserId,
+        sender_name: &str,
+        receiver_id: &UserId,
+    ) -> Result<Room<'a>> {
+        let room_display_name_suffix = t!(["defaults", "direct_message_room_display_name_suffix"]).l(DEFAULT_LANGUAGE);
+        let display_name = Some(format!("{} {}", sender_name, room_display_name_suffix));
+        let room_id = Room::create(matrix_api, None, &display_name, sender_id, Some(receiver_id))?;
+
+        // invite the bot user into the direct message room to be able to read the room state
+        // the bot will leave as soon as the AS gets the join event
+        let invitee_id = config.matrix_bot_user_id()?;
+        matrix_api.invite(room_id.clone(), invitee_id, sender_id.clone())?;
+        info!(logger, "Direct message room {} successfully created", &room_id);
+
+        let room = Room::new(config, logger, matrix_api, room_id);
+        room.add_to_cache(channel_id, receiver_id);
+        Ok(room)
+    }
+
+    /// Start a direct message between a Matrix user and a Rocket.Chat user. The direct message is
+    /// created on the Rocket.Chat server and an existing direct message room on Matrix is reused.
+    pub fn start_dm(
+        config: &'a Config,
+        connection: &'a SqliteConnection,
+        logger: &'a Logger,
+        matrix_api: &'a MatrixApi,
+        rocketchat_api: &RocketchatApi,
+        rocketchat_server_id: &str,
+        rocketchat_username: &str,
+        receiver_id: &UserId,
+    ) -> Result<Room<'a>> {
+        let user = match rocketchat_api.users_info(rocketchat_username) {
+            Ok(user) => user,
+            Err(err) => {
+                if err.is_rocketchat_authentication_failure() {
+                    return Err(err);
+                }
+
+                debug!(logger, "Could not get user info for {}: {}", rocketchat_username, err);
+                bail_error!(
+                    ErrorKind::RocketchatUserNotFound(rocketchat_username.to_string()),
+                    t!(["errors", "rocketchat_user_not_found"])
+                        .with_vars(vec![("rocketchat_username", rocketchat_username.to_string())])
+                );
+            }
+        };
+
+        let direct_message = rocketchat_api.im_create(&user.username)?;
+        let virtual_user = VirtualUser::new(config, connection, logger, matrix_api);
+        let sender_id = virtual_user.find_or_register(rocketchat_server_id, &user.id, &user.username)?;
+
+        match Room::get_dm(config, connection, logger, matrix_api, direct_message.id.clone(), &sender_id, receiver_id)? {
+            Some(room) => {
+                debug!(logger, "Direct message room {} with {} exists already", room.id, rocketchat_username);
+                room.join_user(receiver_id.clone(), sender_id)?;
+                Ok(room)
+            }
+            None => Room::create_dm(config, logger, matrix_api, direct_message.id, &sender_id, &user.username, receiver_id),
+        }
+    }
+
     /// Bridges a room that is already bridged (for other users) for a new user.
     pub fn bridge_for_user(&self, user_id: UserId, rocketchat_channel_name: String) -> Result<()> {
         debug!(self.logger, "Briding existing room, Rocket.Chat channel: {}", rocketchat_channel_name);
@@ -157,12 +231,17 @@ impl<'a> Room<'a> {
     }
 
     /// Checks if a room is an admin room.
-    pub fn is_admin_room(&self) -> Result<bool> {
+    pub fn is_admin_room(&self, connection: &SqliteConnection) -> Result<bool> {
         // it cannot be an admin room if the bot user does not have access to it
         if !self.is_accessible_by_bot()? {
             return Ok(false);
         }
 
+        // plumbed rooms are not created by the application service, but they are no admin rooms either
+        if PlumbedRoom::find_by_matrix_room_id(connection, &self.id)?.is_some() {
+            return Ok(false);
+        }
+
         let matrix_bot_user_id = self.config.matrix_bot_user_id()?;
         let user_ids = self.user_ids(None)?;
         let bot_user_in_room = user_ids.iter().any(|id| id == &matrix_bot_user_id);
@@ -182,6 +261,32 @@ impl<'a> Room<'a> {
         Ok(Some(rocketchat_channel_id.to_string()))
     }
 
+    /// Gets the Rocket.Chat server and the channel id for a room that is bridged to Matrix, this
+    /// includes plumbed rooms and direct messages that were started by inviting a virtual user.
+    pub fn rocketchat_channel(&self, connection: &SqliteConnection) -> Result<Option<(RocketchatServer, String)>> {
+        // plumbed rooms are not associated with an alias of the application service
+        if let Some(plumbed_room) = PlumbedRoom::find_by_matrix_room_id(connection, &self.id)? {
+            let server = RocketchatServer::find_by_id(connection, &plumbed_room.rocketchat_server_id)?;
+            return Ok(server.map(|server| (server, plumbed_room.rocketchat_channel_id)));
+        }
+
+        // neither are direct messages that were started on Matrix, their channel is recorded
+        if let Some(bridged_room) = BridgedRoom::find_by_matrix_room_id(connection, &self.id)? {
+            if bridged_room.room_type() == Some(RoomType::DirectMessage) {
+                let server = RocketchatServer::find_by_id(connection, &bridged_room.rocketchat_server_id)?;
+                return Ok(server.map(|server| (server, bridged_room.rocketchat_channel_id)));
+            }
+        }
+
+        if let Some(channel_id) = self.rocketchat_channel_id()? {
+            if let Some(server) = self.rocketchat_server(connection)? {
+                return Ok(Some((server, channel_id)));
+            }
+        }
+
+        Ok(None)
+    }
+
     /// Checks if an admin room is connected to a Rocket.Chat server.
     pub fn is_connected(&self, connection: &SqliteConnection) -> Result<bool> {
         match self.matrix_api.get_room_topic(self.id.clone())? {
@@ -193,18 +298,25 @@ impl<'a> Room<'a> {
         }
     }
 
-    /// Find the Matrix user in a direct message room
+    /// Find the Matrix user in a direct message room. Returns `None` for rooms with more than two
+    /// members, they belong to direct messages with multiple users that have no single Matrix user.
     pub fn direct_message_matrix_user(&self) -> Result<Option<UserId>> {
         let room_creator_id = self.matrix_api.get_room_creator(self.id.clone())?;
         let user_ids = self.user_ids(Some(room_creator_id))?;
         if user_ids.len() > 2 {
-            bail_error!(ErrorKind::GettingMatrixUserForDirectMessageRoomError);
+            return Ok(None);
         }
 
         let user_id = user_ids.into_iter().find(|id| !self.config.is_application_service_virtual_user(id));
         Ok(user_id)
     }
 
+    /// Checks if a user has the power level of a room admin.
+    pub fn is_room_admin(&self, user_id: &UserId) -> Result<bool> {
+        let power_level = self.matrix_api.get_user_power_level(self.id.clone(), user_id.clone())?;
+        Ok(power_level >= ROOM_ADMIN_POWER_LEVEL)
+    }
+
     /// Determine if the bot user has access to a room.
     pub fn is_accessible_by_bot(&self) -> Result<bool> {
         self.matrix_api.is_room_accessible_by_bot(self.id.clone())
@@ -249,6 +361,11 @@ impl<'a> Room<'a> {
         };
 
         let user_on_rocketchat_server = UserOnRocketchatServer::find(conn, user_matrix_id, server_id)?;
+        if !user_on_rocketchat_server.is_logged_in() {
+            debug!(self.logger, "The receiver {} of this direct message is not logged in", user_matrix_id);
+            return Ok(None);
+        }
+
         let rocketchat_user_id = user_on_rocketchat_server.rocketchat_user_id.clone().unwrap_or_default();
         let rocketchat_auth_token = user_on_rocketchat_server.rocketchat_auth_token.clone().unwrap_or_default();
         let rocketchat_api = RocketchatApi::new(server.rocketchat_url.clone(), self.logger.clone())?
@@ -257,7 +374,13 @@ impl<'a> Room<'a> {
         // It is safe to check if a direct channel ID contains the virtual users ID, because a user
         // can only have one direct message room with another user. Which means when the virtual user
         // ID is part of the channel name, the direct message channel with that user is found.
-        let direct_message_channels = rocketchat_api.dm_list()?;
+        let direct_message_channels = match rocketchat_api.dm_list() {
+            Err(ref err) if err.is_rocketchat_authentication_failure() => {
+                server.expire_session(self.config, conn, self.logger, self.matrix_api, user_matrix_id)?;
+                return Ok(None);
+            }
+            result => result?,
+        };
         for direct_message_channel in direct_message_channels {
             if direct_message_channel.id.to_lowercase().contains(&virtual_user_id) {
                 return Ok(Some((server, direct_message_channel.id.clone())));
@@ -286,13 +409,14 @@ impl<'a> Room<'a> {
     /// Join all users that are in a Rocket.Chat room to the Matrix room.
     pub fn join_all_rocketchat_users(
         &self,
+        connection: &SqliteConnection,
         rocketchat_api: &RocketchatApi,
         usernames: &[String],
         rocketchat_server_id: &str,
     ) -> Result<()> {
         debug!(self.logger, "Starting to add virtual users to room {}", self.id);
 
-        let virtual_user = VirtualUser::new(self.config, self.logger, self.matrix_api);
+        let virtual_user = VirtualUser::new(self.config, connection, self.logger, self.matrix_api);
 
         let bot_user_id = self.config.matrix_bot_user_id()?;
         for username in usernames.iter() {
@@ -307,6 +431,125 @@ impl<'a> Room<'a> {
         Ok(())
     }
 
+    /// Synchronize the virtual users in the room with the members of the Rocket.Chat channel. Members
+    /// that are missing are joined, virtual users of members that left the channel leave the room.
+    pub fn sync_rocketchat_users(
+        &self,
+        connection: &SqliteConnection,
+        members: &[User],
+        rocketchat_server_id: &str,
+    ) -> Result<()> {
+        debug!(self.logger, "Synchronizing virtual users of room {}", self.id);
+
+        let virtual_user = VirtualUser::new(self.config, connection, self.logger, self.matrix_api);
+        let bot_user_id = self.config.matrix_bot_user_id()?;
+        let user_ids = self.user_ids(Some(bot_user_id.clone()))?;
+
+        let mut member_user_ids = Vec::new();
+        for member in members {
+            let user_id = virtual_user.build_user_id(&member.id, rocketchat_server_id)?;
+            if !user_ids.contains(&user_id) {
+                let user_id = virtual_user.find_or_register(rocketchat_server_id, &member.id, &member.username)?;
+                self.join_user(user_id.clone(), bot_user_id.clone())?;
+            }
+            member_user_ids.push(user_id);
+        }
+
+        for user_id in user_ids {
+            let (server_id, _) = VirtualUser::rocketchat_server_and_user_id_from_matrix_id(&user_id);
+            let is_virtual_user_of_server =
+                self.config.is_application_service_virtual_user(&user_id) && server_id == rocketchat_server_id;
+            if is_virtual_user_of_server && !member_user_ids.contains(&user_id) {
+                debug!(self.logger, "Virtual user {} left the Rocket.Chat channel, leaving room {}", user_id, self.id);
+                self.matrix_api.leave_room(self.id.clone(), user_id)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Kick the Matrix users that are not members of a private Rocket.Chat group. Users that are not
+    /// logged in on the Rocket.Chat server cannot prove their membership and are kicked as well.
+    pub fn kick_users_without_group_membership(
+        &self,
+        connection: &SqliteConnection,
+        user_ids: Vec<UserId>,
+        members: &[User],
+        server: &RocketchatServer,
+    ) -> Result<()> {
+        let bot_user_id = self.config.matrix_bot_user_id()?;
+        let users_on_rocketchat_server =
+            UserOnRocketchatServer::find_by_matrix_user_ids(connection, user_ids.clone(), server.id.clone())?;
+
+        for user_id in user_ids.into_iter().filter(|id| !self.config.is_application_service_user(id)) {
+            let is_member = users_on_rocketchat_server
+                .iter()
+                .filter(|user| user.matrix_user_id == user_id && user.is_logged_in())
+                .filter_map(|user| user.rocketchat_user_id.as_ref())
+                .any(|rocketchat_user_id| members.iter().any(|member| &member.id == rocketchat_user_id));
+
+            if !is_member {
+                info!(self.logger, "Kicking user {} from room {}, not a member of the group", user_id, self.id);
+                let reason = t!(["errors", "rocketchat_group_membership_required"])
+                    .with_vars(vec![("rocketchat_url", server.rocketchat_url.clone())])
+                    .l(DEFAULT_LANGUAGE);
+                self.matrix_api.kick_user(self.id.clone(), user_id, bot_user_id.clone(), Some(reason))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Synchronize the power levels in the room with the roles of the members of the Rocket.Chat
+    /// channel. Members that use the bridge get the power level for their Matrix user, all other
+    /// members for their virtual user. Rocket.Chat doesn't send role changes to the webhook, so this
+    /// only happens when the room is bridged and when members join or leave the channel.
+    pub fn sync_rocketchat_roles(
+        &self,
+        connection: &SqliteConnection,
+        roles: &[MemberRoles],
+        rocketchat_server_id: &str,
+    ) -> Result<()> {
+        debug!(self.logger, "Synchronizing power levels of room {}", self.id);
+
+        let current_power_levels = self.matrix_api.get_user_power_levels(self.id.clone())?;
+
+        let mut power_levels = HashMap::new();
+        for member_roles in roles {
+            let user_id = self.matrix_user_id_for_rocketchat_user(connection, &member_roles.user.id, rocketchat_server_id)?;
+            let power_level = member_roles.role().map(Role::power_level).unwrap_or_default();
+            power_levels.insert(user_id, power_level);
+        }
+
+        // members without a role are not part of the roles, so users with elevated permissions that
+        // are missing lost their role
+        for (user_id, power_level) in &current_power_levels {
+            if *power_level > 0
+                && !power_levels.contains_key(user_id)
+                && self.is_rocketchat_user(connection, user_id, rocketchat_server_id)?
+            {
+                power_levels.insert(user_id.clone(), 0);
+            }
+        }
+
+        // plumbed rooms are owned by their Matrix admins, so Matrix users keep the permissions they
+        // were granted on Matrix
+        let is_plumbed_room = PlumbedRoom::find_by_matrix_room_id(connection, &self.id)?.is_some();
+        power_levels.retain(|user_id, power_level| {
+            let current_power_level = current_power_levels.get(user_id).cloned().unwrap_or_default();
+            let is_matrix_user = !self.config.is_application_service_virtual_user(user_id);
+            *power_level != current_power_level && !(is_plumbed_room && is_matrix_user && *power_level < current_power_level)
+        });
+
+        if power_levels.is_empty() {
+            return Ok(());
+        }
+
+        debug!(self.logger, "Updating power levels of {} users in room {}", power_levels.len(), self.id);
+        let bot_user_id = self.config.matrix_bot_user_id()?;
+        self.matrix_api.set_user_power_levels(self.id.clone(), bot_user_id, power_levels)
+    }
+
     /// Get all aliases fro a room.
     pub fn aliases(&self) -> Result<Vec<RoomAliasId>> {
         let bot_user_id = self.config.matrix_bot_user_id()?;
@@ -334,6 +577,38 @@ impl<'a> Room<'a> {
         UserOnRocketchatServer::find_by_matrix_user_ids(connection, user_ids, rocketchat_server_id)
     }
 
+    fn matrix_user_id_for_rocketchat_user(
+        &self,
+        connection: &SqliteConnection,
+        rocketchat_user_id: &str,
+        rocketchat_server_id: &str,
+    ) -> Result<UserId> {
+        let user_on_rocketchat_server = UserOnRocketchatServer::find_by_rocketchat_user_id(
+            connection,
+            rocketchat_server_id.to_string(),
+            rocketchat_user_id.to_string(),
+        )?;
+
+        match user_on_rocketchat_server.filter(|user| user.is_logged_in()) {
+            Some(user_on_rocketchat_server) => Ok(user_on_rocketchat_server.matrix_user_id),
+            None => {
+                let virtual_user = VirtualUser::new(self.config, connection, self.logger, self.matrix_api);
+                virtual_user.build_user_id(rocketchat_user_id, rocketchat_server_id)
+            }
+        }
+    }
+
+    fn is_rocketchat_user(&self, connection: &SqliteConnection, user_id: &UserId, rocketchat_server_id: &str) -> Result<bool> {
+        if self.config.is_application_service_virtual_user(user_id) {
+            let (server_id, _) = VirtualUser::rocketchat_server_and_user_id_from_matrix_id(user_id);
+            return Ok(server_id == rocketchat_server_id);
+        }
+
+        let user_on_rocketchat_server =
+            UserOnRocketchatServer::find_by_matrix_user_id(connection, user_id, rocketchat_server_id.to_string())?;
+        Ok(user_on_rocketchat_server.filter(|user| user.is_logged_in()).is_some())
+    }
+
     /// Add a room to the cache.
     /// This will speed-up future direct messages because the direct message room lookup is done via
     /// cache instead of going through the users rooms.
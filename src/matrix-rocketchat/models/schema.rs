@@ -7,6 +7,10 @@ table! {
         rocketchat_token -> Nullable<Text>,
         created_at -> Timestamp,
         updated_at -> Timestamp,
+        created_by -> Nullable<Text>,
+        last_message_received -> BigInt,
+        space_room_id -> Nullable<Text>,
+        rocketchat_token_hash -> Nullable<Text>,
     }
 }
 
@@ -19,5 +23,83 @@ table! {
         rocketchat_auth_token -> Nullable<Text>,
         created_at -> Timestamp,
         updated_at -> Timestamp,
+        matrix_access_token -> Nullable<Text>,
+        admin_room_id -> Nullable<Text>,
+    }
+}
+
+table! {
+    relay_rooms (matrix_room_id) {
+        matrix_room_id -> Text,
+        rocketchat_server_id -> Text,
+        relay_matrix_user_id -> Text,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    sent_rocketchat_messages (rocketchat_server_id, rocketchat_message_id) {
+        rocketchat_server_id -> Text,
+        rocketchat_message_id -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    login_tokens (token_hash) {
+        token_hash -> Text,
+        matrix_user_id -> Text,
+        rocketchat_server_id -> Text,
+        admin_room_id -> Text,
+        expires_at -> BigInt,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    recorded_errors (id) {
+        id -> Integer,
+        matrix_user_id -> Text,
+        rocketchat_server_id -> Text,
+        message -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    plumbed_rooms (matrix_room_id) {
+        matrix_room_id -> Text,
+        rocketchat_server_id -> Text,
+        rocketchat_channel_id -> Text,
+        created_by -> Text,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    virtual_users (matrix_user_id) {
+        matrix_user_id -> Text,
+        rocketchat_server_id -> Text,
+        rocketchat_user_id -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    puppet_events (event_id) {
+        event_id -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    bridged_rooms (matrix_room_id) {
+        matrix_room_id -> Text,
+        rocketchat_server_id -> Text,
+        rocketchat_channel_id -> Text,
+        room_type -> Text,
+        created_at -> Timestamp,
     }
 }
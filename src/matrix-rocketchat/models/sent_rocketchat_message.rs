@@ -0,0 +1,77 @@
+use std::sync::{PoisonError, RwLock, RwLockReadGuard};
+
+use diesel;
+use diesel::dsl::sql;
+use diesel::prelude::*;
+use diesel::sql_types::Bool;
+use diesel::sqlite::SqliteConnection;
+
+use errors::*;
+use models::schema::sent_rocketchat_messages;
+
+lazy_static! {
+    /// Held for reading while a message is posted to Rocket.Chat until its ID is stored, the echo
+    /// check waits for the write lock. This way an echo cannot be processed before the ID is known.
+    static ref SENDING: RwLock<()> = { RwLock::new(()) };
+}
+
+/// A message that was forwarded from Matrix to Rocket.Chat. It is used to detect the echo of the
+/// message, which is sent back to the application service by the Rocket.Chat webhook.
+#[derive(Associations, Debug, Identifiable, Queryable)]
+#[primary_key(rocketchat_server_id, rocketchat_message_id)]
+#[table_name = "sent_rocketchat_messages"]
+pub struct SentRocketchatMessage {
+    /// The unique id for the Rocket.Chat server
+    pub rocketchat_server_id: String,
+    /// The ID of the message on the Rocket.Chat server
+    pub rocketchat_message_id: String,
+    /// created timestamp
+    pub created_at: String,
+}
+
+/// A new `SentRocketchatMessage`, not yet saved.
+#[derive(Insertable)]
+#[table_name = "sent_rocketchat_messages"]
+pub struct NewSentRocketchatMessage<'a> {
+    /// The unique id for the Rocket.Chat server
+    pub rocketchat_server_id: &'a str,
+    /// The ID of the message on the Rocket.Chat server
+    pub rocketchat_message_id: &'a str,
+}
+
+impl SentRocketchatMessage {
+    /// Has to be held from posting a message to the Rocket.Chat server until its ID is inserted,
+    /// the webhook can deliver the echo before the server responds to the post.
+    pub fn sending() -> RwLockReadGuard<'static, ()> {
+        SENDING.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Remember a message that was sent to the Rocket.Chat server.
+    pub fn insert(connection: &SqliteConnection, rocketchat_server_id: &str, rocketchat_message_id: &str) -> Result<()> {
+        let new_sent_rocketchat_message = NewSentRocketchatMessage { rocketchat_server_id, rocketchat_message_id };
+        diesel::replace_into(sent_rocketchat_messages::table)
+            .values(&new_sent_rocketchat_message)
+            .execute(connection)
+            .chain_err(|| ErrorKind::DBInsertError)?;
+        Ok(())
+    }
+
+    /// Remove a message from the list of sent messages. Returns `true` if the message was sent
+    /// by the application service. Waits until the messages that are currently sent are inserted.
+    pub fn remove(connection: &SqliteConnection, rocketchat_server_id: &str, rocketchat_message_id: &str) -> Result<bool> {
+        let _sending = SENDING.write().unwrap_or_else(PoisonError::into_inner);
+        let deleted_rows = diesel::delete(sent_rocketchat_messages::table.find((rocketchat_server_id, rocketchat_message_id)))
+            .execute(connection)
+            .chain_err(|| ErrorKind::DBDeleteError)?;
+        Ok(deleted_rows > 0)
+    }
+
+    /// Remove the entries of messages which echo never arrived. Returns the number of removed entries.
+    pub fn delete_expired(connection: &SqliteConnection) -> Result<usize> {
+        let expired = sql::<Bool>("created_at < datetime('now', '-1 day')");
+        let deleted_rows = diesel::delete(sent_rocketchat_messages::table.filter(expired))
+            .execute(connection)
+            .chain_err(|| ErrorKind::DBDeleteError)?;
+        Ok(deleted_rows)
+    }
+}
@@ -0,0 +1,200 @@
+use std::sync::RwLock;
+
+use diesel::sqlite::SqliteConnection;
+use diesel::Connection;
+use ring::aead::{self, Aad, Nonce, OpeningKey, SealingKey, AES_256_GCM, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use ring::{digest, hmac};
+
+use errors::*;
+use models::{RocketchatServer, UserOnRocketchatServer};
+
+/// Prefix of encrypted tokens, tokens without the prefix are stored in plaintext
+const ENCRYPTED_TOKEN_PREFIX: &str = "enc:v1:";
+/// Length of the configured key in bytes
+const KEY_LEN: usize = 32;
+
+lazy_static! {
+    /// The key that the models use to encrypt tokens before they are stored in the database
+    static ref CURRENT_TOKEN_KEY: RwLock<Option<TokenKey>> = { RwLock::new(None) };
+}
+
+/// An AES-256-GCM key to encrypt the tokens that are stored in the database.
+#[derive(Clone)]
+pub struct TokenKey {
+    /// The configured key
+    key: [u8; KEY_LEN],
+    /// The key to hash tokens, it's derived from the encryption key
+    hash_key: [u8; 32],
+}
+
+impl TokenKey {
+    /// Create a key from the configuration. The key has to be 32 random bytes encoded as hex, for
+    /// example the output of `openssl rand -hex 32`. It's used as it is, so passphrases are rejected.
+    pub fn new(hex_key: &str) -> Result<TokenKey> {
+        let bytes = match from_hex(hex_key.trim()) {
+            Ok(bytes) => bytes,
+            Err(_) => bail_error!(ErrorKind::InvalidDatabaseEncryptionKey),
+        };
+        if bytes.len() != KEY_LEN {
+            bail_error!(ErrorKind::InvalidDatabaseEncryptionKey);
+        }
+
+        let mut key = [0u8; KEY_LEN];
+        key.copy_from_slice(&bytes);
+        let mut hash_key = [0u8; 32];
+        hash_key.copy_from_slice(hmac::sign(&hmac::SigningKey::new(&digest::SHA256, &key), b"token hash").as_ref());
+        Ok(TokenKey { key, hash_key })
+    }
+
+    /// Set the key that is used when tokens are read from or written to the database. Tokens
+    /// are stored in plaintext if there is no key.
+    pub fn set_current(token_key: Option<TokenKey>) -> Result<()> {
+        let mut current_token_key = CURRENT_TOKEN_KEY.write().map_err(|_| simple_error!(ErrorKind::InternalServerError))?;
+        *current_token_key = token_key;
+        Ok(())
+    }
+
+    /// Encrypt a token with the current key. The token is returned unchanged if there is no key.
+    pub fn encrypt_with_current(token: Option<String>) -> Result<Option<String>> {
+        let current_token_key = CURRENT_TOKEN_KEY.read().map_err(|_| simple_error!(ErrorKind::InternalServerError))?;
+        match token {
+            Some(token) => Ok(Some(encrypt(&token, current_token_key.as_ref())?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Hash a token with the current key, see `TokenKey::hash`. Without a key the tokens are
+    /// stored in plaintext, so they are hashed with a plain SHA-256 that isn't keyed.
+    pub fn hash_with_current(token: &str) -> Result<String> {
+        let current_token_key = CURRENT_TOKEN_KEY.read().map_err(|_| simple_error!(ErrorKind::InternalServerError))?;
+        Ok(hash(token, current_token_key.as_ref()))
+    }
+
+    /// Decrypt a token that was read from the database with the current key.
+    pub fn decrypt_with_current(token: Option<String>) -> Result<Option<String>> {
+        let current_token_key = CURRENT_TOKEN_KEY.read().map_err(|_| simple_error!(ErrorKind::InternalServerError))?;
+        match token {
+            Some(token) => Ok(Some(decrypt(&token, current_token_key.as_ref())?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Encrypt a token, the nonce is stored in front of the ciphertext.
+    pub fn encrypt(&self, token: &str) -> Result<String> {
+        let sealing_key =
+            SealingKey::new(&AES_256_GCM, &self.key).map_err(|_| simple_error!(ErrorKind::TokenEncryptionError))?;
+        let mut nonce = [0u8; NONCE_LEN];
+        SystemRandom::new().fill(&mut nonce).map_err(|_| simple_error!(ErrorKind::TokenEncryptionError))?;
+
+        let tag_len = AES_256_GCM.tag_len();
+        let mut in_out = token.as_bytes().to_vec();
+        in_out.resize(token.len() + tag_len, 0);
+        let len = aead::seal_in_place(&sealing_key, Nonce::assume_unique_for_key(nonce), Aad::empty(), &mut in_out, tag_len)
+            .map_err(|_| simple_error!(ErrorKind::TokenEncryptionError))?;
+
+        let mut encrypted_token = nonce.to_vec();
+        encrypted_token.extend_from_slice(&in_out[..len]);
+        Ok(format!("{}{}", ENCRYPTED_TOKEN_PREFIX, to_hex(&encrypted_token)))
+    }
+
+    /// Hash a token to look it up in the database. Unlike the encrypted token the hash is always
+    /// the same, it's keyed so that it cannot be used to guess the token without the key.
+    pub fn hash(&self, token: &str) -> String {
+        let signing_key = hmac::SigningKey::new(&digest::SHA256, &self.hash_key);
+        to_hex(hmac::sign(&signing_key, token.as_bytes()).as_ref())
+    }
+
+    /// Decrypt a token. Tokens that were stored before the encryption was enabled are returned
+    /// unchanged.
+    pub fn decrypt(&self, token: &str) -> Result<String> {
+        if !token.starts_with(ENCRYPTED_TOKEN_PREFIX) {
+            return Ok(token.to_string());
+        }
+
+        let encrypted_token = from_hex(&token[ENCRYPTED_TOKEN_PREFIX.len()..])?;
+        if encrypted_token.len() < NONCE_LEN {
+            bail_error!(ErrorKind::TokenDecryptionError("The token is too short".to_string()));
+        }
+
+        let (nonce_bytes, ciphertext) = encrypted_token.split_at(NONCE_LEN);
+        let mut nonce = [0u8; NONCE_LEN];
+        nonce.copy_from_slice(nonce_bytes);
+        let opening_key = OpeningKey::new(&AES_256_GCM, &self.key)
+            .map_err(|_| simple_error!(ErrorKind::TokenDecryptionError("Invalid key".to_string())))?;
+
+        let mut in_out = ciphertext.to_vec();
+        let token = aead::open_in_place(&opening_key, Nonce::assume_unique_for_key(nonce), Aad::empty(), 0, &mut in_out)
+            .map_err(|_| simple_error!(ErrorKind::TokenDecryptionError("The key doesn't match".to_string())))?;
+        String::from_utf8(token.to_vec())
+            .chain_err(|| ErrorKind::TokenDecryptionError("The token is not valid UTF-8".to_string()))
+            .map_err(Error::from)
+    }
+
+    /// Encrypt all tokens in the database with a new key. Tokens that are encrypted with the old
+    /// key are decrypted first, plaintext tokens are encrypted. Without an old key the tokens are
+    /// decrypted with the new key. Without a new key the tokens are stored in plaintext again.
+    /// The hashes of the tokens are updated as well. Returns the number of tokens that were
+    /// updated.
+    pub fn reencrypt_tokens(
+        connection: &SqliteConnection,
+        old_token_key: Option<&TokenKey>,
+        new_token_key: Option<&TokenKey>,
+    ) -> Result<usize> {
+        let decrypt_token = |token: &str| decrypt(token, old_token_key.or(new_token_key));
+        let encrypt_token = |token: &str| encrypt(token, new_token_key);
+        let hash_token = |token: &str| hash(token, new_token_key);
+        let reencrypt = |token: &str| encrypt_token(&decrypt_token(token)?);
+
+        connection.transaction(|| {
+            let updated_server_tokens =
+                RocketchatServer::reencrypt_tokens(connection, &decrypt_token, &encrypt_token, &hash_token)?;
+            let updated_user_tokens = UserOnRocketchatServer::reencrypt_tokens(connection, &reencrypt)?;
+            Ok(updated_server_tokens + updated_user_tokens)
+        })
+    }
+}
+
+fn encrypt(token: &str, token_key: Option<&TokenKey>) -> Result<String> {
+    match token_key {
+        Some(token_key) => token_key.encrypt(token),
+        None => Ok(token.to_string()),
+    }
+}
+
+// Without a key the tokens are stored in plaintext anyway, so a plain hash is good enough.
+fn hash(token: &str, token_key: Option<&TokenKey>) -> String {
+    match token_key {
+        Some(token_key) => token_key.hash(token),
+        None => to_hex(digest::digest(&digest::SHA256, token.as_bytes()).as_ref()),
+    }
+}
+
+fn decrypt(token: &str, token_key: Option<&TokenKey>) -> Result<String> {
+    match token_key {
+        Some(token_key) => token_key.decrypt(token),
+        None if token.starts_with(ENCRYPTED_TOKEN_PREFIX) => {
+            bail_error!(ErrorKind::TokenDecryptionError("The token is encrypted, but no key is configured".to_string()))
+        }
+        None => Ok(token.to_string()),
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(hex: &str) -> Result<Vec<u8>> {
+    if hex.len() % 2 != 0 || !hex.is_ascii() {
+        bail_error!(ErrorKind::TokenDecryptionError("The token is not hex encoded".to_string()));
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&hex[i..i + 2], 16)
+                .chain_err(|| ErrorKind::TokenDecryptionError("The token is not hex encoded".to_string()))
+                .map_err(Error::from)
+        })
+        .collect()
+}
@@ -0,0 +1,57 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use ruma_identifiers::UserId;
+
+use models::Credentials;
+
+/// Time in which the user has to send the two-factor authentication code
+pub const TWO_FACTOR_LOGIN_TIMEOUT_IN_SECONDS: u64 = 300;
+
+lazy_static! {
+    /// Logins that are waiting for a two-factor authentication code. They are only kept in memory,
+    /// because they contain the password of the user.
+    static ref PENDING_LOGINS: Mutex<HashMap<(String, String), TwoFactorLogin>> = { Mutex::new(HashMap::new()) };
+}
+
+/// A login on a Rocket.Chat server that is waiting for the two-factor authentication code of the
+/// user.
+pub struct TwoFactorLogin {
+    /// The unique id for the Rocket.Chat server
+    pub rocketchat_server_id: String,
+    /// The credentials that were used to start the login
+    pub credentials: Credentials,
+    /// The time when the login was started
+    pub started_at: Instant,
+}
+
+impl TwoFactorLogin {
+    /// Create a new `TwoFactorLogin`.
+    pub fn new(rocketchat_server_id: String, credentials: Credentials) -> TwoFactorLogin {
+        TwoFactorLogin { rocketchat_server_id, credentials, started_at: Instant::now() }
+    }
+
+    /// Keep the login until the user sends the two-factor authentication code. Replaces a pending
+    /// login of the user on the same server.
+    pub fn save(self) {
+        if let Ok(mut pending_logins) = PENDING_LOGINS.lock() {
+            pending_logins.retain(|_, login| !login.is_expired());
+            let key = (self.credentials.user_id.to_string(), self.rocketchat_server_id.clone());
+            pending_logins.insert(key, self);
+        }
+    }
+
+    /// Remove the pending login of the user. Returns `None` if no login is waiting for a
+    /// two-factor authentication code or if the login timed out.
+    pub fn take(user_id: &UserId, rocketchat_server_id: &str) -> Option<TwoFactorLogin> {
+        let mut pending_logins = PENDING_LOGINS.lock().ok()?;
+        let key = (user_id.to_string(), rocketchat_server_id.to_string());
+        pending_logins.remove(&key).and_then(|login| if login.is_expired() { None } else { Some(login) })
+    }
+
+    /// Checks if the user didn't send the code in time.
+    pub fn is_expired(&self) -> bool {
+        self.started_at.elapsed() > Duration::from_secs(TWO_FACTOR_LOGIN_TIMEOUT_IN_SECONDS)
+    }
+}
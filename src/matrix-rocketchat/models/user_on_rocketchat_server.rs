@@ -3,10 +3,11 @@ use std::time::{SystemTime, UNIX_EPOCH};
 use diesel;
 use diesel::prelude::*;
 use diesel::sqlite::SqliteConnection;
-use ruma_identifiers::UserId;
+use ruma_identifiers::{RoomId, UserId};
 
 use errors::*;
 use models::schema::users_on_rocketchat_servers;
+use models::TokenKey;
 
 /// A user on a Rocket.Chat server.
 #[derive(Associations, Debug, Identifiable, Queryable)]
@@ -21,12 +22,17 @@ pub struct UserOnRocketchatServer {
     pub rocketchat_server_id: String,
     /// The users unique id on the Rocket.Chat server.
     pub rocketchat_user_id: Option<String>,
-    /// The token to identify reuqests from the Rocket.Chat server
+    /// The token to identify reuqests from the Rocket.Chat server, it's encrypted in the database
     pub rocketchat_auth_token: Option<String>,
     /// created timestamp
     pub created_at: String,
     /// updated timestamp
     pub updated_at: String,
+    /// The users access token on the Matrix homeserver, used to send messages as the user (double puppeting),
+    /// it's encrypted in the database
+    pub matrix_access_token: Option<String>,
+    /// The admin room in which the user logged in, notifications about the session are sent there
+    pub admin_room_id: Option<RoomId>,
 }
 
 /// A new `Room`, not yet saved.
@@ -63,8 +69,16 @@ impl UserOnRocketchatServer {
                 )?;
             }
             None => {
+                let encrypted_user_on_rocketchat_server = NewUserOnRocketchatServer {
+                    matrix_user_id: user_on_rocketchat_server.matrix_user_id.clone(),
+                    rocketchat_server_id: user_on_rocketchat_server.rocketchat_server_id.clone(),
+                    rocketchat_user_id: user_on_rocketchat_server.rocketchat_user_id.clone(),
+                    rocketchat_auth_token: TokenKey::encrypt_with_current(
+                        user_on_rocketchat_server.rocketchat_auth_token.clone(),
+                    )?,
+                };
                 diesel::insert_into(users_on_rocketchat_servers::table)
-                    .values(user_on_rocketchat_server)
+                    .values(&encrypted_user_on_rocketchat_server)
                     .execute(connection)
                     .chain_err(|| ErrorKind::DBInsertError)?;
             }
@@ -84,11 +98,11 @@ impl UserOnRocketchatServer {
         matrix_user_id: &UserId,
         rocketchat_server_id: String,
     ) -> Result<UserOnRocketchatServer> {
-        let user_on_rocketchat_server = users_on_rocketchat_servers::table
+        let user_on_rocketchat_server: UserOnRocketchatServer = users_on_rocketchat_servers::table
             .find((matrix_user_id, rocketchat_server_id))
             .first(connection)
             .chain_err(|| ErrorKind::DBSelectError)?;
-        Ok(user_on_rocketchat_server)
+        user_on_rocketchat_server.decrypt()
     }
 
     /// Find a `UserOnRocketchatServer` by his matrix user ID and the Rocket.Chat server ID, return
@@ -98,7 +112,7 @@ impl UserOnRocketchatServer {
         matrix_user_id: &UserId,
         rocketchat_server_id: String,
     ) -> Result<Option<UserOnRocketchatServer>> {
-        let user_on_rocketchat_server = users_on_rocketchat_servers::table
+        let user_on_rocketchat_server: Vec<UserOnRocketchatServer> = users_on_rocketchat_servers::table
             .filter(
                 users_on_rocketchat_servers::matrix_user_id
                     .eq(matrix_user_id)
@@ -106,7 +120,7 @@ impl UserOnRocketchatServer {
             )
             .load(connection)
             .chain_err(|| ErrorKind::DBSelectError)?;
-        Ok(user_on_rocketchat_server.into_iter().next())
+        user_on_rocketchat_server.into_iter().next().map(UserOnRocketchatServer::decrypt).transpose()
     }
 
     /// Find a list of `UserOnRocketchatServer` by their matrix user Id and the Rocket.Chat server ID.
@@ -115,7 +129,7 @@ impl UserOnRocketchatServer {
         matrix_user_ids: Vec<UserId>,
         rocketchat_server_id: String,
     ) -> Result<Vec<UserOnRocketchatServer>> {
-        let users_on_rocketchat_server = users_on_rocketchat_servers::table
+        let users_on_rocketchat_server: Vec<UserOnRocketchatServer> = users_on_rocketchat_servers::table
             .filter(
                 users_on_rocketchat_servers::matrix_user_id
                     .eq_any(matrix_user_ids)
@@ -123,7 +137,7 @@ impl UserOnRocketchatServer {
             )
             .load(connection)
             .chain_err(|| ErrorKind::DBSelectError)?;
-        Ok(users_on_rocketchat_server)
+        users_on_rocketchat_server.into_iter().map(UserOnRocketchatServer::decrypt).collect()
     }
 
     /// Find a `UserOnRocketchatServer` by his Rocket.Chat user ID. Returns `None`,
@@ -133,7 +147,7 @@ impl UserOnRocketchatServer {
         rocketchat_server_id: String,
         rocketchat_user_id: String,
     ) -> Result<Option<UserOnRocketchatServer>> {
-        let users_on_rocketchat_servers = users_on_rocketchat_servers::table
+        let users_on_rocketchat_servers: Vec<UserOnRocketchatServer> = users_on_rocketchat_servers::table
             .filter(
                 users_on_rocketchat_servers::rocketchat_server_id
                     .eq(rocketchat_server_id)
@@ -141,7 +155,17 @@ impl UserOnRocketchatServer {
             )
             .load(connection)
             .chain_err(|| ErrorKind::DBSelectError)?;
-        Ok(users_on_rocketchat_servers.into_iter().next())
+        users_on_rocketchat_servers.into_iter().next().map(UserOnRocketchatServer::decrypt).transpose()
+    }
+
+    /// Find all users that are logged in on the Rocket.Chat server.
+    pub fn find_logged_in(connection: &SqliteConnection, rocketchat_server_id: &str) -> Result<Vec<UserOnRocketchatServer>> {
+        let users_on_rocketchat_server: Vec<UserOnRocketchatServer> = users_on_rocketchat_servers::table
+            .filter(users_on_rocketchat_servers::rocketchat_server_id.eq(rocketchat_server_id))
+            .filter(users_on_rocketchat_servers::rocketchat_auth_token.is_not_null())
+            .load(connection)
+            .chain_err(|| ErrorKind::DBSelectError)?;
+        users_on_rocketchat_server.into_iter().map(UserOnRocketchatServer::decrypt).collect()
     }
 
     /// Update the users credentials.
@@ -153,16 +177,42 @@ impl UserOnRocketchatServer {
     ) -> Result<()> {
         self.rocketchat_user_id = rocketchat_user_id.clone();
         self.rocketchat_auth_token = rocketchat_auth_token.clone();
+        let encrypted_rocketchat_auth_token = TokenKey::encrypt_with_current(rocketchat_auth_token)?;
         diesel::update(users_on_rocketchat_servers::table.find((&self.matrix_user_id, self.rocketchat_server_id.clone())))
             .set((
                 users_on_rocketchat_servers::rocketchat_user_id.eq(rocketchat_user_id),
-                users_on_rocketchat_servers::rocketchat_auth_token.eq(rocketchat_auth_token),
+                users_on_rocketchat_servers::rocketchat_auth_token.eq(encrypted_rocketchat_auth_token),
             ))
             .execute(connection)
             .chain_err(|| ErrorKind::DBUpdateError)?;
         Ok(())
     }
 
+    /// Update the access token that is used to send messages on behalf of the Matrix user.
+    pub fn set_matrix_access_token(
+        &mut self,
+        connection: &SqliteConnection,
+        matrix_access_token: Option<String>,
+    ) -> Result<()> {
+        self.matrix_access_token = matrix_access_token.clone();
+        let encrypted_matrix_access_token = TokenKey::encrypt_with_current(matrix_access_token)?;
+        diesel::update(users_on_rocketchat_servers::table.find((&self.matrix_user_id, self.rocketchat_server_id.clone())))
+            .set(users_on_rocketchat_servers::matrix_access_token.eq(encrypted_matrix_access_token))
+            .execute(connection)
+            .chain_err(|| ErrorKind::DBUpdateError)?;
+        Ok(())
+    }
+
+    /// Update the admin room in which the user logged in.
+    pub fn set_admin_room_id(&mut self, connection: &SqliteConnection, admin_room_id: Option<RoomId>) -> Result<()> {
+        self.admin_room_id = admin_room_id.clone();
+        diesel::update(users_on_rocketchat_servers::table.find((&self.matrix_user_id, self.rocketchat_server_id.clone())))
+            .set(users_on_rocketchat_servers::admin_room_id.eq(admin_room_id))
+            .execute(connection)
+            .chain_err(|| ErrorKind::DBUpdateError)?;
+        Ok(())
+    }
+
     /// Update last message sent.
     pub fn set_last_message_sent(&mut self, connection: &SqliteConnection) -> Result<()> {
         let last_message_sent =
@@ -180,4 +230,53 @@ impl UserOnRocketchatServer {
     pub fn is_logged_in(&self) -> bool {
         self.rocketchat_auth_token.is_some()
     }
+
+    /// Encrypt the auth tokens and the Matrix access tokens of all users with a new key, see
+    /// `TokenKey::reencrypt_tokens`. Returns the number of updated tokens.
+    pub fn reencrypt_tokens(connection: &SqliteConnection, reencrypt: &Fn(&str) -> Result<String>) -> Result<usize> {
+        let auth_tokens: Vec<(UserId, String, Option<String>)> = users_on_rocketchat_servers::table
+            .select((
+                users_on_rocketchat_servers::matrix_user_id,
+                users_on_rocketchat_servers::rocketchat_server_id,
+                users_on_rocketchat_servers::rocketchat_auth_token,
+            ))
+            .filter(users_on_rocketchat_servers::rocketchat_auth_token.is_not_null())
+            .load(connection)
+            .chain_err(|| ErrorKind::DBSelectError)?;
+
+        for (matrix_user_id, rocketchat_server_id, auth_token) in &auth_tokens {
+            let auth_token = reencrypt(auth_token.as_ref().map(String::as_str).unwrap_or_default())?;
+            diesel::update(users_on_rocketchat_servers::table.find((matrix_user_id, rocketchat_server_id.as_str())))
+                .set(users_on_rocketchat_servers::rocketchat_auth_token.eq(auth_token))
+                .execute(connection)
+                .chain_err(|| ErrorKind::DBUpdateError)?;
+        }
+
+        let matrix_access_tokens: Vec<(UserId, String, Option<String>)> = users_on_rocketchat_servers::table
+            .select((
+                users_on_rocketchat_servers::matrix_user_id,
+                users_on_rocketchat_servers::rocketchat_server_id,
+                users_on_rocketchat_servers::matrix_access_token,
+            ))
+            .filter(users_on_rocketchat_servers::matrix_access_token.is_not_null())
+            .load(connection)
+            .chain_err(|| ErrorKind::DBSelectError)?;
+
+        for (matrix_user_id, rocketchat_server_id, matrix_access_token) in &matrix_access_tokens {
+            let matrix_access_token = reencrypt(matrix_access_token.as_ref().map(String::as_str).unwrap_or_default())?;
+            diesel::update(users_on_rocketchat_servers::table.find((matrix_user_id, rocketchat_server_id.as_str())))
+                .set(users_on_rocketchat_servers::matrix_access_token.eq(matrix_access_token))
+                .execute(connection)
+                .chain_err(|| ErrorKind::DBUpdateError)?;
+        }
+
+        Ok(auth_tokens.len() + matrix_access_tokens.len())
+    }
+
+    // The tokens are stored encrypted if a key is configured, see `TokenKey`.
+    fn decrypt(mut self) -> Result<UserOnRocketchatServer> {
+        self.rocketchat_auth_token = TokenKey::decrypt_with_current(self.rocketchat_auth_token.take())?;
+        self.matrix_access_token = TokenKey::decrypt_with_current(self.matrix_access_token.take())?;
+        Ok(self)
+    }
 }
@@ -1,26 +1,49 @@
 use std::convert::TryFrom;
 
+use diesel;
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
 use ruma_identifiers::UserId;
 use slog::Logger;
 
 use api::MatrixApi;
 use config::Config;
 use errors::*;
+use models::schema::virtual_users;
 
 /// Provides helper methods to manage virtual users.
 pub struct VirtualUser<'a> {
     /// Application service configuration
     config: &'a Config,
+    /// SQL database connection
+    connection: &'a SqliteConnection,
     /// Logger context
     logger: &'a Logger,
     /// API to call the Matrix homeserver
     matrix_api: &'a MatrixApi,
 }
 
+/// A virtual user that was registered on the Matrix homeserver, not yet saved.
+#[derive(Insertable)]
+#[table_name = "virtual_users"]
+struct NewVirtualUser<'a> {
+    /// The users Matrix ID
+    matrix_user_id: UserId,
+    /// The unique id for the Rocket.Chat server
+    rocketchat_server_id: &'a str,
+    /// The ID of the user on the Rocket.Chat server
+    rocketchat_user_id: &'a str,
+}
+
 impl<'a> VirtualUser<'a> {
     /// Create a new virtual users model, to interact with Matrix virtual users.
-    pub fn new(config: &'a Config, logger: &'a Logger, matrix_api: &'a MatrixApi) -> VirtualUser<'a> {
-        VirtualUser { config, logger, matrix_api }
+    pub fn new(
+        config: &'a Config,
+        connection: &'a SqliteConnection,
+        logger: &'a Logger,
+        matrix_api: &'a MatrixApi,
+    ) -> VirtualUser<'a> {
+        VirtualUser { config, connection, logger, matrix_api }
     }
 
     /// Register a virtual user on the Matrix server and assign it to a Rocket.Chat server.
@@ -40,13 +63,14 @@ impl<'a> VirtualUser<'a> {
             &user_id,
         );
 
-        if self.matrix_api.get_display_name(user_id.clone())?.is_some() {
+        if self.is_registered(&user_id)? {
             debug!(self.logger, "Found user with matrix_id {}", user_id);
             return Ok(user_id);
         }
 
         debug!(self.logger, "No user found, registring a new user with the matrix ID {}", &user_id);
         self.matrix_api.register(user_id.localpart().to_string())?;
+        self.insert(&user_id, rocketchat_server_id, rocketchat_user_id)?;
         debug!(self.logger, "Successfully registred user {}", &user_id);
 
         if let Err(err) = self.matrix_api.set_display_name(user_id.clone(), rocketchat_user_name.to_string()) {
@@ -56,6 +80,28 @@ impl<'a> VirtualUser<'a> {
         Ok(user_id)
     }
 
+    /// Indicates if a virtual user is registered on the Matrix homeserver. Registrations are
+    /// recorded in the database, users that were registered before that are looked up on the
+    /// homeserver once and recorded afterwards.
+    pub fn is_registered(&self, user_id: &UserId) -> Result<bool> {
+        let registered_users: i64 = virtual_users::table
+            .filter(virtual_users::matrix_user_id.eq(user_id))
+            .count()
+            .get_result(self.connection)
+            .chain_err(|| ErrorKind::DBSelectError)?;
+        if registered_users > 0 {
+            return Ok(true);
+        }
+
+        if self.matrix_api.get_display_name(user_id.clone())?.is_none() {
+            return Ok(false);
+        }
+
+        let (rocketchat_server_id, rocketchat_user_id) = VirtualUser::rocketchat_server_and_user_id_from_matrix_id(user_id);
+        self.insert(user_id, &rocketchat_server_id, &rocketchat_user_id)?;
+        Ok(true)
+    }
+
     /// Build the matrix user ID based on the Rocket.Chat user ID and the Rocket.Chat server ID.
     pub fn build_user_id(&self, rocketchat_user_id: &str, rocketchat_server_id: &str) -> Result<UserId> {
         let user_id_local_part = format!("{}_{}_{}", self.config.sender_localpart, rocketchat_server_id, rocketchat_user_id);
@@ -63,6 +109,21 @@ impl<'a> VirtualUser<'a> {
         Ok(UserId::try_from(user_id.as_ref()).chain_err(|| ErrorKind::InvalidUserId(user_id))?)
     }
 
+    /// The Rocket.Chat user ID of a virtual user. Matrix IDs are lowercase, so the recorded ID is
+    /// used if the user is known, otherwise it is extracted from the Matrix ID.
+    pub fn rocketchat_user_id(&self, user_id: &UserId) -> Result<String> {
+        let rocketchat_user_ids: Vec<String> = virtual_users::table
+            .filter(virtual_users::matrix_user_id.eq(user_id))
+            .select(virtual_users::rocketchat_user_id)
+            .load(self.connection)
+            .chain_err(|| ErrorKind::DBSelectError)?;
+
+        match rocketchat_user_ids.into_iter().next() {
+            Some(rocketchat_user_id) => Ok(rocketchat_user_id),
+            None => Ok(VirtualUser::rocketchat_server_and_user_id_from_matrix_id(user_id).1),
+        }
+    }
+
     /// Extracts the Rocket.Chat server and the users Rocket.Chat user ID from the Matrix User ID.
     pub fn rocketchat_server_and_user_id_from_matrix_id(user_id: &UserId) -> (String, String) {
         let user_local_part = user_id.localpart().to_owned();
@@ -73,4 +134,13 @@ impl<'a> VirtualUser<'a> {
 
         (server_id, rocketchat_user_id.to_owned())
     }
+
+    fn insert(&self, user_id: &UserId, rocketchat_server_id: &str, rocketchat_user_id: &str) -> Result<()> {
+        let new_virtual_user = NewVirtualUser { matrix_user_id: user_id.clone(), rocketchat_server_id, rocketchat_user_id };
+        diesel::replace_into(virtual_users::table)
+            .values(&new_virtual_user)
+            .execute(self.connection)
+            .chain_err(|| ErrorKind::DBInsertError)?;
+        Ok(())
+    }
 }
@@ -1,17 +1,25 @@
+use std::thread;
+
 use diesel::sqlite::SqliteConnection;
 use diesel::Connection;
 use hyper_native_tls::NativeTlsServer;
 use iron::{Chain, Iron, Listening};
 use persistent::{State, Write};
+use r2d2::Pool;
+use r2d2_diesel::ConnectionManager;
 use router::Router;
 use slog::Logger;
 
 use api::MatrixApi;
 use config::Config;
 use errors::*;
-use handlers::iron::{Rocketchat, RocketchatLogin, Transactions, Welcome};
-use log::IronLogger;
-use models::ConnectionPool;
+use handlers::iron::{
+    Rocketchat, RocketchatLogin, RocketchatLoginPage, Rooms, ThirdPartyLocation, ThirdPartyProtocol, ThirdPartyUser,
+    Transactions, Users, Welcome,
+};
+use handlers::rocketchat::RoleSynchronizer;
+use log::{self, IronLogger};
+use models::{ConnectionPool, PuppetEvent, RocketchatServer, SentRocketchatMessage, TokenKey};
 
 embed_migrations!("migrations");
 
@@ -32,10 +40,13 @@ impl<'a> Server<'a> {
     /// Runs the application service bridge.
     pub fn run(&self, threads: usize) -> Result<Listening> {
         self.prepare_database()?;
+        TokenKey::set_current(self.token_key()?)?;
+        self.hash_rocketchat_tokens()?;
         let connection_pool = ConnectionPool::create(&self.config.database_url)?;
 
         let matrix_api = MatrixApi::new(self.config, self.logger.clone())?;
         self.setup_bot_user(matrix_api.as_ref())?;
+        self.start_role_sync(connection_pool.clone(), matrix_api.clone());
 
         let router = self.setup_routes(matrix_api);
         let mut chain = Chain::new(router);
@@ -62,20 +73,121 @@ impl<'a> Server<'a> {
         listener.chain_err(|| ErrorKind::ServerStartupError).map_err(Error::from)
     }
 
+    // Rocket.Chat doesn't send a webhook when a role changes, so the roles are synchronized in
+    // the background.
+    fn start_role_sync(&self, connection_pool: Pool<ConnectionManager<SqliteConnection>>, matrix_api: Box<MatrixApi>) {
+        let interval = match self.config.role_sync_interval() {
+            Some(interval) => interval,
+            None => {
+                debug!(self.logger, "Periodic role synchronization is disabled");
+                return;
+            }
+        };
+
+        let config = self.config.clone();
+        let logger = self.logger.clone();
+        thread::spawn(move || loop {
+            thread::sleep(interval);
+
+            let connection = match connection_pool.get().chain_err(|| ErrorKind::GetConnectionError) {
+                Ok(connection) => connection,
+                Err(err) => {
+                    log::log_error(&logger, &Error::from(err));
+                    continue;
+                }
+            };
+
+            let role_synchronizer = RoleSynchronizer::new(&config, &connection, &logger, matrix_api.as_ref());
+            if let Err(err) = role_synchronizer.sync_all() {
+                log::log_error(&logger, &err);
+            }
+        });
+    }
+
     fn setup_routes(&self, matrix_api: Box<MatrixApi>) -> Router {
         debug!(self.logger, "Setting up routes");
         let mut router = Router::new();
         router.get("/", Welcome {}, "welcome");
         router.put("/transactions/:txn_id", Transactions::chain(self.config.clone(), matrix_api.clone()), "transactions");
+        router.get("/rooms/:room_alias", Rooms::chain(self.config.clone(), matrix_api.clone()), "rooms");
+        router.get("/users/:user_id", Users::chain(self.config.clone(), matrix_api.clone()), "users");
+        // older homeservers use the unstable prefix for third party lookups
+        for prefix in &["/_matrix/app/unstable", "/_matrix/app/v1"] {
+            router.get(
+                format!("{}/thirdparty/protocol/:protocol", prefix),
+                ThirdPartyProtocol::chain(self.config.clone()),
+                format!("{}_thirdparty_protocol", prefix),
+            );
+            router.get(
+                format!("{}/thirdparty/location/:protocol", prefix),
+                ThirdPartyLocation::chain(self.config.clone(), matrix_api.clone()),
+                format!("{}_thirdparty_location", prefix),
+            );
+            router.get(
+                format!("{}/thirdparty/user/:protocol", prefix),
+                ThirdPartyUser::chain(self.config.clone(), matrix_api.clone()),
+                format!("{}_thirdparty_user", prefix),
+            );
+        }
         router.post("/rocketchat", Rocketchat::chain(self.config, matrix_api.clone()), "rocketchat");
-        router.post("/rocketchat/login", RocketchatLogin { config: self.config.clone(), matrix_api }, "rocketchat_login");
+        router.post(
+            "/rocketchat/login",
+            RocketchatLogin { config: self.config.clone(), matrix_api: matrix_api.clone() },
+            "rocketchat_login",
+        );
+        router.get(
+            "/rocketchat/login/:token",
+            RocketchatLoginPage { config: self.config.clone(), matrix_api: matrix_api.clone() },
+            "rocketchat_login_page",
+        );
+        router.post(
+            "/rocketchat/login/:token",
+            RocketchatLoginPage { config: self.config.clone(), matrix_api },
+            "rocketchat_login_page_submit",
+        );
         router
     }
 
+    /// Encrypt the tokens that are stored in the database with the configured key. Tokens that
+    /// are encrypted with the old key are decrypted first. Returns the number of updated tokens.
+    pub fn reencrypt_tokens(&self, old_key: Option<&str>) -> Result<usize> {
+        self.prepare_database()?;
+        let connection = SqliteConnection::establish(&self.config.database_url).chain_err(|| ErrorKind::DBConnectionError)?;
+        let old_token_key = match old_key {
+            Some(old_key) => Some(TokenKey::new(old_key)?),
+            None => None,
+        };
+        let new_token_key = self.token_key()?;
+        let updated_tokens = TokenKey::reencrypt_tokens(&connection, old_token_key.as_ref(), new_token_key.as_ref())?;
+
+        info!(self.logger, "Re-encrypted {} tokens", updated_tokens);
+        Ok(updated_tokens)
+    }
+
+    fn token_key(&self) -> Result<Option<TokenKey>> {
+        match self.config.database_encryption_key() {
+            Some(key) => Ok(Some(TokenKey::new(&key)?)),
+            None => Ok(None),
+        }
+    }
+
     fn prepare_database(&self) -> Result<()> {
         debug!(self.logger, "Setting up database {}", self.config.database_url);
         let connection = SqliteConnection::establish(&self.config.database_url).chain_err(|| ErrorKind::DBConnectionError)?;
-        embedded_migrations::run(&connection).map_err(Error::from)
+        embedded_migrations::run(&connection).map_err(Error::from)?;
+
+        let expired_messages = SentRocketchatMessage::delete_expired(&connection)?;
+        debug!(self.logger, "Removed {} sent messages which echo never arrived", expired_messages);
+        let expired_events = PuppetEvent::delete_expired(&connection)?;
+        debug!(self.logger, "Removed {} puppet events that were never received", expired_events);
+        Ok(())
+    }
+
+    fn hash_rocketchat_tokens(&self) -> Result<()> {
+        let connection = SqliteConnection::establish(&self.config.database_url).chain_err(|| ErrorKind::DBConnectionError)?;
+        let hashed_tokens = RocketchatServer::hash_missing_tokens(&connection)?;
+        debug!(self.logger, "Hashed {} Rocket.Chat tokens that were stored without a hash", hashed_tokens);
+        Ok(())
     }
 
     fn setup_bot_user(&self, matrix_api: &MatrixApi) -> Result<()> {
@@ -0,0 +1,264 @@
+extern crate iron;
+extern crate matrix_rocketchat;
+extern crate matrix_rocketchat_test;
+extern crate router;
+extern crate ruma_client_api;
+extern crate ruma_events;
+extern crate ruma_identifiers;
+
+use std::convert::TryFrom;
+
+use iron::status;
+use matrix_rocketchat::api::rocketchat::v1::{IM_CREATE_PATH, USERS_INFO_PATH};
+use matrix_rocketchat::api::MatrixApi;
+use matrix_rocketchat::models::{BridgedRoom, RoomType};
+use matrix_rocketchat_test::{default_timeout, handlers, helpers, MessageForwarder, Test, DEFAULT_LOGGER};
+use ruma_client_api::r0::membership::invite_user::Endpoint as InviteEndpoint;
+use ruma_client_api::r0::room::create_room::Endpoint as CreateRoomEndpoint;
+use ruma_client_api::r0::send::send_message_event::Endpoint as SendMessageEventEndpoint;
+use ruma_client_api::Endpoint;
+use ruma_events::room::member::MembershipState;
+use ruma_identifiers::{RoomId, UserId};
+
+#[test]
+fn successfully_start_a_direct_message_with_a_rocketchat_user() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let (invite_forwarder, invite_receiver) = handlers::MatrixInviteUser::with_forwarder(test.config.as_url.clone());
+    let (create_room_forwarder, create_room_receiver) = handlers::MatrixCreateRoom::with_forwarder(test.config.as_url.clone());
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+    matrix_router.post(InviteEndpoint::router_path(), invite_forwarder, "invite_user");
+    matrix_router.post(CreateRoomEndpoint::router_path(), create_room_forwarder, "create_room");
+
+    let test =
+        test.with_matrix_routes(matrix_router).with_rocketchat_mock().with_connected_admin_room().with_logged_in_user().run();
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard login message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!admin_room_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        "dm other_user".to_string(),
+    );
+
+    // discard admin room creation message
+    create_room_receiver.recv_timeout(default_timeout()).unwrap();
+
+    let create_room_message = create_room_receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(create_room_message.contains("\"name\":\"other_user (DM Rocket.Chat)\""));
+
+    // discard admin room invite
+    invite_receiver.recv_timeout(default_timeout()).unwrap();
+
+    let spec_user_invite = invite_receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(spec_user_invite.contains("\"user_id\":\"@spec_user:localhost\""));
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(message_received_by_matrix.contains("You are invited into the direct message room with other_user."));
+}
+
+#[test]
+fn attempt_to_start_a_direct_message_with_a_user_that_does_not_exist() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+    let mut rocketchat_router = test.default_rocketchat_routes();
+    rocketchat_router.get(
+        USERS_INFO_PATH,
+        handlers::RocketchatErrorResponder { message: "User not found".to_string(), status: status::BadRequest },
+        "users_info",
+    );
+
+    let test = test
+        .with_matrix_routes(matrix_router)
+        .with_rocketchat_mock()
+        .with_custom_rocketchat_routes(rocketchat_router)
+        .with_connected_admin_room()
+        .with_logged_in_user()
+        .run();
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard login message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!admin_room_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        "dm nobody".to_string(),
+    );
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(message_received_by_matrix.contains("No user with the username nobody found on the Rocket.Chat server."));
+}
+
+#[test]
+fn inviting_a_virtual_user_into_a_new_room_bridges_the_room_to_a_direct_message() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let (create_room_forwarder, create_room_receiver) = handlers::MatrixCreateRoom::with_forwarder(test.config.as_url.clone());
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+    matrix_router.post(CreateRoomEndpoint::router_path(), create_room_forwarder, "create_room");
+
+    let test =
+        test.with_matrix_routes(matrix_router).with_rocketchat_mock().with_connected_admin_room().with_logged_in_user().run();
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard login message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    let matrix_api = MatrixApi::new(&test.config, DEFAULT_LOGGER.clone()).unwrap();
+    let spec_user_id = UserId::try_from("@spec_user:localhost").unwrap();
+    let virtual_user_id = UserId::try_from("@rocketchat_rcid_other_user_id:localhost").unwrap();
+    matrix_api.register("rocketchat_rcid_other_user_id".to_string()).unwrap();
+    matrix_api.set_display_name(virtual_user_id.clone(), "Other User".to_string()).unwrap();
+
+    helpers::create_room(&test.config, "spec_room", spec_user_id.clone(), virtual_user_id.clone());
+
+    // discard admin room creation message
+    create_room_receiver.recv_timeout(default_timeout()).unwrap();
+    // discard spec room creation message
+    create_room_receiver.recv_timeout(default_timeout()).unwrap();
+
+    // the room of the inviter is used, no other room is created
+    assert!(create_room_receiver.recv_timeout(default_timeout()).is_err());
+
+    let spec_room_id = RoomId::try_from("!spec_room_id:localhost").unwrap();
+    let connection = test.connection_pool.get().unwrap();
+    let bridged_room = BridgedRoom::find_by_matrix_room_id(&connection, &spec_room_id).unwrap().unwrap();
+    assert_eq!(bridged_room.rocketchat_server_id, "rcid");
+    assert_eq!(bridged_room.rocketchat_channel_id, "spec_user_id_other_user_id");
+    assert_eq!(bridged_room.room_type(), Some(RoomType::DirectMessage));
+
+    let members = matrix_api.get_room_members(spec_room_id, Some(spec_user_id)).unwrap();
+    assert!(members.iter().any(|member| member.state_key == virtual_user_id.to_string()));
+}
+
+#[test]
+fn inviting_a_virtual_user_into_a_room_with_other_users_does_not_start_a_direct_message() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+
+    let test =
+        test.with_matrix_routes(matrix_router).with_rocketchat_mock().with_connected_admin_room().with_logged_in_user().run();
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard login message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    let matrix_api = MatrixApi::new(&test.config, DEFAULT_LOGGER.clone()).unwrap();
+    let spec_user_id = UserId::try_from("@spec_user:localhost").unwrap();
+    let other_user_id = UserId::try_from("@other_user:localhost").unwrap();
+    let virtual_user_id = UserId::try_from("@rocketchat_rcid_other_user_id:localhost").unwrap();
+    matrix_api.register("rocketchat_rcid_other_user_id".to_string()).unwrap();
+
+    helpers::create_room(&test.config, "spec_room", spec_user_id.clone(), other_user_id.clone());
+    let spec_room_id = RoomId::try_from("!spec_room_id:localhost").unwrap();
+    helpers::join(&test.config, spec_room_id.clone(), other_user_id);
+    helpers::invite(&test.config, spec_room_id.clone(), virtual_user_id.clone(), spec_user_id.clone());
+
+    let connection = test.connection_pool.get().unwrap();
+    assert!(BridgedRoom::find_by_matrix_room_id(&connection, &spec_room_id).unwrap().is_none());
+
+    // the virtual user declined the invite
+    let members = matrix_api.get_room_members(spec_room_id, Some(spec_user_id)).unwrap();
+    let virtual_user_membership =
+        members.iter().find(|member| member.state_key == virtual_user_id.to_string()).map(|member| member.content.membership);
+    assert_eq!(virtual_user_membership, Some(MembershipState::Leave));
+}
+
+#[test]
+fn the_virtual_user_declines_the_invite_when_the_inviter_is_not_logged_in() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+
+    let test = test.with_matrix_routes(matrix_router).with_rocketchat_mock().with_connected_admin_room().run();
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    let matrix_api = MatrixApi::new(&test.config, DEFAULT_LOGGER.clone()).unwrap();
+    let spec_user_id = UserId::try_from("@spec_user:localhost").unwrap();
+    let virtual_user_id = UserId::try_from("@rocketchat_rcid_other_user_id:localhost").unwrap();
+    matrix_api.register("rocketchat_rcid_other_user_id".to_string()).unwrap();
+
+    helpers::create_room(&test.config, "spec_room", spec_user_id.clone(), virtual_user_id.clone());
+
+    let spec_room_id = RoomId::try_from("!spec_room_id:localhost").unwrap();
+    let connection = test.connection_pool.get().unwrap();
+    assert!(BridgedRoom::find_by_matrix_room_id(&connection, &spec_room_id).unwrap().is_none());
+
+    let members = matrix_api.get_room_members(spec_room_id, Some(spec_user_id)).unwrap();
+    let virtual_user_membership =
+        members.iter().find(|member| member.state_key == virtual_user_id.to_string()).map(|member| member.content.membership);
+    assert_eq!(virtual_user_membership, Some(MembershipState::Leave));
+}
+
+#[test]
+fn the_virtual_user_leaves_the_room_when_the_direct_message_cannot_be_created() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+    let mut rocketchat_router = test.default_rocketchat_routes();
+    rocketchat_router.post(
+        IM_CREATE_PATH,
+        handlers::RocketchatErrorResponder { message: "IM create error".to_string(), status: status::InternalServerError },
+        "im_create",
+    );
+
+    let test = test
+        .with_matrix_routes(matrix_router)
+        .with_rocketchat_mock()
+        .with_custom_rocketchat_routes(rocketchat_router)
+        .with_connected_admin_room()
+        .with_logged_in_user()
+        .run();
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard login message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    let matrix_api = MatrixApi::new(&test.config, DEFAULT_LOGGER.clone()).unwrap();
+    let spec_user_id = UserId::try_from("@spec_user:localhost").unwrap();
+    let virtual_user_id = UserId::try_from("@rocketchat_rcid_other_user_id:localhost").unwrap();
+    matrix_api.register("rocketchat_rcid_other_user_id".to_string()).unwrap();
+
+    helpers::create_room(&test.config, "spec_room", spec_user_id.clone(), virtual_user_id.clone());
+
+    let spec_room_id = RoomId::try_from("!spec_room_id:localhost").unwrap();
+    let connection = test.connection_pool.get().unwrap();
+    assert!(BridgedRoom::find_by_matrix_room_id(&connection, &spec_room_id).unwrap().is_none());
+
+    let members = matrix_api.get_room_members(spec_room_id, Some(spec_user_id)).unwrap();
+    let virtual_user_membership =
+        members.iter().find(|member| member.state_key == virtual_user_id.to_string()).map(|member| member.content.membership);
+    assert_eq!(virtual_user_membership, Some(MembershipState::Leave));
+}
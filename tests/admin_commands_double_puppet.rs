@@ -0,0 +1,197 @@
+extern crate iron;
+extern crate matrix_rocketchat;
+extern crate matrix_rocketchat_test;
+extern crate router;
+extern crate ruma_client_api;
+extern crate ruma_identifiers;
+
+use std::convert::TryFrom;
+
+use matrix_rocketchat::models::{RocketchatServer, UserOnRocketchatServer};
+use matrix_rocketchat_test::{default_timeout, helpers, MessageForwarder, Test, RS_TOKEN};
+use ruma_client_api::r0::send::send_message_event::Endpoint as SendMessageEventEndpoint;
+use ruma_client_api::Endpoint;
+use ruma_identifiers::{RoomId, UserId};
+
+#[test]
+fn successfully_stores_the_access_token_for_double_puppeting() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+
+    let test =
+        test.with_matrix_routes(matrix_router).with_rocketchat_mock().with_connected_admin_room().with_logged_in_user().run();
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard login message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!admin_room_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        "double-puppet spec_access_token".to_string(),
+    );
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(message_received_by_matrix.contains("are now sent as your Matrix user"));
+
+    let connection = test.connection_pool.get().unwrap();
+    let server = RocketchatServer::find_by_token(&connection, RS_TOKEN).unwrap().unwrap();
+    let user_id = UserId::try_from("@spec_user:localhost").unwrap();
+    let user_on_rocketchat_server = UserOnRocketchatServer::find(&connection, &user_id, server.id).unwrap();
+    assert_eq!(user_on_rocketchat_server.matrix_access_token, Some("spec_access_token".to_string()));
+}
+
+#[test]
+fn successfully_removes_the_access_token_for_double_puppeting() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+
+    let test =
+        test.with_matrix_routes(matrix_router).with_rocketchat_mock().with_connected_admin_room().with_logged_in_user().run();
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard login message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!admin_room_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        "double-puppet spec_access_token".to_string(),
+    );
+
+    // discard double puppeting enabled message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!admin_room_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        "double-puppet disable".to_string(),
+    );
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(message_received_by_matrix.contains("Your access token was removed"));
+
+    let connection = test.connection_pool.get().unwrap();
+    let server = RocketchatServer::find_by_token(&connection, RS_TOKEN).unwrap().unwrap();
+    let user_id = UserId::try_from("@spec_user:localhost").unwrap();
+    let user_on_rocketchat_server = UserOnRocketchatServer::find(&connection, &user_id, server.id).unwrap();
+    assert_eq!(user_on_rocketchat_server.matrix_access_token, None);
+}
+
+#[test]
+fn the_double_puppet_command_is_redacted() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let (redact_forwarder, redact_receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+    matrix_router.put("/_matrix/client/r0/rooms/:room_id/redact/:event_id/:txn_id", redact_forwarder, "redact_event");
+
+    let test =
+        test.with_matrix_routes(matrix_router).with_rocketchat_mock().with_connected_admin_room().with_logged_in_user().run();
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard login message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard login redaction
+    redact_receiver.recv_timeout(default_timeout()).unwrap();
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!admin_room_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        "double-puppet spec_access_token".to_string(),
+    );
+
+    let redaction_received_by_matrix = redact_receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(redaction_received_by_matrix.contains("The message contains credentials"));
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(message_received_by_matrix.contains("are now sent as your Matrix user"));
+    assert!(message_received_by_matrix.contains("Your message was redacted, because it contained your credentials."));
+}
+
+#[test]
+fn attempt_to_store_an_access_token_that_belongs_to_another_user() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+
+    let test =
+        test.with_matrix_routes(matrix_router).with_rocketchat_mock().with_connected_admin_room().with_logged_in_user().run();
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard login message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!admin_room_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        "double-puppet other_access_token".to_string(),
+    );
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(message_received_by_matrix.contains("The access token is not valid for @spec_user:localhost"));
+    assert!(message_received_by_matrix.contains("Your message was redacted, because it contained your credentials."));
+
+    let connection = test.connection_pool.get().unwrap();
+    let server = RocketchatServer::find_by_token(&connection, RS_TOKEN).unwrap().unwrap();
+    let user_id = UserId::try_from("@spec_user:localhost").unwrap();
+    let user_on_rocketchat_server = UserOnRocketchatServer::find(&connection, &user_id, server.id).unwrap();
+    assert_eq!(user_on_rocketchat_server.matrix_access_token, None);
+}
+
+#[test]
+fn attempt_to_store_an_invalid_access_token() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+
+    let test =
+        test.with_matrix_routes(matrix_router).with_rocketchat_mock().with_connected_admin_room().with_logged_in_user().run();
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard login message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!admin_room_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        "double-puppet not_a_token".to_string(),
+    );
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(message_received_by_matrix.contains("The access token is not valid for @spec_user:localhost"));
+
+    let connection = test.connection_pool.get().unwrap();
+    let server = RocketchatServer::find_by_token(&connection, RS_TOKEN).unwrap().unwrap();
+    let user_id = UserId::try_from("@spec_user:localhost").unwrap();
+    let user_on_rocketchat_server = UserOnRocketchatServer::find(&connection, &user_id, server.id).unwrap();
+    assert_eq!(user_on_rocketchat_server.matrix_access_token, None);
+}
@@ -158,7 +158,7 @@ fn help_command_when_logged_in() {
     receiver.recv_timeout(default_timeout()).unwrap();
 
     let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
-    assert!(message_received_by_matrix.contains("`list` Lists all public rooms from the Rocket.Chat server"));
+    assert!(message_received_by_matrix.contains("`list [filter] [--joined] [--bridged] [--page N]` Lists the public rooms"));
     assert!(message_received_by_matrix.contains("`bridge rocketchatroomnname` Bridge a Rocket.Chat room"));
     assert!(message_received_by_matrix.contains(
         "`unbridge rocketchatroomnname` Unbridge a Rocket.Chat room \
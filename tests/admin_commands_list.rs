@@ -72,6 +72,353 @@ fn sucessfully_list_rocketchat_rooms() {
     assert!(message_received_by_matrix.contains("**bridged_channel**"));
 }
 
+#[test]
+fn the_room_list_is_sorted_and_contains_member_counts_and_topics() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+    let mut rocketchat_router = test.default_rocketchat_routes();
+    let channels = test.channel_list();
+    channels.lock().unwrap().insert("normal_channel", Vec::new());
+    channels.lock().unwrap().insert("joined_channel", vec!["spec_user"]);
+    channels.lock().unwrap().insert("bridged_channel", vec!["spec_user", "other_user"]);
+    let mut users_in_rooms = HashMap::new();
+    users_in_rooms.insert("spec_user_id", vec!["joined_channel", "bridged_channel"]);
+    rocketchat_router.get(
+        CHANNELS_LIST_JOINED_PATH,
+        handlers::RocketchatJoinedRooms { users_in_rooms: users_in_rooms },
+        "joined_channels",
+    );
+
+    let test = test
+        .with_matrix_routes(matrix_router)
+        .with_rocketchat_mock()
+        .with_custom_rocketchat_routes(rocketchat_router)
+        .with_connected_admin_room()
+        .with_logged_in_user()
+        .with_bridged_room(("bridged_channel", vec!["spec_user", "other_user"]))
+        .run();
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard login message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard bridge message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!admin_room_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        "list".to_string(),
+    );
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    let bridged_channel_position = message_received_by_matrix.find("**bridged_channel**").unwrap();
+    let joined_channel_position = message_received_by_matrix.find("*joined_channel*").unwrap();
+    let normal_channel_position = message_received_by_matrix.find("normal_channel").unwrap();
+    assert!(bridged_channel_position < joined_channel_position);
+    assert!(joined_channel_position < normal_channel_position);
+    assert!(message_received_by_matrix.contains("**bridged_channel** (2 members) - bridged_channel topic"));
+}
+
+#[test]
+fn filter_the_room_list_by_name() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+    let mut rocketchat_router = test.default_rocketchat_routes();
+    let channels = test.channel_list();
+    channels.lock().unwrap().insert("normal_channel", Vec::new());
+    channels.lock().unwrap().insert("joined_channel", vec!["spec_user"]);
+    channels.lock().unwrap().insert("bridged_channel", vec!["spec_user", "other_user"]);
+    let mut users_in_rooms = HashMap::new();
+    users_in_rooms.insert("spec_user_id", vec!["joined_channel", "bridged_channel"]);
+    rocketchat_router.get(
+        CHANNELS_LIST_JOINED_PATH,
+        handlers::RocketchatJoinedRooms { users_in_rooms: users_in_rooms },
+        "joined_channels",
+    );
+
+    let test = test
+        .with_matrix_routes(matrix_router)
+        .with_rocketchat_mock()
+        .with_custom_rocketchat_routes(rocketchat_router)
+        .with_connected_admin_room()
+        .with_logged_in_user()
+        .with_bridged_room(("bridged_channel", vec!["spec_user", "other_user"]))
+        .run();
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard login message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard bridge message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!admin_room_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        "list JOINED".to_string(),
+    );
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(message_received_by_matrix.contains("*joined_channel*"));
+    assert!(!message_received_by_matrix.contains("normal_channel"));
+    assert!(!message_received_by_matrix.contains("bridged_channel"));
+}
+
+#[test]
+fn only_list_joined_rooms() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+    let mut rocketchat_router = test.default_rocketchat_routes();
+    let channels = test.channel_list();
+    channels.lock().unwrap().insert("normal_channel", Vec::new());
+    channels.lock().unwrap().insert("joined_channel", vec!["spec_user"]);
+    channels.lock().unwrap().insert("bridged_channel", vec!["spec_user", "other_user"]);
+    let mut users_in_rooms = HashMap::new();
+    users_in_rooms.insert("spec_user_id", vec!["joined_channel", "bridged_channel"]);
+    rocketchat_router.get(
+        CHANNELS_LIST_JOINED_PATH,
+        handlers::RocketchatJoinedRooms { users_in_rooms: users_in_rooms },
+        "joined_channels",
+    );
+
+    let test = test
+        .with_matrix_routes(matrix_router)
+        .with_rocketchat_mock()
+        .with_custom_rocketchat_routes(rocketchat_router)
+        .with_connected_admin_room()
+        .with_logged_in_user()
+        .with_bridged_room(("bridged_channel", vec!["spec_user", "other_user"]))
+        .run();
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard login message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard bridge message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!admin_room_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        "list --joined".to_string(),
+    );
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(message_received_by_matrix.contains("*joined_channel*"));
+    assert!(message_received_by_matrix.contains("**bridged_channel**"));
+    assert!(!message_received_by_matrix.contains("normal_channel"));
+}
+
+#[test]
+fn only_list_bridged_rooms() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+    let mut rocketchat_router = test.default_rocketchat_routes();
+    let channels = test.channel_list();
+    channels.lock().unwrap().insert("normal_channel", Vec::new());
+    channels.lock().unwrap().insert("joined_channel", vec!["spec_user"]);
+    channels.lock().unwrap().insert("bridged_channel", vec!["spec_user", "other_user"]);
+    let mut users_in_rooms = HashMap::new();
+    users_in_rooms.insert("spec_user_id", vec!["joined_channel", "bridged_channel"]);
+    rocketchat_router.get(
+        CHANNELS_LIST_JOINED_PATH,
+        handlers::RocketchatJoinedRooms { users_in_rooms: users_in_rooms },
+        "joined_channels",
+    );
+
+    let test = test
+        .with_matrix_routes(matrix_router)
+        .with_rocketchat_mock()
+        .with_custom_rocketchat_routes(rocketchat_router)
+        .with_connected_admin_room()
+        .with_logged_in_user()
+        .with_bridged_room(("bridged_channel", vec!["spec_user", "other_user"]))
+        .run();
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard login message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard bridge message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!admin_room_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        "list channel --bridged".to_string(),
+    );
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(message_received_by_matrix.contains("**bridged_channel**"));
+    assert!(!message_received_by_matrix.contains("joined_channel"));
+    assert!(!message_received_by_matrix.contains("normal_channel"));
+}
+
+#[test]
+fn the_user_gets_a_message_when_no_room_matches_the_filter() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+    let mut rocketchat_router = test.default_rocketchat_routes();
+    let channels = test.channel_list();
+    channels.lock().unwrap().insert("normal_channel", Vec::new());
+    channels.lock().unwrap().insert("joined_channel", vec!["spec_user"]);
+    channels.lock().unwrap().insert("bridged_channel", vec!["spec_user", "other_user"]);
+    let mut users_in_rooms = HashMap::new();
+    users_in_rooms.insert("spec_user_id", vec!["joined_channel", "bridged_channel"]);
+    rocketchat_router.get(
+        CHANNELS_LIST_JOINED_PATH,
+        handlers::RocketchatJoinedRooms { users_in_rooms: users_in_rooms },
+        "joined_channels",
+    );
+
+    let test = test
+        .with_matrix_routes(matrix_router)
+        .with_rocketchat_mock()
+        .with_custom_rocketchat_routes(rocketchat_router)
+        .with_connected_admin_room()
+        .with_logged_in_user()
+        .with_bridged_room(("bridged_channel", vec!["spec_user", "other_user"]))
+        .run();
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard login message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard bridge message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!admin_room_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        "list nonexisting".to_string(),
+    );
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(message_received_by_matrix.contains("No rooms found."));
+}
+
+#[test]
+fn the_user_gets_a_message_when_the_page_does_not_exist() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+    let mut rocketchat_router = test.default_rocketchat_routes();
+    let channels = test.channel_list();
+    channels.lock().unwrap().insert("normal_channel", Vec::new());
+    channels.lock().unwrap().insert("joined_channel", vec!["spec_user"]);
+    channels.lock().unwrap().insert("bridged_channel", vec!["spec_user", "other_user"]);
+    let mut users_in_rooms = HashMap::new();
+    users_in_rooms.insert("spec_user_id", vec!["joined_channel", "bridged_channel"]);
+    rocketchat_router.get(
+        CHANNELS_LIST_JOINED_PATH,
+        handlers::RocketchatJoinedRooms { users_in_rooms: users_in_rooms },
+        "joined_channels",
+    );
+
+    let test = test
+        .with_matrix_routes(matrix_router)
+        .with_rocketchat_mock()
+        .with_custom_rocketchat_routes(rocketchat_router)
+        .with_connected_admin_room()
+        .with_logged_in_user()
+        .with_bridged_room(("bridged_channel", vec!["spec_user", "other_user"]))
+        .run();
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard login message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard bridge message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!admin_room_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        "list --page 2".to_string(),
+    );
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(message_received_by_matrix.contains("Page 2 doesn't exist, the list has 1 pages."));
+}
+
+#[test]
+fn the_user_gets_the_usage_when_the_page_is_not_a_number() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+    let mut rocketchat_router = test.default_rocketchat_routes();
+    let channels = test.channel_list();
+    channels.lock().unwrap().insert("normal_channel", Vec::new());
+    channels.lock().unwrap().insert("joined_channel", vec!["spec_user"]);
+    channels.lock().unwrap().insert("bridged_channel", vec!["spec_user", "other_user"]);
+    let mut users_in_rooms = HashMap::new();
+    users_in_rooms.insert("spec_user_id", vec!["joined_channel", "bridged_channel"]);
+    rocketchat_router.get(
+        CHANNELS_LIST_JOINED_PATH,
+        handlers::RocketchatJoinedRooms { users_in_rooms: users_in_rooms },
+        "joined_channels",
+    );
+
+    let test = test
+        .with_matrix_routes(matrix_router)
+        .with_rocketchat_mock()
+        .with_custom_rocketchat_routes(rocketchat_router)
+        .with_connected_admin_room()
+        .with_logged_in_user()
+        .with_bridged_room(("bridged_channel", vec!["spec_user", "other_user"]))
+        .run();
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard login message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard bridge message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!admin_room_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        "list --page first".to_string(),
+    );
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(message_received_by_matrix.contains("Invalid arguments for `list`"));
+}
+
 #[test]
 fn the_user_gets_a_message_when_getting_room_list_failes() {
     let test = Test::new();
@@ -16,7 +16,7 @@ use http::Method;
 use iron::status;
 use matrix_rocketchat::api::rocketchat::v1::LOGIN_PATH;
 use matrix_rocketchat::api::{MatrixApi, RequestData, RestApi};
-use matrix_rocketchat::models::Credentials;
+use matrix_rocketchat::models::{Credentials, LoginRequest, LoginToken};
 use matrix_rocketchat::models::{RocketchatServer, UserOnRocketchatServer};
 use matrix_rocketchat_test::{default_timeout, handlers, helpers, MessageForwarder, Test, DEFAULT_LOGGER};
 use reqwest::StatusCode;
@@ -56,6 +56,151 @@ fn sucessfully_login_via_chat_mesage() {
     assert!(message_received_by_matrix.contains("You are logged in."));
 }
 
+#[test]
+fn sucessfully_login_with_a_personal_access_token_via_chat_mesage() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+    let test = test.with_matrix_routes(matrix_router).with_rocketchat_mock().with_connected_admin_room().run();
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!admin_room_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        "login-token spec_user_id spec_personal_access_token".to_string(),
+    );
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(message_received_by_matrix.contains("You are logged in."));
+
+    let connection = test.connection_pool.get().unwrap();
+    let rocketchat_server = RocketchatServer::find(&connection, &test.rocketchat_mock_url.clone().unwrap()).unwrap();
+    let user_on_rocketchat_server =
+        UserOnRocketchatServer::find(&connection, &UserId::try_from("@spec_user:localhost").unwrap(), rocketchat_server.id)
+            .unwrap();
+    assert_eq!(user_on_rocketchat_server.rocketchat_user_id.unwrap(), "spec_user_id");
+    assert_eq!(user_on_rocketchat_server.rocketchat_auth_token.unwrap(), "spec_personal_access_token");
+}
+
+#[test]
+fn personal_access_token_that_belongs_to_another_user_when_logging_in_via_chat_message() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+    let test = test.with_matrix_routes(matrix_router).with_rocketchat_mock().with_connected_admin_room().run();
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!admin_room_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        "login-token other_user_id spec_personal_access_token".to_string(),
+    );
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(message_received_by_matrix.contains("The personal access token is not valid for the given user ID"));
+
+    let connection = test.connection_pool.get().unwrap();
+    let rocketchat_server = RocketchatServer::find(&connection, &test.rocketchat_mock_url.clone().unwrap()).unwrap();
+    let user_on_rocketchat_server =
+        UserOnRocketchatServer::find(&connection, &UserId::try_from("@spec_user:localhost").unwrap(), rocketchat_server.id)
+            .unwrap();
+    assert!(user_on_rocketchat_server.rocketchat_auth_token.is_none());
+}
+
+#[test]
+fn sucessfully_login_with_two_factor_authentication_via_chat_message() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+    let mut rocketchat_router = test.default_rocketchat_routes();
+    rocketchat_router.post(LOGIN_PATH, handlers::RocketchatTwoFactorLogin { code: "123456" }, "login");
+    let test = test
+        .with_matrix_routes(matrix_router)
+        .with_rocketchat_mock()
+        .with_custom_rocketchat_routes(rocketchat_router)
+        .with_connected_admin_room()
+        .run();
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!admin_room_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        "login spec_user secret".to_string(),
+    );
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(message_received_by_matrix.contains("Your Rocket.Chat account uses two-factor authentication"));
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!admin_room_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        "2fa 000000".to_string(),
+    );
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(message_received_by_matrix.contains("The two-factor authentication code is not valid"));
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!admin_room_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        "2fa 123456".to_string(),
+    );
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(message_received_by_matrix.contains("You are logged in."));
+
+    let connection = test.connection_pool.get().unwrap();
+    let rocketchat_server = RocketchatServer::find(&connection, &test.rocketchat_mock_url.clone().unwrap()).unwrap();
+    let user_on_rocketchat_server =
+        UserOnRocketchatServer::find(&connection, &UserId::try_from("@spec_user:localhost").unwrap(), rocketchat_server.id)
+            .unwrap();
+    assert_eq!(user_on_rocketchat_server.rocketchat_auth_token.unwrap(), "spec_auth_token");
+}
+
+#[test]
+fn sending_a_two_factor_authentication_code_without_a_pending_login_returns_an_error() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+    let test = test.with_matrix_routes(matrix_router).with_rocketchat_mock().with_connected_admin_room().run();
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!admin_room_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        "2fa 123456".to_string(),
+    );
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(message_received_by_matrix.contains("There is no login that is waiting for a two-factor authentication code"));
+}
+
 #[test]
 fn wrong_password_when_logging_in_via_chat_message() {
     let test = Test::new();
@@ -91,6 +236,211 @@ fn wrong_password_when_logging_in_via_chat_message() {
     assert!(message_received_by_matrix.contains("Authentication failed!"));
 }
 
+#[test]
+fn the_login_command_is_redacted_after_a_successful_login() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let (redact_forwarder, redact_receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+    matrix_router.put("/_matrix/client/r0/rooms/:room_id/redact/:event_id/:txn_id", redact_forwarder, "redact_event");
+    let test = test.with_matrix_routes(matrix_router).with_rocketchat_mock().with_connected_admin_room().run();
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!admin_room_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        "login spec_user secret".to_string(),
+    );
+
+    let redaction_received_by_matrix = redact_receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(redaction_received_by_matrix.contains("The message contains credentials"));
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(message_received_by_matrix.contains("You are logged in."));
+    assert!(message_received_by_matrix.contains("Your message was redacted, because it contained your credentials."));
+}
+
+#[test]
+fn mistyped_login_commands_are_redacted() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let (redact_forwarder, redact_receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+    matrix_router.put("/_matrix/client/r0/rooms/:room_id/redact/:event_id/:txn_id", redact_forwarder, "redact_event");
+    let test = test.with_matrix_routes(matrix_router).with_rocketchat_mock().with_connected_admin_room().run();
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    let messages =
+        vec!["LOGIN spec_user secret", "\"login\" spec_user secret", "loginn spec_user secret", "login spec_user \"secret"];
+    for message in messages {
+        helpers::send_room_message_from_matrix(
+            &test.config.as_url,
+            RoomId::try_from("!admin_room_id:localhost").unwrap(),
+            UserId::try_from("@spec_user:localhost").unwrap(),
+            message.to_string(),
+        );
+
+        let redaction_received_by_matrix = redact_receiver.recv_timeout(default_timeout()).unwrap();
+        assert!(redaction_received_by_matrix.contains("The message contains credentials"));
+
+        let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+        assert!(message_received_by_matrix.contains("Your message was redacted, because it contained your credentials."));
+    }
+}
+
+#[test]
+fn passwords_can_contain_quotes() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let (login_forwarder, login_receiver) = MessageForwarder::new();
+    let mut rocketchat_router = test.default_rocketchat_routes();
+    rocketchat_router.post(LOGIN_PATH, login_forwarder, "login");
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+    let test = test
+        .with_matrix_routes(matrix_router)
+        .with_rocketchat_mock()
+        .with_custom_rocketchat_routes(rocketchat_router)
+        .with_connected_admin_room()
+        .run();
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!admin_room_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        "login spec_user se\"cr'et".to_string(),
+    );
+
+    let login_received_by_rocketchat = login_receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(login_received_by_rocketchat.contains(r#""password":"se\"cr'et""#));
+}
+
+#[test]
+fn the_login_command_is_redacted_when_the_login_fails() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let (redact_forwarder, redact_receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+    matrix_router.put("/_matrix/client/r0/rooms/:room_id/redact/:event_id/:txn_id", redact_forwarder, "redact_event");
+    let mut rocketchat_router = test.default_rocketchat_routes();
+    rocketchat_router.post(
+        LOGIN_PATH,
+        handlers::RocketchatLogin { successful: false, rocketchat_user_id: Arc::new(Mutex::new(None)) },
+        "login",
+    );
+    let test = test
+        .with_matrix_routes(matrix_router)
+        .with_custom_rocketchat_routes(rocketchat_router)
+        .with_rocketchat_mock()
+        .with_connected_admin_room()
+        .run();
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!admin_room_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        "login spec_user wrong_password".to_string(),
+    );
+
+    redact_receiver.recv_timeout(default_timeout()).unwrap();
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(message_received_by_matrix.contains("Authentication failed!"));
+    assert!(message_received_by_matrix.contains("Your message was redacted, because it contained your credentials."));
+}
+
+#[test]
+fn the_user_is_told_that_the_login_command_is_redacted_when_an_internal_error_occurs() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let (redact_forwarder, redact_receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+    matrix_router.put("/_matrix/client/r0/rooms/:room_id/redact/:event_id/:txn_id", redact_forwarder, "redact_event");
+    let mut rocketchat_router = test.default_rocketchat_routes();
+    rocketchat_router.post(
+        LOGIN_PATH,
+        handlers::MatrixErrorResponder { status: status::InternalServerError, message: "Something went wrong".to_string() },
+        "login",
+    );
+    let test = test
+        .with_matrix_routes(matrix_router)
+        .with_custom_rocketchat_routes(rocketchat_router)
+        .with_rocketchat_mock()
+        .with_connected_admin_room()
+        .run();
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!admin_room_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        "login spec_user secret".to_string(),
+    );
+
+    redact_receiver.recv_timeout(default_timeout()).unwrap();
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(message_received_by_matrix.contains("An internal error occurred"));
+    assert!(message_received_by_matrix.contains("Your message was redacted, because it contained your credentials."));
+}
+
+#[test]
+fn the_user_is_told_when_the_login_command_cannot_be_redacted() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+    matrix_router.put(
+        "/_matrix/client/r0/rooms/:room_id/redact/:event_id/:txn_id",
+        handlers::MatrixErrorResponder { status: status::Forbidden, message: "Insufficient power level".to_string() },
+        "redact_event",
+    );
+    let test = test.with_matrix_routes(matrix_router).with_rocketchat_mock().with_connected_admin_room().run();
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!admin_room_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        "login spec_user secret".to_string(),
+    );
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(message_received_by_matrix.contains("You are logged in."));
+    assert!(message_received_by_matrix.contains("it could not be redacted"));
+}
+
 #[test]
 fn login_multiple_times_via_chat_message() {
     let test = Test::new();
@@ -121,11 +471,17 @@ fn login_multiple_times_via_chat_message() {
 fn sucessfully_login_via_rest_api() {
     let test = Test::new().with_rocketchat_mock().with_connected_admin_room().run();
 
-    let login_request = Credentials {
-        user_id: UserId::try_from("@spec_user:localhost").unwrap(),
-        rocketchat_username: "spec_user".to_string(),
-        password: "secret".to_string(),
-        rocketchat_url: test.rocketchat_mock_url.clone().unwrap(),
+    let login_request = LoginRequest {
+        token: create_login_token(&test),
+        credentials: Credentials {
+            user_id: UserId::try_from("@spec_user:localhost").unwrap(),
+            rocketchat_username: "spec_user".to_string(),
+            password: "secret".to_string(),
+            rocketchat_user_id: None,
+            personal_access_token: None,
+            code: None,
+            rocketchat_url: test.rocketchat_mock_url.clone().unwrap(),
+        },
     };
     let payload = to_string(&login_request).unwrap();
     let (response, status_code) = RestApi::call(
@@ -151,6 +507,33 @@ fn sucessfully_login_via_rest_api() {
     assert_eq!(user_on_rocketchat_server.rocketchat_auth_token.unwrap(), "spec_auth_token");
 }
 
+#[test]
+fn sucessfully_login_with_a_personal_access_token_via_rest_api() {
+    let test = Test::new().with_rocketchat_mock().with_connected_admin_room().run();
+
+    let payload = r#"{"token": "LOGIN_TOKEN", "user_id": "@spec_user:localhost", "rocketchat_user_id": "spec_user_id", "personal_access_token": "spec_personal_access_token", "rocketchat_url": "ROCKETCHAT_URL"}"#
+        .replace("LOGIN_TOKEN", &create_login_token(&test))
+        .replace("ROCKETCHAT_URL", &test.rocketchat_mock_url.clone().unwrap());
+    let (response, status_code) = RestApi::call(
+        &Method::POST,
+        &format!("http://{}/rocketchat/login", test.as_listening.as_ref().unwrap().socket),
+        RequestData::Body(payload),
+        &HashMap::new(),
+        None,
+    )
+    .unwrap();
+
+    assert!(response.contains("You are logged in."));
+    assert!(status_code.is_success());
+
+    let connection = test.connection_pool.get().unwrap();
+    let rocketchat_server = RocketchatServer::find(&connection, &test.rocketchat_mock_url.clone().unwrap()).unwrap();
+    let user_on_rocketchat_server =
+        UserOnRocketchatServer::find(&connection, &UserId::try_from("@spec_user:localhost").unwrap(), rocketchat_server.id)
+            .unwrap();
+    assert_eq!(user_on_rocketchat_server.rocketchat_auth_token.unwrap(), "spec_personal_access_token");
+}
+
 #[test]
 fn wrong_password_when_logging_in_via_rest_api() {
     let test = Test::new();
@@ -163,11 +546,17 @@ fn wrong_password_when_logging_in_via_rest_api() {
     );
     let test = test.with_custom_rocketchat_routes(rocketchat_router).with_rocketchat_mock().with_connected_admin_room().run();
 
-    let login_request = Credentials {
-        user_id: UserId::try_from("@spec_user:localhost").unwrap(),
-        rocketchat_username: "spec_user".to_string(),
-        password: "wrong_password".to_string(),
-        rocketchat_url: test.rocketchat_mock_url.clone().unwrap(),
+    let login_request = LoginRequest {
+        token: create_login_token(&test),
+        credentials: Credentials {
+            user_id: UserId::try_from("@spec_user:localhost").unwrap(),
+            rocketchat_username: "spec_user".to_string(),
+            password: "wrong_password".to_string(),
+            rocketchat_user_id: None,
+            personal_access_token: None,
+            code: None,
+            rocketchat_url: test.rocketchat_mock_url.clone().unwrap(),
+        },
     };
     let payload = to_string(&login_request).unwrap();
     let (response, status_code) = RestApi::call(
@@ -182,6 +571,46 @@ fn wrong_password_when_logging_in_via_rest_api() {
     assert_eq!(status_code, StatusCode::UNAUTHORIZED);
 }
 
+#[test]
+fn a_login_token_cannot_be_used_again_after_a_failed_login_via_rest_api() {
+    let test = Test::new();
+
+    let mut rocketchat_router = test.default_rocketchat_routes();
+    rocketchat_router.post(
+        LOGIN_PATH,
+        handlers::RocketchatLogin { successful: false, rocketchat_user_id: Arc::new(Mutex::new(None)) },
+        "login",
+    );
+    let test = test.with_custom_rocketchat_routes(rocketchat_router).with_rocketchat_mock().with_connected_admin_room().run();
+
+    let mut login_request = LoginRequest {
+        token: create_login_token(&test),
+        credentials: Credentials {
+            user_id: UserId::try_from("@spec_user:localhost").unwrap(),
+            rocketchat_username: "spec_user".to_string(),
+            password: "wrong_password".to_string(),
+            rocketchat_user_id: None,
+            personal_access_token: None,
+            code: None,
+            rocketchat_url: test.rocketchat_mock_url.clone().unwrap(),
+        },
+    };
+    let url = format!("http://{}/rocketchat/login", test.as_listening.as_ref().unwrap().socket);
+    let payload = to_string(&login_request).unwrap();
+    let (response, status_code) =
+        RestApi::call(&Method::POST, &url, RequestData::Body(payload), &HashMap::new(), None).unwrap();
+    assert!(response.contains("Authentication failed!"));
+    assert!(response.contains("The login token was used up by this attempt"));
+    assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+
+    login_request.credentials.password = "other_password".to_string();
+    let payload = to_string(&login_request).unwrap();
+    let (response, status_code) =
+        RestApi::call(&Method::POST, &url, RequestData::Body(payload), &HashMap::new(), None).unwrap();
+    assert!(response.contains("The login token is invalid or expired"));
+    assert_eq!(status_code, StatusCode::FORBIDDEN);
+}
+
 #[test]
 fn login_multiple_times_via_rest_message() {
     let test = Test::new();
@@ -195,19 +624,25 @@ fn login_multiple_times_via_rest_message() {
     // discard connect message
     receiver.recv_timeout(default_timeout()).unwrap();
 
-    let login_request = Credentials {
-        user_id: UserId::try_from("@spec_user:localhost").unwrap(),
-        rocketchat_username: "spec_user".to_string(),
-        password: "secret".to_string(),
-        rocketchat_url: test.rocketchat_mock_url.clone().unwrap(),
-    };
-    let payload = to_string(&login_request).unwrap();
-
     for _ in 0..2 {
+        let login_request = LoginRequest {
+            token: create_login_token(&test),
+            credentials: Credentials {
+                user_id: UserId::try_from("@spec_user:localhost").unwrap(),
+                rocketchat_username: "spec_user".to_string(),
+                password: "secret".to_string(),
+                rocketchat_user_id: None,
+                personal_access_token: None,
+                code: None,
+                rocketchat_url: test.rocketchat_mock_url.clone().unwrap(),
+            },
+        };
+        let payload = to_string(&login_request).unwrap();
+
         let (response, status_code) = RestApi::call(
             &Method::POST,
             &format!("http://{}/rocketchat/login", test.as_listening.as_ref().unwrap().socket),
-            RequestData::Body(payload.clone()),
+            RequestData::Body(payload),
             &HashMap::new(),
             None,
         )
@@ -240,11 +675,17 @@ fn login_via_rest_api_with_invalid_payload() {
 fn login_via_rest_api_with_a_non_existing_rocketchat_server() {
     let test = Test::new().run();
 
-    let login_request = Credentials {
-        user_id: UserId::try_from("@spec_user:localhost").unwrap(),
-        rocketchat_username: "spec_user".to_string(),
-        password: "secret".to_string(),
-        rocketchat_url: "http://nonexisting.foo".to_string(),
+    let login_request = LoginRequest {
+        token: "some_token".to_string(),
+        credentials: Credentials {
+            user_id: UserId::try_from("@spec_user:localhost").unwrap(),
+            rocketchat_username: "spec_user".to_string(),
+            password: "secret".to_string(),
+            rocketchat_user_id: None,
+            personal_access_token: None,
+            code: None,
+            rocketchat_url: "http://nonexisting.foo".to_string(),
+        },
     };
     let payload = to_string(&login_request).unwrap();
 
@@ -261,6 +702,69 @@ fn login_via_rest_api_with_a_non_existing_rocketchat_server() {
     assert_eq!(status_code, StatusCode::NOT_FOUND);
 }
 
+#[test]
+fn login_via_rest_api_without_a_valid_login_token() {
+    let test = Test::new().with_rocketchat_mock().with_connected_admin_room().run();
+
+    let payload = r#"{"token": "invalid_token", "user_id": "@spec_user:localhost", "rocketchat_username": "spec_user", "password": "secret", "rocketchat_url": "ROCKETCHAT_URL"}"#
+        .replace("ROCKETCHAT_URL", &test.rocketchat_mock_url.clone().unwrap());
+    let (response, status_code) = RestApi::call(
+        &Method::POST,
+        &format!("http://{}/rocketchat/login", test.as_listening.as_ref().unwrap().socket),
+        RequestData::Body(payload),
+        &HashMap::new(),
+        None,
+    )
+    .unwrap();
+    assert!(response.contains("The login token is invalid or expired"));
+    assert_eq!(status_code, StatusCode::FORBIDDEN);
+
+    let connection = test.connection_pool.get().unwrap();
+    let rocketchat_server = RocketchatServer::find(&connection, &test.rocketchat_mock_url.clone().unwrap()).unwrap();
+    let user_on_rocketchat_server =
+        UserOnRocketchatServer::find(&connection, &UserId::try_from("@spec_user:localhost").unwrap(), rocketchat_server.id)
+            .unwrap();
+    assert!(user_on_rocketchat_server.rocketchat_auth_token.is_none());
+}
+
+#[test]
+fn a_login_token_can_only_be_used_once_via_rest_api() {
+    let test = Test::new().with_rocketchat_mock().with_connected_admin_room().run();
+
+    let payload = r#"{"token": "LOGIN_TOKEN", "user_id": "@spec_user:localhost", "rocketchat_username": "spec_user", "password": "secret", "rocketchat_url": "ROCKETCHAT_URL"}"#
+        .replace("LOGIN_TOKEN", &create_login_token(&test))
+        .replace("ROCKETCHAT_URL", &test.rocketchat_mock_url.clone().unwrap());
+    let login_url = format!("http://{}/rocketchat/login", test.as_listening.as_ref().unwrap().socket);
+
+    let (_, status_code) =
+        RestApi::call(&Method::POST, &login_url, RequestData::Body(payload.clone()), &HashMap::new(), None).unwrap();
+    assert!(status_code.is_success());
+
+    let (response, status_code) =
+        RestApi::call(&Method::POST, &login_url, RequestData::Body(payload), &HashMap::new(), None).unwrap();
+    assert!(response.contains("The login token is invalid or expired"));
+    assert_eq!(status_code, StatusCode::FORBIDDEN);
+}
+
+#[test]
+fn login_via_rest_api_with_a_login_token_that_was_issued_for_another_user() {
+    let test = Test::new().with_rocketchat_mock().with_connected_admin_room().run();
+
+    let payload = r#"{"token": "LOGIN_TOKEN", "user_id": "@other_user:localhost", "rocketchat_username": "spec_user", "password": "secret", "rocketchat_url": "ROCKETCHAT_URL"}"#
+        .replace("LOGIN_TOKEN", &create_login_token(&test))
+        .replace("ROCKETCHAT_URL", &test.rocketchat_mock_url.clone().unwrap());
+    let (response, status_code) = RestApi::call(
+        &Method::POST,
+        &format!("http://{}/rocketchat/login", test.as_listening.as_ref().unwrap().socket),
+        RequestData::Body(payload),
+        &HashMap::new(),
+        None,
+    )
+    .unwrap();
+    assert!(response.contains("The login token was not issued for @other_user:localhost"));
+    assert_eq!(status_code, StatusCode::FORBIDDEN);
+}
+
 #[test]
 fn the_user_can_login_again_on_the_same_server_with_a_new_admin_room() {
     let test = Test::new();
@@ -438,3 +942,15 @@ fn attempt_to_login_when_the_admin_room_is_not_connected() {
     let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
     assert!(message_received_by_matrix.contains("This room is not connected to a Rocket.Chat server"));
 }
+
+fn create_login_token(test: &Test) -> String {
+    let connection = test.connection_pool.get().unwrap();
+    let rocketchat_server = RocketchatServer::find(&connection, &test.rocketchat_mock_url.clone().unwrap()).unwrap();
+    LoginToken::create(
+        &connection,
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        rocketchat_server.id,
+        RoomId::try_from("!admin_room_id:localhost").unwrap(),
+    )
+    .unwrap()
+}
@@ -0,0 +1,294 @@
+extern crate iron;
+extern crate matrix_rocketchat;
+extern crate matrix_rocketchat_test;
+extern crate router;
+extern crate ruma_client_api;
+extern crate ruma_identifiers;
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+use matrix_rocketchat::api::rocketchat::v1::{CHANNELS_LIST_JOINED_PATH, LOGOUT_PATH};
+use matrix_rocketchat::api::MatrixApi;
+use matrix_rocketchat::models::{RocketchatServer, UserOnRocketchatServer};
+use matrix_rocketchat_test::{default_timeout, handlers, helpers, MessageForwarder, Test, DEFAULT_LOGGER, RS_TOKEN};
+use ruma_client_api::r0::send::send_message_event::Endpoint as SendMessageEventEndpoint;
+use ruma_client_api::Endpoint;
+use ruma_identifiers::{RoomId, UserId};
+
+#[test]
+fn successfully_logout_from_the_rocketchat_server() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+    let (logout_forwarder, logout_receiver) = MessageForwarder::new();
+    let mut rocketchat_router = test.default_rocketchat_routes();
+    rocketchat_router.post(LOGOUT_PATH, logout_forwarder, "logout");
+
+    let test = test
+        .with_matrix_routes(matrix_router)
+        .with_rocketchat_mock()
+        .with_custom_rocketchat_routes(rocketchat_router)
+        .with_connected_admin_room()
+        .with_logged_in_user()
+        .run();
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard login message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!admin_room_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        "logout".to_string(),
+    );
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(message_received_by_matrix.contains("You are logged out from the Rocket.Chat server"));
+
+    // the auth token was invalidated on the Rocket.Chat server
+    logout_receiver.recv_timeout(default_timeout()).unwrap();
+
+    let connection = test.connection_pool.get().unwrap();
+    let server = RocketchatServer::find_by_token(&connection, RS_TOKEN).unwrap().unwrap();
+    let user_id = UserId::try_from("@spec_user:localhost").unwrap();
+    let user_on_rocketchat_server = UserOnRocketchatServer::find(&connection, &user_id, server.id).unwrap();
+    assert!(!user_on_rocketchat_server.is_logged_in());
+    assert_eq!(user_on_rocketchat_server.rocketchat_user_id, None);
+    assert_eq!(user_on_rocketchat_server.matrix_access_token, None);
+    assert_eq!(user_on_rocketchat_server.admin_room_id, None);
+}
+
+#[test]
+fn the_user_is_removed_from_the_bridged_rooms_when_logging_out_with_leave_rooms() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let (kick_forwarder, kick_receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+    matrix_router.post("/_matrix/client/r0/rooms/:room_id/kick", kick_forwarder, "kick_user");
+    let mut rocketchat_router = test.default_rocketchat_routes();
+    let mut users_in_rooms = HashMap::new();
+    users_in_rooms.insert("spec_user_id", vec!["spec_channel"]);
+    rocketchat_router.get(
+        CHANNELS_LIST_JOINED_PATH,
+        handlers::RocketchatJoinedRooms { users_in_rooms: users_in_rooms },
+        "joined_channels",
+    );
+
+    let test = test
+        .with_matrix_routes(matrix_router)
+        .with_rocketchat_mock()
+        .with_custom_rocketchat_routes(rocketchat_router)
+        .with_connected_admin_room()
+        .with_logged_in_user()
+        .with_bridged_room(("spec_channel", vec!["spec_user"]))
+        .run();
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard login message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard bridge message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!admin_room_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        "logout leave-rooms".to_string(),
+    );
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(message_received_by_matrix.contains("You are logged out from the Rocket.Chat server"));
+    assert!(message_received_by_matrix.contains("You were removed from the bridged rooms spec_channel."));
+
+    let kick_request = kick_receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(kick_request.contains("@spec_user:localhost"));
+}
+
+#[test]
+fn the_user_stays_in_rooms_of_channels_the_user_is_not_a_member_of_when_logging_out_with_leave_rooms() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let (kick_forwarder, kick_receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+    matrix_router.post("/_matrix/client/r0/rooms/:room_id/kick", kick_forwarder, "kick_user");
+    let mut rocketchat_router = test.default_rocketchat_routes();
+    rocketchat_router.get(
+        CHANNELS_LIST_JOINED_PATH,
+        handlers::RocketchatJoinedRooms { users_in_rooms: HashMap::new() },
+        "joined_channels",
+    );
+
+    let test = test
+        .with_matrix_routes(matrix_router)
+        .with_rocketchat_mock()
+        .with_custom_rocketchat_routes(rocketchat_router)
+        .with_connected_admin_room()
+        .with_logged_in_user()
+        .with_bridged_room(("spec_channel", vec!["spec_user"]))
+        .run();
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard login message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard bridge message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!admin_room_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        "logout leave-rooms".to_string(),
+    );
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(message_received_by_matrix.contains("You are logged out from the Rocket.Chat server"));
+    assert!(!message_received_by_matrix.contains("You were removed from the bridged rooms"));
+
+    assert!(kick_receiver.recv_timeout(default_timeout()).is_err());
+}
+
+#[test]
+fn the_user_stays_in_plumbed_rooms_when_logging_out_with_leave_rooms() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let (kick_forwarder, kick_receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+    matrix_router.post("/_matrix/client/r0/rooms/:room_id/kick", kick_forwarder, "kick_user");
+    let mut rocketchat_router = test.default_rocketchat_routes();
+    let mut users_in_rooms = HashMap::new();
+    users_in_rooms.insert("spec_user_id", vec!["joined_channel"]);
+    rocketchat_router.get(
+        CHANNELS_LIST_JOINED_PATH,
+        handlers::RocketchatJoinedRooms { users_in_rooms: users_in_rooms },
+        "joined_channels",
+    );
+    let channels = test.channel_list();
+    channels.lock().unwrap().insert("joined_channel", vec!["spec_user"]);
+
+    let test = test
+        .with_matrix_routes(matrix_router)
+        .with_rocketchat_mock()
+        .with_custom_rocketchat_routes(rocketchat_router)
+        .with_connected_admin_room()
+        .with_logged_in_user()
+        .run();
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard login message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    let spec_user_id = UserId::try_from("@spec_user:localhost").unwrap();
+    let matrix_api = MatrixApi::new(&test.config, DEFAULT_LOGGER.clone()).unwrap();
+    let room_id = matrix_api.create_room(Some("community_room".to_string()), None, &spec_user_id).unwrap();
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!admin_room_id:localhost").unwrap(),
+        spec_user_id.clone(),
+        "plumb !community_room_id:localhost joined_channel".to_string(),
+    );
+
+    // discard invite bot message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    helpers::invite(&test.config, room_id.clone(), UserId::try_from("@rocketchat:localhost").unwrap(), spec_user_id.clone());
+
+    // discard plumbed message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!admin_room_id:localhost").unwrap(),
+        spec_user_id.clone(),
+        "logout leave-rooms".to_string(),
+    );
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(message_received_by_matrix.contains("You are logged out from the Rocket.Chat server"));
+    assert!(!message_received_by_matrix.contains("You were removed from the bridged rooms"));
+
+    assert!(kick_receiver.recv_timeout(default_timeout()).is_err());
+}
+
+#[test]
+fn the_user_stays_in_the_bridged_rooms_when_logging_out_without_leave_rooms() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let (kick_forwarder, kick_receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+    matrix_router.post("/_matrix/client/r0/rooms/:room_id/kick", kick_forwarder, "kick_user");
+
+    let test = test
+        .with_matrix_routes(matrix_router)
+        .with_rocketchat_mock()
+        .with_connected_admin_room()
+        .with_logged_in_user()
+        .with_bridged_room(("spec_channel", vec!["spec_user"]))
+        .run();
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard login message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard bridge message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!admin_room_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        "logout".to_string(),
+    );
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(message_received_by_matrix.contains("You are logged out from the Rocket.Chat server"));
+    assert!(!message_received_by_matrix.contains("You were removed from the bridged rooms"));
+
+    assert!(kick_receiver.recv_timeout(default_timeout()).is_err());
+}
+
+#[test]
+fn the_user_gets_a_message_when_logging_out_without_being_logged_in() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+
+    let test = test.with_matrix_routes(matrix_router).with_rocketchat_mock().with_connected_admin_room().run();
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!admin_room_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        "logout".to_string(),
+    );
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(message_received_by_matrix.contains("You are not logged in on the Rocket.Chat server"));
+}
@@ -0,0 +1,198 @@
+extern crate iron;
+extern crate matrix_rocketchat;
+extern crate matrix_rocketchat_test;
+extern crate router;
+extern crate ruma_client_api;
+extern crate ruma_identifiers;
+extern crate serde_json;
+
+use std::convert::TryFrom;
+
+use matrix_rocketchat::api::rocketchat::WebhookMessage;
+use matrix_rocketchat::api::MatrixApi;
+use matrix_rocketchat_test::{default_timeout, helpers, MessageForwarder, Test, DEFAULT_LOGGER, RS_TOKEN};
+use ruma_client_api::r0::send::send_message_event::Endpoint as SendMessageEventEndpoint;
+use ruma_client_api::Endpoint;
+use ruma_identifiers::{RoomId, UserId};
+use serde_json::to_string;
+
+#[test]
+fn successfully_plumb_an_existing_matrix_room() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+    let channels = test.channel_list();
+    channels.lock().unwrap().insert("joined_channel", vec!["spec_user"]);
+
+    let test =
+        test.with_matrix_routes(matrix_router).with_rocketchat_mock().with_connected_admin_room().with_logged_in_user().run();
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard login message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    let spec_user_id = UserId::try_from("@spec_user:localhost").unwrap();
+    let matrix_api = MatrixApi::new(&test.config, DEFAULT_LOGGER.clone()).unwrap();
+    let room_id = matrix_api.create_room(Some("community_room".to_string()), None, &spec_user_id).unwrap();
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!admin_room_id:localhost").unwrap(),
+        spec_user_id.clone(),
+        "plumb !community_room_id:localhost joined_channel".to_string(),
+    );
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(message_received_by_matrix
+        .contains("Invite @rocketchat:localhost into !community_room_id:localhost to bridge it with joined_channel."));
+
+    helpers::invite(&test.config, room_id.clone(), UserId::try_from("@rocketchat:localhost").unwrap(), spec_user_id);
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(message_received_by_matrix.contains("The room !community_room_id:localhost is now bridged."));
+
+    let message = WebhookMessage {
+        message_id: "spec_id".to_string(),
+        token: Some(RS_TOKEN.to_string()),
+        channel_id: "joined_channel_id".to_string(),
+        channel_name: Some("joined_channel".to_string()),
+        user_id: "new_user_id".to_string(),
+        user_name: "new_spec_user".to_string(),
+        text: "spec_message".to_string(),
+    };
+    let payload = to_string(&message).unwrap();
+
+    helpers::simulate_message_from_rocketchat(&test.config.as_url, &payload);
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(message_received_by_matrix.contains("spec_message"));
+}
+
+#[test]
+fn attempt_to_plumb_a_room_in_which_the_user_is_not_an_admin() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+    let channels = test.channel_list();
+    channels.lock().unwrap().insert("joined_channel", vec!["spec_user"]);
+
+    let test =
+        test.with_matrix_routes(matrix_router).with_rocketchat_mock().with_connected_admin_room().with_logged_in_user().run();
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard login message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    let other_user_id = UserId::try_from("@other_user:localhost").unwrap();
+    let matrix_api = MatrixApi::new(&test.config, DEFAULT_LOGGER.clone()).unwrap();
+    matrix_api.register("other_user".to_string()).unwrap();
+    let room_id = matrix_api.create_room(Some("other_room".to_string()), None, &other_user_id).unwrap();
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!admin_room_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        "plumb !other_room_id:localhost joined_channel".to_string(),
+    );
+
+    // discard invite bot message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    helpers::invite(&test.config, room_id, UserId::try_from("@rocketchat:localhost").unwrap(), other_user_id);
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(
+        message_received_by_matrix.contains("Only admins of the room !other_room_id:localhost can bridge it or unbridge it.")
+    );
+}
+
+#[test]
+fn attempt_to_plumb_a_matrix_room_that_does_not_exist() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+    let channels = test.channel_list();
+    channels.lock().unwrap().insert("joined_channel", vec!["spec_user"]);
+
+    let test =
+        test.with_matrix_routes(matrix_router).with_rocketchat_mock().with_connected_admin_room().with_logged_in_user().run();
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard login message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!admin_room_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        "plumb not_a_room joined_channel".to_string(),
+    );
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(message_received_by_matrix.contains("No Matrix room with the ID or alias not_a_room found."));
+}
+
+#[test]
+fn unbridging_a_plumbed_room_keeps_the_matrix_room() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+    let channels = test.channel_list();
+    channels.lock().unwrap().insert("joined_channel", vec!["spec_user"]);
+
+    let test =
+        test.with_matrix_routes(matrix_router).with_rocketchat_mock().with_connected_admin_room().with_logged_in_user().run();
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard login message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    let spec_user_id = UserId::try_from("@spec_user:localhost").unwrap();
+    let matrix_api = MatrixApi::new(&test.config, DEFAULT_LOGGER.clone()).unwrap();
+    let room_id = matrix_api.create_room(Some("community_room".to_string()), None, &spec_user_id).unwrap();
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!admin_room_id:localhost").unwrap(),
+        spec_user_id.clone(),
+        "plumb !community_room_id:localhost joined_channel".to_string(),
+    );
+
+    // discard invite bot message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    helpers::invite(&test.config, room_id.clone(), UserId::try_from("@rocketchat:localhost").unwrap(), spec_user_id.clone());
+
+    // discard plumbed message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!admin_room_id:localhost").unwrap(),
+        spec_user_id.clone(),
+        "unbridge joined_channel".to_string(),
+    );
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(message_received_by_matrix.contains("joined_channel is now unbridged."));
+
+    // the Matrix users are still in the room
+    let members = matrix_api.get_room_members(room_id, Some(spec_user_id.clone())).unwrap();
+    assert!(members.iter().any(|member| member.state_key == spec_user_id.to_string()));
+}
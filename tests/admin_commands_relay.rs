@@ -0,0 +1,211 @@
+extern crate iron;
+extern crate matrix_rocketchat;
+extern crate matrix_rocketchat_test;
+extern crate router;
+extern crate ruma_client_api;
+extern crate ruma_identifiers;
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+use matrix_rocketchat::api::rocketchat::v1::{CHAT_POST_MESSAGE_PATH, ROOMS_UPLOAD_PATH};
+use matrix_rocketchat::api::MatrixApi;
+use matrix_rocketchat_test::{default_timeout, handlers, helpers, MessageForwarder, Test, DEFAULT_LOGGER};
+use ruma_client_api::r0::media::get_content::Endpoint as GetContentEndpoint;
+use ruma_client_api::r0::send::send_message_event::Endpoint as SendMessageEventEndpoint;
+use ruma_client_api::Endpoint;
+use ruma_identifiers::{RoomId, UserId};
+
+#[test]
+fn successfully_relays_messages_from_users_that_are_not_logged_in() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let (rocketchat_message_forwarder, rocketchat_receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+    let mut rocketchat_router = test.default_rocketchat_routes();
+    rocketchat_router.post(CHAT_POST_MESSAGE_PATH, rocketchat_message_forwarder, "post_text_message");
+
+    let test = test
+        .with_matrix_routes(matrix_router)
+        .with_rocketchat_mock()
+        .with_custom_rocketchat_routes(rocketchat_router)
+        .with_connected_admin_room()
+        .with_logged_in_user()
+        .with_bridged_room(("spec_channel", vec!["spec_user"]))
+        .run();
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard login message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard bridge message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!admin_room_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        "relay spec_channel".to_string(),
+    );
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(message_received_by_matrix.contains("are now relayed to spec_channel via your account"));
+
+    let other_user_id = UserId::try_from("@other_user:localhost").unwrap();
+    let matrix_api = MatrixApi::new(&test.config, DEFAULT_LOGGER.clone()).unwrap();
+    matrix_api.register("other_user".to_string()).unwrap();
+    helpers::invite(
+        &test.config,
+        RoomId::try_from("!spec_channel_id:localhost").unwrap(),
+        other_user_id.clone(),
+        UserId::try_from("@rocketchat:localhost").unwrap(),
+    );
+    helpers::join(&test.config, RoomId::try_from("!spec_channel_id:localhost").unwrap(), other_user_id.clone());
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!spec_channel_id:localhost").unwrap(),
+        other_user_id,
+        "relayed message".to_string(),
+    );
+
+    let message_received_by_rocketchat = rocketchat_receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(message_received_by_rocketchat.contains("**@other_user:localhost**: relayed message"));
+    assert!(message_received_by_rocketchat.contains("\"alias\":\"@other_user:localhost\""));
+}
+
+#[test]
+fn relayed_files_are_uploaded_once_with_the_relay_message() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let (rocketchat_message_forwarder, rocketchat_receiver) = MessageForwarder::new();
+    let (upload_forwarder, upload_receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+    let mut files = HashMap::new();
+    files.insert("spec_id".to_string(), b"file".to_vec());
+    matrix_router.get(GetContentEndpoint::router_path(), handlers::MatrixGetContentHandler { files }, "get_file");
+    let mut rocketchat_router = test.default_rocketchat_routes();
+    rocketchat_router.post(CHAT_POST_MESSAGE_PATH, rocketchat_message_forwarder, "post_text_message");
+    rocketchat_router.post(format!("{}{}", ROOMS_UPLOAD_PATH, "/:channel_id"), upload_forwarder, "upload");
+
+    let test = test
+        .with_matrix_routes(matrix_router)
+        .with_rocketchat_mock()
+        .with_custom_rocketchat_routes(rocketchat_router)
+        .with_connected_admin_room()
+        .with_logged_in_user()
+        .with_bridged_room(("spec_channel", vec!["spec_user"]))
+        .run();
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard login message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard bridge message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!admin_room_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        "relay spec_channel".to_string(),
+    );
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(message_received_by_matrix.contains("are now relayed to spec_channel via your account"));
+
+    let other_user_id = UserId::try_from("@other_user:localhost").unwrap();
+    let matrix_api = MatrixApi::new(&test.config, DEFAULT_LOGGER.clone()).unwrap();
+    matrix_api.register("other_user".to_string()).unwrap();
+    helpers::invite(
+        &test.config,
+        RoomId::try_from("!spec_channel_id:localhost").unwrap(),
+        other_user_id.clone(),
+        UserId::try_from("@rocketchat:localhost").unwrap(),
+    );
+    helpers::join(&test.config, RoomId::try_from("!spec_channel_id:localhost").unwrap(), other_user_id.clone());
+
+    helpers::send_file_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!spec_channel_id:localhost").unwrap(),
+        other_user_id,
+        "spec_file.txt".to_string(),
+        "mxc://localhost/spec_id".to_string(),
+    );
+
+    let file_received_by_rocketchat = upload_receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(file_received_by_rocketchat.contains("**@other_user:localhost**: spec_file.txt"));
+    assert!(rocketchat_receiver.recv_timeout(default_timeout()).is_err());
+}
+
+#[test]
+fn do_not_relay_messages_when_relaying_is_not_enabled_for_the_room() {
+    let test = Test::new();
+    let (rocketchat_message_forwarder, rocketchat_receiver) = MessageForwarder::new();
+    let mut rocketchat_router = test.default_rocketchat_routes();
+    rocketchat_router.post(CHAT_POST_MESSAGE_PATH, rocketchat_message_forwarder, "post_text_message");
+
+    let test = test
+        .with_rocketchat_mock()
+        .with_custom_rocketchat_routes(rocketchat_router)
+        .with_connected_admin_room()
+        .with_logged_in_user()
+        .with_bridged_room(("spec_channel", vec!["spec_user"]))
+        .run();
+
+    let other_user_id = UserId::try_from("@other_user:localhost").unwrap();
+    let matrix_api = MatrixApi::new(&test.config, DEFAULT_LOGGER.clone()).unwrap();
+    matrix_api.register("other_user".to_string()).unwrap();
+    helpers::invite(
+        &test.config,
+        RoomId::try_from("!spec_channel_id:localhost").unwrap(),
+        other_user_id.clone(),
+        UserId::try_from("@rocketchat:localhost").unwrap(),
+    );
+    helpers::join(&test.config, RoomId::try_from("!spec_channel_id:localhost").unwrap(), other_user_id.clone());
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!spec_channel_id:localhost").unwrap(),
+        other_user_id,
+        "not relayed message".to_string(),
+    );
+
+    assert!(rocketchat_receiver.recv_timeout(default_timeout()).is_err());
+}
+
+#[test]
+fn attempting_to_relay_a_room_that_is_not_bridged_returns_an_error() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+    let channels = test.channel_list();
+    channels.lock().unwrap().insert("normal_channel", vec!["spec_user"]);
+
+    let test =
+        test.with_matrix_routes(matrix_router).with_rocketchat_mock().with_connected_admin_room().with_logged_in_user().run();
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard login message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!admin_room_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        "relay normal_channel".to_string(),
+    );
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(message_received_by_matrix.contains("The channel or group normal_channel is not bridged for you"));
+}
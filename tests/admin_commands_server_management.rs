@@ -0,0 +1,322 @@
+extern crate diesel;
+extern crate iron;
+extern crate matrix_rocketchat;
+extern crate matrix_rocketchat_test;
+extern crate router;
+extern crate ruma_client_api;
+extern crate ruma_identifiers;
+
+use std::convert::TryFrom;
+use std::sync::mpsc::channel;
+use std::thread;
+
+use diesel::RunQueryDsl;
+use iron::{Iron, Listening};
+use matrix_rocketchat::models::RocketchatServer;
+use matrix_rocketchat_test::{
+    default_timeout, get_free_socket_addr, handlers, helpers, MessageForwarder, Test, DEFAULT_ROCKETCHAT_VERSION, IRON_THREADS,
+    RS_TOKEN,
+};
+use router::Router;
+use ruma_client_api::r0::send::send_message_event::Endpoint as SendMessageEventEndpoint;
+use ruma_client_api::Endpoint;
+use ruma_identifiers::{RoomId, UserId};
+
+#[test]
+fn successfully_disconnect_the_admin_room() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+    let test = test.with_matrix_routes(matrix_router).with_rocketchat_mock().with_connected_admin_room().run();
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!admin_room_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        "disconnect".to_string(),
+    );
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    let expected_message =
+        format!("This room is no longer connected to the Rocket.Chat server {}", test.rocketchat_mock_url.clone().unwrap());
+    assert!(message_received_by_matrix.contains(&expected_message));
+
+    // the admin room is no longer connected, so a new server can be connected
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!admin_room_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        "list".to_string(),
+    );
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(message_received_by_matrix.contains("This room is not connected to a Rocket.Chat server"));
+
+    // the server itself is not removed
+    let connection = test.connection_pool.get().unwrap();
+    let rocketchat_server = RocketchatServer::find_by_token(&connection, RS_TOKEN).unwrap();
+    assert!(rocketchat_server.is_some());
+}
+
+#[test]
+fn successfully_rotate_the_rocketchat_token() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+    let test = test.with_matrix_routes(matrix_router).with_rocketchat_mock().with_connected_admin_room().run();
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!admin_room_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        "rotate-token new_spec_token".to_string(),
+    );
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    let expected_message =
+        format!("The token of the Rocket.Chat server {} was changed", test.rocketchat_mock_url.clone().unwrap());
+    assert!(message_received_by_matrix.contains(&expected_message));
+
+    let connection = test.connection_pool.get().unwrap();
+    assert!(RocketchatServer::find_by_token(&connection, RS_TOKEN).unwrap().is_none());
+    let rocketchat_server = RocketchatServer::find_by_token(&connection, "new_spec_token").unwrap().unwrap();
+    assert_eq!(rocketchat_server.rocketchat_url, test.rocketchat_mock_url.clone().unwrap());
+}
+
+#[test]
+fn attempt_to_rotate_the_rocketchat_token_without_a_token() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+    let test = test.with_matrix_routes(matrix_router).with_rocketchat_mock().with_connected_admin_room().run();
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!admin_room_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        "rotate-token".to_string(),
+    );
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(message_received_by_matrix.contains("Invalid arguments for `rotate-token`"));
+
+    let connection = test.connection_pool.get().unwrap();
+    assert!(RocketchatServer::find_by_token(&connection, RS_TOKEN).unwrap().is_some());
+}
+
+#[test]
+fn successfully_change_the_rocketchat_url() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+
+    let (tx, rx) = channel::<Listening>();
+    let socket_addr = get_free_socket_addr();
+
+    thread::spawn(move || {
+        let mut rocketchat_router = Router::new();
+        rocketchat_router.get("/api/info", handlers::RocketchatInfo { version: DEFAULT_ROCKETCHAT_VERSION }, "info");
+        let mut server = Iron::new(rocketchat_router);
+        server.threads = IRON_THREADS;
+        let listening = server.http(&socket_addr).unwrap();
+        tx.send(listening).unwrap();
+    });
+    let mut listening = rx.recv_timeout(default_timeout() * 2).unwrap();
+    let new_rocketchat_url = format!("http://{}", socket_addr);
+
+    let test = test.with_matrix_routes(matrix_router).with_rocketchat_mock().with_connected_admin_room().run();
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!admin_room_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        format!("change-url {}", new_rocketchat_url),
+    );
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    listening.close().unwrap();
+
+    let expected_message = format!(
+        "The URL of the Rocket.Chat server was changed from {} to {}",
+        test.rocketchat_mock_url.clone().unwrap(),
+        new_rocketchat_url
+    );
+    assert!(message_received_by_matrix.contains(&expected_message));
+
+    let connection = test.connection_pool.get().unwrap();
+    let rocketchat_server = RocketchatServer::find_by_token(&connection, RS_TOKEN).unwrap().unwrap();
+    assert_eq!(rocketchat_server.rocketchat_url, new_rocketchat_url);
+    assert!(RocketchatServer::find_by_url(&connection, &test.rocketchat_mock_url.clone().unwrap()).unwrap().is_none());
+}
+
+#[test]
+fn attempt_to_change_the_rocketchat_url_to_an_unreachable_server() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+    let test = test.with_matrix_routes(matrix_router).with_rocketchat_mock().with_connected_admin_room().run();
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    let new_rocketchat_url = format!("http://{}", get_free_socket_addr());
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!admin_room_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        format!("change-url {}", new_rocketchat_url),
+    );
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    let expected_message = format!("Could not reach Rocket.Chat server {}", new_rocketchat_url);
+    assert!(message_received_by_matrix.contains(&expected_message));
+
+    let connection = test.connection_pool.get().unwrap();
+    let rocketchat_server = RocketchatServer::find_by_token(&connection, RS_TOKEN).unwrap().unwrap();
+    assert_eq!(rocketchat_server.rocketchat_url, test.rocketchat_mock_url.clone().unwrap());
+}
+
+#[test]
+fn successfully_delete_the_rocketchat_server() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let (kick_forwarder, kick_receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+    matrix_router.post("/_matrix/client/r0/rooms/:room_id/kick", kick_forwarder, "kick_user");
+
+    let test = test
+        .with_matrix_routes(matrix_router)
+        .with_rocketchat_mock()
+        .with_connected_admin_room()
+        .with_logged_in_user()
+        .with_bridged_room(("spec_channel", vec!["spec_user"]))
+        .run();
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard login message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard bridge message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!admin_room_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        "delete-server".to_string(),
+    );
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    let expected_message = format!(
+        "The Rocket.Chat server {} was deleted together with 1 bridged rooms",
+        test.rocketchat_mock_url.clone().unwrap()
+    );
+    assert!(message_received_by_matrix.contains(&expected_message));
+
+    // the Matrix user is removed from the bridged room
+    let kick_request = kick_receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(kick_request.contains("@spec_user:localhost"));
+    assert!(kick_request.contains("The Rocket.Chat server was deleted from the application service"));
+
+    let connection = test.connection_pool.get().unwrap();
+    assert!(RocketchatServer::find_by_token(&connection, RS_TOKEN).unwrap().is_none());
+    assert!(RocketchatServer::find_by_url(&connection, &test.rocketchat_mock_url.clone().unwrap()).unwrap().is_none());
+}
+
+#[test]
+fn attempt_to_manage_a_rocketchat_server_that_was_connected_by_another_user() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+    let test = test.with_matrix_routes(matrix_router).with_rocketchat_mock().with_connected_admin_room().run();
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    let connection = test.connection_pool.get().unwrap();
+    diesel::sql_query("UPDATE rocketchat_servers SET created_by = '@other_user:localhost'").execute(&connection).unwrap();
+
+    for command in &["rotate-token new_spec_token", "change-url http://localhost:1", "delete-server"] {
+        helpers::send_room_message_from_matrix(
+            &test.config.as_url,
+            RoomId::try_from("!admin_room_id:localhost").unwrap(),
+            UserId::try_from("@spec_user:localhost").unwrap(),
+            command.to_string(),
+        );
+
+        let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+        let expected_message = format!(
+            "Only the user who connected the Rocket.Chat server {} and the bridge admins can manage it.",
+            test.rocketchat_mock_url.clone().unwrap()
+        );
+        assert!(message_received_by_matrix.contains(&expected_message));
+    }
+
+    let rocketchat_server = RocketchatServer::find_by_token(&connection, RS_TOKEN).unwrap().unwrap();
+    assert_eq!(rocketchat_server.rocketchat_url, test.rocketchat_mock_url.clone().unwrap());
+}
+
+#[test]
+fn a_bridge_admin_can_manage_a_rocketchat_server_that_was_connected_by_another_user() {
+    let mut test = Test::new();
+    test.config.bridge_admins = Some(vec![UserId::try_from("@spec_user:localhost").unwrap()]);
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+    let test = test.with_matrix_routes(matrix_router).with_rocketchat_mock().with_connected_admin_room().run();
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    let connection = test.connection_pool.get().unwrap();
+    diesel::sql_query("UPDATE rocketchat_servers SET created_by = '@other_user:localhost'").execute(&connection).unwrap();
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!admin_room_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        "rotate-token new_spec_token".to_string(),
+    );
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    let expected_message =
+        format!("The token of the Rocket.Chat server {} was changed", test.rocketchat_mock_url.clone().unwrap());
+    assert!(message_received_by_matrix.contains(&expected_message));
+
+    assert!(RocketchatServer::find_by_token(&connection, "new_spec_token").unwrap().is_some());
+}
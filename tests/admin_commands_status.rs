@@ -0,0 +1,196 @@
+extern crate iron;
+extern crate matrix_rocketchat;
+extern crate matrix_rocketchat_test;
+extern crate router;
+extern crate ruma_client_api;
+extern crate ruma_identifiers;
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+use iron::status;
+use matrix_rocketchat::api::rocketchat::v1::{CHANNELS_LIST_JOINED_PATH, CHAT_POST_MESSAGE_PATH, DM_LIST_PATH};
+use matrix_rocketchat_test::{default_timeout, handlers, helpers, MessageForwarder, Test, DEFAULT_ROCKETCHAT_VERSION};
+use ruma_client_api::r0::send::send_message_event::Endpoint as SendMessageEventEndpoint;
+use ruma_client_api::Endpoint;
+use ruma_identifiers::{RoomId, UserId};
+
+#[test]
+fn status_of_a_logged_in_user() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+    let mut rocketchat_router = test.default_rocketchat_routes();
+    let direct_messages_list_handler =
+        handlers::RocketchatDirectMessagesList { direct_messages: HashMap::new(), status: status::Ok };
+    rocketchat_router.get(DM_LIST_PATH, direct_messages_list_handler, "direct_messages_list");
+    let mut users_in_rooms = HashMap::new();
+    users_in_rooms.insert("spec_user_id", vec!["spec_channel"]);
+    rocketchat_router.get(CHANNELS_LIST_JOINED_PATH, handlers::RocketchatJoinedRooms { users_in_rooms }, "joined_channels");
+
+    let test = test
+        .with_matrix_routes(matrix_router)
+        .with_rocketchat_mock()
+        .with_custom_rocketchat_routes(rocketchat_router)
+        .with_connected_admin_room()
+        .with_logged_in_user()
+        .with_bridged_room(("spec_channel", vec!["spec_user"]))
+        .run();
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard login message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard bridge message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!admin_room_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        "status".to_string(),
+    );
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    let expected_server =
+        format!("Rocket.Chat server: {} (version {})", test.rocketchat_mock_url.clone().unwrap(), DEFAULT_ROCKETCHAT_VERSION);
+    assert!(message_received_by_matrix.contains(&expected_server));
+    assert!(message_received_by_matrix.contains("Login: logged in as spec_user"));
+    assert!(message_received_by_matrix.contains("Bridged rooms: 1, direct messages: 0"));
+    assert!(message_received_by_matrix.contains("Your last message from Matrix to Rocket.Chat: never"));
+    assert!(message_received_by_matrix.contains("Last message from Rocket.Chat to Matrix (any user on this server): never"));
+    assert!(message_received_by_matrix.contains("none"));
+}
+
+#[test]
+fn the_status_only_counts_bridged_rooms_of_channels_that_the_user_joined_on_rocketchat() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+    let mut rocketchat_router = test.default_rocketchat_routes();
+    let direct_messages_list_handler =
+        handlers::RocketchatDirectMessagesList { direct_messages: HashMap::new(), status: status::Ok };
+    rocketchat_router.get(DM_LIST_PATH, direct_messages_list_handler, "direct_messages_list");
+    // the user left the channel on Rocket.Chat
+    let users_in_rooms = HashMap::new();
+    rocketchat_router.get(CHANNELS_LIST_JOINED_PATH, handlers::RocketchatJoinedRooms { users_in_rooms }, "joined_channels");
+
+    let test = test
+        .with_matrix_routes(matrix_router)
+        .with_rocketchat_mock()
+        .with_custom_rocketchat_routes(rocketchat_router)
+        .with_connected_admin_room()
+        .with_logged_in_user()
+        .with_bridged_room(("spec_channel", vec!["spec_user"]))
+        .run();
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard login message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard bridge message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!admin_room_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        "status".to_string(),
+    );
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(message_received_by_matrix.contains("Bridged rooms: 0, direct messages: 0"));
+}
+
+#[test]
+fn status_of_a_user_that_is_not_logged_in() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+    let test = test.with_matrix_routes(matrix_router).with_rocketchat_mock().with_connected_admin_room().run();
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!admin_room_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        "whoami".to_string(),
+    );
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(message_received_by_matrix.contains("Login: not logged in"));
+    assert!(message_received_by_matrix.contains("Bridged rooms: unknown, direct messages: unknown"));
+}
+
+#[test]
+fn the_status_contains_errors_that_happened_when_forwarding_messages() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+    let mut rocketchat_router = test.default_rocketchat_routes();
+    rocketchat_router.post(
+        CHAT_POST_MESSAGE_PATH,
+        handlers::RocketchatErrorResponder {
+            message: "Rocket.Chat chat.postMessage error".to_string(),
+            status: status::InternalServerError,
+        },
+        "post_text_message",
+    );
+    let direct_messages_list_handler =
+        handlers::RocketchatDirectMessagesList { direct_messages: HashMap::new(), status: status::Ok };
+    rocketchat_router.get(DM_LIST_PATH, direct_messages_list_handler, "direct_messages_list");
+    let mut users_in_rooms = HashMap::new();
+    users_in_rooms.insert("spec_user_id", vec!["spec_channel"]);
+    rocketchat_router.get(CHANNELS_LIST_JOINED_PATH, handlers::RocketchatJoinedRooms { users_in_rooms }, "joined_channels");
+
+    let test = test
+        .with_matrix_routes(matrix_router)
+        .with_rocketchat_mock()
+        .with_custom_rocketchat_routes(rocketchat_router)
+        .with_connected_admin_room()
+        .with_logged_in_user()
+        .with_bridged_room(("spec_channel", vec!["spec_user"]))
+        .run();
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard login message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard bridge message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!spec_channel_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        "spec message".to_string(),
+    );
+
+    // discard error message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!admin_room_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        "status".to_string(),
+    );
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(message_received_by_matrix.contains("Recent errors when forwarding your messages from Matrix to Rocket.Chat"));
+    assert!(message_received_by_matrix.contains("Rocket.Chat chat.postMessage error"));
+}
@@ -11,7 +11,7 @@ use ruma_client_api::Endpoint;
 use ruma_identifiers::{RoomId, UserId};
 
 #[test]
-fn unknown_commands_from_the_admin_room_are_ignored() {
+fn unknown_commands_from_the_admin_room_are_answered() {
     let test = Test::new();
     let (message_forwarder, receiver) = MessageForwarder::new();
     let mut matrix_router = test.default_matrix_routes();
@@ -26,8 +26,11 @@ fn unknown_commands_from_the_admin_room_are_ignored() {
         "bogus command".to_string(),
     );
 
-    // we don't get a message, because the command is ignored and no error occurs
-    receiver.recv_timeout(default_timeout()).is_err();
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(message_received_by_matrix.contains("Unknown command `bogus`, send `help` to see the available commands."));
 }
 
 #[test]
@@ -14,6 +14,7 @@ use iron::status;
 use matrix_rocketchat::api::rocketchat::v1::{CHAT_POST_MESSAGE_PATH, ROOMS_UPLOAD_PATH};
 use matrix_rocketchat::api::rocketchat::WebhookMessage;
 use matrix_rocketchat::api::MatrixApi;
+use matrix_rocketchat::models::{RocketchatServer, UserOnRocketchatServer};
 use matrix_rocketchat_test::{default_timeout, handlers, helpers, MessageForwarder, Test, DEFAULT_LOGGER, RS_TOKEN};
 use ruma_client_api::r0::media::get_content::Endpoint as GetContentEndpoint;
 use ruma_client_api::r0::send::send_message_event::Endpoint as SendMessageEventEndpoint;
@@ -225,6 +226,65 @@ fn error_message_when_uploading_the_file_on_rocketchat_fails_when_forwarding_a_f
     );
 }
 
+#[test]
+fn the_user_is_asked_to_login_again_when_the_rocketchat_session_expired_while_uploading_a_file() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+    let mut files = HashMap::new();
+    files.insert("spec_id".to_string(), b"file".to_vec());
+    matrix_router.get(GetContentEndpoint::router_path(), handlers::MatrixGetContentHandler { files: files }, "get_file");
+    let mut rocketchat_router = test.default_rocketchat_routes();
+    rocketchat_router.post(
+        format!("{}{}", ROOMS_UPLOAD_PATH, "/:channel_id"),
+        handlers::RocketchatErrorResponder {
+            message: "You must be logged in to do this.".to_string(),
+            status: status::Unauthorized,
+        },
+        "upload",
+    );
+
+    let test = test
+        .with_rocketchat_mock()
+        .with_matrix_routes(matrix_router)
+        .with_custom_rocketchat_routes(rocketchat_router)
+        .with_connected_admin_room()
+        .with_logged_in_user()
+        .with_bridged_room(("spec_channel", vec!["spec_user"]))
+        .run();
+
+    helpers::send_file_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!spec_channel_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        "spec_image.txt".to_string(),
+        "mxc://localhost/spec_id".to_string(),
+    );
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard login message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard bridge message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(message_received_by_matrix.contains(&format!(
+        "Your session on the Rocket.Chat server {} expired or was revoked.",
+        test.rocketchat_mock_url.clone().unwrap()
+    )));
+
+    let connection = test.connection_pool.get().unwrap();
+    let rocketchat_server = RocketchatServer::find(&connection, &test.rocketchat_mock_url.clone().unwrap()).unwrap();
+    let user_on_rocketchat_server =
+        UserOnRocketchatServer::find(&connection, &UserId::try_from("@spec_user:localhost").unwrap(), rocketchat_server.id)
+            .unwrap();
+    assert!(!user_on_rocketchat_server.is_logged_in());
+}
+
 #[test]
 fn no_message_is_forwarded_when_the_image_cannot_be_found() {
     let test = Test::new();
@@ -472,6 +532,198 @@ fn the_user_gets_a_message_when_forwarding_a_message_failes() {
     assert!(message_received_by_matrix.contains("An internal error occurred"));
 }
 
+#[test]
+fn the_user_is_asked_to_login_again_when_the_rocketchat_session_expired() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+    let mut rocketchat_router = test.default_rocketchat_routes();
+    rocketchat_router.post(
+        CHAT_POST_MESSAGE_PATH,
+        handlers::RocketchatErrorResponder {
+            message: "You must be logged in to do this.".to_string(),
+            status: status::Unauthorized,
+        },
+        "post_text_message",
+    );
+
+    let test = test
+        .with_matrix_routes(matrix_router)
+        .with_rocketchat_mock()
+        .with_custom_rocketchat_routes(rocketchat_router)
+        .with_connected_admin_room()
+        .with_logged_in_user()
+        .with_bridged_room(("spec_channel", vec!["spec_user"]))
+        .run();
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!spec_channel_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        "spec message".to_string(),
+    );
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard login message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard bridge message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(message_received_by_matrix.contains(&format!(
+        "Your session on the Rocket.Chat server {} expired or was revoked.",
+        test.rocketchat_mock_url.clone().unwrap()
+    )));
+
+    let connection = test.connection_pool.get().unwrap();
+    let rocketchat_server = RocketchatServer::find(&connection, &test.rocketchat_mock_url.clone().unwrap()).unwrap();
+    let user_on_rocketchat_server =
+        UserOnRocketchatServer::find(&connection, &UserId::try_from("@spec_user:localhost").unwrap(), rocketchat_server.id)
+            .unwrap();
+    assert!(!user_on_rocketchat_server.is_logged_in());
+    assert_eq!(user_on_rocketchat_server.admin_room_id.unwrap(), RoomId::try_from("!admin_room_id:localhost").unwrap());
+
+    // the message isn't forwarded until the user logs in again
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!spec_channel_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        "spec message".to_string(),
+    );
+
+    assert!(receiver.recv_timeout(default_timeout()).is_err());
+}
+
+#[test]
+fn users_without_a_recorded_admin_room_are_asked_to_login_again_in_the_admin_room_with_the_server_url_as_topic() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+    let mut rocketchat_router = test.default_rocketchat_routes();
+    rocketchat_router.post(
+        CHAT_POST_MESSAGE_PATH,
+        handlers::RocketchatErrorResponder {
+            message: "You must be logged in to do this.".to_string(),
+            status: status::Unauthorized,
+        },
+        "post_text_message",
+    );
+
+    let test = test
+        .with_matrix_routes(matrix_router)
+        .with_rocketchat_mock()
+        .with_custom_rocketchat_routes(rocketchat_router)
+        .with_connected_admin_room()
+        .with_logged_in_user()
+        .with_bridged_room(("spec_channel", vec!["spec_user"]))
+        .run();
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard login message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard bridge message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    // users that logged in before the admin room was recorded
+    let connection = test.connection_pool.get().unwrap();
+    let spec_user_id = UserId::try_from("@spec_user:localhost").unwrap();
+    let rocketchat_server = RocketchatServer::find(&connection, &test.rocketchat_mock_url.clone().unwrap()).unwrap();
+    let mut user_on_rocketchat_server =
+        UserOnRocketchatServer::find(&connection, &spec_user_id, rocketchat_server.id.clone()).unwrap();
+    user_on_rocketchat_server.set_admin_room_id(&connection, None).unwrap();
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!spec_channel_id:localhost").unwrap(),
+        spec_user_id.clone(),
+        "spec message".to_string(),
+    );
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(message_received_by_matrix.contains(&format!(
+        "Your session on the Rocket.Chat server {} expired or was revoked.",
+        test.rocketchat_mock_url.clone().unwrap()
+    )));
+
+    let user_on_rocketchat_server = UserOnRocketchatServer::find(&connection, &spec_user_id, rocketchat_server.id).unwrap();
+    assert!(!user_on_rocketchat_server.is_logged_in());
+    assert_eq!(user_on_rocketchat_server.admin_room_id.unwrap(), RoomId::try_from("!admin_room_id:localhost").unwrap());
+}
+
+#[test]
+fn rooms_that_cannot_be_checked_are_skipped_when_looking_for_the_admin_room_of_a_user() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+    let error_responder =
+        handlers::MatrixErrorResponder { status: status::InternalServerError, message: "Could not get room topic".to_string() };
+    matrix_router.get(
+        "/_matrix/client/r0/rooms/!spec_channel_id:localhost/state/m.room.topic",
+        error_responder,
+        "get_room_topic",
+    );
+    let mut rocketchat_router = test.default_rocketchat_routes();
+    rocketchat_router.post(
+        CHAT_POST_MESSAGE_PATH,
+        handlers::RocketchatErrorResponder {
+            message: "You must be logged in to do this.".to_string(),
+            status: status::Unauthorized,
+        },
+        "post_text_message",
+    );
+
+    let test = test
+        .with_matrix_routes(matrix_router)
+        .with_rocketchat_mock()
+        .with_custom_rocketchat_routes(rocketchat_router)
+        .with_connected_admin_room()
+        .with_logged_in_user()
+        .with_bridged_room(("spec_channel", vec!["spec_user"]))
+        .run();
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard login message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard bridge message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    // users that logged in before the admin room was recorded
+    let connection = test.connection_pool.get().unwrap();
+    let spec_user_id = UserId::try_from("@spec_user:localhost").unwrap();
+    let rocketchat_server = RocketchatServer::find(&connection, &test.rocketchat_mock_url.clone().unwrap()).unwrap();
+    let mut user_on_rocketchat_server =
+        UserOnRocketchatServer::find(&connection, &spec_user_id, rocketchat_server.id.clone()).unwrap();
+    user_on_rocketchat_server.set_admin_room_id(&connection, None).unwrap();
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!spec_channel_id:localhost").unwrap(),
+        spec_user_id.clone(),
+        "spec message".to_string(),
+    );
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(message_received_by_matrix.contains(&format!(
+        "Your session on the Rocket.Chat server {} expired or was revoked.",
+        test.rocketchat_mock_url.clone().unwrap()
+    )));
+
+    let user_on_rocketchat_server = UserOnRocketchatServer::find(&connection, &spec_user_id, rocketchat_server.id).unwrap();
+    assert_eq!(user_on_rocketchat_server.admin_room_id.unwrap(), RoomId::try_from("!admin_room_id:localhost").unwrap());
+}
+
 #[test]
 fn the_user_gets_a_message_when_when_getting_the_canonical_room_alias_failes() {
     let test = Test::new();
@@ -16,7 +16,9 @@ use std::sync::Mutex;
 
 use http::{Method, StatusCode};
 use iron::{status, Chain};
-use matrix_rocketchat::api::rocketchat::v1::{Attachment, File, Message, UserInfo, CHAT_GET_MESSAGE_PATH};
+use matrix_rocketchat::api::rocketchat::v1::{
+    Attachment, File, Message, UserInfo, CHAT_GET_MESSAGE_PATH, CHAT_POST_MESSAGE_PATH,
+};
 use matrix_rocketchat::api::rocketchat::WebhookMessage;
 use matrix_rocketchat::api::{MatrixApi, RequestData, RestApi};
 use matrix_rocketchat::models::Room;
@@ -886,15 +888,18 @@ fn ignore_messages_to_a_room_that_is_not_bridged() {
 }
 
 #[test]
-fn ignore_messages_forwarded_from_rocketchat_if_the_non_virtual_user_just_sent_a_message_on_matrix_to_avoid_loops() {
+fn ignore_messages_forwarded_from_rocketchat_if_the_message_was_sent_by_the_application_service_to_avoid_loops() {
     let test = Test::new();
     let (message_forwarder, receiver) = MessageForwarder::new();
     let mut matrix_router = test.default_matrix_routes();
     matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+    let mut rocketchat_router = test.default_rocketchat_routes();
+    rocketchat_router.post(CHAT_POST_MESSAGE_PATH, handlers::RocketchatPostMessage { message_id: "spec_id" }, "post_message");
 
     let test = test
         .with_matrix_routes(matrix_router)
         .with_rocketchat_mock()
+        .with_custom_rocketchat_routes(rocketchat_router)
         .with_connected_admin_room()
         .with_logged_in_user()
         .with_bridged_room(("spec_channel", vec!["spec_user"]))
@@ -932,6 +937,57 @@ fn ignore_messages_forwarded_from_rocketchat_if_the_non_virtual_user_just_sent_a
     assert!(receiver.recv_timeout(default_timeout()).is_err());
 }
 
+#[test]
+fn forward_messages_from_rocketchat_that_were_sent_right_after_a_message_from_matrix() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+    let mut rocketchat_router = test.default_rocketchat_routes();
+    rocketchat_router.post(CHAT_POST_MESSAGE_PATH, handlers::RocketchatPostMessage { message_id: "spec_id" }, "post_message");
+
+    let test = test
+        .with_matrix_routes(matrix_router)
+        .with_rocketchat_mock()
+        .with_custom_rocketchat_routes(rocketchat_router)
+        .with_connected_admin_room()
+        .with_logged_in_user()
+        .with_bridged_room(("spec_channel", vec!["spec_user"]))
+        .run();
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!spec_channel_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        "message from Matrix".to_string(),
+    );
+
+    let message = WebhookMessage {
+        message_id: "other_spec_id".to_string(),
+        token: Some(RS_TOKEN.to_string()),
+        channel_id: "spec_channel_id".to_string(),
+        channel_name: Some("spec_channel".to_string()),
+        user_id: "spec_user_id".to_string(),
+        user_name: "spec_user".to_string(),
+        text: "message from a Rocket.Chat client".to_string(),
+    };
+    let payload = to_string(&message).unwrap();
+
+    helpers::simulate_message_from_rocketchat(&test.config.as_url, &payload);
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard login message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard room bridged message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(message_received_by_matrix.contains("message from a Rocket.Chat client"));
+}
+
 #[test]
 fn do_not_forward_messages_when_the_channel_was_bridged_but_is_unbridged_now() {
     let test = Test::new();
@@ -15,7 +15,7 @@ use std::sync::{Arc, Mutex};
 
 use iron::{status, Chain};
 use matrix_rocketchat::api::rocketchat::v1::{
-    Attachment, File, Message, UserInfo, CHAT_GET_MESSAGE_PATH, DM_LIST_PATH, LOGIN_PATH, ME_PATH,
+    Attachment, File, Message, UserInfo, CHAT_GET_MESSAGE_PATH, DM_LIST_PATH, IM_MEMBERS_PATH, LOGIN_PATH, ME_PATH,
 };
 use matrix_rocketchat::api::rocketchat::WebhookMessage;
 use matrix_rocketchat::api::MatrixApi;
@@ -133,6 +133,69 @@ fn successfully_forwards_a_direct_message_to_matrix() {
     assert!(second_message_received_by_matrix.contains("Yay"));
 }
 
+#[test]
+fn successfully_forwards_a_direct_message_to_a_dm_room_that_another_matrix_user_joined() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::with_path_filter("other_userDMRocketChat_id:localhost");
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+    let mut rocketchat_router = test.default_rocketchat_routes();
+    let mut direct_messages = HashMap::new();
+    direct_messages.insert("spec_user_id_other_user_id", vec!["spec_user", "other_user"]);
+    let direct_messages_list_handler =
+        handlers::RocketchatDirectMessagesList { direct_messages: direct_messages, status: status::Ok };
+    rocketchat_router.get(DM_LIST_PATH, direct_messages_list_handler, "direct_messages_list");
+
+    let test = test
+        .with_matrix_routes(matrix_router)
+        .with_rocketchat_mock()
+        .with_custom_rocketchat_routes(rocketchat_router)
+        .with_connected_admin_room()
+        .with_logged_in_user()
+        .run();
+
+    let first_direct_message = WebhookMessage {
+        message_id: "spec_id_1".to_string(),
+        token: Some(RS_TOKEN.to_string()),
+        channel_id: "spec_user_id_other_user_id".to_string(),
+        channel_name: None,
+        user_id: "other_user_id".to_string(),
+        user_name: "other_user".to_string(),
+        text: "Hey there".to_string(),
+    };
+    let first_direct_message_payload = to_string(&first_direct_message).unwrap();
+
+    helpers::simulate_message_from_rocketchat(&test.config.as_url, &first_direct_message_payload);
+
+    let first_message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(first_message_received_by_matrix.contains("Hey there"));
+
+    let room_id = RoomId::try_from("!other_userDMRocketChat_id:localhost").unwrap();
+    let spec_user_id = UserId::try_from("@spec_user:localhost").unwrap();
+    let third_user_id = UserId::try_from("@third_user:localhost").unwrap();
+    helpers::join(&test.config, room_id.clone(), spec_user_id.clone());
+    let matrix_api = MatrixApi::new(&test.config, DEFAULT_LOGGER.clone()).unwrap();
+    matrix_api.register("third_user".to_string()).unwrap();
+    helpers::invite(&test.config, room_id.clone(), third_user_id.clone(), spec_user_id);
+    helpers::join(&test.config, room_id, third_user_id);
+
+    let second_direct_message = WebhookMessage {
+        message_id: "spec_id_2".to_string(),
+        token: Some(RS_TOKEN.to_string()),
+        channel_id: "spec_user_id_other_user_id".to_string(),
+        channel_name: None,
+        user_id: "other_user_id".to_string(),
+        user_name: "other_user".to_string(),
+        text: "Yay".to_string(),
+    };
+    let second_direct_message_payload = to_string(&second_direct_message).unwrap();
+
+    helpers::simulate_message_from_rocketchat(&test.config.as_url, &second_direct_message_payload);
+
+    let second_message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(second_message_received_by_matrix.contains("Yay"));
+}
+
 #[test]
 fn successfully_forwards_a_direct_message_to_an_existing_dm_room_on_matrix() {
     let test = Test::new();
@@ -1100,3 +1163,131 @@ fn no_room_is_created_when_getting_the_displayname_respones_cannot_be_deserializ
     // no room is created on the Matrix server
     assert!(create_room_receiver.recv_timeout(default_timeout()).is_err());
 }
+
+#[test]
+fn successfully_forwards_a_direct_message_with_multiple_users_to_matrix() {
+    let test = Test::new();
+    let (create_room_forwarder, create_room_receiver) = handlers::MatrixCreateRoom::with_forwarder(test.config.as_url.clone());
+    let (invite_forwarder, invite_receiver) = handlers::MatrixInviteUser::with_forwarder(test.config.as_url.clone());
+    let (message_forwarder, receiver) =
+        MessageForwarder::with_path_filter("other_userspec_userthird_userDMRocketChat_id:localhost");
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+    matrix_router.post(CreateRoomEndpoint::router_path(), create_room_forwarder, "create_room");
+    matrix_router.post(InviteEndpoint::router_path(), invite_forwarder, "invite_user");
+    let mut rocketchat_router = test.default_rocketchat_routes();
+    let mut direct_messages = HashMap::new();
+    direct_messages.insert("group_dm_id", vec!["spec_user", "other_user", "third_user"]);
+    let direct_messages_list_handler =
+        handlers::RocketchatDirectMessagesList { direct_messages: direct_messages.clone(), status: status::Ok };
+    rocketchat_router.get(DM_LIST_PATH, direct_messages_list_handler, "direct_messages_list");
+    let direct_message_members_handler =
+        handlers::RocketchatDirectMessageMembers { direct_messages: Arc::new(Mutex::new(direct_messages)) };
+    rocketchat_router.get(IM_MEMBERS_PATH, direct_message_members_handler, "im_members");
+
+    let test = test
+        .with_matrix_routes(matrix_router)
+        .with_rocketchat_mock()
+        .with_custom_rocketchat_routes(rocketchat_router)
+        .with_connected_admin_room()
+        .with_logged_in_user()
+        .run();
+
+    let first_direct_message = WebhookMessage {
+        message_id: "spec_id_1".to_string(),
+        token: Some(RS_TOKEN.to_string()),
+        channel_id: "group_dm_id".to_string(),
+        channel_name: None,
+        user_id: "other_user_id".to_string(),
+        user_name: "other_user".to_string(),
+        text: "Hey there".to_string(),
+    };
+    let first_direct_message_payload = to_string(&first_direct_message).unwrap();
+
+    helpers::simulate_message_from_rocketchat(&test.config.as_url, &first_direct_message_payload);
+
+    // discard admin room creation message
+    create_room_receiver.recv_timeout(default_timeout()).unwrap();
+
+    let create_room_message = create_room_receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(create_room_message.contains("\"name\":\"other_user, spec_user, third_user (DM Rocket.Chat)\""));
+
+    // discard admin room invite
+    invite_receiver.recv_timeout(default_timeout()).unwrap();
+
+    let spec_user_invite = invite_receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(spec_user_invite.contains("\"user_id\":\"@spec_user:localhost\""));
+
+    let first_message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(first_message_received_by_matrix.contains("Hey there"));
+
+    let matrix_api = MatrixApi::new(&test.config, DEFAULT_LOGGER.clone()).unwrap();
+    let room_id = RoomId::try_from("!other_userspec_userthird_userDMRocketChat_id:localhost").unwrap();
+    let room = Room::new(&test.config, &DEFAULT_LOGGER, &(*matrix_api), room_id);
+    let user_ids = room.user_ids(Some(test.config.matrix_bot_user_id().unwrap())).unwrap();
+    assert!(user_ids.iter().any(|id| id == &UserId::try_from("@rocketchat_rcid_other_user_id:localhost").unwrap()));
+    assert!(user_ids.iter().any(|id| id == &UserId::try_from("@rocketchat_rcid_third_user_id:localhost").unwrap()));
+    assert!(!user_ids.iter().any(|id| id == &UserId::try_from("@rocketchat_rcid_spec_user_id:localhost").unwrap()));
+
+    let second_direct_message = WebhookMessage {
+        message_id: "spec_id_2".to_string(),
+        token: Some(RS_TOKEN.to_string()),
+        channel_id: "group_dm_id".to_string(),
+        channel_name: None,
+        user_id: "third_user_id".to_string(),
+        user_name: "third_user".to_string(),
+        text: "Hi everyone".to_string(),
+    };
+    let second_direct_message_payload = to_string(&second_direct_message).unwrap();
+
+    helpers::simulate_message_from_rocketchat(&test.config.as_url, &second_direct_message_payload);
+
+    let second_message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(second_message_received_by_matrix.contains("Hi everyone"));
+
+    // the existing room is used for the second message
+    assert!(create_room_receiver.recv_timeout(default_timeout()).is_err());
+}
+
+#[test]
+fn no_room_is_created_for_a_direct_message_with_multiple_users_when_no_logged_in_user_is_a_member() {
+    let test = Test::new();
+    let (create_room_forwarder, create_room_receiver) = handlers::MatrixCreateRoom::with_forwarder(test.config.as_url.clone());
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.post(CreateRoomEndpoint::router_path(), create_room_forwarder, "create_room");
+    let mut rocketchat_router = test.default_rocketchat_routes();
+    let mut direct_messages = HashMap::new();
+    direct_messages.insert("group_dm_id", vec!["other_user", "third_user", "fourth_user"]);
+    let direct_messages_list_handler =
+        handlers::RocketchatDirectMessagesList { direct_messages: HashMap::new(), status: status::Ok };
+    rocketchat_router.get(DM_LIST_PATH, direct_messages_list_handler, "direct_messages_list");
+    let direct_message_members_handler =
+        handlers::RocketchatDirectMessageMembers { direct_messages: Arc::new(Mutex::new(direct_messages)) };
+    rocketchat_router.get(IM_MEMBERS_PATH, direct_message_members_handler, "im_members");
+
+    let test = test
+        .with_matrix_routes(matrix_router)
+        .with_rocketchat_mock()
+        .with_custom_rocketchat_routes(rocketchat_router)
+        .with_connected_admin_room()
+        .with_logged_in_user()
+        .run();
+
+    let direct_message = WebhookMessage {
+        message_id: "spec_id_1".to_string(),
+        token: Some(RS_TOKEN.to_string()),
+        channel_id: "group_dm_id".to_string(),
+        channel_name: None,
+        user_id: "other_user_id".to_string(),
+        user_name: "other_user".to_string(),
+        text: "Hey there".to_string(),
+    };
+    let direct_message_payload = to_string(&direct_message).unwrap();
+
+    helpers::simulate_message_from_rocketchat(&test.config.as_url, &direct_message_payload);
+
+    // discard admin room creation
+    create_room_receiver.recv_timeout(default_timeout()).unwrap();
+    // no room is created on the Matrix server
+    assert!(create_room_receiver.recv_timeout(default_timeout()).is_err());
+}
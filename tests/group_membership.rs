@@ -0,0 +1,228 @@
+extern crate matrix_rocketchat;
+extern crate matrix_rocketchat_test;
+extern crate router;
+extern crate ruma_identifiers;
+
+use std::convert::TryFrom;
+
+use matrix_rocketchat::models::{BridgedRoom, RocketchatServer, RoomType, UserOnRocketchatServer};
+use matrix_rocketchat_test::{default_timeout, helpers, MessageForwarder, Test, RS_TOKEN};
+use ruma_identifiers::{RoomId, UserId};
+
+#[test]
+fn a_user_that_is_not_a_member_of_the_group_is_kicked_when_joining_the_bridged_room() {
+    let test = Test::new();
+    let (kick_forwarder, kick_receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.post("/_matrix/client/r0/rooms/:room_id/kick", kick_forwarder, "kick_user");
+
+    let test = test
+        .with_matrix_routes(matrix_router)
+        .with_rocketchat_mock()
+        .with_connected_admin_room()
+        .with_logged_in_user()
+        .with_bridged_group(("spec_group", vec!["spec_user"]))
+        .run();
+
+    let room_id = RoomId::try_from("!spec_group_id:localhost").unwrap();
+    let user_id = UserId::try_from("@other_user:localhost").unwrap();
+    helpers::join(&test.config, room_id.clone(), user_id.clone());
+    helpers::send_join_event_from_matrix(&test.config.as_url, room_id, user_id, None);
+
+    let kick_request = kick_receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(kick_request.contains("@other_user:localhost"));
+    assert!(kick_request.contains("Only members of the private Rocket.Chat group can be in this room"));
+}
+
+#[test]
+fn a_member_of_the_group_can_join_the_bridged_room() {
+    let test = Test::new();
+    let (kick_forwarder, kick_receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.post("/_matrix/client/r0/rooms/:room_id/kick", kick_forwarder, "kick_user");
+
+    let test = test
+        .with_matrix_routes(matrix_router)
+        .with_rocketchat_mock()
+        .with_connected_admin_room()
+        .with_logged_in_user()
+        .with_bridged_group(("spec_group", vec!["spec_user"]))
+        .run();
+
+    helpers::send_join_event_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!spec_group_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        None,
+    );
+
+    assert!(kick_receiver.recv_timeout(default_timeout()).is_err());
+}
+
+#[test]
+fn a_user_that_was_removed_from_the_group_is_kicked_from_the_bridged_room() {
+    let test = Test::new();
+    let (kick_forwarder, kick_receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.post("/_matrix/client/r0/rooms/:room_id/kick", kick_forwarder, "kick_user");
+    let groups = test.group_list();
+
+    let test = test
+        .with_matrix_routes(matrix_router)
+        .with_rocketchat_mock()
+        .with_connected_admin_room()
+        .with_logged_in_user()
+        .with_bridged_group(("spec_group", vec!["spec_user", "other_user"]))
+        .run();
+
+    groups.lock().unwrap().insert("spec_group", vec!["other_user"]);
+
+    let payload = r#"{
+            "token": "RS_TOKEN",
+            "channel_id": "spec_group_id",
+            "channel_name": "spec_group",
+            "user_id": "spec_user_id",
+            "user_name": "spec_user"
+        }"#
+    .replace("RS_TOKEN", RS_TOKEN);
+    helpers::simulate_message_from_rocketchat(&test.config.as_url, &payload);
+
+    let kick_request = kick_receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(kick_request.contains("@spec_user:localhost"));
+}
+
+#[test]
+fn a_user_is_kicked_from_a_bridged_group_if_the_membership_cannot_be_verified() {
+    let test = Test::new();
+    let (kick_forwarder, kick_receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.post("/_matrix/client/r0/rooms/:room_id/kick", kick_forwarder, "kick_user");
+
+    let test = test
+        .with_matrix_routes(matrix_router)
+        .with_rocketchat_mock()
+        .with_connected_admin_room()
+        .with_logged_in_user()
+        .with_bridged_group(("spec_group", vec!["spec_user", "other_user"]))
+        .run();
+
+    // nobody that can see the group is logged in anymore
+    let connection = test.connection_pool.get().unwrap();
+    let server = RocketchatServer::find_by_token(&connection, RS_TOKEN).unwrap().unwrap();
+    let spec_user_id = UserId::try_from("@spec_user:localhost").unwrap();
+    let mut user_on_rocketchat_server = UserOnRocketchatServer::find(&connection, &spec_user_id, server.id).unwrap();
+    user_on_rocketchat_server.set_credentials(&connection, None, None).unwrap();
+
+    let room_id = RoomId::try_from("!spec_group_id:localhost").unwrap();
+    let user_id = UserId::try_from("@other_user:localhost").unwrap();
+    helpers::join(&test.config, room_id.clone(), user_id.clone());
+    helpers::send_join_event_from_matrix(&test.config.as_url, room_id, user_id, None);
+
+    let kick_request = kick_receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(kick_request.contains("@other_user:localhost"));
+}
+
+#[test]
+fn a_user_that_is_not_a_member_of_a_public_channel_can_join_the_bridged_room() {
+    let test = Test::new();
+    let (kick_forwarder, kick_receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.post("/_matrix/client/r0/rooms/:room_id/kick", kick_forwarder, "kick_user");
+
+    let test = test
+        .with_matrix_routes(matrix_router)
+        .with_rocketchat_mock()
+        .with_connected_admin_room()
+        .with_logged_in_user()
+        .with_bridged_room(("spec_channel", vec!["spec_user"]))
+        .run();
+
+    let room_id = RoomId::try_from("!spec_channel_id:localhost").unwrap();
+    let user_id = UserId::try_from("@other_user:localhost").unwrap();
+    helpers::join(&test.config, room_id.clone(), user_id.clone());
+    helpers::send_join_event_from_matrix(&test.config.as_url, room_id, user_id, None);
+
+    assert!(kick_receiver.recv_timeout(default_timeout()).is_err());
+}
+
+#[test]
+fn a_user_can_join_the_bridged_room_of_a_public_channel_if_nobody_is_logged_in() {
+    let test = Test::new();
+    let (kick_forwarder, kick_receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.post("/_matrix/client/r0/rooms/:room_id/kick", kick_forwarder, "kick_user");
+
+    let test = test
+        .with_matrix_routes(matrix_router)
+        .with_rocketchat_mock()
+        .with_connected_admin_room()
+        .with_logged_in_user()
+        .with_bridged_room(("spec_channel", vec!["spec_user"]))
+        .run();
+
+    let connection = test.connection_pool.get().unwrap();
+    let server = RocketchatServer::find_by_token(&connection, RS_TOKEN).unwrap().unwrap();
+    let spec_user_id = UserId::try_from("@spec_user:localhost").unwrap();
+    let mut user_on_rocketchat_server = UserOnRocketchatServer::find(&connection, &spec_user_id, server.id).unwrap();
+    user_on_rocketchat_server.set_credentials(&connection, None, None).unwrap();
+
+    let room_id = RoomId::try_from("!spec_channel_id:localhost").unwrap();
+    let user_id = UserId::try_from("@other_user:localhost").unwrap();
+    helpers::join(&test.config, room_id.clone(), user_id.clone());
+    helpers::send_join_event_from_matrix(&test.config.as_url, room_id, user_id, None);
+
+    assert!(kick_receiver.recv_timeout(default_timeout()).is_err());
+}
+
+#[test]
+fn a_user_can_join_a_bridged_room_with_an_unknown_type_if_nobody_is_logged_in() {
+    let test = Test::new();
+    let (kick_forwarder, kick_receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.post("/_matrix/client/r0/rooms/:room_id/kick", kick_forwarder, "kick_user");
+
+    let test = test
+        .with_matrix_routes(matrix_router)
+        .with_rocketchat_mock()
+        .with_connected_admin_room()
+        .with_logged_in_user()
+        .with_bridged_group(("spec_group", vec!["spec_user", "other_user"]))
+        .run();
+
+    // the room was bridged before the type of the room was recorded
+    let connection = test.connection_pool.get().unwrap();
+    let room_id = RoomId::try_from("!spec_group_id:localhost").unwrap();
+    let bridged_room = BridgedRoom::find_by_matrix_room_id(&connection, &room_id).unwrap().unwrap();
+    bridged_room.delete(&connection).unwrap();
+
+    let server = RocketchatServer::find_by_token(&connection, RS_TOKEN).unwrap().unwrap();
+    let spec_user_id = UserId::try_from("@spec_user:localhost").unwrap();
+    let mut user_on_rocketchat_server = UserOnRocketchatServer::find(&connection, &spec_user_id, server.id).unwrap();
+    user_on_rocketchat_server.set_credentials(&connection, None, None).unwrap();
+
+    let user_id = UserId::try_from("@other_user:localhost").unwrap();
+    helpers::join(&test.config, room_id.clone(), user_id.clone());
+    helpers::send_join_event_from_matrix(&test.config.as_url, room_id, user_id, None);
+
+    assert!(kick_receiver.recv_timeout(default_timeout()).is_err());
+}
+
+#[test]
+fn the_type_of_a_room_is_recorded_when_it_is_bridged() {
+    let test = Test::new()
+        .with_rocketchat_mock()
+        .with_connected_admin_room()
+        .with_logged_in_user()
+        .with_bridged_room(("spec_channel", vec!["spec_user"]))
+        .with_bridged_group(("spec_group", vec!["spec_user"]))
+        .run();
+
+    let connection = test.connection_pool.get().unwrap();
+    let channel_room_id = RoomId::try_from("!spec_channel_id:localhost").unwrap();
+    let channel_room = BridgedRoom::find_by_matrix_room_id(&connection, &channel_room_id).unwrap().unwrap();
+    assert_eq!(channel_room.room_type(), Some(RoomType::Channel));
+
+    let group_room_id = RoomId::try_from("!spec_group_id:localhost").unwrap();
+    let group_room = BridgedRoom::find_by_matrix_room_id(&connection, &group_room_id).unwrap().unwrap();
+    assert_eq!(group_room.room_type(), Some(RoomType::Group));
+}
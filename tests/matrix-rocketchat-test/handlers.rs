@@ -12,11 +12,12 @@ use super::{
     UsersInRooms, AS_TOKEN, DEFAULT_LOGGER,
 };
 use iron::prelude::*;
+use iron::url::form_urlencoded;
 use iron::url::percent_encoding::percent_decode;
 use iron::url::Url;
 use iron::{status, BeforeMiddleware, Chain, Handler};
 use matrix_rocketchat::api::rocketchat::v1::Message as RocketchatMessage;
-use matrix_rocketchat::api::rocketchat::{Channel, User};
+use matrix_rocketchat::api::rocketchat::{Channel, MemberRoles, User};
 use matrix_rocketchat::errors::{MatrixErrorResponse, RocketchatErrorResponse};
 use persistent::Write;
 use router::Router;
@@ -95,6 +96,75 @@ impl Handler for RocketchatLogin {
     }
 }
 
+pub struct RocketchatTwoFactorLogin {
+    pub code: &'static str,
+}
+
+impl Handler for RocketchatTwoFactorLogin {
+    fn handle(&self, request: &mut Request) -> IronResult<Response> {
+        debug!(DEFAULT_LOGGER, "Rocket.Chat mock server got two-factor login request");
+
+        let request_payload = extract_payload(request);
+        let login_request: Value = serde_json::from_str(&request_payload).unwrap();
+
+        let (status, payload) = match login_request.get("code").and_then(|code| code.as_str()) {
+            Some(code) if code == self.code => (
+                status::Ok,
+                r#"{
+                    "status": "success",
+                    "data": {
+                        "authToken": "spec_auth_token",
+                        "userId": "spec_user_id"
+                    }
+                }"#,
+            ),
+            Some(_) => (
+                status::Unauthorized,
+                r#"{
+                    "status": "error",
+                    "error": "totp-invalid",
+                    "message": "TOTP Invalid [totp-invalid]"
+                }"#,
+            ),
+            None => (
+                status::Unauthorized,
+                r#"{
+                    "status": "error",
+                    "error": "totp-required",
+                    "message": "TOTP Required [totp-required]",
+                    "details": { "method": "totp" }
+                }"#,
+            ),
+        };
+
+        Ok(Response::with((status, payload.to_string())))
+    }
+}
+
+pub struct RocketchatImCreate {}
+
+impl Handler for RocketchatImCreate {
+    fn handle(&self, request: &mut Request) -> IronResult<Response> {
+        debug!(DEFAULT_LOGGER, "Rocket.Chat mock server got create direct message request");
+
+        let request_payload = extract_payload(request);
+        let im_create_request: Value = serde_json::from_str(&request_payload).unwrap();
+        let username = im_create_request.get("username").and_then(|username| username.as_str()).unwrap_or_default();
+
+        let payload = r#"{
+            "room": {
+                "_id": "spec_user_id_USERNAME_id",
+                "t": "d",
+                "usernames": ["spec_user", "USERNAME"]
+            },
+            "success": true
+        }"#
+        .replace("USERNAME", username);
+
+        Ok(Response::with((status::Ok, payload)))
+    }
+}
+
 pub struct RocketchatMe {
     pub username: Arc<Mutex<String>>,
 }
@@ -124,10 +194,12 @@ impl Handler for RocketchatChannelsList {
 
         let mut channels: Vec<String> = Vec::new();
 
-        for channel_name in self.channels.lock().unwrap().keys().map(|k| *k) {
+        for (channel_name, members) in self.channels.lock().unwrap().iter() {
             let channel = r#"{
                 "_id": "CHANNEL_NAME_id",
                 "name": "CHANNEL_NAME",
+                "topic": "CHANNEL_NAME topic",
+                "usersCount": USERS_COUNT,
                 "t": "c",
                 "msgs": 0,
                 "u": {
@@ -139,11 +211,17 @@ impl Handler for RocketchatChannelsList {
                 "sysMes": true,
                 "_updatedAt": "2017-02-12T13:20:22.092Z"
             }"#
-            .replace("CHANNEL_NAME", channel_name);
+            .replace("CHANNEL_NAME", channel_name)
+            .replace("USERS_COUNT", &members.len().to_string());
             channels.push(channel);
         }
 
-        let payload = "{ \"channels\": [".to_string() + &channels.join(",") + "], \"success\": true }";
+        let payload = format!(
+            "{{ \"channels\": [{}], \"count\": {}, \"offset\": 0, \"total\": {}, \"success\": true }}",
+            channels.join(","),
+            channels.len(),
+            channels.len()
+        );
 
         Ok(Response::with((self.status, payload)))
     }
@@ -190,6 +268,25 @@ impl Handler for RocketchatRoomMembers {
     }
 }
 
+pub struct RocketchatRoomRoles {
+    pub roles: Arc<Mutex<HashMap<&'static str, &'static str>>>,
+}
+
+impl Handler for RocketchatRoomRoles {
+    fn handle(&self, _request: &mut Request) -> IronResult<Response> {
+        debug!(DEFAULT_LOGGER, "Rocket.Chat mock server got room roles request");
+
+        let mut member_roles = Vec::new();
+        for (user_name, role) in self.roles.lock().unwrap().iter() {
+            let user = User { id: format!("{}_id", user_name), username: user_name.to_string() };
+            member_roles.push(MemberRoles { user, roles: vec![role.to_string()] });
+        }
+
+        let payload = format!("{{\"roles\": {}, \"success\": true}}", serde_json::to_string(&member_roles).unwrap());
+        Ok(Response::with((status::Ok, payload)))
+    }
+}
+
 pub struct RocketchatGroupsList {
     pub groups: Arc<Mutex<HashMap<&'static str, Vec<&'static str>>>>,
     pub status: status::Status,
@@ -201,10 +298,12 @@ impl Handler for RocketchatGroupsList {
 
         let mut groups: Vec<String> = Vec::new();
 
-        for group_name in self.groups.lock().unwrap().keys().map(|k| *k) {
+        for (group_name, members) in self.groups.lock().unwrap().iter() {
             let channel = r#"{
                 "_id": "GROUP_NAME_id",
                 "name": "GROUP_NAME",
+                "topic": "GROUP_NAME topic",
+                "usersCount": USERS_COUNT,
                 "t": "c",
                 "msgs": 0,
                 "u": {
@@ -216,11 +315,17 @@ impl Handler for RocketchatGroupsList {
                 "sysMes": true,
                 "_updatedAt": "2017-02-12T13:20:22.092Z"
             }"#
-            .replace("GROUP_NAME", group_name);
+            .replace("GROUP_NAME", group_name)
+            .replace("USERS_COUNT", &members.len().to_string());
             groups.push(channel);
         }
 
-        let payload = "{ \"groups\": [".to_string() + &groups.join(",") + "], \"success\": true }";
+        let payload = format!(
+            "{{ \"groups\": [{}], \"count\": {}, \"offset\": 0, \"total\": {}, \"success\": true }}",
+            groups.join(","),
+            groups.len(),
+            groups.len()
+        );
 
         Ok(Response::with((self.status, payload)))
     }
@@ -244,7 +349,12 @@ impl Handler for RocketchatJoinedRooms {
             Some(room_names) => {
                 let mut rooms = Vec::new();
                 for room_name in room_names {
-                    let room = Channel { id: format!("{}_id", room_name), name: Some(room_name.to_string()) };
+                    let room = Channel {
+                        id: format!("{}_id", room_name),
+                        name: Some(room_name.to_string()),
+                        topic: None,
+                        users_count: None,
+                    };
                     rooms.push(room);
                 }
 
@@ -296,6 +406,41 @@ impl Handler for RocketchatDirectMessagesList {
     }
 }
 
+pub struct RocketchatDirectMessageMembers {
+    pub direct_messages: Arc<Mutex<HashMap<&'static str, Vec<&'static str>>>>,
+}
+
+impl Handler for RocketchatDirectMessageMembers {
+    fn handle(&self, request: &mut Request) -> IronResult<Response> {
+        debug!(DEFAULT_LOGGER, "Rocket.Chat mock server got direct message members request");
+
+        let url: Url = request.url.clone().into();
+        let mut query_pairs = url.query_pairs();
+        let (_, room_id) = query_pairs.find(|&(ref key, _)| key == "roomId").unwrap().to_owned();
+        let room_id_ref: &str = room_id.as_ref();
+
+        let (status, payload) = match self.direct_messages.lock().unwrap().get(room_id_ref) {
+            Some(user_names) => {
+                let users: Vec<User> = user_names
+                    .iter()
+                    .map(|user_name| User { id: format!("{}_id", user_name), username: user_name.to_string() })
+                    .collect();
+                let members = serde_json::to_string(&users).unwrap();
+                let payload = format!(
+                    "{{\"members\": {}, \"count\": {}, \"offset\": 0,\"total\": {}, \"success\": true}}",
+                    members,
+                    users.len(),
+                    users.len()
+                );
+                (status::Ok, payload)
+            }
+            None => (status::BadRequest, r#"{"success": false, "error": "error-room-not-found"}"#.to_string()),
+        };
+
+        Ok(Response::with((status, payload)))
+    }
+}
+
 pub struct RocketchatMessageResponder {
     pub message: Arc<Mutex<Option<RocketchatMessage>>>,
 }
@@ -325,6 +470,19 @@ impl Handler for RocketchatMessageResponder {
     }
 }
 
+pub struct RocketchatPostMessage {
+    pub message_id: &'static str,
+}
+
+impl Handler for RocketchatPostMessage {
+    fn handle(&self, _request: &mut Request) -> IronResult<Response> {
+        debug!(DEFAULT_LOGGER, "Rocket.Chat mock server got post message request");
+
+        let payload = "{ \"success\": true, \"message\": { \"_id\": \"".to_string() + self.message_id + "\" }}";
+        Ok(Response::with((status::Ok, payload)))
+    }
+}
+
 pub struct RocketchatUsersInfo {}
 
 impl Handler for RocketchatUsersInfo {
@@ -332,10 +490,15 @@ impl Handler for RocketchatUsersInfo {
         debug!(DEFAULT_LOGGER, "Rocket.Chat mock server got user info request");
 
         let url: Url = request.url.clone().into();
-        let mut query_pairs = url.query_pairs();
-
-        let (status, payload) = match query_pairs.find(|&(ref key, _)| key == "username") {
-            Some((_, ref username)) => (
+        // in the tests the user ID is constructed by appending _id to the username
+        let username = url
+            .query_pairs()
+            .find(|&(ref key, _)| key == "username")
+            .map(|(_, username)| username.to_string())
+            .or_else(|| url.query_pairs().find(|&(ref key, _)| key == "userId").map(|(_, id)| id.replace("_id", "")));
+
+        let (status, payload) = match username {
+            Some(ref username) => (
                 status::Ok,
                 r#"{
                     "user": {
@@ -582,6 +745,33 @@ impl Handler for MatrixGetDisplayName {
     }
 }
 
+/// Access tokens `<name>_access_token` belong to the user `@<name>_user:localhost`, all other
+/// tokens are unknown.
+pub struct MatrixWhoami {}
+
+impl Handler for MatrixWhoami {
+    fn handle(&self, request: &mut Request) -> IronResult<Response> {
+        debug!(DEFAULT_LOGGER, "Matrix mock server got whoami request");
+        let query = request.url.query().unwrap_or_default().to_string();
+        let access_token = form_urlencoded::parse(query.as_bytes())
+            .find(|&(ref key, _)| key == "access_token")
+            .map(|(_, value)| value.into_owned())
+            .unwrap_or_default();
+
+        if !access_token.ends_with("_access_token") {
+            let payload = r#"{
+                    "errcode":"M_UNKNOWN_TOKEN",
+                    "error":"Unrecognised access token"
+                }"#;
+            return Ok(Response::with((status::Unauthorized, payload.to_string())));
+        }
+
+        let user_id = format!("@{}_user:localhost", access_token.trim_end_matches("_access_token"));
+        let payload = json!({ "user_id": user_id }).to_string();
+        Ok(Response::with((status::Ok, payload)))
+    }
+}
+
 pub struct MatrixCreateRoom {
     pub as_url: String,
 }
@@ -949,6 +1139,34 @@ impl Handler for GetRoomAlias {
     }
 }
 
+pub struct PutRoomAlias {}
+
+impl Handler for PutRoomAlias {
+    fn handle(&self, request: &mut Request) -> IronResult<Response> {
+        debug!(DEFAULT_LOGGER, "Matrix mock server got put room alias request");
+
+        let params = request.extensions.get::<Router>().unwrap().clone();
+        let url_room_alias = params.find("room_alias").unwrap();
+        let decoded_room_alias = percent_decode(url_room_alias.as_bytes()).decode_utf8().unwrap();
+        let room_alias = RoomAliasId::try_from(decoded_room_alias.as_ref()).unwrap();
+
+        let request_payload = extract_payload(request);
+        let payload: serde_json::Value = serde_json::from_str(&request_payload).unwrap();
+        let room_id = RoomId::try_from(payload["room_id"].as_str().unwrap()).unwrap();
+
+        if let Err(err) = add_alias_to_room(request, room_id, room_alias) {
+            debug!(DEFAULT_LOGGER, "{}", err);
+            let payload = r#"{
+                "errcode":"M_UNKNOWN",
+                "error":"Room alias already exists."
+            }"#;
+            return Ok(Response::with((status::Conflict, payload.to_string())));
+        }
+
+        Ok(Response::with((status::Ok, "{}".to_string())))
+    }
+}
+
 pub struct DeleteRoomAlias {}
 
 impl Handler for DeleteRoomAlias {
@@ -996,6 +1214,8 @@ impl Handler for GetRoomState {
             EventType::RoomCreate => get_state_from_room(request, room_id, user_id.clone(), "creator".to_string()),
             EventType::RoomCanonicalAlias => get_state_from_room(request, room_id, user_id.clone(), "alias".to_string()),
             EventType::RoomTopic => get_state_from_room(request, room_id, user_id.clone(), "topic".to_string()),
+            // the mock doesn't store power levels, so the room creator is the only room admin
+            EventType::RoomPowerLevels => get_state_from_room(request, room_id, user_id.clone(), "users".to_string()),
             _ => panic!("Event type {} not covered", event_type),
         };
 
@@ -4,6 +4,7 @@ use std::convert::TryFrom;
 use super::{DEFAULT_LOGGER, HS_TOKEN};
 use diesel::sqlite::SqliteConnection;
 use http::{Method, StatusCode};
+use iron::url::form_urlencoded;
 use matrix_rocketchat::api::{MatrixApi, RequestData, RestApi};
 use matrix_rocketchat::models::Events;
 use matrix_rocketchat::models::UserOnRocketchatServer;
@@ -129,6 +130,52 @@ pub fn send_leave_event_from_matrix(as_url: &str, room_id: RoomId, user_id: User
     simulate_message_from_matrix(as_url, &leave_payload);
 }
 
+pub fn send_kick_event_from_matrix(as_url: &str, room_id: RoomId, user_id: UserId, kicker_id: UserId) {
+    let kick_event = MemberEvent {
+        content: MemberEventContent {
+            avatar_url: None,
+            displayname: None,
+            membership: MembershipState::Leave,
+            third_party_invite: None,
+            is_direct: None,
+        },
+        event_id: EventId::new("localhost").unwrap(),
+        event_type: EventType::RoomMember,
+        invite_room_state: None,
+        prev_content: None,
+        room_id: Some(room_id),
+        state_key: format!("{}", user_id),
+        unsigned: None,
+        sender: kicker_id,
+        origin_server_ts: 0,
+    };
+
+    let events = Events { events: vec![Box::new(Event::RoomMember(kick_event))] };
+    let kick_payload = to_string(&events).unwrap();
+    simulate_message_from_matrix(as_url, &kick_payload);
+}
+
+pub fn send_power_levels_event_from_matrix(
+    as_url: &str,
+    room_id: RoomId,
+    sender_id: UserId,
+    previous_power_levels: HashMap<UserId, i64>,
+    power_levels: HashMap<UserId, i64>,
+) {
+    let mut power_levels_event = Map::new();
+    power_levels_event.insert("content".to_string(), power_levels_content(power_levels));
+    power_levels_event.insert("prev_content".to_string(), power_levels_content(previous_power_levels));
+    power_levels_event.insert("event_id".to_string(), Value::String(EventId::new("localhost").unwrap().to_string()));
+    power_levels_event.insert("type".to_string(), Value::String(EventType::RoomPowerLevels.to_string()));
+    power_levels_event.insert("room_id".to_string(), Value::String(room_id.to_string()));
+    power_levels_event.insert("sender".to_string(), Value::String(sender_id.to_string()));
+    power_levels_event.insert("state_key".to_string(), Value::String("".to_string()));
+    power_levels_event.insert("origin_server_ts".to_string(), json!(0));
+
+    let power_levels_payload = json!({ "events": [power_levels_event] }).to_string();
+    simulate_message_from_matrix(as_url, &power_levels_payload);
+}
+
 pub fn send_room_message_from_matrix(as_url: &str, room_id: RoomId, user_id: UserId, body: String) {
     let message_event = MessageEvent {
         content: MessageEventContent::Text(TextMessageEventContent { body: body, msgtype: MessageType::Text }),
@@ -274,6 +321,29 @@ pub fn simulate_message_from_matrix(as_url: &str, payload: &str) -> (String, Sta
     RestApi::call(&Method::PUT, &url, RequestData::Body(payload.to_owned()), &params, None).unwrap()
 }
 
+pub fn query_room_alias(as_url: &str, room_alias_id: &RoomAliasId) -> (String, StatusCode) {
+    let encoded_room_alias = form_urlencoded::byte_serialize(room_alias_id.to_string().as_bytes()).collect::<String>();
+    let url = format!("{}/rooms/{}", as_url, encoded_room_alias);
+    let mut params = HashMap::new();
+    params.insert("access_token", HS_TOKEN);
+    RestApi::call(&Method::GET, &url, RequestData::Body("".to_owned()), &params, None).unwrap()
+}
+
+pub fn query_user(as_url: &str, user_id: &UserId) -> (String, StatusCode) {
+    let encoded_user_id = form_urlencoded::byte_serialize(user_id.to_string().as_bytes()).collect::<String>();
+    let url = format!("{}/users/{}", as_url, encoded_user_id);
+    let mut params = HashMap::new();
+    params.insert("access_token", HS_TOKEN);
+    RestApi::call(&Method::GET, &url, RequestData::Body("".to_owned()), &params, None).unwrap()
+}
+
+pub fn query_thirdparty(as_url: &str, kind: &str, protocol: &str, fields: Vec<(&str, &str)>) -> (String, StatusCode) {
+    let url = format!("{}/_matrix/app/v1/thirdparty/{}/{}", as_url, kind, protocol);
+    let mut params: HashMap<&str, &str> = fields.into_iter().collect();
+    params.insert("access_token", HS_TOKEN);
+    RestApi::call(&Method::GET, &url, RequestData::Body("".to_owned()), &params, None).unwrap()
+}
+
 pub fn simulate_message_from_rocketchat(as_url: &str, payload: &str) -> (String, StatusCode) {
     let url = format!("{}/rocketchat", as_url);
     let params = HashMap::new();
@@ -305,3 +375,23 @@ pub fn add_room_alias_id(config: &Config, room_id: RoomId, room_alias_id: RoomAl
 
     RestApi::call_matrix(&SendStateEventForEmptyKeyEndpoint::method(), &endpoint, payload, &params).unwrap();
 }
+
+fn power_levels_content(power_levels: HashMap<UserId, i64>) -> Value {
+    let mut users = Map::new();
+    for (user_id, power_level) in power_levels {
+        users.insert(user_id.to_string(), json!(power_level));
+    }
+
+    json!({
+        "ban": 50,
+        "events": {},
+        "events_default": 0,
+        "invite": 50,
+        "kick": 50,
+        "notifications": { "room": 50 },
+        "redact": 50,
+        "state_default": 50,
+        "users": users,
+        "users_default": 0
+    })
+}
@@ -47,7 +47,8 @@ use iron::prelude::*;
 use iron::typemap::Key;
 use iron::{status, Chain, Iron, Listening};
 use matrix_rocketchat::api::rocketchat::v1::{
-    CHANNELS_LIST_PATH, CHANNELS_MEMBERS_PATH, GROUPS_LIST_PATH, GROUPS_MEMBERS_PATH, LOGIN_PATH, ME_PATH, USERS_INFO_PATH,
+    CHANNELS_LIST_PATH, CHANNELS_MEMBERS_PATH, CHANNELS_ROLES_PATH, GROUPS_LIST_PATH, GROUPS_MEMBERS_PATH, GROUPS_ROLES_PATH,
+    IM_CREATE_PATH, LOGIN_PATH, ME_PATH, USERS_INFO_PATH,
 };
 use matrix_rocketchat::api::MatrixApi;
 use matrix_rocketchat::models::ConnectionPool;
@@ -57,6 +58,7 @@ use r2d2::Pool;
 use r2d2_diesel::ConnectionManager;
 use router::Router;
 use ruma_client_api::r0::account::register::Endpoint as RegisterEndpoint;
+use ruma_client_api::r0::alias::create_alias::Endpoint as CreateAliasEndpoint;
 use ruma_client_api::r0::alias::delete_alias::Endpoint as DeleteAliasEndpoint;
 use ruma_client_api::r0::alias::get_alias::Endpoint as GetAliasEndpoint;
 use ruma_client_api::r0::membership::invite_user::Endpoint as InviteUserEndpoint;
@@ -86,6 +88,8 @@ const AS_TOKEN: &str = "at";
 pub const HS_TOKEN: &str = "ht";
 /// Rocket.Chat token used in the tests
 pub const RS_TOKEN: &str = "rt";
+/// Key to encrypt the tokens in the test database
+pub const DATABASE_ENCRYPTION_KEY: &str = "5c1e0a7f3b9d2c4e6f8a1b3c5d7e9f0a2b4c6d8e0f1a3b5c7d9e1f2a4b6c8d0e";
 /// Number of threads that iron uses when running tests
 pub const IRON_THREADS: usize = 4;
 /// The version the mock Rocket.Chat server announces
@@ -195,6 +199,9 @@ pub struct Test {
     /// A list of Rocket.Chat groups that are returned when querying the Rocket.Chat mock
     /// groups.list endpoint
     pub groups: Arc<Mutex<HashMap<&'static str, Vec<&'static str>>>>,
+    /// The roles of Rocket.Chat users (username and role) that are returned when querying the
+    /// Rocket.Chat mock channels.roles and groups.roles endpoints
+    pub roles: Arc<Mutex<HashMap<&'static str, &'static str>>>,
     /// The matrix homeserver mock listening server
     pub hs_listening: Option<Listening>,
     /// Routes that the homeserver mock can handle
@@ -233,6 +240,7 @@ impl Test {
             config: config,
             connection_pool: connection_pool,
             groups: Arc::new(Mutex::new(HashMap::new())),
+            roles: Arc::new(Mutex::new(HashMap::new())),
             hs_listening: None,
             with_logged_in_user: false,
             matrix_homeserver_mock_router: None,
@@ -554,6 +562,8 @@ impl Test {
 
         router.put(SetDisplaynameEndpoint::router_path(), handlers::MatrixSetDisplayName {}, "set_displayname");
 
+        router.get("/_matrix/client/r0/account/whoami", handlers::MatrixWhoami {}, "whoami");
+
         router.post(
             CreateRoomEndpoint::router_path(),
             handlers::MatrixCreateRoom { as_url: self.config.as_url.clone() },
@@ -571,6 +581,7 @@ impl Test {
         get_room_alias.link_before(handlers::PermissionCheck {});
         router.get(GetAliasEndpoint::router_path(), get_room_alias, "get_room_alias");
 
+        router.put(CreateAliasEndpoint::router_path(), handlers::PutRoomAlias {}, "put_room_alias");
         router.delete(DeleteAliasEndpoint::router_path(), handlers::DeleteRoomAlias {}, "delete_room_alias");
 
         router.post("*", handlers::EmptyJson {}, "default_post");
@@ -596,6 +607,7 @@ impl Test {
         let me_username = Arc::new(Mutex::new("spec_user".to_string()));
         router.get(ME_PATH, handlers::RocketchatMe { username: Arc::clone(&me_username) }, "me");
         router.get(USERS_INFO_PATH, handlers::RocketchatUsersInfo {}, "users_info");
+        router.post(IM_CREATE_PATH, handlers::RocketchatImCreate {}, "im_create");
 
         router.get(
             CHANNELS_LIST_PATH,
@@ -621,6 +633,9 @@ impl Test {
             "get_group_members",
         );
 
+        router.get(CHANNELS_ROLES_PATH, handlers::RocketchatRoomRoles { roles: Arc::clone(&self.roles) }, "channels_roles");
+        router.get(GROUPS_ROLES_PATH, handlers::RocketchatRoomRoles { roles: Arc::clone(&self.roles) }, "groups_roles");
+
         router.post("*", handlers::EmptyJson {}, "default_post");
         router.put("*", handlers::EmptyJson {}, "default_put");
 
@@ -636,6 +651,11 @@ impl Test {
     pub fn group_list(&self) -> Arc<Mutex<HashMap<&'static str, Vec<&'static str>>>> {
         Arc::clone(&self.groups)
     }
+
+    /// Get the roles of Rocket.Chat users that are used by the Rocket.Chat mock.
+    pub fn role_list(&self) -> Arc<Mutex<HashMap<&'static str, &'static str>>> {
+        Arc::clone(&self.roles)
+    }
 }
 
 impl Drop for Test {
@@ -668,6 +688,7 @@ pub fn build_test_config(temp_dir: &TempDir) -> Config {
         as_url: as_url,
         // is set if a homeserver mock is used in the test
         hs_url: "".to_string(),
+        hs_public_url: None,
         hs_domain: "localhost".to_string(),
         sender_localpart: "rocketchat".to_string(),
         database_url: database_url.to_string(),
@@ -679,6 +700,13 @@ pub fn build_test_config(temp_dir: &TempDir) -> Config {
         use_https: false,
         pkcs12_path: None,
         pkcs12_password: None,
+        relay_message_template: None,
+        double_puppeting_shared_secret: None,
+        database_encryption_key: Some(DATABASE_ENCRYPTION_KEY.to_string()),
+        bridge_admins: None,
+        publish_rooms_to_directory: None,
+        create_spaces: None,
+        role_sync_interval_in_seconds: Some(0),
     }
 }
 
@@ -0,0 +1,194 @@
+extern crate iron;
+extern crate matrix_rocketchat;
+extern crate matrix_rocketchat_test;
+extern crate router;
+extern crate ruma_client_api;
+extern crate ruma_identifiers;
+extern crate serde_json;
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::sync::{Arc, Mutex};
+
+use iron::status;
+use matrix_rocketchat::api::rocketchat::v1::{DM_LIST_PATH, IM_MEMBERS_PATH};
+use matrix_rocketchat::api::rocketchat::WebhookMessage;
+use matrix_rocketchat_test::{default_timeout, handlers, helpers, MessageForwarder, Test, RS_TOKEN};
+use ruma_client_api::r0::room::create_room::Endpoint as CreateRoomEndpoint;
+use ruma_client_api::Endpoint;
+use ruma_identifiers::{RoomId, UserId};
+use serde_json::to_string;
+
+const ROOM_DIRECTORY_PATH: &str = "/_matrix/client/r0/directory/list/appservice/:network_id/:room_id";
+const SPACE_CHILD_PATH: &str = "/_matrix/client/r0/rooms/:room_id/state/m.space.child/:state_key";
+
+#[test]
+fn a_bridged_public_channel_is_published_to_the_room_directory() {
+    let mut test = Test::new();
+    test.config.publish_rooms_to_directory = Some(true);
+    let (directory_forwarder, directory_receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(ROOM_DIRECTORY_PATH, directory_forwarder, "set_room_directory_visibility");
+
+    let _test = test
+        .with_matrix_routes(matrix_router)
+        .with_rocketchat_mock()
+        .with_connected_admin_room()
+        .with_logged_in_user()
+        .with_bridged_room(("spec_channel", vec!["spec_user"]))
+        .run();
+
+    let directory_request = directory_receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(directory_request.contains("\"visibility\":\"public\""));
+}
+
+#[test]
+fn bridged_rooms_are_not_published_to_the_room_directory_by_default() {
+    let test = Test::new();
+    let (directory_forwarder, directory_receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(ROOM_DIRECTORY_PATH, directory_forwarder, "set_room_directory_visibility");
+
+    let _test = test
+        .with_matrix_routes(matrix_router)
+        .with_rocketchat_mock()
+        .with_connected_admin_room()
+        .with_logged_in_user()
+        .with_bridged_room(("spec_channel", vec!["spec_user"]))
+        .run();
+
+    assert!(directory_receiver.recv_timeout(default_timeout()).is_err());
+}
+
+#[test]
+fn a_bridged_group_is_neither_published_to_the_room_directory_nor_added_to_the_space() {
+    let mut test = Test::new();
+    test.config.publish_rooms_to_directory = Some(true);
+    test.config.create_spaces = Some(true);
+    let (directory_forwarder, directory_receiver) = MessageForwarder::new();
+    let (space_child_forwarder, space_child_receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(ROOM_DIRECTORY_PATH, directory_forwarder, "set_room_directory_visibility");
+    matrix_router.put(SPACE_CHILD_PATH, space_child_forwarder, "set_space_child");
+
+    let _test = test
+        .with_matrix_routes(matrix_router)
+        .with_rocketchat_mock()
+        .with_connected_admin_room()
+        .with_logged_in_user()
+        .with_bridged_group(("spec_group", vec!["spec_user"]))
+        .run();
+
+    assert!(directory_receiver.recv_timeout(default_timeout()).is_err());
+    assert!(space_child_receiver.recv_timeout(default_timeout()).is_err());
+}
+
+#[test]
+fn a_direct_message_with_multiple_users_is_neither_published_to_the_room_directory_nor_added_to_the_space() {
+    let mut test = Test::new();
+    test.config.publish_rooms_to_directory = Some(true);
+    test.config.create_spaces = Some(true);
+    let (directory_forwarder, directory_receiver) = MessageForwarder::new();
+    let (space_child_forwarder, space_child_receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(ROOM_DIRECTORY_PATH, directory_forwarder, "set_room_directory_visibility");
+    matrix_router.put(SPACE_CHILD_PATH, space_child_forwarder, "set_space_child");
+    let mut rocketchat_router = test.default_rocketchat_routes();
+    let mut direct_messages = HashMap::new();
+    direct_messages.insert("group_dm_id", vec!["spec_user", "other_user", "third_user"]);
+    let direct_messages_list_handler =
+        handlers::RocketchatDirectMessagesList { direct_messages: direct_messages.clone(), status: status::Ok };
+    rocketchat_router.get(DM_LIST_PATH, direct_messages_list_handler, "direct_messages_list");
+    let direct_message_members_handler =
+        handlers::RocketchatDirectMessageMembers { direct_messages: Arc::new(Mutex::new(direct_messages)) };
+    rocketchat_router.get(IM_MEMBERS_PATH, direct_message_members_handler, "im_members");
+
+    let test = test
+        .with_matrix_routes(matrix_router)
+        .with_rocketchat_mock()
+        .with_custom_rocketchat_routes(rocketchat_router)
+        .with_connected_admin_room()
+        .with_logged_in_user()
+        .run();
+
+    let direct_message = WebhookMessage {
+        message_id: "spec_id".to_string(),
+        token: Some(RS_TOKEN.to_string()),
+        channel_id: "group_dm_id".to_string(),
+        channel_name: None,
+        user_id: "other_user_id".to_string(),
+        user_name: "other_user".to_string(),
+        text: "Hey there".to_string(),
+    };
+    let payload = to_string(&direct_message).unwrap();
+    helpers::simulate_message_from_rocketchat(&test.config.as_url, &payload);
+
+    assert!(directory_receiver.recv_timeout(default_timeout()).is_err());
+    assert!(space_child_receiver.recv_timeout(default_timeout()).is_err());
+}
+
+#[test]
+fn a_bridged_public_channel_is_added_to_the_space_of_the_rocketchat_server() {
+    let mut test = Test::new();
+    test.config.create_spaces = Some(true);
+    let (create_room_forwarder, create_room_receiver) = handlers::MatrixCreateRoom::with_forwarder(test.config.as_url.clone());
+    let (space_child_forwarder, space_child_receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.post(CreateRoomEndpoint::router_path(), create_room_forwarder, "create_room");
+    matrix_router.put(SPACE_CHILD_PATH, space_child_forwarder, "set_space_child");
+
+    let _test = test
+        .with_matrix_routes(matrix_router)
+        .with_rocketchat_mock()
+        .with_connected_admin_room()
+        .with_logged_in_user()
+        .with_bridged_room(("spec_channel", vec!["spec_user"]))
+        .run();
+
+    let mut space_requests = 0;
+    while let Ok(create_room_request) = create_room_receiver.recv_timeout(default_timeout()) {
+        if create_room_request.contains("\"type\":\"m.space\"") {
+            space_requests += 1;
+        }
+    }
+    assert_eq!(space_requests, 1);
+
+    let space_child_request = space_child_receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(space_child_request.contains("\"via\":[\"localhost\"]"));
+}
+
+#[test]
+fn an_unbridged_room_is_removed_from_the_space_of_the_rocketchat_server() {
+    let mut test = Test::new();
+    test.config.create_spaces = Some(true);
+    let (space_child_forwarder, space_child_receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SPACE_CHILD_PATH, space_child_forwarder, "set_space_child");
+
+    let test = test
+        .with_matrix_routes(matrix_router)
+        .with_rocketchat_mock()
+        .with_connected_admin_room()
+        .with_logged_in_user()
+        .with_bridged_room(("spec_channel", vec!["spec_user"]))
+        .run();
+
+    let space_child_request = space_child_receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(space_child_request.contains("\"via\":[\"localhost\"]"));
+
+    helpers::leave_room(
+        &test.config,
+        RoomId::try_from("!spec_channel_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+    );
+
+    helpers::send_room_message_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!admin_room_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        "unbridge spec_channel".to_string(),
+    );
+
+    let space_child_request = space_child_receiver.recv_timeout(default_timeout()).unwrap();
+    assert_eq!(space_child_request, "{}");
+}
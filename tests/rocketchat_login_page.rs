@@ -0,0 +1,112 @@
+extern crate http;
+extern crate iron;
+extern crate matrix_rocketchat;
+extern crate matrix_rocketchat_test;
+extern crate router;
+extern crate ruma_client_api;
+extern crate ruma_identifiers;
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+use http::{Method, StatusCode};
+use matrix_rocketchat::api::{RequestData, RestApi};
+use matrix_rocketchat::models::{RocketchatServer, UserOnRocketchatServer};
+use matrix_rocketchat_test::{default_timeout, helpers, MessageForwarder, Test};
+use ruma_client_api::r0::send::send_message_event::Endpoint as SendMessageEventEndpoint;
+use ruma_client_api::Endpoint;
+use ruma_identifiers::{RoomId, UserId};
+
+#[test]
+fn successfully_login_via_the_login_page() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+    let test = test.with_matrix_routes(matrix_router).with_rocketchat_mock().with_connected_admin_room().run();
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    let login_url = request_login_link(&test.config.as_url, &receiver);
+
+    let (body, status) =
+        RestApi::call(&Method::GET, &login_url, RequestData::Body("".to_string()), &HashMap::new(), None).unwrap();
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("Logging in Matrix user @spec_user:localhost"));
+
+    let form = "username=spec_user&password=secret".to_string();
+    let (body, status) = RestApi::call(&Method::POST, &login_url, RequestData::Body(form), &HashMap::new(), None).unwrap();
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("You are logged in."));
+
+    // the admin room is notified about the login
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(message_received_by_matrix.contains("You are logged in."));
+
+    let connection = test.connection_pool.get().unwrap();
+    let rocketchat_server = RocketchatServer::find(&connection, &test.rocketchat_mock_url.clone().unwrap()).unwrap();
+    let user_on_rocketchat_server =
+        UserOnRocketchatServer::find(&connection, &UserId::try_from("@spec_user:localhost").unwrap(), rocketchat_server.id)
+            .unwrap();
+    assert_eq!(user_on_rocketchat_server.rocketchat_auth_token.unwrap(), "spec_auth_token");
+
+    // the link can only be used once
+    let (_, status) =
+        RestApi::call(&Method::GET, &login_url, RequestData::Body("".to_string()), &HashMap::new(), None).unwrap();
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn the_login_page_shows_an_error_when_the_credentials_are_wrong() {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+    let test = test.with_matrix_routes(matrix_router).with_rocketchat_mock().with_connected_admin_room().run();
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    let login_url = request_login_link(&test.config.as_url, &receiver);
+
+    let form = "rocketchat_user_id=other_user_id&personal_access_token=spec_personal_access_token".to_string();
+    let (body, status) = RestApi::call(&Method::POST, &login_url, RequestData::Body(form), &HashMap::new(), None).unwrap();
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("The personal access token is not valid for the given user ID"));
+
+    // the link can still be used after a failed login
+    let (_, status) =
+        RestApi::call(&Method::GET, &login_url, RequestData::Body("".to_string()), &HashMap::new(), None).unwrap();
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[test]
+fn the_login_page_returns_not_found_when_the_token_is_invalid() {
+    let test = Test::new().with_rocketchat_mock().with_connected_admin_room().run();
+    let login_url = format!("{}/rocketchat/login/invalid_token", test.config.as_url);
+
+    let (body, status) =
+        RestApi::call(&Method::GET, &login_url, RequestData::Body("".to_string()), &HashMap::new(), None).unwrap();
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert!(body.contains("The login link is invalid or expired"));
+}
+
+fn request_login_link(as_url: &str, receiver: &std::sync::mpsc::Receiver<String>) -> String {
+    helpers::send_room_message_from_matrix(
+        as_url,
+        RoomId::try_from("!admin_room_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        "login-link".to_string(),
+    );
+
+    let message_received_by_matrix = receiver.recv_timeout(default_timeout()).unwrap();
+    let login_path = "/rocketchat/login/";
+    let token_start = message_received_by_matrix.find(login_path).unwrap() + login_path.len();
+    let token: String = message_received_by_matrix[token_start..].chars().take_while(|c| c.is_ascii_hexdigit()).collect();
+    format!("{}{}{}", as_url, login_path, token)
+}
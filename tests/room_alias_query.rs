@@ -0,0 +1,132 @@
+extern crate http;
+extern crate matrix_rocketchat;
+extern crate matrix_rocketchat_test;
+extern crate ruma_client_api;
+extern crate ruma_identifiers;
+
+use std::convert::TryFrom;
+
+use http::StatusCode;
+use matrix_rocketchat::api::MatrixApi;
+use matrix_rocketchat::models::Room;
+use matrix_rocketchat_test::{handlers, helpers, Test, DEFAULT_LOGGER};
+use ruma_client_api::r0::send::send_state_event_for_empty_key::Endpoint as SendStateEventForEmptyKeyEndpoint;
+use ruma_client_api::Endpoint;
+use ruma_identifiers::{RoomAliasId, RoomId, UserId};
+
+#[test]
+fn a_public_channel_is_bridged_when_the_homeserver_queries_its_room_alias() {
+    let test = Test::new();
+    test.channel_list().lock().unwrap().insert("spec_channel", vec!["spec_user", "other_user"]);
+    let test = test.with_rocketchat_mock().with_connected_admin_room().with_logged_in_user().run();
+
+    let room_alias_id = RoomAliasId::try_from("#rocketchat#rcid#spec_channel_id:localhost").unwrap();
+    let (_, status_code) = helpers::query_room_alias(&test.config.as_url, &room_alias_id);
+    assert_eq!(status_code, StatusCode::OK);
+
+    let matrix_api = MatrixApi::new(&test.config, DEFAULT_LOGGER.clone()).unwrap();
+    let room_id = matrix_api.get_room_alias(room_alias_id).unwrap().unwrap();
+    assert_eq!(room_id, RoomId::try_from("!spec_channel_id:localhost").unwrap());
+
+    let room = Room::new(&test.config, &DEFAULT_LOGGER, &(*matrix_api), room_id);
+    let other_user_id = UserId::try_from("@rocketchat_rcid_other_user_id:localhost").unwrap();
+    assert!(room.user_ids(None).unwrap().iter().any(|id| id == &other_user_id));
+}
+
+#[test]
+fn a_public_channel_is_bridged_when_the_homeserver_queries_the_room_alias_with_the_channel_name() {
+    let test = Test::new();
+    test.channel_list().lock().unwrap().insert("spec_channel", vec!["spec_user"]);
+    let test = test.with_rocketchat_mock().with_connected_admin_room().with_logged_in_user().run();
+
+    let room_alias_id = RoomAliasId::try_from("#rocketchat_rcid_spec_channel:localhost").unwrap();
+    let (_, status_code) = helpers::query_room_alias(&test.config.as_url, &room_alias_id);
+    assert_eq!(status_code, StatusCode::OK);
+
+    let matrix_api = MatrixApi::new(&test.config, DEFAULT_LOGGER.clone()).unwrap();
+    let room_id = matrix_api.get_room_alias(room_alias_id).unwrap().unwrap();
+    assert_eq!(room_id, RoomId::try_from("!spec_channel_id:localhost").unwrap());
+}
+
+#[test]
+fn the_room_alias_of_an_already_bridged_channel_points_to_the_bridged_room() {
+    let test = Test::new()
+        .with_rocketchat_mock()
+        .with_connected_admin_room()
+        .with_logged_in_user()
+        .with_bridged_room(("spec_channel", vec!["spec_user"]))
+        .run();
+
+    let room_alias_id = RoomAliasId::try_from("#rocketchat_rcid_spec_channel:localhost").unwrap();
+    let (_, status_code) = helpers::query_room_alias(&test.config.as_url, &room_alias_id);
+    assert_eq!(status_code, StatusCode::OK);
+
+    let matrix_api = MatrixApi::new(&test.config, DEFAULT_LOGGER.clone()).unwrap();
+    let room_id = matrix_api.get_room_alias(room_alias_id).unwrap().unwrap();
+    assert_eq!(room_id, RoomId::try_from("!spec_channel_id:localhost").unwrap());
+}
+
+#[test]
+fn a_channel_that_is_bridged_via_its_room_alias_is_made_public() {
+    let test = Test::new();
+    let (send_room_state, receiver) = handlers::SendRoomState::with_forwarder();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendStateEventForEmptyKeyEndpoint::router_path(), send_room_state, "send_room_state");
+    test.channel_list().lock().unwrap().insert("spec_channel", vec!["spec_user"]);
+    let test =
+        test.with_matrix_routes(matrix_router).with_rocketchat_mock().with_connected_admin_room().with_logged_in_user().run();
+
+    let room_alias_id = RoomAliasId::try_from("#rocketchat_rcid_spec_channel:localhost").unwrap();
+    let (_, status_code) = helpers::query_room_alias(&test.config.as_url, &room_alias_id);
+    assert_eq!(status_code, StatusCode::OK);
+
+    assert!(receiver.try_iter().any(|state| state.contains("\"join_rule\":\"public\"")));
+}
+
+#[test]
+fn the_join_rule_of_an_already_bridged_room_is_not_changed_when_the_homeserver_queries_its_room_alias() {
+    let test = Test::new();
+    let (send_room_state, receiver) = handlers::SendRoomState::with_forwarder();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendStateEventForEmptyKeyEndpoint::router_path(), send_room_state, "send_room_state");
+    let test = test
+        .with_matrix_routes(matrix_router)
+        .with_rocketchat_mock()
+        .with_connected_admin_room()
+        .with_logged_in_user()
+        .with_bridged_room(("spec_channel", vec!["spec_user"]))
+        .run();
+
+    // discard the state events that were sent when the room was bridged
+    receiver.try_iter().count();
+
+    let room_alias_id = RoomAliasId::try_from("#rocketchat_rcid_spec_channel:localhost").unwrap();
+    let (_, status_code) = helpers::query_room_alias(&test.config.as_url, &room_alias_id);
+    assert_eq!(status_code, StatusCode::OK);
+
+    assert!(receiver.try_iter().all(|state| !state.contains("join_rule")));
+}
+
+#[test]
+fn private_groups_are_not_bridged_on_demand() {
+    let test = Test::new();
+    test.group_list().lock().unwrap().insert("spec_group", vec!["spec_user"]);
+    let test = test.with_rocketchat_mock().with_connected_admin_room().with_logged_in_user().run();
+
+    let room_alias_id = RoomAliasId::try_from("#rocketchat_rcid_spec_group:localhost").unwrap();
+    let (_, status_code) = helpers::query_room_alias(&test.config.as_url, &room_alias_id);
+    assert_eq!(status_code, StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn the_homeserver_gets_not_found_for_an_unknown_room_alias() {
+    let test = Test::new().with_rocketchat_mock().with_connected_admin_room().with_logged_in_user().run();
+
+    let room_alias_id = RoomAliasId::try_from("#rocketchat_rcid_unknown_channel:localhost").unwrap();
+    let (_, status_code) = helpers::query_room_alias(&test.config.as_url, &room_alias_id);
+    assert_eq!(status_code, StatusCode::NOT_FOUND);
+
+    let room_alias_id = RoomAliasId::try_from("#rocketchat_unknownserver_spec_channel:localhost").unwrap();
+    let (_, status_code) = helpers::query_room_alias(&test.config.as_url, &room_alias_id);
+    assert_eq!(status_code, StatusCode::NOT_FOUND);
+}
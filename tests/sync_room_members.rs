@@ -0,0 +1,185 @@
+extern crate iron;
+extern crate matrix_rocketchat;
+extern crate matrix_rocketchat_test;
+extern crate router;
+extern crate ruma_identifiers;
+extern crate serde_json;
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::sync::{Arc, Mutex};
+
+use iron::status;
+use matrix_rocketchat::api::rocketchat::v1::{CHANNELS_KICK_PATH, DM_LIST_PATH, IM_MEMBERS_PATH};
+use matrix_rocketchat::api::rocketchat::WebhookMessage;
+use matrix_rocketchat::api::MatrixApi;
+use matrix_rocketchat::models::Room;
+use matrix_rocketchat_test::{default_timeout, handlers, helpers, MessageForwarder, Test, DEFAULT_LOGGER, RS_TOKEN};
+use ruma_identifiers::{RoomId, UserId};
+use serde_json::to_string;
+
+#[test]
+fn a_user_that_joined_the_rocketchat_channel_is_added_to_the_bridged_room() {
+    let test = Test::new();
+    let channels = test.channel_list();
+    let test = test
+        .with_rocketchat_mock()
+        .with_connected_admin_room()
+        .with_logged_in_user()
+        .with_bridged_room(("spec_channel", vec!["spec_user"]))
+        .run();
+
+    channels.lock().unwrap().insert("spec_channel", vec!["spec_user", "new_user"]);
+
+    let payload = r#"{
+            "token": "RS_TOKEN",
+            "channel_id": "spec_channel_id",
+            "channel_name": "spec_channel",
+            "user_id": "new_user_id",
+            "user_name": "new_user"
+        }"#
+    .replace("RS_TOKEN", RS_TOKEN);
+    helpers::simulate_message_from_rocketchat(&test.config.as_url, &payload);
+
+    let matrix_api = MatrixApi::new(&test.config, DEFAULT_LOGGER.clone()).unwrap();
+    let room =
+        Room::new(&test.config, &DEFAULT_LOGGER, &(*matrix_api), RoomId::try_from("!spec_channel_id:localhost").unwrap());
+    let user_ids = room.user_ids(None).unwrap();
+    assert!(user_ids.iter().any(|id| id == &UserId::try_from("@rocketchat_rcid_new_user_id:localhost").unwrap()));
+}
+
+#[test]
+fn a_user_that_left_the_rocketchat_channel_is_removed_from_the_bridged_room() {
+    let test = Test::new();
+    let channels = test.channel_list();
+    let test = test
+        .with_rocketchat_mock()
+        .with_connected_admin_room()
+        .with_logged_in_user()
+        .with_bridged_room(("spec_channel", vec!["spec_user", "other_user"]))
+        .run();
+
+    let matrix_api = MatrixApi::new(&test.config, DEFAULT_LOGGER.clone()).unwrap();
+    let room =
+        Room::new(&test.config, &DEFAULT_LOGGER, &(*matrix_api), RoomId::try_from("!spec_channel_id:localhost").unwrap());
+    let other_user_id = UserId::try_from("@rocketchat_rcid_other_user_id:localhost").unwrap();
+    assert!(room.user_ids(None).unwrap().iter().any(|id| id == &other_user_id));
+
+    channels.lock().unwrap().insert("spec_channel", vec!["spec_user"]);
+
+    let payload = r#"{
+            "token": "RS_TOKEN",
+            "channel_id": "spec_channel_id",
+            "channel_name": "spec_channel",
+            "user_id": "other_user_id",
+            "user_name": "other_user"
+        }"#
+    .replace("RS_TOKEN", RS_TOKEN);
+    helpers::simulate_message_from_rocketchat(&test.config.as_url, &payload);
+
+    assert!(!room.user_ids(None).unwrap().iter().any(|id| id == &other_user_id));
+}
+
+#[test]
+fn a_virtual_user_that_is_kicked_on_matrix_is_removed_from_the_rocketchat_channel() {
+    let test = Test::new();
+    let (kick_forwarder, kick_receiver) = MessageForwarder::new();
+    let mut rocketchat_router = test.default_rocketchat_routes();
+    rocketchat_router.post(CHANNELS_KICK_PATH, kick_forwarder, "channels_kick");
+
+    let test = test
+        .with_rocketchat_mock()
+        .with_custom_rocketchat_routes(rocketchat_router)
+        .with_connected_admin_room()
+        .with_logged_in_user()
+        .with_bridged_room(("spec_channel", vec!["spec_user", "other_user"]))
+        .run();
+
+    helpers::send_kick_event_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!spec_channel_id:localhost").unwrap(),
+        UserId::try_from("@rocketchat_rcid_other_user_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+    );
+
+    let kick_message = kick_receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(kick_message.contains("\"roomId\":\"spec_channel_id\""));
+    assert!(kick_message.contains("\"userId\":\"other_user_id\""));
+}
+
+#[test]
+fn a_virtual_user_that_is_kicked_by_a_user_who_is_not_logged_in_is_not_removed_from_the_rocketchat_channel() {
+    let test = Test::new();
+    let (kick_forwarder, kick_receiver) = MessageForwarder::new();
+    let mut rocketchat_router = test.default_rocketchat_routes();
+    rocketchat_router.post(CHANNELS_KICK_PATH, kick_forwarder, "channels_kick");
+
+    let test = test
+        .with_rocketchat_mock()
+        .with_custom_rocketchat_routes(rocketchat_router)
+        .with_connected_admin_room()
+        .with_logged_in_user()
+        .with_bridged_room(("spec_channel", vec!["spec_user", "other_user"]))
+        .run();
+
+    helpers::send_kick_event_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!spec_channel_id:localhost").unwrap(),
+        UserId::try_from("@rocketchat_rcid_other_user_id:localhost").unwrap(),
+        UserId::try_from("@not_logged_in_user:localhost").unwrap(),
+    );
+
+    assert!(kick_receiver.recv_timeout(default_timeout()).is_err());
+}
+
+#[test]
+fn a_user_that_left_a_direct_message_with_multiple_users_is_removed_from_the_bridged_room() {
+    let test = Test::new();
+    let mut rocketchat_router = test.default_rocketchat_routes();
+    let mut direct_messages = HashMap::new();
+    direct_messages.insert("group_dm_id", vec!["spec_user", "other_user", "third_user"]);
+    let direct_messages_list_handler =
+        handlers::RocketchatDirectMessagesList { direct_messages: direct_messages.clone(), status: status::Ok };
+    rocketchat_router.get(DM_LIST_PATH, direct_messages_list_handler, "direct_messages_list");
+    let direct_message_members = Arc::new(Mutex::new(direct_messages));
+    let direct_message_members_handler =
+        handlers::RocketchatDirectMessageMembers { direct_messages: Arc::clone(&direct_message_members) };
+    rocketchat_router.get(IM_MEMBERS_PATH, direct_message_members_handler, "im_members");
+
+    let test = test
+        .with_rocketchat_mock()
+        .with_custom_rocketchat_routes(rocketchat_router)
+        .with_connected_admin_room()
+        .with_logged_in_user()
+        .run();
+
+    let direct_message = WebhookMessage {
+        message_id: "spec_id".to_string(),
+        token: Some(RS_TOKEN.to_string()),
+        channel_id: "group_dm_id".to_string(),
+        channel_name: None,
+        user_id: "other_user_id".to_string(),
+        user_name: "other_user".to_string(),
+        text: "Hey there".to_string(),
+    };
+    helpers::simulate_message_from_rocketchat(&test.config.as_url, &to_string(&direct_message).unwrap());
+
+    let matrix_api = MatrixApi::new(&test.config, DEFAULT_LOGGER.clone()).unwrap();
+    let room_id = RoomId::try_from("!other_userspec_userthird_userDMRocketChat_id:localhost").unwrap();
+    let room = Room::new(&test.config, &DEFAULT_LOGGER, &(*matrix_api), room_id);
+    let third_user_id = UserId::try_from("@rocketchat_rcid_third_user_id:localhost").unwrap();
+    assert!(room.user_ids(None).unwrap().iter().any(|id| id == &third_user_id));
+
+    direct_message_members.lock().unwrap().insert("group_dm_id", vec!["spec_user", "other_user"]);
+
+    let payload = r#"{
+            "token": "RS_TOKEN",
+            "channel_id": "group_dm_id",
+            "user_id": "third_user_id",
+            "user_name": "third_user"
+        }"#
+    .replace("RS_TOKEN", RS_TOKEN);
+    helpers::simulate_message_from_rocketchat(&test.config.as_url, &payload);
+
+    assert!(!room.user_ids(None).unwrap().iter().any(|id| id == &third_user_id));
+}
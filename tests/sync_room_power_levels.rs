@@ -0,0 +1,165 @@
+extern crate matrix_rocketchat;
+extern crate matrix_rocketchat_test;
+extern crate router;
+extern crate ruma_client_api;
+extern crate ruma_identifiers;
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::sync::mpsc::Receiver;
+
+use matrix_rocketchat::api::rocketchat::v1::CHANNELS_ADD_OWNER_PATH;
+use matrix_rocketchat_test::{default_timeout, handlers, helpers, MessageForwarder, Test, RS_TOKEN};
+use ruma_client_api::r0::send::send_state_event_for_empty_key::Endpoint as SendStateEventForEmptyKeyEndpoint;
+use ruma_client_api::Endpoint;
+use ruma_identifiers::{RoomId, UserId};
+
+#[test]
+fn a_rocketchat_owner_gets_the_owner_power_level_when_the_room_is_bridged() {
+    let test = Test::new();
+    let (send_room_state, receiver) = handlers::SendRoomState::with_forwarder();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendStateEventForEmptyKeyEndpoint::router_path(), send_room_state, "send_room_state");
+    test.role_list().lock().unwrap().insert("other_user", "owner");
+
+    let _test = test
+        .with_matrix_routes(matrix_router)
+        .with_rocketchat_mock()
+        .with_connected_admin_room()
+        .with_logged_in_user()
+        .with_bridged_room(("spec_channel", vec!["spec_user", "other_user"]))
+        .run();
+
+    assert!(received_power_level(&receiver, "@rocketchat_rcid_other_user_id:localhost", 95));
+}
+
+#[test]
+fn a_role_that_changed_on_rocketchat_is_synchronized_with_the_next_membership_event() {
+    let test = Test::new();
+    let (send_room_state, receiver) = handlers::SendRoomState::with_forwarder();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendStateEventForEmptyKeyEndpoint::router_path(), send_room_state, "send_room_state");
+    let roles = test.role_list();
+
+    let test = test
+        .with_matrix_routes(matrix_router)
+        .with_rocketchat_mock()
+        .with_connected_admin_room()
+        .with_logged_in_user()
+        .with_bridged_room(("spec_channel", vec!["spec_user", "other_user"]))
+        .run();
+
+    // discard the room states that were sent while bridging the room
+    while receiver.recv_timeout(default_timeout()).is_ok() {}
+
+    roles.lock().unwrap().insert("other_user", "moderator");
+
+    let payload = r#"{
+            "token": "RS_TOKEN",
+            "channel_id": "spec_channel_id",
+            "channel_name": "spec_channel",
+            "user_id": "other_user_id",
+            "user_name": "other_user"
+        }"#
+    .replace("RS_TOKEN", RS_TOKEN);
+    helpers::simulate_message_from_rocketchat(&test.config.as_url, &payload);
+
+    assert!(received_power_level(&receiver, "@rocketchat_rcid_other_user_id:localhost", 50));
+}
+
+#[test]
+fn a_role_that_changed_on_rocketchat_is_synchronized_periodically() {
+    let mut test = Test::new();
+    test.config.role_sync_interval_in_seconds = Some(1);
+    let (send_room_state, receiver) = handlers::SendRoomState::with_forwarder();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendStateEventForEmptyKeyEndpoint::router_path(), send_room_state, "send_room_state");
+    let roles = test.role_list();
+
+    let _test = test
+        .with_matrix_routes(matrix_router)
+        .with_rocketchat_mock()
+        .with_connected_admin_room()
+        .with_logged_in_user()
+        .with_bridged_room(("spec_channel", vec!["spec_user", "other_user"]))
+        .run();
+
+    // discard the room states that were sent while bridging the room
+    while receiver.recv_timeout(default_timeout()).is_ok() {}
+
+    // no membership changed, the role change is picked up by the periodic synchronization
+    roles.lock().unwrap().insert("other_user", "moderator");
+
+    assert!(received_power_level(&receiver, "@rocketchat_rcid_other_user_id:localhost", 50));
+}
+
+#[test]
+fn a_power_level_change_on_matrix_is_forwarded_to_rocketchat_as_role_change() {
+    let test = Test::new();
+    let (add_owner_forwarder, add_owner_receiver) = MessageForwarder::new();
+    let mut rocketchat_router = test.default_rocketchat_routes();
+    rocketchat_router.post(CHANNELS_ADD_OWNER_PATH, add_owner_forwarder, "channels_add_owner");
+
+    let test = test
+        .with_rocketchat_mock()
+        .with_custom_rocketchat_routes(rocketchat_router)
+        .with_connected_admin_room()
+        .with_logged_in_user()
+        .with_bridged_room(("spec_channel", vec!["spec_user", "other_user"]))
+        .run();
+
+    let virtual_user_id = UserId::try_from("@rocketchat_rcid_other_user_id:localhost").unwrap();
+    let mut power_levels = HashMap::new();
+    power_levels.insert(virtual_user_id, 100);
+    helpers::send_power_levels_event_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!spec_channel_id:localhost").unwrap(),
+        UserId::try_from("@spec_user:localhost").unwrap(),
+        HashMap::new(),
+        power_levels,
+    );
+
+    let add_owner_message = add_owner_receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(add_owner_message.contains("\"roomId\":\"spec_channel_id\""));
+    assert!(add_owner_message.contains("\"userId\":\"other_user_id\""));
+}
+
+#[test]
+fn a_power_level_change_from_a_user_who_is_not_logged_in_is_not_forwarded_to_rocketchat() {
+    let test = Test::new();
+    let (add_owner_forwarder, add_owner_receiver) = MessageForwarder::new();
+    let mut rocketchat_router = test.default_rocketchat_routes();
+    rocketchat_router.post(CHANNELS_ADD_OWNER_PATH, add_owner_forwarder, "channels_add_owner");
+
+    let test = test
+        .with_rocketchat_mock()
+        .with_custom_rocketchat_routes(rocketchat_router)
+        .with_connected_admin_room()
+        .with_logged_in_user()
+        .with_bridged_room(("spec_channel", vec!["spec_user", "other_user"]))
+        .run();
+
+    let virtual_user_id = UserId::try_from("@rocketchat_rcid_other_user_id:localhost").unwrap();
+    let mut power_levels = HashMap::new();
+    power_levels.insert(virtual_user_id, 100);
+    helpers::send_power_levels_event_from_matrix(
+        &test.config.as_url,
+        RoomId::try_from("!spec_channel_id:localhost").unwrap(),
+        UserId::try_from("@not_logged_in_user:localhost").unwrap(),
+        HashMap::new(),
+        power_levels,
+    );
+
+    assert!(add_owner_receiver.recv_timeout(default_timeout()).is_err());
+}
+
+fn received_power_level(receiver: &Receiver<String>, user_id: &str, power_level: i64) -> bool {
+    let expected_power_level = format!("\"{}\":{}", user_id, power_level);
+    while let Ok(room_state) = receiver.recv_timeout(default_timeout()) {
+        if room_state.contains(&expected_power_level) {
+            return true;
+        }
+    }
+
+    false
+}
@@ -0,0 +1,101 @@
+extern crate http;
+extern crate iron;
+extern crate matrix_rocketchat;
+extern crate matrix_rocketchat_test;
+
+use http::StatusCode;
+use iron::status;
+use matrix_rocketchat::api::rocketchat::v1::CHANNELS_LIST_PATH;
+use matrix_rocketchat_test::{handlers, helpers, Test};
+
+#[test]
+fn the_connected_rocketchat_servers_are_instances_of_the_protocol() {
+    let test = Test::new().with_rocketchat_mock().with_connected_admin_room().run();
+
+    let (protocol, status_code) = helpers::query_thirdparty(&test.config.as_url, "protocol", "rocketchat", Vec::new());
+    assert_eq!(status_code, StatusCode::OK);
+    assert!(protocol.contains("\"network_id\":\"rcid\""));
+    assert!(protocol.contains("\"location_fields\":[\"server\",\"channel\"]"));
+    assert!(protocol.contains("\"user_fields\":[\"server\",\"username\"]"));
+}
+
+#[test]
+fn the_homeserver_gets_not_found_for_an_unknown_protocol() {
+    let test = Test::new().with_rocketchat_mock().with_connected_admin_room().run();
+
+    let (_, status_code) = helpers::query_thirdparty(&test.config.as_url, "protocol", "irc", Vec::new());
+    assert_eq!(status_code, StatusCode::NOT_FOUND);
+
+    let (_, status_code) = helpers::query_thirdparty(&test.config.as_url, "location", "irc", Vec::new());
+    assert_eq!(status_code, StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn a_public_channel_is_mapped_to_its_portal_alias() {
+    let test = Test::new();
+    test.channel_list().lock().unwrap().insert("spec_channel", vec!["spec_user"]);
+    test.group_list().lock().unwrap().insert("spec_group", vec!["spec_user"]);
+    let test = test.with_rocketchat_mock().with_connected_admin_room().with_logged_in_user().run();
+
+    let fields = vec![("server", "rcid"), ("channel", "spec_channel")];
+    let (locations, status_code) = helpers::query_thirdparty(&test.config.as_url, "location", "rocketchat", fields);
+    assert_eq!(status_code, StatusCode::OK);
+    assert!(locations.contains("\"alias\":\"#rocketchat_rcid_spec_channel:localhost\""));
+
+    // private groups are not listed
+    let (locations, status_code) = helpers::query_thirdparty(&test.config.as_url, "location", "rocketchat", Vec::new());
+    assert_eq!(status_code, StatusCode::OK);
+    assert!(locations.contains("\"alias\":\"#rocketchat_rcid_spec_channel:localhost\""));
+    assert!(!locations.contains("spec_group"));
+}
+
+#[test]
+fn no_locations_are_found_for_an_unknown_channel() {
+    let test = Test::new().with_rocketchat_mock().with_connected_admin_room().with_logged_in_user().run();
+
+    let fields = vec![("server", "rcid"), ("channel", "unknown_channel")];
+    let (locations, status_code) = helpers::query_thirdparty(&test.config.as_url, "location", "rocketchat", fields);
+    assert_eq!(status_code, StatusCode::OK);
+    assert_eq!(locations, "[]");
+}
+
+#[test]
+fn no_locations_are_found_when_the_rocketchat_server_cannot_list_the_channels() {
+    let test = Test::new();
+    let mut rocketchat_router = test.default_rocketchat_routes();
+    rocketchat_router.get(
+        CHANNELS_LIST_PATH,
+        handlers::RocketchatErrorResponder { message: "List Error".to_string(), status: status::InternalServerError },
+        "channels_list",
+    );
+    let test = test
+        .with_rocketchat_mock()
+        .with_custom_rocketchat_routes(rocketchat_router)
+        .with_connected_admin_room()
+        .with_logged_in_user()
+        .run();
+
+    let (locations, status_code) = helpers::query_thirdparty(&test.config.as_url, "location", "rocketchat", Vec::new());
+    assert_eq!(status_code, StatusCode::OK);
+    assert_eq!(locations, "[]");
+}
+
+#[test]
+fn a_rocketchat_user_is_mapped_to_its_virtual_user() {
+    let test = Test::new().with_rocketchat_mock().with_connected_admin_room().with_logged_in_user().run();
+
+    let fields = vec![("server", "rcid"), ("username", "other_user")];
+    let (users, status_code) = helpers::query_thirdparty(&test.config.as_url, "user", "rocketchat", fields);
+    assert_eq!(status_code, StatusCode::OK);
+    assert!(users.contains("\"userid\":\"@rocketchat_rcid_other_user_id:localhost\""));
+}
+
+#[test]
+fn no_users_are_found_on_an_unknown_server() {
+    let test = Test::new().with_rocketchat_mock().with_connected_admin_room().with_logged_in_user().run();
+
+    let fields = vec![("server", "unknownserver"), ("username", "other_user")];
+    let (users, status_code) = helpers::query_thirdparty(&test.config.as_url, "user", "rocketchat", fields);
+    assert_eq!(status_code, StatusCode::OK);
+    assert_eq!(users, "[]");
+}
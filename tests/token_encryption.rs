@@ -0,0 +1,171 @@
+#[macro_use]
+extern crate diesel;
+extern crate matrix_rocketchat;
+extern crate matrix_rocketchat_test;
+extern crate ruma_client_api;
+extern crate ruma_identifiers;
+
+use std::convert::TryFrom;
+
+use diesel::sql_types::Text;
+use diesel::sqlite::SqliteConnection;
+use diesel::RunQueryDsl;
+use matrix_rocketchat::models::{RocketchatServer, TokenKey, UserOnRocketchatServer};
+use matrix_rocketchat_test::{default_timeout, MessageForwarder, Test, DATABASE_ENCRYPTION_KEY, RS_TOKEN};
+use ruma_client_api::r0::send::send_message_event::Endpoint as SendMessageEventEndpoint;
+use ruma_client_api::Endpoint;
+use ruma_identifiers::UserId;
+
+#[derive(QueryableByName)]
+struct StoredToken {
+    #[sql_type = "Text"]
+    token: String,
+}
+
+#[test]
+fn tokens_are_stored_encrypted_in_the_database() {
+    let test = logged_in_test();
+    let connection = test.connection_pool.get().unwrap();
+
+    let auth_token = stored_auth_token(&connection);
+    assert!(auth_token.starts_with("enc:v1:"));
+    assert!(!auth_token.contains("spec_auth_token"));
+
+    let rocketchat_token = stored_rocketchat_token(&connection);
+    assert!(rocketchat_token.starts_with("enc:v1:"));
+
+    let rocketchat_server = RocketchatServer::find(&connection, &test.rocketchat_mock_url.clone().unwrap()).unwrap();
+    assert_eq!(rocketchat_server.rocketchat_token.clone().unwrap(), RS_TOKEN);
+    let found_server = RocketchatServer::find_by_token(&connection, RS_TOKEN).unwrap().unwrap();
+    assert_eq!(found_server.id, rocketchat_server.id);
+
+    let user_on_rocketchat_server =
+        UserOnRocketchatServer::find(&connection, &UserId::try_from("@spec_user:localhost").unwrap(), rocketchat_server.id)
+            .unwrap();
+    assert_eq!(user_on_rocketchat_server.rocketchat_auth_token.unwrap(), "spec_auth_token");
+}
+
+#[test]
+fn existing_tokens_can_be_decrypted_and_encrypted_again() {
+    let test = logged_in_test();
+    let connection = test.connection_pool.get().unwrap();
+    let token_key = TokenKey::new(DATABASE_ENCRYPTION_KEY).unwrap();
+
+    let updated_tokens = TokenKey::reencrypt_tokens(&connection, Some(&token_key), None).unwrap();
+    assert_eq!(updated_tokens, 2);
+    assert_eq!(stored_auth_token(&connection), "spec_auth_token");
+    assert_eq!(stored_rocketchat_token(&connection), RS_TOKEN);
+
+    // plaintext tokens that were stored before the encryption was enabled can still be read
+    let rocketchat_server = RocketchatServer::find(&connection, &test.rocketchat_mock_url.clone().unwrap()).unwrap();
+    assert_eq!(rocketchat_server.rocketchat_token.clone().unwrap(), RS_TOKEN);
+
+    let updated_tokens = TokenKey::reencrypt_tokens(&connection, None, Some(&token_key)).unwrap();
+    assert_eq!(updated_tokens, 2);
+    assert!(stored_auth_token(&connection).starts_with("enc:v1:"));
+    assert!(stored_rocketchat_token(&connection).starts_with("enc:v1:"));
+
+    let user_on_rocketchat_server =
+        UserOnRocketchatServer::find(&connection, &UserId::try_from("@spec_user:localhost").unwrap(), rocketchat_server.id)
+            .unwrap();
+    assert_eq!(user_on_rocketchat_server.rocketchat_auth_token.unwrap(), "spec_auth_token");
+}
+
+#[test]
+fn matrix_access_tokens_are_stored_encrypted_in_the_database() {
+    let test = logged_in_test();
+    let connection = test.connection_pool.get().unwrap();
+    let rocketchat_server = RocketchatServer::find(&connection, &test.rocketchat_mock_url.clone().unwrap()).unwrap();
+    let spec_user_id = UserId::try_from("@spec_user:localhost").unwrap();
+
+    let mut user_on_rocketchat_server =
+        UserOnRocketchatServer::find(&connection, &spec_user_id, rocketchat_server.id.clone()).unwrap();
+    user_on_rocketchat_server.set_matrix_access_token(&connection, Some("spec_access_token".to_string())).unwrap();
+
+    let matrix_access_token = stored_matrix_access_token(&connection);
+    assert!(matrix_access_token.starts_with("enc:v1:"));
+    assert!(!matrix_access_token.contains("spec_access_token"));
+
+    let user_on_rocketchat_server = UserOnRocketchatServer::find(&connection, &spec_user_id, rocketchat_server.id).unwrap();
+    assert_eq!(user_on_rocketchat_server.matrix_access_token.unwrap(), "spec_access_token");
+
+    let token_key = TokenKey::new(DATABASE_ENCRYPTION_KEY).unwrap();
+    let updated_tokens = TokenKey::reencrypt_tokens(&connection, Some(&token_key), None).unwrap();
+    assert_eq!(updated_tokens, 3);
+    assert_eq!(stored_matrix_access_token(&connection), "spec_access_token");
+}
+
+#[test]
+fn servers_that_were_connected_before_the_tokens_were_hashed_can_be_found_by_their_token() {
+    let test = logged_in_test();
+    let connection = test.connection_pool.get().unwrap();
+
+    diesel::sql_query("UPDATE rocketchat_servers SET rocketchat_token_hash = NULL").execute(&connection).unwrap();
+    assert!(RocketchatServer::find_by_token(&connection, RS_TOKEN).unwrap().is_none());
+
+    let hashed_tokens = RocketchatServer::hash_missing_tokens(&connection).unwrap();
+    assert_eq!(hashed_tokens, 1);
+    assert!(RocketchatServer::find_by_token(&connection, RS_TOKEN).unwrap().is_some());
+}
+
+#[test]
+fn a_token_cannot_be_decrypted_with_another_key() {
+    let encrypted_token = TokenKey::new(DATABASE_ENCRYPTION_KEY).unwrap().encrypt("spec_auth_token").unwrap();
+
+    assert_eq!(TokenKey::new(DATABASE_ENCRYPTION_KEY).unwrap().decrypt(&encrypted_token).unwrap(), "spec_auth_token");
+    let other_key = "0000000000000000000000000000000000000000000000000000000000000000";
+    assert!(TokenKey::new(other_key).unwrap().decrypt(&encrypted_token).is_err());
+}
+
+#[test]
+fn only_32_bytes_encoded_as_hex_are_accepted_as_key() {
+    assert!(TokenKey::new("spec_database_encryption_key").is_err());
+    assert!(TokenKey::new("5c1e0a7f3b9d2c4e6f8a1b3c5d7e9f0a").is_err());
+    assert!(TokenKey::new(&format!("{}00", DATABASE_ENCRYPTION_KEY)).is_err());
+    assert!(TokenKey::new(&DATABASE_ENCRYPTION_KEY.replace("5c", "zz")).is_err());
+    assert!(TokenKey::new(DATABASE_ENCRYPTION_KEY).is_ok());
+}
+
+fn logged_in_test() -> Test {
+    let test = Test::new();
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+    let test =
+        test.with_matrix_routes(matrix_router).with_rocketchat_mock().with_connected_admin_room().with_logged_in_user().run();
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // wait until the login is finished
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    test
+}
+
+fn stored_auth_token(connection: &SqliteConnection) -> String {
+    let tokens = diesel::sql_query(
+        "SELECT rocketchat_auth_token AS token FROM users_on_rocketchat_servers WHERE rocketchat_auth_token IS NOT NULL",
+    )
+    .load::<StoredToken>(connection)
+    .unwrap();
+    tokens.into_iter().next().unwrap().token
+}
+
+fn stored_rocketchat_token(connection: &SqliteConnection) -> String {
+    let tokens =
+        diesel::sql_query("SELECT rocketchat_token AS token FROM rocketchat_servers WHERE rocketchat_token IS NOT NULL")
+            .load::<StoredToken>(connection)
+            .unwrap();
+    tokens.into_iter().next().unwrap().token
+}
+
+fn stored_matrix_access_token(connection: &SqliteConnection) -> String {
+    let tokens = diesel::sql_query(
+        "SELECT matrix_access_token AS token FROM users_on_rocketchat_servers WHERE matrix_access_token IS NOT NULL",
+    )
+    .load::<StoredToken>(connection)
+    .unwrap();
+    tokens.into_iter().next().unwrap().token
+}
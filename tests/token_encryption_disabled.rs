@@ -0,0 +1,74 @@
+#[macro_use]
+extern crate diesel;
+extern crate matrix_rocketchat;
+extern crate matrix_rocketchat_test;
+extern crate ruma_client_api;
+extern crate ruma_identifiers;
+
+use std::convert::TryFrom;
+
+use diesel::sql_types::Text;
+use diesel::sqlite::SqliteConnection;
+use diesel::RunQueryDsl;
+use matrix_rocketchat::models::{RocketchatServer, UserOnRocketchatServer};
+use matrix_rocketchat_test::{default_timeout, MessageForwarder, Test, RS_TOKEN};
+use ruma_client_api::r0::send::send_message_event::Endpoint as SendMessageEventEndpoint;
+use ruma_client_api::Endpoint;
+use ruma_identifiers::UserId;
+
+// The key is global, so the tests without a key cannot run in the same process as the tests with a key.
+
+#[derive(QueryableByName)]
+struct StoredToken {
+    #[sql_type = "Text"]
+    token: String,
+}
+
+#[test]
+fn tokens_are_stored_in_plaintext_if_no_key_is_configured() {
+    let test = Test::new();
+    let mut config = test.config.clone();
+    config.database_encryption_key = None;
+    let (message_forwarder, receiver) = MessageForwarder::new();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.put(SendMessageEventEndpoint::router_path(), message_forwarder, "send_message_event");
+    let test = test
+        .with_custom_config(config)
+        .with_matrix_routes(matrix_router)
+        .with_rocketchat_mock()
+        .with_connected_admin_room()
+        .with_logged_in_user()
+        .run();
+
+    // discard welcome message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // discard connect message
+    receiver.recv_timeout(default_timeout()).unwrap();
+    // wait until the login is finished
+    receiver.recv_timeout(default_timeout()).unwrap();
+
+    let connection = test.connection_pool.get().unwrap();
+    let spec_user_id = UserId::try_from("@spec_user:localhost").unwrap();
+    let rocketchat_server = RocketchatServer::find(&connection, &test.rocketchat_mock_url.clone().unwrap()).unwrap();
+    let mut user_on_rocketchat_server =
+        UserOnRocketchatServer::find(&connection, &spec_user_id, rocketchat_server.id.clone()).unwrap();
+    user_on_rocketchat_server.set_matrix_access_token(&connection, Some("spec_access_token".to_string())).unwrap();
+
+    assert_eq!(stored_token(&connection, "rocketchat_token", "rocketchat_servers"), RS_TOKEN);
+    assert_eq!(stored_token(&connection, "rocketchat_auth_token", "users_on_rocketchat_servers"), "spec_auth_token");
+    assert_eq!(stored_token(&connection, "matrix_access_token", "users_on_rocketchat_servers"), "spec_access_token");
+
+    let found_server = RocketchatServer::find_by_token(&connection, RS_TOKEN).unwrap().unwrap();
+    assert_eq!(found_server.id, rocketchat_server.id);
+    assert!(RocketchatServer::find_by_token(&connection, "other_token").unwrap().is_none());
+
+    let user_on_rocketchat_server = UserOnRocketchatServer::find(&connection, &spec_user_id, rocketchat_server.id).unwrap();
+    assert_eq!(user_on_rocketchat_server.rocketchat_auth_token.unwrap(), "spec_auth_token");
+    assert_eq!(user_on_rocketchat_server.matrix_access_token.unwrap(), "spec_access_token");
+}
+
+fn stored_token(connection: &SqliteConnection, column: &str, table: &str) -> String {
+    let query = format!("SELECT {} AS token FROM {} WHERE {} IS NOT NULL", column, table, column);
+    let tokens = diesel::sql_query(query).load::<StoredToken>(connection).unwrap();
+    tokens.into_iter().next().unwrap().token
+}
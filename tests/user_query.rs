@@ -0,0 +1,83 @@
+extern crate http;
+extern crate matrix_rocketchat;
+extern crate matrix_rocketchat_test;
+extern crate router;
+extern crate ruma_client_api;
+extern crate ruma_identifiers;
+
+use std::convert::TryFrom;
+
+use http::StatusCode;
+use matrix_rocketchat::api::MatrixApi;
+use matrix_rocketchat_test::{default_timeout, handlers, helpers, Test, DEFAULT_LOGGER};
+use ruma_client_api::r0::account::register::Endpoint as RegisterEndpoint;
+use ruma_client_api::Endpoint;
+use ruma_identifiers::UserId;
+
+#[test]
+fn a_virtual_user_is_registered_when_the_homeserver_queries_it() {
+    let test = Test::new();
+    let (register_forwarder, register_receiver) = handlers::MatrixRegister::with_forwarder();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.post(RegisterEndpoint::router_path(), register_forwarder, "register");
+
+    let test =
+        test.with_matrix_routes(matrix_router).with_rocketchat_mock().with_connected_admin_room().with_logged_in_user().run();
+
+    // discard bot and spec user registration
+    register_receiver.recv_timeout(default_timeout()).unwrap();
+    register_receiver.recv_timeout(default_timeout()).unwrap();
+
+    let user_id = UserId::try_from("@rocketchat_rcid_other_user_id:localhost").unwrap();
+    let (_, status_code) = helpers::query_user(&test.config.as_url, &user_id);
+    assert_eq!(status_code, StatusCode::OK);
+
+    let register_message = register_receiver.recv_timeout(default_timeout()).unwrap();
+    assert!(register_message.contains("\"username\":\"rocketchat_rcid_other_user_id\""));
+
+    let matrix_api = MatrixApi::new(&test.config, DEFAULT_LOGGER.clone()).unwrap();
+    let display_name = matrix_api.get_display_name(user_id).unwrap().unwrap();
+    assert_eq!(display_name, "other_user");
+}
+
+#[test]
+fn a_virtual_user_that_is_queried_twice_is_only_registered_once() {
+    let test = Test::new();
+    let (register_forwarder, register_receiver) = handlers::MatrixRegister::with_forwarder();
+    let mut matrix_router = test.default_matrix_routes();
+    matrix_router.post(RegisterEndpoint::router_path(), register_forwarder, "register");
+
+    let test =
+        test.with_matrix_routes(matrix_router).with_rocketchat_mock().with_connected_admin_room().with_logged_in_user().run();
+
+    // discard bot and spec user registration
+    register_receiver.recv_timeout(default_timeout()).unwrap();
+    register_receiver.recv_timeout(default_timeout()).unwrap();
+
+    let user_id = UserId::try_from("@rocketchat_rcid_other_user_id:localhost").unwrap();
+    let (_, status_code) = helpers::query_user(&test.config.as_url, &user_id);
+    assert_eq!(status_code, StatusCode::OK);
+    register_receiver.recv_timeout(default_timeout()).unwrap();
+
+    let (_, status_code) = helpers::query_user(&test.config.as_url, &user_id);
+    assert_eq!(status_code, StatusCode::OK);
+    assert!(register_receiver.recv_timeout(default_timeout()).is_err());
+}
+
+#[test]
+fn the_homeserver_gets_not_found_for_a_user_outside_of_the_namespace() {
+    let test = Test::new().with_rocketchat_mock().with_connected_admin_room().with_logged_in_user().run();
+
+    let user_id = UserId::try_from("@other_user:localhost").unwrap();
+    let (_, status_code) = helpers::query_user(&test.config.as_url, &user_id);
+    assert_eq!(status_code, StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn the_homeserver_gets_not_found_for_a_user_on_an_unknown_rocketchat_server() {
+    let test = Test::new().with_rocketchat_mock().with_connected_admin_room().with_logged_in_user().run();
+
+    let user_id = UserId::try_from("@rocketchat_unknownserver_other_user_id:localhost").unwrap();
+    let (_, status_code) = helpers::query_user(&test.config.as_url, &user_id);
+    assert_eq!(status_code, StatusCode::NOT_FOUND);
+}